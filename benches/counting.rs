@@ -0,0 +1,95 @@
+//! Benchmarks for syllable counting and haiku detection.
+//!
+//! Run with `cargo bench`. The messages are generated from a fixed seed so results are
+//! comparable between runs.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use haikubot::{
+    counting::{count_line, WordOverrides},
+    detection::{self, RecentLines},
+    HaikuLine,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serenity::model::id::UserId;
+
+const SEED: u64 = 575;
+
+/// Words the synthetic messages are built from, mostly taken from the dictionary
+const VOCABULARY: &str = "the last winter leaves clinging to black branches explode into birds \
+    an old silent pond frog jumps splash silence again hello there general kenobi database \
+    dictionary abundant haiku morning coffee tonight pizza don't I'm really think about what \
+    you said yesterday server channel message beautiful information";
+
+/// Things people type that aren't in the dictionary
+const NOISE: &str = "lol xD :) https://example.com #$%& @everyone ok!!";
+
+/// Generate chat-like messages of between 1 and 16 words, some of which can't be counted
+fn generate_messages(count: usize) -> Vec<String> {
+    let vocabulary = VOCABULARY.split_whitespace().collect::<Vec<&str>>();
+    let noise = NOISE.split_whitespace().collect::<Vec<&str>>();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    (0..count)
+        .map(|_| {
+            let length = rng.gen_range(1, 17);
+            (0..length)
+                .map(|_| {
+                    if rng.gen_bool(0.05) {
+                        *noise.choose(&mut rng).unwrap()
+                    } else {
+                        *vocabulary.choose(&mut rng).unwrap()
+                    }
+                })
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Run each message through detection the way the message handler does for a channel
+fn detect(messages: &[String]) -> usize {
+    let mut recent: RecentLines = [None, None, None];
+    messages
+        .iter()
+        .filter_map(|message| {
+            let line = HaikuLine {
+                author: UserId(1),
+                content: message.clone(),
+            };
+            detection::on_line(&mut recent, line, &WordOverrides::new())
+        })
+        .count()
+}
+
+fn bench_count_line(c: &mut Criterion) {
+    let messages = generate_messages(1_000);
+    let mut group = c.benchmark_group("count_line");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    group.bench_function("synthetic", |b| {
+        b.iter(|| {
+            for message in messages.iter() {
+                let _ = black_box(count_line(black_box(message)));
+            }
+        })
+    });
+    group.bench_function("haiku line", |b| {
+        b.iter(|| count_line(black_box("Clinging to the black branches")))
+    });
+    group.finish();
+}
+
+fn bench_detection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("detection");
+    for size in [100, 1_000, 10_000].iter() {
+        let messages = generate_messages(*size);
+        group.throughput(Throughput::Elements(*size as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(size),
+            &messages,
+            |b, messages| b.iter(|| detect(black_box(messages))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_count_line, bench_detection);
+criterion_main!(benches);
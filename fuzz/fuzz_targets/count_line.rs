@@ -0,0 +1,10 @@
+//! Feed arbitrary text to the syllable counter. Run from the repository root so the
+//! dictionary can be found: `cargo fuzz run count_line`
+#![no_main]
+use haikubot::counting;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|line: &str| {
+    let _ = counting::count_line(line);
+    let _ = counting::split_into_haiku(line);
+});
@@ -0,0 +1,9 @@
+//! Feed arbitrary sets of messages to the multi-message haiku check, as the message handler
+//! does with the last three messages in a channel: `cargo fuzz run detect_haiku`
+#![no_main]
+use haikubot::counting;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|lines: Vec<String>| {
+    let _ = counting::is_haiku(&lines);
+});
@@ -0,0 +1,9 @@
+CREATE TABLE server_configs (
+    server BIGINT PRIMARY KEY,
+    joined_at TIMESTAMP NOT NULL,
+    left_at TIMESTAMP
+);
+
+-- Every server that already has haikus gets a default config
+INSERT INTO server_configs (server, joined_at)
+SELECT server, MIN(timestamp) FROM haikus GROUP BY server;
@@ -0,0 +1 @@
+ALTER TABLE server_configs ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
@@ -0,0 +1 @@
+ALTER TABLE haikus ADD COLUMN flagged BOOLEAN NOT NULL DEFAULT FALSE;
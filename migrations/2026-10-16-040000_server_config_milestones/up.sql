@@ -0,0 +1 @@
+ALTER TABLE server_configs ADD COLUMN last_milestone BIGINT NOT NULL DEFAULT 0;
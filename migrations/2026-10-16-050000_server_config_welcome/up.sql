@@ -0,0 +1 @@
+ALTER TABLE server_configs ADD COLUMN welcome_enabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+ALTER TABLE server_configs DROP COLUMN random_weighting;
+ALTER TABLE haikus DROP COLUMN votes;
+ALTER TABLE haikus DROP COLUMN last_shown_at;
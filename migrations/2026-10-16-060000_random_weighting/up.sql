@@ -0,0 +1,3 @@
+ALTER TABLE haikus ADD COLUMN last_shown_at TIMESTAMP;
+ALTER TABLE haikus ADD COLUMN votes BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE server_configs ADD COLUMN random_weighting TEXT NOT NULL DEFAULT 'uniform';
@@ -0,0 +1,2 @@
+ALTER TABLE server_configs DROP COLUMN laureate;
+ALTER TABLE server_configs DROP COLUMN laureate_role;
@@ -0,0 +1,2 @@
+ALTER TABLE server_configs ADD COLUMN laureate_role BIGINT;
+ALTER TABLE server_configs ADD COLUMN laureate BIGINT;
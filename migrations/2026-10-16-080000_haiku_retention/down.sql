@@ -0,0 +1,3 @@
+DROP TABLE archived_haikus;
+ALTER TABLE server_configs DROP COLUMN retention_action;
+ALTER TABLE server_configs DROP COLUMN retention_years;
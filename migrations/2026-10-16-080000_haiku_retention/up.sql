@@ -0,0 +1,17 @@
+ALTER TABLE server_configs ADD COLUMN retention_years BIGINT;
+ALTER TABLE server_configs ADD COLUMN retention_action TEXT NOT NULL DEFAULT 'archive';
+
+CREATE TABLE archived_haikus (
+    id BIGINT NOT NULL,
+    channel BIGINT NOT NULL,
+    server BIGINT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    author_0 BIGINT NOT NULL,
+    author_1 BIGINT NOT NULL,
+    author_2 BIGINT NOT NULL,
+    message_0 TEXT NOT NULL,
+    message_1 TEXT NOT NULL,
+    message_2 TEXT NOT NULL,
+    archived_at TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    PRIMARY KEY (id, server)
+);
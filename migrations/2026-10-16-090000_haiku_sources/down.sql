@@ -0,0 +1,6 @@
+-- Duplicates removed by the up migration can't be restored
+DROP INDEX haikus_source;
+ALTER TABLE haikus DROP COLUMN source_line;
+ALTER TABLE haikus DROP COLUMN source_message_2;
+ALTER TABLE haikus DROP COLUMN source_message_1;
+ALTER TABLE haikus DROP COLUMN source_message_0;
@@ -0,0 +1,23 @@
+-- Before message ids were stored, a replayed gateway event could save the same haiku twice.
+-- Keep the first of any identical haikus saved in the same channel within ten minutes.
+DELETE FROM haikus later
+USING haikus earlier
+WHERE later.server = earlier.server
+    AND later.channel = earlier.channel
+    AND later.id > earlier.id
+    AND later.author_0 = earlier.author_0
+    AND later.author_1 = earlier.author_1
+    AND later.author_2 = earlier.author_2
+    AND later.message_0 = earlier.message_0
+    AND later.message_1 = earlier.message_1
+    AND later.message_2 = earlier.message_2
+    AND later.timestamp >= earlier.timestamp
+    AND later.timestamp < earlier.timestamp + INTERVAL '10 minutes';
+
+ALTER TABLE haikus ADD COLUMN source_message_0 BIGINT;
+ALTER TABLE haikus ADD COLUMN source_message_1 BIGINT;
+ALTER TABLE haikus ADD COLUMN source_message_2 BIGINT;
+ALTER TABLE haikus ADD COLUMN source_line BIGINT;
+
+-- The line that completes a haiku identifies it, so the same messages can't be saved twice
+CREATE UNIQUE INDEX haikus_source ON haikus (server, source_message_2, source_line);
@@ -0,0 +1,9 @@
+CREATE TABLE command_usage (
+    id BIGSERIAL PRIMARY KEY,
+    server BIGINT NOT NULL,
+    command TEXT NOT NULL,
+    used_at TIMESTAMP NOT NULL,
+    latency_ms BIGINT NOT NULL
+);
+
+CREATE INDEX command_usage_server_used_at ON command_usage (server, used_at);
@@ -0,0 +1,7 @@
+CREATE TABLE user_preferences (
+    user_id BIGINT PRIMARY KEY,
+    notify_detected BOOLEAN NOT NULL DEFAULT FALSE,
+    notify_bookmarked BOOLEAN NOT NULL DEFAULT FALSE,
+    notify_contest_win BOOLEAN NOT NULL DEFAULT FALSE,
+    notify_vote_threshold BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1 @@
+DROP INDEX haikus_server_timestamp;
@@ -0,0 +1,2 @@
+-- Leaderboards count each period's haikus straight from the table
+CREATE INDEX haikus_server_timestamp ON haikus (server, timestamp);
@@ -0,0 +1 @@
+ALTER TABLE server_configs DROP COLUMN global_stats_enabled;
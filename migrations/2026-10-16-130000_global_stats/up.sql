@@ -0,0 +1 @@
+ALTER TABLE server_configs ADD COLUMN global_stats_enabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,31 @@
+-- Per-line message timestamps and syllable counts are lost
+ALTER TABLE haikus RENAME COLUMN source_message TO source_message_2;
+ALTER TABLE haikus ADD COLUMN source_message_0 BIGINT;
+ALTER TABLE haikus ADD COLUMN source_message_1 BIGINT;
+ALTER TABLE haikus ADD COLUMN author_0 BIGINT;
+ALTER TABLE haikus ADD COLUMN author_1 BIGINT;
+ALTER TABLE haikus ADD COLUMN author_2 BIGINT;
+ALTER TABLE haikus ADD COLUMN message_0 TEXT;
+ALTER TABLE haikus ADD COLUMN message_1 TEXT;
+ALTER TABLE haikus ADD COLUMN message_2 TEXT;
+
+UPDATE haikus SET author_0 = line.author, message_0 = line.content,
+    source_message_0 = line.message_id
+FROM haiku_lines line
+WHERE line.server = haikus.server AND line.haiku_id = haikus.id AND line.position = 0;
+UPDATE haikus SET author_1 = line.author, message_1 = line.content,
+    source_message_1 = line.message_id
+FROM haiku_lines line
+WHERE line.server = haikus.server AND line.haiku_id = haikus.id AND line.position = 1;
+UPDATE haikus SET author_2 = line.author, message_2 = line.content
+FROM haiku_lines line
+WHERE line.server = haikus.server AND line.haiku_id = haikus.id AND line.position = 2;
+
+ALTER TABLE haikus ALTER COLUMN author_0 SET NOT NULL;
+ALTER TABLE haikus ALTER COLUMN author_1 SET NOT NULL;
+ALTER TABLE haikus ALTER COLUMN author_2 SET NOT NULL;
+ALTER TABLE haikus ALTER COLUMN message_0 SET NOT NULL;
+ALTER TABLE haikus ALTER COLUMN message_1 SET NOT NULL;
+ALTER TABLE haikus ALTER COLUMN message_2 SET NOT NULL;
+
+DROP TABLE haiku_lines;
@@ -0,0 +1,35 @@
+-- Each line of a haiku gets a row of its own, with who wrote it, which message it came from and
+-- when it was sent
+CREATE TABLE haiku_lines (
+    server BIGINT NOT NULL,
+    haiku_id BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    author BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    message_id BIGINT,
+    sent_at TIMESTAMP NOT NULL,
+    syllables BIGINT NOT NULL,
+    PRIMARY KEY (server, haiku_id, position),
+    FOREIGN KEY (haiku_id, server) REFERENCES haikus (id, server) ON DELETE CASCADE
+);
+
+CREATE INDEX haiku_lines_author ON haiku_lines (server, author);
+
+-- Lines saved so far were counted as part of a haiku, and weren't timed separately from it
+INSERT INTO haiku_lines (server, haiku_id, position, author, content, message_id, sent_at, syllables)
+SELECT server, id, 0, author_0, message_0, source_message_0, timestamp, 5 FROM haikus
+UNION ALL
+SELECT server, id, 1, author_1, message_1, source_message_1, timestamp, 7 FROM haikus
+UNION ALL
+SELECT server, id, 2, author_2, message_2, source_message_2, timestamp, 5 FROM haikus;
+
+ALTER TABLE haikus DROP COLUMN author_0;
+ALTER TABLE haikus DROP COLUMN author_1;
+ALTER TABLE haikus DROP COLUMN author_2;
+ALTER TABLE haikus DROP COLUMN message_0;
+ALTER TABLE haikus DROP COLUMN message_1;
+ALTER TABLE haikus DROP COLUMN message_2;
+ALTER TABLE haikus DROP COLUMN source_message_0;
+ALTER TABLE haikus DROP COLUMN source_message_1;
+-- The message that completed a haiku stays with it, as haikus_source stops it being saved twice
+ALTER TABLE haikus RENAME COLUMN source_message_2 TO source_message;
@@ -0,0 +1,3 @@
+-- Text posted alongside each announced haiku, with placeholders filled in. None posts the haiku
+-- on its own.
+ALTER TABLE server_configs ADD COLUMN announcement_template TEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE quiz_scores (
+    server BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    correct BIGINT NOT NULL DEFAULT 0,
+    answered BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (server, user_id)
+);
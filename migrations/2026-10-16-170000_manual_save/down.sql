@@ -0,0 +1,3 @@
+ALTER TABLE server_configs DROP COLUMN manual_save_role;
+ALTER TABLE server_configs DROP COLUMN manual_save_emoji;
+DROP TABLE detection_disabled_channels;
@@ -0,0 +1,10 @@
+-- Channels where haikus are only saved when someone reacts to ask for it
+CREATE TABLE detection_disabled_channels (
+    server BIGINT NOT NULL,
+    channel BIGINT NOT NULL,
+    PRIMARY KEY (server, channel)
+);
+
+-- The reaction that asks for a haiku to be saved in those channels, and who may use it
+ALTER TABLE server_configs ADD COLUMN manual_save_emoji TEXT;
+ALTER TABLE server_configs ADD COLUMN manual_save_role BIGINT;
@@ -0,0 +1,3 @@
+ALTER TABLE haiku_lines DROP COLUMN author_name;
+ALTER TABLE server_configs DROP COLUMN detect_webhook_messages;
+ALTER TABLE server_configs DROP COLUMN detect_bot_messages;
@@ -0,0 +1,6 @@
+-- Whether messages from bots and webhooks (e.g. bridges from IRC or Matrix) can be haikus
+ALTER TABLE server_configs ADD COLUMN detect_bot_messages BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE server_configs ADD COLUMN detect_webhook_messages BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The name a line was posted under, shown when its author isn't a member (e.g. a webhook)
+ALTER TABLE haiku_lines ADD COLUMN author_name TEXT;
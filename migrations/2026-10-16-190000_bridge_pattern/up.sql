@@ -0,0 +1,2 @@
+-- A regex picking the real author's name out of messages relayed by a bridge bot
+ALTER TABLE server_configs ADD COLUMN bridge_pattern TEXT;
@@ -0,0 +1,2 @@
+-- Whether haikus need all three lines from the same author to be saved
+ALTER TABLE server_configs ADD COLUMN same_author_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+CREATE TABLE word_overrides (
+    server BIGINT NOT NULL,
+    word TEXT NOT NULL,
+    syllables BIGINT NOT NULL,
+    PRIMARY KEY (server, word)
+);
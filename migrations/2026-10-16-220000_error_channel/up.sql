@@ -0,0 +1,2 @@
+-- Where the bot tells admins when something goes wrong, instead of failing silently
+ALTER TABLE server_configs ADD COLUMN error_channel BIGINT;
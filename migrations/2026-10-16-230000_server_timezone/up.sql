@@ -0,0 +1,2 @@
+-- The IANA timezone the server's leaderboard periods and monthly laureate roll over in
+ALTER TABLE server_configs ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
@@ -0,0 +1,5 @@
+-- When each of the bot's periodic jobs last ran, so restarts don't delay them or lose track
+CREATE TABLE scheduled_jobs (
+    name TEXT PRIMARY KEY,
+    last_run_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,10 @@
+-- How far /scanhistory has got through each channel, so scans can carry on where they stopped
+CREATE TABLE history_scans (
+    channel BIGINT PRIMARY KEY,
+    server BIGINT NOT NULL,
+    -- The oldest message checked so far, or NULL if the scan hasn't checked any yet
+    before_message BIGINT,
+    until_time TIMESTAMP NOT NULL,
+    messages BIGINT NOT NULL DEFAULT 0,
+    haikus BIGINT NOT NULL DEFAULT 0
+);
@@ -0,0 +1,8 @@
+DROP TABLE haiku_numbers;
+
+-- Ids handed out by server may have passed the shared sequence
+SELECT setval('haikus_id_seq', GREATEST(
+    (SELECT MAX(id) FROM haikus),
+    (SELECT MAX(id) FROM archived_haikus),
+    (SELECT last_value FROM haikus_id_seq)
+));
@@ -0,0 +1,15 @@
+-- The last number given to a haiku in each server, so new haikus are numbered one after another
+-- within their server rather than taking the next value of a sequence shared by every server,
+-- which skips values whenever a save is rolled back. Existing haikus keep their ids.
+CREATE TABLE haiku_numbers (
+    server BIGINT PRIMARY KEY,
+    last_number BIGINT NOT NULL
+);
+
+INSERT INTO haiku_numbers (server, last_number)
+SELECT server, MAX(id) FROM (
+    SELECT server, id FROM haikus
+    UNION ALL
+    SELECT server, id FROM archived_haikus
+) AS numbered
+GROUP BY server;
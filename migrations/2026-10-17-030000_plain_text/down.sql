@@ -0,0 +1,2 @@
+ALTER TABLE server_configs DROP COLUMN plain_text;
+ALTER TABLE user_preferences DROP COLUMN plain_text;
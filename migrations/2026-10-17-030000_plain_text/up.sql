@@ -0,0 +1,5 @@
+-- Whether someone wants haikus as plain text instead of embeds, e.g. for a screen reader. NULL
+-- follows the server's default.
+ALTER TABLE user_preferences ADD COLUMN plain_text BOOLEAN;
+-- Whether haikus are shown as plain text in the server to anyone who hasn't chosen for themselves
+ALTER TABLE server_configs ADD COLUMN plain_text BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Haikus marked by their authors or moderators as needing a content warning, whose lines are
+-- hidden behind spoilers and which are never picked at random
+ALTER TABLE haikus ADD COLUMN sensitive BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+ALTER TABLE haikus DROP COLUMN mirror_message;
+ALTER TABLE server_configs DROP COLUMN archive_webhook_token;
+ALTER TABLE server_configs DROP COLUMN archive_webhook;
+ALTER TABLE server_configs DROP COLUMN archive_channel;
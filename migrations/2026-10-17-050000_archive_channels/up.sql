@@ -0,0 +1,6 @@
+-- The channel every saved haiku is mirrored to, through a webhook the bot made there
+ALTER TABLE server_configs ADD COLUMN archive_channel BIGINT;
+ALTER TABLE server_configs ADD COLUMN archive_webhook BIGINT;
+ALTER TABLE server_configs ADD COLUMN archive_webhook_token TEXT;
+-- The haiku's message in the archive channel, so it can be edited or deleted along with the haiku
+ALTER TABLE haikus ADD COLUMN mirror_message BIGINT;
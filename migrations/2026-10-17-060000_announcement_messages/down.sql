@@ -0,0 +1 @@
+ALTER TABLE haikus DROP COLUMN announcement_message;
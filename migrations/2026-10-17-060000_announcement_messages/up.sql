@@ -0,0 +1,3 @@
+-- The message the haiku was announced in, in the channel it was written in, so it can be edited or
+-- deleted along with the haiku
+ALTER TABLE haikus ADD COLUMN announcement_message BIGINT;
@@ -0,0 +1,3 @@
+-- Whether someone's haikus are listed on the HTTP API and in their feed, so they can show them on
+-- their own site
+ALTER TABLE user_preferences ADD COLUMN public_feed BOOLEAN NOT NULL DEFAULT FALSE;
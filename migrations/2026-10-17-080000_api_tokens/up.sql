@@ -0,0 +1,15 @@
+-- Tokens letting outside tools, e.g. a form on a server's website, add and delete its haikus
+-- through the HTTP API. Only a hash of each token is kept, the token itself is shown once.
+CREATE TABLE api_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    server BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    -- NULL if the token never expires
+    expires_at TIMESTAMP
+);
+
+CREATE INDEX api_tokens_server ON api_tokens (server);
@@ -0,0 +1 @@
+DROP TABLE haiku_edits;
@@ -0,0 +1,15 @@
+-- Each change made to a line of a haiku with /edithaiku, with what the line said before, so the
+-- haiku's earlier versions can be shown
+CREATE TABLE haiku_edits (
+    id BIGSERIAL PRIMARY KEY,
+    server BIGINT NOT NULL,
+    haiku_id BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    previous TEXT NOT NULL,
+    content TEXT NOT NULL,
+    editor BIGINT NOT NULL,
+    edited_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (haiku_id, server) REFERENCES haikus (id, server) ON DELETE CASCADE
+);
+
+CREATE INDEX haiku_edits_haiku ON haiku_edits (server, haiku_id);
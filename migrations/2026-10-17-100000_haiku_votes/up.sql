@@ -0,0 +1,14 @@
+-- Who has voted for each haiku, so each person's vote only counts once. haikus.votes stays as a
+-- count of these rows, kept in step when a vote is added, for weighting random picks.
+CREATE TABLE haiku_votes (
+    server BIGINT NOT NULL,
+    haiku_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    voted_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (server, haiku_id, user_id),
+    FOREIGN KEY (haiku_id, server) REFERENCES haikus (id, server) ON DELETE CASCADE
+);
+
+-- Votes so far were counted per click rather than per person, so there's no telling who cast
+-- them. Counting starts again.
+UPDATE haikus SET votes = 0;
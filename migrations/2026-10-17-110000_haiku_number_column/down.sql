@@ -0,0 +1,34 @@
+-- Archived haikus keep their numbers as ids
+ALTER TABLE haiku_lines DROP CONSTRAINT haiku_lines_haiku_id_server_fkey;
+ALTER TABLE haiku_edits DROP CONSTRAINT haiku_edits_haiku_id_server_fkey;
+ALTER TABLE haiku_votes DROP CONSTRAINT haiku_votes_haiku_id_server_fkey;
+
+UPDATE haiku_lines SET haiku_id = -haiku_id;
+UPDATE haiku_lines SET haiku_id = haikus.id
+FROM haikus
+WHERE haikus.server = haiku_lines.server AND haikus.number = -haiku_lines.haiku_id;
+UPDATE haiku_edits SET haiku_id = haikus.id
+FROM haikus
+WHERE haikus.server = haiku_edits.server AND haikus.number = haiku_edits.haiku_id;
+UPDATE haiku_votes SET haiku_id = -haiku_id;
+UPDATE haiku_votes SET haiku_id = haikus.id
+FROM haikus
+WHERE haikus.server = haiku_votes.server AND haikus.number = -haiku_votes.haiku_id;
+
+ALTER TABLE haiku_lines ADD FOREIGN KEY (haiku_id, server)
+    REFERENCES haikus (id, server) ON DELETE CASCADE;
+ALTER TABLE haiku_edits ADD FOREIGN KEY (haiku_id, server)
+    REFERENCES haikus (id, server) ON DELETE CASCADE;
+ALTER TABLE haiku_votes ADD FOREIGN KEY (haiku_id, server)
+    REFERENCES haikus (id, server) ON DELETE CASCADE;
+
+ALTER TABLE haikus DROP COLUMN number;
+
+DELETE FROM haiku_numbers;
+INSERT INTO haiku_numbers (server, last_number)
+SELECT server, MAX(id) FROM (
+    SELECT server, id FROM haikus
+    UNION ALL
+    SELECT server, id FROM archived_haikus
+) AS numbered
+GROUP BY server;
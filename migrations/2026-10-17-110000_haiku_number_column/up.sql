@@ -0,0 +1,66 @@
+-- Haikus saved before haiku_numbers kept the ids they got from the sequence shared by every
+-- server, so a server's numbers didn't start from 1. Number every haiku in its own column instead,
+-- in the order they were written, counting archived haikus too so archiving never reuses a number.
+-- Ids are left to the sequence again and only used to tell rows apart.
+ALTER TABLE haikus ADD COLUMN number BIGINT;
+
+CREATE TEMPORARY TABLE haiku_renumbering AS
+SELECT server, id, archived,
+    ROW_NUMBER() OVER (PARTITION BY server ORDER BY timestamp, id) AS number
+FROM (
+    SELECT server, id, timestamp, FALSE AS archived FROM haikus
+    UNION ALL
+    SELECT server, id, timestamp, TRUE AS archived FROM archived_haikus
+) AS all_haikus;
+
+UPDATE haikus SET number = renumbering.number
+FROM haiku_renumbering renumbering
+WHERE NOT renumbering.archived
+    AND renumbering.server = haikus.server AND renumbering.id = haikus.id;
+
+ALTER TABLE haikus ALTER COLUMN number SET NOT NULL;
+ALTER TABLE haikus ADD CONSTRAINT haikus_number UNIQUE (number, server);
+
+-- Archived haikus are only ever listed by number. Ids are negated first so no two rows share one
+-- part way through.
+UPDATE archived_haikus SET id = -id;
+UPDATE archived_haikus SET id = renumbering.number
+FROM haiku_renumbering renumbering
+WHERE renumbering.archived
+    AND renumbering.server = archived_haikus.server AND renumbering.id = -archived_haikus.id;
+
+-- Lines, edits and votes point at their haiku's number rather than its id
+ALTER TABLE haiku_lines DROP CONSTRAINT haiku_lines_haiku_id_server_fkey;
+ALTER TABLE haiku_edits DROP CONSTRAINT haiku_edits_haiku_id_server_fkey;
+ALTER TABLE haiku_votes DROP CONSTRAINT haiku_votes_haiku_id_server_fkey;
+
+UPDATE haiku_lines SET haiku_id = -haiku_id;
+UPDATE haiku_lines SET haiku_id = haikus.number
+FROM haikus
+WHERE haikus.server = haiku_lines.server AND haikus.id = -haiku_lines.haiku_id;
+UPDATE haiku_edits SET haiku_id = haikus.number
+FROM haikus
+WHERE haikus.server = haiku_edits.server AND haikus.id = haiku_edits.haiku_id;
+UPDATE haiku_votes SET haiku_id = -haiku_id;
+UPDATE haiku_votes SET haiku_id = haikus.number
+FROM haikus
+WHERE haikus.server = haiku_votes.server AND haikus.id = -haiku_votes.haiku_id;
+
+ALTER TABLE haiku_lines ADD FOREIGN KEY (haiku_id, server)
+    REFERENCES haikus (number, server) ON DELETE CASCADE;
+ALTER TABLE haiku_edits ADD FOREIGN KEY (haiku_id, server)
+    REFERENCES haikus (number, server) ON DELETE CASCADE;
+ALTER TABLE haiku_votes ADD FOREIGN KEY (haiku_id, server)
+    REFERENCES haikus (number, server) ON DELETE CASCADE;
+
+DELETE FROM haiku_numbers;
+INSERT INTO haiku_numbers (server, last_number)
+SELECT server, MAX(number) FROM haiku_renumbering GROUP BY server;
+
+-- Ids given out from haiku_numbers may have passed the sequence
+SELECT setval('haikus_id_seq', GREATEST(
+    (SELECT MAX(id) FROM haikus),
+    (SELECT last_value FROM haikus_id_seq)
+));
+
+DROP TABLE haiku_renumbering;
@@ -0,0 +1,3 @@
+ALTER TABLE server_configs DROP COLUMN public_feeds;
+ALTER TABLE user_preferences ADD COLUMN public_feed BOOLEAN NOT NULL DEFAULT FALSE;
+DROP TABLE public_feeds;
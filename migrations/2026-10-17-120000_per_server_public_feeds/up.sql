@@ -0,0 +1,12 @@
+-- Whose haikus are listed on the HTTP API and in a feed, in each server they've opted in for
+CREATE TABLE public_feeds (
+    server BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (server, user_id)
+);
+
+-- Opting in now only applies to one server, so anyone who had opted in everywhere needs to again
+ALTER TABLE user_preferences DROP COLUMN public_feed;
+
+-- Whether a server allows its members' haikus to be public at all
+ALTER TABLE server_configs ADD COLUMN public_feeds BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,49 @@
+//! The secrets behind API tokens. Only a hash of each secret is stored, so someone who can read the
+//! database still can't use the tokens in it.
+
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+/// Marks a secret as a haikubot token, e.g. for secret scanners
+const SECRET_PREFIX: &str = "haikubot_";
+const SECRET_LENGTH: usize = 40;
+
+/// A new secret for a token, to be shown once to whoever made it
+pub fn generate_secret() -> String {
+    let random = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .collect::<String>();
+    format!("{}{}", SECRET_PREFIX, random)
+}
+
+/// The hash a token is stored and looked up by. Secrets are long and random, so unlike passwords
+/// they don't need a salted, slow hash.
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate_secret, hash_secret};
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret();
+        assert!(secret.starts_with("haikubot_"));
+        assert_eq!(secret.len(), "haikubot_".len() + 40);
+        assert!(secret
+            .chars()
+            .all(|c| c == '_' || c.is_ascii_alphanumeric()));
+        assert_ne!(secret, generate_secret());
+    }
+
+    #[test]
+    fn test_hash_secret() {
+        assert_eq!(
+            hash_secret("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(hash_secret("haikubot_a"), hash_secret("haikubot_b"));
+    }
+}
@@ -0,0 +1,170 @@
+//! Mirroring every saved haiku to a server's archive channel through a webhook, giving the server
+//! a gallery of its haikus to browse in Discord, and keeping each mirror in step with its haiku
+
+use crate::{
+    errors::report_error,
+    formatting::{format_haiku, server_wants_plain_text, to_embed_data, FormattedHaiku},
+    i18n::guild_locale,
+    models::{ArchiveWebhook, Haiku},
+    repository::HaikuRepository,
+    retry::with_retry,
+};
+use serde_json::Value;
+use serenity::{
+    client::Context,
+    model::{
+        id::{GuildId, MessageId},
+        misc::Mentionable,
+        webhook::Webhook,
+    },
+};
+
+fn archive(repository: &dyn HaikuRepository, server_id: GuildId) -> Option<ArchiveWebhook> {
+    repository
+        .get_server_config(server_id)
+        .and_then(|config| config.archive)
+}
+
+async fn webhook(ctx: &Context, archive: &ArchiveWebhook) -> serenity::Result<Webhook> {
+    with_retry(&ctx.http, || {
+        ctx.http
+            .get_webhook_with_token(*archive.webhook.as_u64(), &archive.token)
+    })
+    .await
+}
+
+/// The haiku as it's shown in the archive
+async fn mirror(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    id: i64,
+    haiku: &Haiku,
+) -> (Option<String>, Vec<Value>) {
+    let locale = guild_locale(repository, Some(haiku.server));
+    let embed_data = to_embed_data(id, haiku, locale, ctx).await;
+    let formatted = format_haiku(
+        embed_data,
+        server_wants_plain_text(repository, Some(haiku.server)),
+    );
+    let embeds = match &formatted {
+        FormattedHaiku::Embed(embed) => {
+            vec![serde_json::to_value(&embed.0).expect("Could not serialise haiku embed")]
+        }
+        FormattedHaiku::Text(_) => Vec::new(),
+    };
+    (formatted.content(None), embeds)
+}
+
+/// Post a newly saved haiku in the server's archive channel, if it has one, remembering the message
+/// so it can be changed along with the haiku
+pub async fn mirror_haiku(ctx: &Context, repository: &dyn HaikuRepository, id: i64, haiku: &Haiku) {
+    let archive = match archive(repository, haiku.server) {
+        Some(archive) => archive,
+        None => return,
+    };
+    let (content, embeds) = mirror(ctx, repository, id, haiku).await;
+    let result = match webhook(ctx, &archive).await {
+        Ok(webhook) => {
+            with_retry(&ctx.http, || {
+                webhook.execute(&ctx.http, true, |message| {
+                    if let Some(content) = &content {
+                        message
+                            .content(content)
+                            .allowed_mentions(|mentions| mentions.empty_parse());
+                    }
+                    message.embeds(embeds.clone())
+                })
+            })
+            .await
+        }
+        Err(why) => Err(why),
+    };
+    match result {
+        Ok(Some(message)) => repository.set_mirror_message(haiku.server, id, Some(message.id)),
+        Ok(None) => {}
+        Err(why) => {
+            println!("Failed to mirror haiku {} to archive: {:?}", id, why);
+            let locale = guild_locale(repository, Some(haiku.server));
+            let content = t!(
+                locale,
+                "error_channel.archive_failed",
+                id = id,
+                channel = archive.channel.mention()
+            );
+            report_error(ctx, repository, haiku.server, &content).await;
+        }
+    }
+}
+
+/// Show a haiku's mirror in the archive as the haiku is now, e.g. after a content warning is added
+pub async fn update_mirror(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    id: i64,
+) {
+    let (archive, message_id, haiku) = match (
+        archive(repository, server_id),
+        repository.get_mirror_message(server_id, id),
+        repository.get_haiku(server_id, id),
+    ) {
+        (Some(archive), Some(message_id), Some((_, haiku))) => (archive, message_id, haiku),
+        _ => return,
+    };
+    let (content, embeds) = mirror(ctx, repository, id, &haiku).await;
+    let result = match webhook(ctx, &archive).await {
+        Ok(webhook) => with_retry(&ctx.http, || {
+            webhook.edit_message(&ctx.http, message_id, |message| {
+                message
+                    .content(content.as_deref().unwrap_or_default())
+                    .allowed_mentions(|mentions| mentions.empty_parse())
+                    .embeds(embeds.clone())
+            })
+        })
+        .await
+        .map(|_| ()),
+        Err(why) => Err(why),
+    };
+    // The mirror may be in an archive channel the server has since moved away from
+    if let Err(why) = result {
+        println!("Failed to update mirror of haiku {}: {:?}", id, why);
+    }
+}
+
+/// Take a deleted haiku's mirror out of the archive. The message id has to be looked up before the
+/// haiku is deleted.
+pub async fn delete_mirror(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    message_id: MessageId,
+) {
+    let archive = match archive(repository, server_id) {
+        Some(archive) => archive,
+        None => return,
+    };
+    let result = match webhook(ctx, &archive).await {
+        Ok(webhook) => {
+            with_retry(&ctx.http, || webhook.delete_message(&ctx.http, message_id)).await
+        }
+        Err(why) => Err(why),
+    };
+    if let Err(why) = result {
+        println!("Failed to delete mirror message {}: {:?}", message_id, why);
+    }
+}
+
+/// Delete the webhook of an archive the server has stopped using. Its messages stay in the
+/// channel, but can't be changed along with their haikus any more.
+pub async fn remove_webhook(ctx: &Context, archive: &ArchiveWebhook) {
+    let result = match webhook(ctx, archive).await {
+        Ok(webhook) => with_retry(&ctx.http, || webhook.delete(&ctx.http)).await,
+        Err(why) => Err(why),
+    };
+    if let Err(why) = result {
+        println!(
+            "Failed to delete archive webhook {}: {:?}",
+            archive.webhook, why
+        );
+    }
+}
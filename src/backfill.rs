@@ -0,0 +1,91 @@
+//! Checking messages sent while the bot was offline for haikus, once it's connected again
+
+use crate::{
+    bulk::{bulk, Bucket},
+    config::Config,
+    detection::pipeline::IncomingMessage,
+    outage,
+    repository::repository,
+    HaikuTracker,
+};
+use serenity::{client::Context, model::channel::Message};
+
+/// Discord won't send more than this many messages at once
+const MAX_BACKFILL: u64 = 100;
+
+/// Fetch the messages sent since the last one seen in each channel with recent lines, and run
+/// them through the detection pipeline in the order they were sent. Only channels whose recent
+/// lines were kept, e.g. restored from a checkpoint, are checked.
+pub async fn backfill_missed_messages(ctx: Context) {
+    let (depth, can_read_messages, pipeline) = {
+        let data = ctx.data.read().await;
+        let config = data.get::<Config>().expect("Expected Config in TypeMap");
+        (
+            config.detection.backfill_messages.min(MAX_BACKFILL),
+            config.intents.message_content,
+            data.get::<HaikuTracker>()
+                .expect("Expected HaikuTracker in TypeMap")
+                .clone(),
+        )
+    };
+    if depth == 0 || !can_read_messages || outage::is_down(&ctx).await {
+        return;
+    }
+    let repository = repository(&ctx).await;
+    let bulk = bulk(&ctx).await;
+    let bot_id = ctx.cache.current_user_id().await;
+    let mut checked = 0;
+    for (channel_id, last_seen) in pipeline.last_seen() {
+        let server_id = match channel_id.to_channel(&ctx).await {
+            Ok(channel) => match channel.guild() {
+                Some(channel) => channel.guild_id,
+                None => continue,
+            },
+            Err(why) => {
+                println!(
+                    "Could not fetch channel {} to backfill: {:?}",
+                    channel_id, why
+                );
+                continue;
+            }
+        };
+        if !repository.is_detection_enabled(server_id, channel_id) {
+            continue;
+        }
+        let messages = bulk
+            .run(&ctx.http, Bucket::ChannelHistory(channel_id), || {
+                channel_id.messages(&ctx.http, |retriever| {
+                    retriever.after(last_seen).limit(depth)
+                })
+            })
+            .await;
+        let mut messages = match messages {
+            Ok(messages) => messages,
+            Err(why) => {
+                println!(
+                    "Could not fetch missed messages in {}: {:?}",
+                    channel_id, why
+                );
+                continue;
+            }
+        };
+        messages.sort_by_key(|message: &Message| message.id);
+        for message in messages
+            .iter()
+            .filter(|message| message.author.id != bot_id)
+        {
+            // Haikus saved from the same message before aren't saved again
+            pipeline
+                .run_missed(
+                    &ctx,
+                    &*repository,
+                    IncomingMessage::in_server(message, server_id),
+                )
+                .await;
+            checked += 1;
+        }
+    }
+    if checked > 0 {
+        println!("Checked {} missed messages for haikus", checked);
+    }
+}
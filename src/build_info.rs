@@ -0,0 +1,8 @@
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short hash of the commit the bot was built from, set by build.rs
+pub const GIT_HASH: &str = env!("HAIKUBOT_GIT_HASH");
+pub const REPOSITORY_URL: &str = "https://github.com/bumblepie/haikubot-rs";
+
+pub fn version_string() -> String {
+    format!("v{} ({})", VERSION, GIT_HASH)
+}
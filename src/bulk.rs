@@ -0,0 +1,125 @@
+//! Pacing for features that make lots of Discord requests at once, like scanning a channel's
+//! history or DMing every author of a haiku, so they're spread out over time rather than using up
+//! the rate limits that commands and announcements need
+
+use crate::retry::with_retry;
+use serenity::{
+    client::Context,
+    http::Http,
+    model::id::ChannelId,
+    prelude::{Mutex, TypeMapKey},
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Between any two bulk requests, whatever they're for
+const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The rate limit a bulk request counts against, following Discord's buckets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bucket {
+    /// Reading a channel's messages
+    ChannelHistory(ChannelId),
+    /// Opening DM channels and sending DMs, which Discord limits across the whole bot
+    DirectMessages,
+}
+
+impl Bucket {
+    /// Between two requests in the bucket
+    fn interval(&self) -> Duration {
+        match self {
+            Bucket::ChannelHistory(_) => Duration::from_secs(1),
+            Bucket::DirectMessages => Duration::from_secs(1),
+        }
+    }
+}
+
+/// When the next request in each bucket, and in all of them, may be made
+#[derive(Default)]
+pub struct Schedule {
+    next: HashMap<Bucket, Instant>,
+    next_any: Option<Instant>,
+}
+
+impl Schedule {
+    /// Book the earliest time a request in the bucket may be made, from `now`
+    pub fn reserve(&mut self, bucket: Bucket, now: Instant) -> Instant {
+        self.next.retain(|_, next| *next > now);
+        let slot = [self.next.get(&bucket).copied(), self.next_any]
+            .iter()
+            .flatten()
+            .fold(now, |slot, next| slot.max(*next));
+        self.next.insert(bucket, slot + bucket.interval());
+        self.next_any = Some(slot + MIN_INTERVAL);
+        slot
+    }
+}
+
+#[derive(Default)]
+pub struct BulkExecutor {
+    schedule: Mutex<Schedule>,
+}
+
+pub struct BulkRequests;
+impl TypeMapKey for BulkRequests {
+    type Value = Arc<BulkExecutor>;
+}
+
+pub async fn bulk(ctx: &Context) -> Arc<BulkExecutor> {
+    ctx.data
+        .read()
+        .await
+        .get::<BulkRequests>()
+        .expect("Expected BulkRequests in TypeMap")
+        .clone()
+}
+
+impl BulkExecutor {
+    /// Make a request once it's its turn, retrying it like any other if it fails
+    pub async fn run<T, F, Fut>(
+        &self,
+        http: &Http,
+        bucket: Bucket,
+        request: F,
+    ) -> serenity::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = serenity::Result<T>>,
+    {
+        let slot = self.schedule.lock().await.reserve(bucket, Instant::now());
+        tokio::time::sleep_until(slot.into()).await;
+        with_retry(http, request).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bucket, Schedule, MIN_INTERVAL};
+    use serenity::model::id::ChannelId;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_reserve() {
+        let now = Instant::now();
+        let mut schedule = Schedule::default();
+        let history = Bucket::ChannelHistory(ChannelId(1));
+        assert_eq!(schedule.reserve(history, now), now);
+        assert_eq!(schedule.reserve(history, now), now + Duration::from_secs(1));
+        // Other buckets only wait for the gap between any two requests
+        assert_eq!(
+            schedule.reserve(Bucket::ChannelHistory(ChannelId(2)), now),
+            now + Duration::from_secs(1) + MIN_INTERVAL
+        );
+        assert_eq!(
+            schedule.reserve(Bucket::DirectMessages, now),
+            now + Duration::from_secs(1) + MIN_INTERVAL * 2
+        );
+        // Nothing to wait for once the bookings have passed
+        let later = now + Duration::from_secs(10);
+        assert_eq!(schedule.reserve(history, later), later);
+    }
+}
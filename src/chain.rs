@@ -0,0 +1,192 @@
+//! The haiku chain mini-game: a channel has a few minutes to write as many haikus on a theme as
+//! it can, which are then posted together
+
+use crate::{
+    counting::{is_haiku, split_into_haiku, NotHaiku},
+    i18n::Locale,
+};
+use chrono::{DateTime, Utc};
+use serenity::model::{
+    id::{ChannelId, UserId},
+    misc::Mentionable,
+};
+use std::collections::HashMap;
+
+/// Themes to pick from when a chain is started without one
+pub const THEMES: &[&str] = &[
+    "autumn leaves",
+    "the sea",
+    "morning coffee",
+    "first snow",
+    "the moon",
+    "a long journey",
+    "city lights",
+    "rain",
+    "cats",
+    "silence",
+];
+
+/// How many entries are shown when a chain is posted, so it fits in one message
+const MAX_ENTRIES_SHOWN: usize = 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainEntry {
+    pub author: UserId,
+    pub lines: [String; 3],
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub theme: String,
+    pub ends_at: DateTime<Utc>,
+    pub entries: Vec<ChainEntry>,
+}
+
+/// The chain running in each channel, if any
+#[derive(Default)]
+pub struct ChainGames {
+    chains: HashMap<ChannelId, Chain>,
+}
+
+impl ChainGames {
+    pub fn new() -> Self {
+        ChainGames::default()
+    }
+
+    /// Start a chain in a channel, returning false if there's already one running there
+    pub fn start(&mut self, channel: ChannelId, theme: String, ends_at: DateTime<Utc>) -> bool {
+        if self.chains.contains_key(&channel) {
+            return false;
+        }
+        self.chains.insert(
+            channel,
+            Chain {
+                theme,
+                ends_at,
+                entries: Vec::new(),
+            },
+        );
+        true
+    }
+
+    /// Add a message to the channel's chain if it's a haiku sent before the chain ends,
+    /// returning its position in the chain (counting from 1)
+    pub fn submit(
+        &mut self,
+        channel: ChannelId,
+        author: UserId,
+        content: &str,
+        sent_at: DateTime<Utc>,
+    ) -> Option<usize> {
+        let chain = self.chains.get_mut(&channel)?;
+        if sent_at >= chain.ends_at {
+            return None;
+        }
+        let lines = parse_entry(content).ok()?;
+        chain.entries.push(ChainEntry { author, lines });
+        Some(chain.entries.len())
+    }
+
+    /// End the chain in a channel, returning it
+    pub fn finish(&mut self, channel: ChannelId) -> Option<Chain> {
+        self.chains.remove(&channel)
+    }
+}
+
+/// The lines of a chain entry, written either one per line or all on one line
+pub fn parse_entry(content: &str) -> Result<[String; 3], NotHaiku> {
+    let lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<String>>();
+    if lines.len() == 1 {
+        let [line_1, line_2, line_3] = split_into_haiku(&lines[0])?;
+        return Ok([line_1.text(), line_2.text(), line_3.text()]);
+    }
+    is_haiku(&lines)?;
+    Ok([lines[0].clone(), lines[1].clone(), lines[2].clone()])
+}
+
+/// A finished chain's entries, one after another
+pub fn format_chain(chain: &Chain, locale: Locale) -> String {
+    if chain.entries.is_empty() {
+        return t!(locale, "chain.no_entries");
+    }
+    let mut entries = chain
+        .entries
+        .iter()
+        .take(MAX_ENTRIES_SHOWN)
+        .enumerate()
+        .map(|(index, entry)| {
+            format!(
+                "{}\n{}",
+                t!(
+                    locale,
+                    "chain.entry",
+                    number = index + 1,
+                    author = entry.author.mention()
+                ),
+                entry.lines.join("\n")
+            )
+        })
+        .collect::<Vec<String>>();
+    if chain.entries.len() > MAX_ENTRIES_SHOWN {
+        entries.push(t!(
+            locale,
+            "chain.more_entries",
+            count = chain.entries.len() - MAX_ENTRIES_SHOWN
+        ));
+    }
+    entries.join("\n\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_chain, parse_entry, ChainGames};
+    use crate::{counting::NotHaiku, i18n::Locale};
+    use chrono::{Duration, Utc};
+    use serenity::model::id::{ChannelId, UserId};
+
+    const ENTRY: &str = "An old silent pond\nA frog jumps into the pond\nSplash! Silence again";
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(parse_entry(ENTRY).unwrap()[1], "A frog jumps into the pond");
+        assert_eq!(
+            parse_entry(&ENTRY.replace('\n', " ")).unwrap()[2],
+            "Splash! Silence again"
+        );
+        assert_eq!(
+            parse_entry("An old silent pond\nA frog jumps into the pond"),
+            Err(NotHaiku::WrongLineCount(2))
+        );
+    }
+
+    #[test]
+    fn test_chain_games() {
+        let mut games = ChainGames::new();
+        let now = Utc::now();
+        let ends_at = now + Duration::minutes(5);
+        assert!(games.start(ChannelId(1), "the pond".to_owned(), ends_at));
+        assert!(!games.start(ChannelId(1), "rain".to_owned(), ends_at));
+
+        assert_eq!(games.submit(ChannelId(1), UserId(100), ENTRY, now), Some(1));
+        assert_eq!(games.submit(ChannelId(1), UserId(200), "hello", now), None);
+        assert_eq!(games.submit(ChannelId(2), UserId(200), ENTRY, now), None);
+        // Too late
+        assert_eq!(
+            games.submit(ChannelId(1), UserId(200), ENTRY, ends_at),
+            None
+        );
+        assert_eq!(games.submit(ChannelId(1), UserId(200), ENTRY, now), Some(2));
+
+        let chain = games.finish(ChannelId(1)).unwrap();
+        assert_eq!(chain.entries.len(), 2);
+        assert_eq!(chain.entries[1].author, UserId(200));
+        assert!(format_chain(&chain, Locale::English).contains("<@200>"));
+        assert!(games.finish(ChannelId(1)).is_none());
+        assert!(games.start(ChannelId(1), "rain".to_owned(), ends_at));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{
+    build_info::{version_string, REPOSITORY_URL},
+    formatting::{format_duration, format_number},
+    framework::response::respond,
+    i18n::{guild_locale, Region},
+    repository::repository,
+    UptimeStart,
+};
+use chrono::Utc;
+use serenity::{
+    async_trait, builder::CreateEmbed, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Show information about the bot
+#[derive(Command)]
+#[name = "about"]
+pub struct AboutCommand;
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for AboutCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let uptime = {
+            let data = ctx.data.read().await;
+            let uptime_start = data
+                .get::<UptimeStart>()
+                .expect("Expected UptimeStart in TypeMap")
+                .clone();
+            Utc::now().signed_duration_since(uptime_start)
+        };
+        let guild_count = ctx.cache.guilds().await.len();
+        let haiku_count = repository.count_all_haikus();
+        let bot_icon_url = ctx.cache.current_user().await.avatar_url();
+
+        let locale = guild_locale(&*repository, command.guild_id);
+        let mut embed = CreateEmbed::default();
+        embed.title("Haikubot");
+        embed.description(t!(locale, "about.description"));
+        embed.url(REPOSITORY_URL);
+        if let Some(bot_icon_url) = bot_icon_url {
+            embed.thumbnail(bot_icon_url);
+        }
+        embed.field(t!(locale, "about.version"), version_string(), true);
+        embed.field(
+            t!(locale, "about.uptime"),
+            format_duration(uptime, locale),
+            true,
+        );
+        let region = Region::from_discord(&command.locale);
+        embed.field(
+            t!(locale, "about.servers"),
+            format_number(guild_count as i64, region),
+            true,
+        );
+        embed.field(
+            t!(locale, "about.haikus"),
+            format_number(haiku_count, region),
+            true,
+        );
+        embed.field(
+            t!(locale, "about.links"),
+            t!(locale, "about.source", url = REPOSITORY_URL),
+            false,
+        );
+        let result = respond(ctx, command, |message| message.add_embed(embed)).await;
+        if let Err(why) = result {
+            println!("Could not send about message: {:?}", why);
+        }
+        Ok(())
+    }
+}
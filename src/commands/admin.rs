@@ -0,0 +1,263 @@
+use crate::{
+    commands::reregister_all,
+    formatting::{format_bytes, format_duration},
+    framework::{
+        options::get_option,
+        progress::Progress,
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::Locale,
+    jobs::record_run,
+    maintenance::run_maintenance,
+    reload::reload,
+    repository::repository,
+    revalidation::revalidate_haikus,
+    runtime_stats::{memory_usage, RuntimeStats},
+    schedule::Job,
+    MessageComponentInteractionHandlers, RegisteredCommands, UptimeStart,
+};
+use chrono::Utc;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::{
+        application_command::{ApplicationCommandInteraction, ApplicationCommandOptionType},
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+const MAX_INVALID_SHOWN: usize = 20;
+
+async fn reply(ctx: &Context, command: &ApplicationCommandInteraction, content: String) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(content)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not send admin message: {:?}", why);
+    }
+}
+
+/// Reload the config file and the syllable dictionary
+#[derive(Command)]
+#[name = "admin-reload"]
+pub struct AdminReloadCommand;
+
+impl AdminReloadCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("admin-reload")
+            .subcommand(&["admin", "reload"])
+            .owner_only()
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for AdminReloadCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let content = reload(ctx).await;
+        reply(ctx, command, content).await;
+        Ok(())
+    }
+}
+
+/// Register the bot's commands with Discord again
+#[derive(Command)]
+#[name = "admin-register"]
+pub struct AdminRegisterCommand;
+
+impl AdminRegisterCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("admin-register")
+            .subcommand(&["admin", "register"])
+            .owner_only()
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for AdminRegisterCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let commands = reregister_all(ctx).await;
+        reply(ctx, command, format!("Registered {} commands", commands)).await;
+        Ok(())
+    }
+}
+
+/// Vacuum and analyze the database, and prune stale pagination handlers
+#[derive(Command)]
+#[name = "admin-maintenance"]
+pub struct AdminMaintenanceCommand;
+
+impl AdminMaintenanceCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("admin-maintenance")
+            .subcommand(&["admin", "maintenance"])
+            .owner_only()
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for AdminMaintenanceCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let report = run_maintenance(ctx).await;
+        // Counts as the scheduled run, so it isn't done again straight away
+        record_run(ctx, Job::Maintenance).await;
+        reply(ctx, command, report.summary()).await;
+        Ok(())
+    }
+}
+
+/// Check every stored haiku against the current syllable counter
+#[derive(Command)]
+#[name = "admin-revalidate"]
+pub struct AdminRevalidateCommand;
+
+impl AdminRevalidateCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("admin-revalidate")
+            .subcommand(&["admin", "revalidate"])
+            .option(OptionSpec::extra(
+                "flag",
+                ApplicationCommandOptionType::Boolean,
+                "Flag the haikus that no longer validate",
+            ))
+            .owner_only()
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for AdminRevalidateCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let flag = get_option::<bool>(command, "flag").unwrap_or(false);
+        let repository = repository(ctx).await;
+        let total = repository.count_all_haikus() as u64;
+        let progress = match Progress::start(
+            ctx,
+            command,
+            true,
+            Locale::English,
+            "Revalidating haikus".to_owned(),
+            0,
+            total,
+        )
+        .await
+        {
+            Some(progress) => progress,
+            None => return Ok(()),
+        };
+        let checking = progress.clone();
+        let report = match tokio::task::spawn_blocking(move || {
+            revalidate_haikus(&*repository, flag, |checked, total| {
+                checking.update(
+                    checked as u64,
+                    format!("Revalidating haikus: checked {} of {}", checked, total),
+                );
+                !checking.is_cancelled()
+            })
+        })
+        .await
+        {
+            Ok(report) => report,
+            Err(why) => {
+                let content = format!("Revalidation failed: {:?}", why);
+                progress.finish(ctx, content).await;
+                return Ok(());
+            }
+        };
+        let mut lines = vec![report.summary()];
+        lines.extend(
+            report
+                .invalid
+                .iter()
+                .take(MAX_INVALID_SHOWN)
+                .map(|(server_id, id)| format!("Haiku #{} in server {}", id, server_id)),
+        );
+        if flag && !report.invalid.is_empty() {
+            lines.push("These haikus have been flagged".to_owned());
+        }
+        progress.finish(ctx, lines.join("\n")).await;
+        Ok(())
+    }
+}
+
+/// Show the bot's runtime stats
+#[derive(Command)]
+#[name = "admin-stats"]
+pub struct AdminStatsCommand;
+
+impl AdminStatsCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("admin-stats")
+            .subcommand(&["admin", "stats"])
+            .owner_only()
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for AdminStatsCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let stats = {
+            let data = ctx.data.read().await;
+            let uptime_start = *data
+                .get::<UptimeStart>()
+                .expect("Expected UptimeStart in TypeMap");
+            let runtime_stats = data
+                .get::<RuntimeStats>()
+                .expect("Expected RuntimeStats in TypeMap");
+            let registered_commands = data
+                .get::<RegisteredCommands>()
+                .expect("Expected RegisteredCommands in TypeMap")
+                .len();
+            let component_handlers = data
+                .get::<MessageComponentInteractionHandlers>()
+                .expect("Expected Handlers in TypeMap")
+                .len();
+            vec![
+                format!(
+                    "Uptime: {}",
+                    format_duration(
+                        Utc::now().signed_duration_since(uptime_start),
+                        Locale::English
+                    )
+                ),
+                format!("Guilds: {}", ctx.cache.guilds().await.len()),
+                format!("Messages processed: {}", runtime_stats.messages_processed()),
+                format!("Haikus detected: {}", runtime_stats.haikus_detected()),
+                format!(
+                    "Memory usage: {}",
+                    memory_usage()
+                        .map(format_bytes)
+                        .unwrap_or("unknown".to_owned())
+                ),
+                format!("Registered commands: {}", registered_commands),
+                format!("Component handlers: {}", component_handlers),
+            ]
+        };
+        reply(ctx, command, stats.join("\n")).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    api_tokens::{generate_secret, hash_secret},
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    models::{ApiScope, ApiToken},
+    repository::repository,
+};
+use chrono::{DateTime, Duration, Utc};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+        Permissions,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Tokens last at most a year, unless they're made to never expire
+const MAX_TOKEN_DAYS: i64 = 365;
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The scopes a token can be made with
+enum Scopes {
+    Submit,
+    Delete,
+    SubmitAndDelete,
+}
+
+impl Scopes {
+    fn scopes(&self) -> Vec<ApiScope> {
+        match self {
+            Scopes::Submit => vec![ApiScope::Submit],
+            Scopes::Delete => vec![ApiScope::Delete],
+            Scopes::SubmitAndDelete => vec![ApiScope::Submit, ApiScope::Delete],
+        }
+    }
+}
+
+impl Choice for Scopes {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("Add haikus", "submit"),
+            ("Delete haikus", "delete"),
+            ("Add and delete haikus", "submit_delete"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "submit" => Some(Scopes::Submit),
+            "delete" => Some(Scopes::Delete),
+            "submit_delete" => Some(Scopes::SubmitAndDelete),
+            _ => None,
+        }
+    }
+}
+
+fn describe_scopes(scopes: &[ApiScope], locale: Locale) -> String {
+    scopes
+        .iter()
+        .map(|scope| match scope {
+            ApiScope::Submit => t!(locale, "apitoken.scope_submit"),
+            ApiScope::Delete => t!(locale, "apitoken.scope_delete"),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn describe_expiry(token: &ApiToken, now: DateTime<Utc>, locale: Locale) -> String {
+    match token.expires_at {
+        None => t!(locale, "apitoken.never_expires"),
+        Some(expires_at) if token.is_expired(now) => t!(
+            locale,
+            "apitoken.expired",
+            date = expires_at.format("%Y-%m-%d")
+        ),
+        Some(expires_at) => t!(
+            locale,
+            "apitoken.expires",
+            date = expires_at.format("%Y-%m-%d")
+        ),
+    }
+}
+
+/// Tokens are secrets, so only whoever ran the command sees them
+async fn reply(ctx: &Context, command: &ApplicationCommandInteraction, content: String) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(content)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not send API token message: {:?}", why);
+    }
+}
+
+/// Make a token that lets a tool outside Discord, like a form on your website, add or delete this
+/// server's haikus through the HTTP API
+#[derive(Command)]
+#[name = "apitoken-create"]
+pub struct ApiTokenCreateCommand {
+    /// What the token is for, e.g. "Website form"
+    name: String,
+    /// What the token can be used for
+    scopes: String,
+    /// How many days the token works for, or 0 for it to never expire
+    days: i64,
+}
+
+impl ApiTokenCreateCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("apitoken-create")
+            .subcommand(&["apitoken", "create"])
+            .option(OptionSpec::new("scopes").choices::<Scopes>())
+            .option(OptionSpec::new("days").default(30))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ApiTokenCreateCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let (server_id, scopes) = match (command.guild_id, Scopes::from_value(&self.scopes)) {
+            (Some(server_id), Some(scopes)) => (server_id, scopes),
+            _ => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, Some(server_id));
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            let content = t!(locale, "apitoken.invalid_name", max = MAX_NAME_LENGTH);
+            reply(ctx, command, content).await;
+            return Ok(());
+        }
+        if !(0..=MAX_TOKEN_DAYS).contains(&self.days) {
+            let content = t!(locale, "apitoken.invalid_days", max = MAX_TOKEN_DAYS);
+            reply(ctx, command, content).await;
+            return Ok(());
+        }
+        let now = Utc::now();
+        let token = ApiToken {
+            server: server_id,
+            name: name.to_owned(),
+            scopes: scopes.scopes(),
+            created_by: command.user.id,
+            created_at: now,
+            expires_at: match self.days {
+                0 => None,
+                days => Some(now + Duration::days(days)),
+            },
+        };
+        let secret = generate_secret();
+        let id = repository.create_api_token(&token, &hash_secret(&secret));
+        println!(
+            "{} made API token #{} in server {}",
+            command.user.id, id, server_id
+        );
+        let content = t!(
+            locale,
+            "apitoken.created",
+            id = id,
+            name = token.name,
+            scopes = describe_scopes(&token.scopes, locale),
+            expiry = describe_expiry(&token, now, locale),
+            token = secret
+        );
+        reply(ctx, command, content).await;
+        Ok(())
+    }
+}
+
+/// List this server's API tokens, without the tokens themselves
+#[derive(Command)]
+#[name = "apitoken-list"]
+pub struct ApiTokenListCommand;
+
+impl ApiTokenListCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("apitoken-list")
+            .subcommand(&["apitoken", "list"])
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ApiTokenListCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, Some(server_id));
+        let tokens = repository.get_api_tokens(server_id);
+        let content = if tokens.is_empty() {
+            t!(locale, "apitoken.none")
+        } else {
+            let now = Utc::now();
+            let mut lines = vec![t!(locale, "apitoken.list_title")];
+            lines.extend(tokens.iter().map(|(id, token)| {
+                t!(
+                    locale,
+                    "apitoken.list_entry",
+                    id = id,
+                    name = token.name,
+                    scopes = describe_scopes(&token.scopes, locale),
+                    expiry = describe_expiry(token, now, locale)
+                )
+            }));
+            lines.join("\n")
+        };
+        reply(ctx, command, content).await;
+        Ok(())
+    }
+}
+
+/// Stop one of this server's API tokens from working
+#[derive(Command)]
+#[name = "apitoken-revoke"]
+pub struct ApiTokenRevokeCommand {
+    /// Id of the token, from /apitoken list
+    id: i64,
+}
+
+impl ApiTokenRevokeCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("apitoken-revoke")
+            .subcommand(&["apitoken", "revoke"])
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ApiTokenRevokeCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, Some(server_id));
+        let content = if repository.delete_api_token(server_id, self.id) {
+            println!(
+                "{} revoked API token #{} in server {}",
+                command.user.id, self.id, server_id
+            );
+            t!(locale, "apitoken.revoked", id = self.id)
+        } else {
+            t!(locale, "apitoken.not_found", id = self.id)
+        };
+        reply(ctx, command, content).await;
+        Ok(())
+    }
+}
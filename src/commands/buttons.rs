@@ -0,0 +1,324 @@
+use crate::{
+    commands::random::next_random_haiku,
+    formatting::{
+        format_edit_history, format_haiku, format_haiku_quote, to_embed_data, wants_plain_text,
+    },
+    framework::components::{custom_id, parse_custom_id, ComponentHandler},
+    i18n::{guild_locale, Locale},
+    models::Notification,
+    notifications::{crossed_vote_threshold, notify_authors},
+    repository::{repository, HaikuRepository},
+    retry::with_retry,
+};
+use serenity::{
+    async_trait,
+    builder::CreateComponents,
+    client::Context,
+    model::{
+        channel::Message,
+        id::GuildId,
+        interactions::{
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+};
+
+pub const HAIKU_COMPONENT_PREFIX: &str = "haiku";
+
+/// Whether a haiku has been changed with /edithaiku, so has a history to show
+pub fn is_edited(repository: &dyn HaikuRepository, server_id: GuildId, id: i64) -> bool {
+    !repository.get_haiku_edits(server_id, id).is_empty()
+}
+
+/// The buttons shown under every haiku embed, with one for its history if it's been edited
+pub fn haiku_buttons(
+    components: &mut CreateComponents,
+    id: i64,
+    edited: bool,
+    locale: Locale,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id(custom_id(HAIKU_COMPONENT_PREFIX, "random"))
+                .label(t!(locale, "buttons.random"))
+                .style(ButtonStyle::Primary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id(custom_id(
+                    HAIKU_COMPONENT_PREFIX,
+                    &format!("bookmark:{}", id),
+                ))
+                .label(t!(locale, "buttons.bookmark"))
+                .style(ButtonStyle::Secondary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id(custom_id(HAIKU_COMPONENT_PREFIX, &format!("share:{}", id)))
+                .label(t!(locale, "buttons.share"))
+                .style(ButtonStyle::Secondary)
+        });
+        if edited {
+            row.create_button(|button| {
+                button
+                    .custom_id(custom_id(
+                        HAIKU_COMPONENT_PREFIX,
+                        &format!("history:{}", id),
+                    ))
+                    .label(t!(locale, "buttons.history"))
+                    .style(ButtonStyle::Secondary)
+            });
+        }
+        row
+    })
+}
+
+/// The buttons under a haiku shown without its authors: one to reveal them, then the usual ones
+pub fn hidden_author_buttons(
+    components: &mut CreateComponents,
+    id: i64,
+    edited: bool,
+    locale: Locale,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id(custom_id(HAIKU_COMPONENT_PREFIX, &format!("author:{}", id)))
+                .label(t!(locale, "buttons.reveal_author"))
+                .style(ButtonStyle::Success)
+        })
+    });
+    haiku_buttons(components, id, edited, locale)
+}
+
+/// A button that shows the whole haiku a line shown on its own (e.g. by /randomline) came from
+pub fn reveal_button(
+    components: &mut CreateComponents,
+    id: i64,
+    locale: Locale,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id(custom_id(HAIKU_COMPONENT_PREFIX, &format!("reveal:{}", id)))
+                .label(t!(locale, "buttons.reveal"))
+                .style(ButtonStyle::Secondary)
+        })
+    })
+}
+
+/// Handles the buttons from [haiku_buttons], [hidden_author_buttons] and [reveal_button] on any
+/// message, however old
+pub struct HaikuButtonHandler;
+
+#[async_trait]
+impl ComponentHandler for HaikuButtonHandler {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        original_message: &mut Message,
+    ) {
+        let server_id = match interaction.guild_id {
+            Some(server_id) => server_id,
+            None => return,
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, Some(server_id));
+        let (_, action) = parse_custom_id(&interaction.data.custom_id);
+        let (action, id) = match action.split_once(':') {
+            Some((action, id)) => (action, id.parse::<i64>().ok()),
+            None => (action, None),
+        };
+        let haiku_and_id = match (action, id) {
+            ("random", _) => next_random_haiku(ctx, &*repository, server_id, None).await,
+            (_, Some(id)) => repository.get_haiku(server_id, id),
+            _ => None,
+        };
+        let (id, haiku) = match haiku_and_id {
+            Some(haiku_and_id) => haiku_and_id,
+            None => {
+                let content = match id {
+                    Some(id) => t!(locale, "errors.haiku_not_found", id = id),
+                    None => t!(locale, "buttons.no_haikus"),
+                };
+                reply_privately(ctx, interaction, content).await;
+                return;
+            }
+        };
+        let edited = is_edited(&*repository, server_id, id);
+
+        match action {
+            "random" | "reveal" => {
+                let embed_data = to_embed_data(id, &haiku, locale, ctx).await;
+                let plain_text =
+                    wants_plain_text(&*repository, interaction.user.id, Some(server_id));
+                let formatted = format_haiku(embed_data, plain_text);
+                let result = with_retry(&ctx.http, || {
+                    interaction.create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::ChannelMessageWithSource)
+                            .interaction_response_data(|message| {
+                                if let Some(content) = formatted.content(None) {
+                                    message
+                                        .content(content)
+                                        .allowed_mentions(|mentions| mentions.empty_parse());
+                                }
+                                if let Some(embed) = formatted.embed() {
+                                    message.add_embed(embed);
+                                }
+                                message.components(|components| {
+                                    haiku_buttons(components, id, edited, locale)
+                                })
+                            })
+                    })
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Failed to send haiku msg: {:?}", why);
+                }
+            }
+            "author" => {
+                // Shown to everyone, as they've all been guessing, so it stays as it was sent
+                let embed_data = to_embed_data(id, &haiku, locale, ctx).await;
+                let formatted = format_haiku(embed_data, original_message.embeds.is_empty());
+                let result = with_retry(&ctx.http, || {
+                    interaction.create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|message| {
+                                if let Some(content) = formatted.content(None) {
+                                    message
+                                        .content(content)
+                                        .allowed_mentions(|mentions| mentions.empty_parse());
+                                }
+                                if let Some(embed) = formatted.embed() {
+                                    message.add_embed(embed);
+                                }
+                                message.components(|components| {
+                                    haiku_buttons(components, id, edited, locale)
+                                })
+                            })
+                    })
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Failed to reveal haiku author: {:?}", why);
+                }
+            }
+            "bookmark" => {
+                let embed_data = to_embed_data(id, &haiku, locale, ctx).await;
+                let plain_text =
+                    wants_plain_text(&*repository, interaction.user.id, Some(server_id));
+                let formatted = format_haiku(embed_data, plain_text);
+                let result = with_retry(&ctx.http, || {
+                    interaction.user.direct_message(&ctx.http, |message| {
+                        if let Some(content) = formatted.content(None) {
+                            message
+                                .content(content)
+                                .allowed_mentions(|mentions| mentions.empty_parse());
+                        }
+                        if let Some(embed) = formatted.embed() {
+                            message.set_embed(embed);
+                        }
+                        message
+                    })
+                })
+                .await;
+                let bookmarked = match result {
+                    Ok(_) => true,
+                    Err(why) => {
+                        println!("Could not send bookmarked haiku: {:?}", why);
+                        false
+                    }
+                };
+                let content = match bookmarked {
+                    true => t!(locale, "buttons.bookmarked", id = id),
+                    false => t!(locale, "buttons.bookmark_failed"),
+                };
+                reply_privately(ctx, interaction, content).await;
+                if !bookmarked {
+                    return;
+                }
+
+                // Bookmarking a haiku is the closest thing to voting for it. Each person's vote
+                // only counts once, and only the first bookmark tells the authors.
+                let bookmarker = interaction.user.id;
+                let votes = match repository.vote_for_haiku(server_id, id, bookmarker) {
+                    Some(votes) => votes,
+                    None => return,
+                };
+                let content = t!(locale, "notifications.bookmarked", id = id);
+                notify_authors(
+                    ctx,
+                    &*repository,
+                    id,
+                    &haiku,
+                    Notification::Bookmarked,
+                    Some(bookmarker),
+                    &content,
+                )
+                .await;
+                if let Some(threshold) = crossed_vote_threshold(votes - 1, votes) {
+                    let content = t!(
+                        locale,
+                        "notifications.vote_threshold",
+                        id = id,
+                        votes = threshold
+                    );
+                    notify_authors(
+                        ctx,
+                        &*repository,
+                        id,
+                        &haiku,
+                        Notification::VoteThreshold,
+                        None,
+                        &content,
+                    )
+                    .await;
+                }
+            }
+            "share" => {
+                let sensitive = repository.is_haiku_sensitive(server_id, id);
+                let quote = format_haiku_quote(id, &haiku, locale, sensitive);
+                reply_privately(ctx, interaction, quote).await;
+            }
+            "history" => {
+                let edits = repository.get_haiku_edits(server_id, id);
+                let content = if edits.is_empty() {
+                    t!(locale, "history.none", id = id)
+                } else {
+                    let sensitive = repository.is_haiku_sensitive(server_id, id);
+                    format_edit_history(id, &haiku, &edits, locale, sensitive)
+                };
+                reply_privately(ctx, interaction, content).await;
+            }
+            _ => println!("Unknown haiku button {}", interaction.data.custom_id),
+        }
+    }
+}
+
+/// Respond to a button press with a message only the person who pressed it can see
+async fn reply_privately(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+    content: String,
+) {
+    let result = with_retry(&ctx.http, || {
+        interaction.create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| {
+                    message
+                        .content(&content)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Failed to respond to component interaction: {:?}", why);
+    }
+}
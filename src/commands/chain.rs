@@ -0,0 +1,173 @@
+use crate::{
+    chain::{format_chain, ChainGames, THEMES},
+    config::Config,
+    framework::{
+        options::get_option,
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    repository::repository,
+    retry::with_retry,
+};
+use chrono::Utc;
+use rand::seq::SliceRandom;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        id::ChannelId,
+        interactions::{
+            application_command::{ApplicationCommandInteraction, ApplicationCommandOptionType},
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+    },
+    prelude::{Mutex, TypeMapKey},
+    utils::Color,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+use std::{sync::Arc, time::Duration};
+
+const DEFAULT_MINUTES: i64 = 10;
+const MAX_MINUTES: i64 = 60;
+
+pub struct ChainTracker;
+impl TypeMapKey for ChainTracker {
+    type Value = Arc<Mutex<ChainGames>>;
+}
+
+/// Start a haiku chain: everyone in the channel has a few minutes to write haikus on a theme
+#[derive(Command)]
+#[name = "chain-start"]
+pub struct ChainStartCommand {
+    /// How many minutes the chain stays open for
+    minutes: i64,
+}
+
+impl ChainStartCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("chain-start")
+            .subcommand(&["chain", "start"])
+            .option(OptionSpec::new("minutes").default(DEFAULT_MINUTES))
+            .option(OptionSpec::extra(
+                "theme",
+                ApplicationCommandOptionType::String,
+                "What the haikus should be about, or leave it out for a random theme",
+            ))
+    }
+}
+
+/// The chain games running in every channel
+pub async fn chain_games(ctx: &Context) -> Arc<Mutex<ChainGames>> {
+    ctx.data
+        .read()
+        .await
+        .get::<ChainTracker>()
+        .expect("Expected ChainTracker in TypeMap")
+        .clone()
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ChainStartCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        if command.guild_id.is_none() {
+            return Ok(());
+        }
+        let can_read_messages = ctx
+            .data
+            .read()
+            .await
+            .get::<Config>()
+            .expect("Expected Config in TypeMap")
+            .intents
+            .message_content;
+        if !can_read_messages {
+            let result = respond(ctx, command, |message| {
+                message
+                    .content(t!(locale, "chain.unavailable"))
+                    .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Could not send chain message: {:?}", why);
+            }
+            return Ok(());
+        }
+        let minutes = self.minutes.max(1).min(MAX_MINUTES);
+        let theme = get_option::<String>(command, "theme")
+            .filter(|theme| !theme.trim().is_empty())
+            .unwrap_or_else(|| {
+                THEMES
+                    .choose(&mut rand::thread_rng())
+                    .copied()
+                    .unwrap_or_default()
+                    .to_owned()
+            });
+        let ends_at = Utc::now() + chrono::Duration::minutes(minutes);
+        let started =
+            chain_games(ctx)
+                .await
+                .lock()
+                .await
+                .start(command.channel_id, theme.clone(), ends_at);
+        let result = if started {
+            tokio::spawn(finish_chain(
+                ctx.clone(),
+                command.channel_id,
+                locale,
+                Duration::from_secs(minutes as u64 * 60),
+            ));
+            respond(ctx, command, |message| {
+                message.content(t!(
+                    locale,
+                    "chain.started",
+                    theme = theme,
+                    minutes = minutes
+                ))
+            })
+            .await
+        } else {
+            respond(ctx, command, |message| {
+                message
+                    .content(t!(locale, "chain.already_running"))
+                    .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+            })
+            .await
+        };
+        if let Err(why) = result {
+            println!("Could not send chain message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Wait for a chain to run out of time, then post everything that was written
+async fn finish_chain(ctx: Context, channel_id: ChannelId, locale: Locale, after: Duration) {
+    tokio::time::sleep(after).await;
+    let chain = match chain_games(&ctx).await.lock().await.finish(channel_id) {
+        Some(chain) => chain,
+        None => return,
+    };
+    let description = format_chain(&chain, locale);
+    let result = with_retry(&ctx.http, || {
+        channel_id.send_message(&ctx.http, |message| {
+            message.embed(|embed| {
+                embed
+                    .title(t!(locale, "chain.finished", theme = chain.theme))
+                    .description(&description)
+                    .color(Color::DARK_GREEN)
+            })
+        })
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Failed to send finished chain: {:?}", why);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    formatting::format_number,
+    framework::{
+        pagination::{Page, Pages, Paginator},
+        response::respond,
+    },
+    i18n::{guild_locale, Locale, Region},
+    repository::repository,
+};
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        id::ChannelId, interactions::application_command::ApplicationCommandInteraction,
+        misc::Mentionable,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// How many channels are listed on each page
+const CHANNELS_PER_PAGE: usize = 10;
+
+/// Show which channels in this server have produced the most haikus
+#[derive(Command)]
+#[name = "channelstats"]
+pub struct ChannelStatsCommand;
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ChannelStatsCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        if let Some(server_id) = command.guild_id {
+            let locale = guild_locale(&*repository, command.guild_id);
+            let counts = repository.count_haikus_by_channel(server_id);
+            if counts.is_empty() {
+                let result = respond(ctx, command, |message| {
+                    message.content(t!(locale, "channelstats.no_haikus"))
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Could not send channel stats message: {:?}", why);
+                }
+                return Ok(());
+            }
+
+            let pages = ChannelStatsPages {
+                counts,
+                locale,
+                region: Region::from_discord(&command.locale),
+            };
+            Paginator::new(pages, locale).send(ctx, command).await;
+        }
+        Ok(())
+    }
+}
+
+/// Every channel's count is loaded up front, since servers don't have that many channels
+pub struct ChannelStatsPages {
+    counts: Vec<(ChannelId, i64)>,
+    locale: Locale,
+    region: Region,
+}
+
+#[async_trait]
+impl Pages for ChannelStatsPages {
+    fn page_count(&self) -> usize {
+        page_count(&self.counts)
+    }
+
+    async fn show(&mut self, _ctx: &Context, page: usize) -> Page {
+        let mut shown = Page::default();
+        format_channel_stats(
+            &self.counts,
+            page,
+            self.locale,
+            self.region,
+            shown.embed.get_or_insert_with(CreateEmbed::default),
+        );
+        shown
+    }
+}
+
+fn page_count(counts: &[(ChannelId, i64)]) -> usize {
+    (counts.len() + CHANNELS_PER_PAGE - 1) / CHANNELS_PER_PAGE
+}
+
+/// A ranked list of the channels on one page, ranks continuing from the previous pages
+fn format_channel_stats<'a>(
+    counts: &[(ChannelId, i64)],
+    page: usize,
+    locale: Locale,
+    region: Region,
+    embed: &'a mut CreateEmbed,
+) -> &'a mut CreateEmbed {
+    embed.title(t!(locale, "channelstats.title"));
+    embed.description(
+        counts
+            .iter()
+            .enumerate()
+            .skip(page * CHANNELS_PER_PAGE)
+            .take(CHANNELS_PER_PAGE)
+            .map(|(index, (channel_id, count))| {
+                t!(
+                    locale,
+                    "channelstats.entry",
+                    rank = format_number(index as i64 + 1, region),
+                    channel = channel_id.mention(),
+                    count = format_number(*count, region)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+    );
+    embed.footer(|footer| {
+        footer.text(t!(
+            locale,
+            "channelstats.page",
+            page = page + 1,
+            pages = page_count(counts)
+        ))
+    });
+    embed
+}
@@ -0,0 +1,1155 @@
+use crate::{
+    archive::remove_webhook,
+    database::{RandomWeighting, RetentionAction},
+    detection::bridge::{parse_bridge_pattern, BridgePatternError, MAX_PATTERN_LENGTH, NICK_GROUP},
+    formatting::{validate_template, TemplateError, TEMPLATE_PLACEHOLDERS},
+    framework::{
+        checks::{missing_channel_permissions, posting_permissions},
+        options::get_option,
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    guilds::update_laureate,
+    i18n::{guild_locale, Locale},
+    models::ArchiveWebhook,
+    repository::repository,
+    retry::with_retry,
+    schedule::parse_timezone,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        channel::PartialChannel,
+        guild::Role,
+        interactions::application_command::{
+            ApplicationCommandInteraction, ApplicationCommandOptionType,
+        },
+        misc::Mentionable,
+        Permissions,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// More syllables than any real word should need
+const MAX_OVERRIDE_SYLLABLES: i64 = 12;
+
+impl Choice for Locale {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![("English", "en"), ("Deutsch", "de")]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        Locale::from_code(value)
+    }
+}
+
+impl Choice for bool {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![("On", "on"), ("Off", "off")]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl Choice for RandomWeighting {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("Every haiku equally", "uniform"),
+            ("Favour haikus not shown in a while", "least_recently_shown"),
+            ("Favour the most voted haikus", "most_voted"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        RandomWeighting::from_code(value)
+    }
+}
+
+impl Choice for RetentionAction {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![("Archive them", "archive"), ("Delete them", "delete")]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        RetentionAction::from_code(value)
+    }
+}
+
+/// Change the language the bot uses in this server
+#[derive(Command)]
+#[name = "config-language"]
+pub struct ConfigLanguageCommand {
+    /// The language to use
+    language: String,
+}
+
+impl ConfigLanguageCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-language")
+            .subcommand(&["config", "language"])
+            .option(OptionSpec::new("language").choices::<Locale>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigLanguageCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, locale) = match (command.guild_id, Locale::from_value(&self.language)) {
+            (Some(server_id), Some(locale)) => (server_id, locale),
+            _ => return Ok(()),
+        };
+        {
+            repository.set_server_locale(server_id, locale.code());
+        }
+        let content = t!(
+            locale,
+            "language.set",
+            language = t!(locale, "language.name")
+        );
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether to explain the bot to people when their first haiku in this server is found
+#[derive(Command)]
+#[name = "config-welcome"]
+pub struct ConfigWelcomeCommand {
+    /// Whether to send the welcome message
+    welcome: String,
+}
+
+impl ConfigWelcomeCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-welcome")
+            .subcommand(&["config", "welcome"])
+            .option(OptionSpec::new("welcome").choices::<bool>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigWelcomeCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.welcome)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        repository.set_server_welcome(server_id, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if enabled {
+            t!(locale, "welcome.enabled")
+        } else {
+            t!(locale, "welcome.disabled")
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose which haikus /randomhaiku favours in this server
+#[derive(Command)]
+#[name = "config-random"]
+pub struct ConfigRandomCommand {
+    /// Which haikus to favour
+    weighting: String,
+}
+
+impl ConfigRandomCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-random")
+            .subcommand(&["config", "random"])
+            .option(OptionSpec::new("weighting").choices::<RandomWeighting>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigRandomCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, weighting) = match (
+            command.guild_id,
+            RandomWeighting::from_value(&self.weighting),
+        ) {
+            (Some(server_id), Some(weighting)) => (server_id, weighting),
+            _ => return Ok(()),
+        };
+        repository.set_server_random_weighting(server_id, weighting);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = match weighting {
+            RandomWeighting::Uniform => t!(locale, "random_weighting.uniform"),
+            RandomWeighting::LeastRecentlyShown => {
+                t!(locale, "random_weighting.least_recently_shown")
+            }
+            RandomWeighting::MostVoted => t!(locale, "random_weighting.most_voted"),
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Give a role to whoever has written the most haikus in this server this month
+#[derive(Command)]
+#[name = "config-laureate"]
+pub struct ConfigLaureateCommand;
+
+impl ConfigLaureateCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-laureate")
+            .subcommand(&["config", "laureate"])
+            .option(OptionSpec::extra(
+                "role",
+                ApplicationCommandOptionType::Role,
+                "The role to give the top poet, or leave it out to stop giving one",
+            ))
+            .permissions(Permissions::MANAGE_GUILD | Permissions::MANAGE_ROLES)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigLaureateCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let role = get_option::<Role>(command, "role");
+        // The previous laureate shouldn't keep a role that's no longer handed out
+        if let Some(config) = repository.get_server_config(server_id) {
+            if let (Some(old_role), Some(laureate)) = (config.laureate_role, config.laureate) {
+                let result = with_retry(&ctx.http, || {
+                    ctx.http
+                        .remove_member_role(server_id.0, laureate.0, old_role.0)
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Could not remove laureate role in {}: {:?}", server_id, why);
+                }
+            }
+        }
+        repository.set_server_laureate_role(server_id, role.as_ref().map(|role| role.id));
+        let content = match role {
+            None => t!(locale, "laureate.disabled"),
+            Some(role) if !update_laureate(ctx, &*repository, server_id).await => {
+                t!(locale, "laureate.cannot_assign", role = role.mention())
+            }
+            Some(role) => match repository
+                .get_server_config(server_id)
+                .and_then(|config| config.laureate)
+            {
+                Some(laureate) => t!(
+                    locale,
+                    "laureate.enabled_with",
+                    role = role.mention(),
+                    user = laureate.mention()
+                ),
+                None => t!(locale, "laureate.enabled", role = role.mention()),
+            },
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose how long haikus are kept in this server before they're archived or deleted
+#[derive(Command)]
+#[name = "config-retention"]
+pub struct ConfigRetentionCommand {
+    /// How many years to keep haikus for, or 0 to keep them forever
+    years: i64,
+    /// What to do with haikus once they're older than that
+    action: String,
+}
+
+impl ConfigRetentionCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-retention")
+            .subcommand(&["config", "retention"])
+            .option(
+                OptionSpec::new("action")
+                    .choices::<RetentionAction>()
+                    .default("archive"),
+            )
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigRetentionCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, action) =
+            match (command.guild_id, RetentionAction::from_value(&self.action)) {
+                (Some(server_id), Some(action)) => (server_id, action),
+                _ => return Ok(()),
+            };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let content = if self.years < 0 {
+            t!(locale, "retention.invalid")
+        } else if self.years == 0 {
+            repository.set_server_retention(server_id, None, action);
+            t!(locale, "retention.disabled")
+        } else {
+            repository.set_server_retention(server_id, Some(self.years), action);
+            match action {
+                RetentionAction::Archive => t!(locale, "retention.archive", years = self.years),
+                RetentionAction::Delete => t!(locale, "retention.delete", years = self.years),
+            }
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether this server's haiku counts are included in /globalstats
+#[derive(Command)]
+#[name = "config-globalstats"]
+pub struct ConfigGlobalStatsCommand {
+    /// Whether to share this server's counts (never its haikus or who wrote them)
+    share: String,
+}
+
+impl ConfigGlobalStatsCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-globalstats")
+            .subcommand(&["config", "globalstats"])
+            .option(OptionSpec::new("share").choices::<bool>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigGlobalStatsCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.share)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        repository.set_server_global_stats(server_id, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if enabled {
+            t!(locale, "globalstats.enabled")
+        } else {
+            t!(locale, "globalstats.disabled")
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose the text posted with each haiku the bot finds in this server
+#[derive(Command)]
+#[name = "config-announcement"]
+pub struct ConfigAnnouncementCommand;
+
+impl ConfigAnnouncementCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-announcement")
+            .subcommand(&["config", "announcement"])
+            .option(OptionSpec::extra(
+                "template",
+                ApplicationCommandOptionType::String,
+                "e.g. \"Haiku #{id} by {author}!\", or leave it out to post haikus on their own",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigAnnouncementCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let template = get_option::<String>(command, "template")
+            .filter(|template| !template.trim().is_empty());
+        let content = match template {
+            None => {
+                repository.set_server_announcement_template(server_id, None);
+                t!(locale, "announcement.disabled")
+            }
+            Some(template) => match validate_template(&template) {
+                Ok(()) => {
+                    repository.set_server_announcement_template(server_id, Some(&template));
+                    t!(locale, "announcement.enabled", template = template)
+                }
+                Err(TemplateError::UnknownPlaceholder(placeholder)) => t!(
+                    locale,
+                    "announcement.unknown_placeholder",
+                    placeholder = format!("{{{}}}", placeholder),
+                    placeholders = TEMPLATE_PLACEHOLDERS
+                        .iter()
+                        .map(|placeholder| format!("`{{{}}}`", placeholder))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+                Err(TemplateError::Unclosed) => t!(locale, "announcement.unclosed"),
+                Err(TemplateError::TooLong) => t!(locale, "announcement.too_long"),
+            },
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether haikus are found automatically in a channel
+#[derive(Command)]
+#[name = "config-detection"]
+pub struct ConfigDetectionCommand {
+    /// Whether to look for haikus in every message sent in the channel
+    enabled: String,
+}
+
+impl ConfigDetectionCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-detection")
+            .subcommand(&["config", "detection"])
+            .option(OptionSpec::new("enabled").choices::<bool>())
+            .option(OptionSpec::extra(
+                "channel",
+                ApplicationCommandOptionType::Channel,
+                "The channel to change, or leave it out for this one",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigDetectionCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.enabled)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        let channel_id = get_option::<PartialChannel>(command, "channel")
+            .map(|channel| channel.id)
+            .unwrap_or(command.channel_id);
+        repository.set_channel_detection(server_id, channel_id, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if enabled {
+            let missing = missing_channel_permissions(ctx, channel_id, posting_permissions()).await;
+            let enabled = t!(locale, "detection.enabled", channel = channel_id.mention());
+            if missing.is_empty() {
+                enabled
+            } else {
+                let warning = t!(locale, "detection.cannot_announce", permissions = missing);
+                format!("{}\n{}", enabled, warning)
+            }
+        } else {
+            t!(locale, "detection.disabled", channel = channel_id.mention())
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose a reaction that saves a haiku in channels where they aren't found automatically
+#[derive(Command)]
+#[name = "config-manualsave"]
+pub struct ConfigManualSaveCommand;
+
+impl ConfigManualSaveCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-manualsave")
+            .subcommand(&["config", "manualsave"])
+            .option(OptionSpec::extra(
+                "emoji",
+                ApplicationCommandOptionType::String,
+                "The emoji to react to a haiku's last message with, or leave it out to turn this off",
+            ))
+            .option(OptionSpec::extra(
+                "role",
+                ApplicationCommandOptionType::Role,
+                "Who can save haikus by reacting",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigManualSaveCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let emoji = get_option::<String>(command, "emoji")
+            .map(|emoji| emoji.trim().to_owned())
+            .filter(|emoji| !emoji.is_empty());
+        let role = get_option::<Role>(command, "role");
+        let content = match (emoji, role) {
+            (None, _) => {
+                repository.set_server_manual_save(server_id, None, None);
+                t!(locale, "manualsave.disabled")
+            }
+            (Some(_), None) => t!(locale, "manualsave.needs_role"),
+            (Some(emoji), Some(role)) => {
+                repository.set_server_manual_save(server_id, Some(&emoji), Some(role.id));
+                t!(
+                    locale,
+                    "manualsave.enabled",
+                    emoji = emoji,
+                    role = role.mention()
+                )
+            }
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether messages from bots and webhooks can be haikus in this server
+#[derive(Command)]
+#[name = "config-senders"]
+pub struct ConfigSendersCommand {
+    /// Whether messages sent by other bots can be haikus
+    bots: String,
+    /// Whether messages relayed by webhooks, e.g. from IRC or Matrix, can be haikus
+    webhooks: String,
+}
+
+impl ConfigSendersCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-senders")
+            .subcommand(&["config", "senders"])
+            .option(OptionSpec::new("bots").choices::<bool>())
+            .option(OptionSpec::new("webhooks").choices::<bool>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigSendersCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, bots, webhooks) = match (
+            command.guild_id,
+            bool::from_value(&self.bots),
+            bool::from_value(&self.webhooks),
+        ) {
+            (Some(server_id), Some(bots), Some(webhooks)) => (server_id, bots, webhooks),
+            _ => return Ok(()),
+        };
+        repository.set_server_senders(server_id, bots, webhooks);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let allowed = |enabled: bool| {
+            if enabled {
+                t!(locale, "senders.allowed")
+            } else {
+                t!(locale, "senders.ignored")
+            }
+        };
+        let content = t!(
+            locale,
+            "senders.updated",
+            bots = allowed(bots),
+            webhooks = allowed(webhooks)
+        );
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Credit bridged messages like "<nick> message" to the person who wrote them, not the bridge
+#[derive(Command)]
+#[name = "config-bridge"]
+pub struct ConfigBridgeCommand;
+
+impl ConfigBridgeCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-bridge")
+            .subcommand(&["config", "bridge"])
+            .option(OptionSpec::extra(
+                "pattern",
+                ApplicationCommandOptionType::String,
+                "A regex with a (?P<nick>...) group, e.g. ^<(?P<nick>[^>]+)> , or leave it out to turn this off",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigBridgeCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let pattern =
+            get_option::<String>(command, "pattern").filter(|pattern| !pattern.trim().is_empty());
+        let content = match pattern {
+            None => {
+                repository.set_server_bridge_pattern(server_id, None);
+                t!(locale, "bridge.disabled")
+            }
+            Some(pattern) => match parse_bridge_pattern(&pattern) {
+                Ok(_) => {
+                    repository.set_server_bridge_pattern(server_id, Some(&pattern));
+                    t!(locale, "bridge.enabled", pattern = pattern)
+                }
+                Err(BridgePatternError::Invalid(why)) => {
+                    t!(locale, "bridge.invalid", error = why)
+                }
+                Err(BridgePatternError::MissingNick) => t!(
+                    locale,
+                    "bridge.missing_nick",
+                    group = format!("(?P<{}>...)", NICK_GROUP)
+                ),
+                Err(BridgePatternError::TooLong) => {
+                    t!(locale, "bridge.too_long", max = MAX_PATTERN_LENGTH)
+                }
+            },
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether a haiku's three lines must all be written by the same person to be saved
+#[derive(Command)]
+#[name = "config-sameauthor"]
+pub struct ConfigSameAuthorCommand {
+    /// Whether haikus need all of their lines from one person
+    enabled: String,
+}
+
+impl ConfigSameAuthorCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-sameauthor")
+            .subcommand(&["config", "sameauthor"])
+            .option(OptionSpec::new("enabled").choices::<bool>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigSameAuthorCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.enabled)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        repository.set_server_same_author(server_id, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if enabled {
+            t!(locale, "sameauthor.enabled")
+        } else {
+            t!(locale, "sameauthor.disabled")
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether haikus are shown as plain text instead of embeds for anyone who hasn't picked
+/// for themselves with /plaintext
+#[derive(Command)]
+#[name = "config-plaintext"]
+pub struct ConfigPlainTextCommand {
+    /// Whether haikus are plain text by default
+    enabled: String,
+}
+
+impl ConfigPlainTextCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-plaintext")
+            .subcommand(&["config", "plaintext"])
+            .option(OptionSpec::new("enabled").choices::<bool>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigPlainTextCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.enabled)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        repository.set_server_plain_text(server_id, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if enabled {
+            t!(locale, "plaintext.server_enabled")
+        } else {
+            t!(locale, "plaintext.server_disabled")
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose whether members can make the haikus they wrote on their own in this server public
+/// with /feed
+#[derive(Command)]
+#[name = "config-feeds"]
+pub struct ConfigFeedsCommand {
+    /// Whether members' feeds can be seen by anyone with the link
+    enabled: String,
+}
+
+impl ConfigFeedsCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-feeds")
+            .subcommand(&["config", "feeds"])
+            .option(OptionSpec::new("enabled").choices::<bool>())
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigFeedsCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.enabled)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        repository.set_server_public_feeds(server_id, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if enabled {
+            t!(locale, "feed.server_enabled")
+        } else {
+            t!(locale, "feed.server_disabled")
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Set how many syllables a word the bot miscounts has in this server, e.g. someone's name
+#[derive(Command)]
+#[name = "config-syllables"]
+pub struct ConfigSyllablesCommand {
+    /// The word, without spaces or punctuation
+    word: String,
+}
+
+impl ConfigSyllablesCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-syllables")
+            .subcommand(&["config", "syllables"])
+            .option(OptionSpec::extra(
+                "syllables",
+                ApplicationCommandOptionType::Integer,
+                "How many syllables the word has, or leave it out to count it normally again",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigSyllablesCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let word = self.word.trim().to_lowercase();
+        let is_word = !word.is_empty() && word.chars().all(|c| c.is_alphanumeric() || c == '\'');
+        let content = match get_option::<i64>(command, "syllables") {
+            _ if !is_word => t!(locale, "syllables.invalid_word", word = self.word),
+            None => {
+                repository.set_word_override(server_id, &word, None);
+                t!(locale, "syllables.removed", word = word)
+            }
+            Some(syllables) if !(1..=MAX_OVERRIDE_SYLLABLES).contains(&syllables) => t!(
+                locale,
+                "syllables.out_of_range",
+                max = MAX_OVERRIDE_SYLLABLES
+            ),
+            Some(syllables) => {
+                repository.set_word_override(server_id, &word, Some(syllables as usize));
+                t!(locale, "syllables.set", word = word, syllables = syllables)
+            }
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose a channel where every haiku saved in this server is posted too, as a gallery to browse.
+/// It works best as a channel only the bot can post in.
+#[derive(Command)]
+#[name = "config-archive"]
+pub struct ConfigArchiveCommand;
+
+impl ConfigArchiveCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-archive")
+            .subcommand(&["config", "archive"])
+            .option(OptionSpec::extra(
+                "channel",
+                ApplicationCommandOptionType::Channel,
+                "The channel to post haikus in, or leave it out to stop posting them",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigArchiveCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let channel_id = get_option::<PartialChannel>(command, "channel").map(|channel| channel.id);
+        let previous = repository
+            .get_server_config(server_id)
+            .and_then(|config| config.archive);
+        let (archive, content) = match channel_id {
+            None => (None, t!(locale, "archive.disabled")),
+            Some(channel_id) => {
+                let missing =
+                    missing_channel_permissions(ctx, channel_id, Permissions::MANAGE_WEBHOOKS)
+                        .await;
+                if !missing.is_empty() {
+                    let content = t!(
+                        locale,
+                        "archive.missing_permissions",
+                        channel = channel_id.mention(),
+                        permissions = missing
+                    );
+                    let result = respond(ctx, command, |message| message.content(content)).await;
+                    if let Err(why) = result {
+                        println!("Could not send config message: {:?}", why);
+                    }
+                    return Ok(());
+                }
+                let name = t!(locale, "archive.webhook_name");
+                let webhook =
+                    with_retry(&ctx.http, || channel_id.create_webhook(&ctx.http, &name)).await;
+                match webhook.map(|webhook| (webhook.id, webhook.token)) {
+                    Ok((webhook, Some(token))) => (
+                        Some(ArchiveWebhook {
+                            channel: channel_id,
+                            webhook,
+                            token,
+                        }),
+                        t!(locale, "archive.enabled", channel = channel_id.mention()),
+                    ),
+                    result => {
+                        println!(
+                            "Could not create archive webhook in {}: {:?}",
+                            channel_id,
+                            result.err()
+                        );
+                        let content = t!(
+                            locale,
+                            "archive.cannot_create",
+                            channel = channel_id.mention()
+                        );
+                        let result =
+                            respond(ctx, command, |message| message.content(content)).await;
+                        if let Err(why) = result {
+                            println!("Could not send config message: {:?}", why);
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        repository.set_server_archive(server_id, archive.as_ref());
+        if let Some(previous) = previous {
+            remove_webhook(ctx, &previous).await;
+        }
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose a channel where the bot tells this server's admins when something goes wrong
+#[derive(Command)]
+#[name = "config-errors"]
+pub struct ConfigErrorsCommand;
+
+impl ConfigErrorsCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-errors")
+            .subcommand(&["config", "errors"])
+            .option(OptionSpec::extra(
+                "channel",
+                ApplicationCommandOptionType::Channel,
+                "The channel to post problems in, or leave it out to stop posting them",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigErrorsCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let channel_id = get_option::<PartialChannel>(command, "channel").map(|channel| channel.id);
+        repository.set_server_error_channel(server_id, channel_id);
+        let missing = match channel_id {
+            Some(channel_id) => {
+                missing_channel_permissions(ctx, channel_id, Permissions::SEND_MESSAGES).await
+            }
+            None => Permissions::empty(),
+        };
+        let content = match channel_id {
+            None => t!(locale, "error_channel.disabled"),
+            Some(channel_id) if !missing.is_empty() => t!(
+                locale,
+                "error_channel.missing_permissions",
+                channel = channel_id.mention(),
+                permissions = missing
+            ),
+            Some(channel_id) => {
+                // Find out now if the bot can't post there, rather than when something goes wrong
+                let result = with_retry(&ctx.http, || {
+                    channel_id.say(&ctx.http, t!(locale, "error_channel.welcome"))
+                })
+                .await;
+                match result {
+                    Ok(_) => t!(
+                        locale,
+                        "error_channel.enabled",
+                        channel = channel_id.mention()
+                    ),
+                    Err(why) => {
+                        println!(
+                            "Could not post in error channel of {}: {:?}",
+                            server_id, why
+                        );
+                        t!(
+                            locale,
+                            "error_channel.cannot_send",
+                            channel = channel_id.mention()
+                        )
+                    }
+                }
+            }
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Choose the timezone this server's weeks, months and years start in
+#[derive(Command)]
+#[name = "config-timezone"]
+pub struct ConfigTimezoneCommand {
+    /// The timezone's name, e.g. Europe/Berlin or America/New_York
+    name: String,
+}
+
+impl ConfigTimezoneCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("config-timezone")
+            .subcommand(&["config", "timezone"])
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ConfigTimezoneCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let locale = guild_locale(&*repository, Some(server_id));
+        let content = match parse_timezone(&self.name) {
+            Some(timezone) => {
+                repository.set_server_timezone(server_id, timezone.name());
+                t!(locale, "timezone.set", timezone = timezone.name())
+            }
+            None => t!(locale, "timezone.invalid", timezone = self.name),
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send config message: {:?}", why);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::guild_locale,
+    posts::refresh_posts,
+    repository::repository,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+        Permissions,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Put a content warning on a haiku you wrote a line of, so it's hidden behind spoilers and never
+/// picked at random. Moderators can do this for any haiku.
+#[derive(Command)]
+#[name = "contentwarning"]
+pub struct ContentWarningCommand {
+    /// Id of the haiku
+    id: i64,
+    /// Whether the haiku needs a content warning
+    enabled: String,
+}
+
+impl ContentWarningCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("contentwarning").option(OptionSpec::new("enabled").choices::<bool>())
+    }
+}
+
+/// Whoever ran the command can manage messages in the server, so may moderate any haiku
+pub fn is_moderator(command: &ApplicationCommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .map_or(false, |permissions| {
+            permissions.intersects(Permissions::ADMINISTRATOR | Permissions::MANAGE_MESSAGES)
+        })
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ContentWarningCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.enabled)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = match repository.get_haiku(server_id, self.id) {
+            None => t!(locale, "errors.haiku_not_found", id = self.id),
+            Some((_, haiku))
+                if !is_moderator(command)
+                    && !haiku
+                        .lines
+                        .iter()
+                        .any(|line| line.author == command.user.id) =>
+            {
+                t!(locale, "contentwarning.not_allowed", id = self.id)
+            }
+            Some(_) => {
+                repository.set_haiku_sensitive(server_id, self.id, enabled);
+                refresh_posts(ctx, &*repository, server_id, self.id).await;
+                if enabled {
+                    t!(locale, "contentwarning.added", id = self.id)
+                } else {
+                    t!(locale, "contentwarning.removed", id = self.id)
+                }
+            }
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send content warning message: {:?}", why);
+        }
+        Ok(())
+    }
+}
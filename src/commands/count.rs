@@ -1,10 +1,12 @@
-use crate::counting::count_line;
+use crate::{
+    counting::{count_line_with, WordOverrides},
+    framework::response::respond,
+    i18n::guild_locale,
+    repository::repository,
+};
 use serenity::{
-    async_trait,
-    client::Context,
-    model::interactions::{
-        application_command::ApplicationCommandInteraction, InteractionResponseType,
-    },
+    async_trait, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
 };
 use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
 use slash_helper_macros::Command;
@@ -24,34 +26,24 @@ impl ApplicationCommandInteractionHandler for CountCommand {
         ctx: &Context,
         command: &ApplicationCommandInteraction,
     ) -> Result<(), InvocationError> {
-        match count_line(&self.phrase) {
-            Ok(syllables) => {
-                command
-                    .create_interaction_response(&ctx.http, |response| {
-                        response
-                            .kind(InteractionResponseType::ChannelMessageWithSource)
-                            .interaction_response_data(|message| {
-                                message.content(format!(
-                                    "The phrase '{}' has {} syllables",
-                                    self.phrase, syllables
-                                ))
-                            })
-                    })
-                    .await
-                    .expect("Could not send uptime message");
-            }
-            Err(_) => {
-                command
-                    .create_interaction_response(&ctx.http, |response| {
-                        response
-                            .kind(InteractionResponseType::ChannelMessageWithSource)
-                            .interaction_response_data(|message| {
-                                message.content("Could not count this phrase")
-                            })
-                    })
-                    .await
-                    .expect("Could not send uptime message");
-            }
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let overrides = match command.guild_id {
+            Some(server_id) => repository.get_word_overrides(server_id),
+            None => WordOverrides::new(),
+        };
+        let content = match count_line_with(&self.phrase, &overrides) {
+            Ok(count) => t!(
+                locale,
+                "count.result",
+                phrase = self.phrase,
+                syllables = count.syllables()
+            ),
+            Err(_) => t!(locale, "count.failed"),
+        };
+        let result = respond(ctx, command, |message| message.content(&content)).await;
+        if let Err(why) = result {
+            println!("Could not send count message: {:?}", why);
         }
         Ok(())
     }
@@ -0,0 +1,185 @@
+use crate::{
+    counting::{count_line, is_haiku},
+    framework::{
+        options::get_option,
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::guild_locale,
+    repository::repository,
+    retry::with_retry,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        channel::{Attachment, Message},
+        id::MessageId,
+        interactions::application_command::{
+            ApplicationCommandInteraction, ApplicationCommandOptionType,
+        },
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+// Keep downloads and responses small, this is meant for drafts rather than novels
+const MAX_FILE_SIZE: u64 = 64 * 1024;
+const MAX_LINES: usize = 500;
+const MAX_HAIKUS_SHOWN: usize = 10;
+// How far back to look for the user's latest attachment when no message is given
+const MESSAGE_SEARCH_LIMIT: u64 = 50;
+
+/// Count the syllables in each line of a text file and find any haikus in it
+#[derive(Command)]
+#[name = "countfile"]
+pub struct CountFileCommand;
+
+impl CountFileCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("countfile").option(OptionSpec::extra(
+            "message",
+            ApplicationCommandOptionType::String,
+            "Link to the message with the file attached (default: your latest attachment here)",
+        ))
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for CountFileCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let message = match get_option::<String>(command, "message") {
+            Some(link) => match parse_message_id(&link) {
+                Some(message_id) => with_retry(&ctx.http, || {
+                    command.channel_id.message(&ctx.http, message_id)
+                })
+                .await
+                .ok(),
+                None => None,
+            },
+            None => find_latest_attachment(ctx, command).await,
+        };
+        let attachment = message
+            .as_ref()
+            .map(|message| message.attachments.iter().find(|file| is_text_file(file)))
+            .flatten();
+        let text = match attachment {
+            Some(attachment) if attachment.size > MAX_FILE_SIZE => Err(t!(
+                locale,
+                "countfile.too_large",
+                max = MAX_FILE_SIZE / 1024
+            )),
+            Some(attachment) => match attachment.download().await {
+                Ok(bytes) => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+                Err(why) => {
+                    println!("Could not download attachment: {:?}", why);
+                    Err(t!(locale, "countfile.download_failed"))
+                }
+            },
+            None => Err(t!(locale, "countfile.no_file")),
+        };
+        let text = match text {
+            Ok(text) => text,
+            Err(error) => {
+                let result = respond(ctx, command, |message| message.content(error)).await;
+                if let Err(why) = result {
+                    println!("Could not send countfile message: {:?}", why);
+                }
+                return Ok(());
+            }
+        };
+
+        let lines = text
+            .lines()
+            .map(|line| line.trim().to_owned())
+            .filter(|line| !line.is_empty())
+            .take(MAX_LINES)
+            .collect::<Vec<String>>();
+        let counts = lines
+            .iter()
+            .map(|line| count_line(line).ok().map(|count| count.syllables()))
+            .collect::<Vec<Option<usize>>>();
+        let haikus = lines
+            .windows(3)
+            .filter(|window| is_haiku(window).is_ok())
+            .collect::<Vec<&[String]>>();
+
+        let line_counts = lines
+            .iter()
+            .zip(counts.iter())
+            .map(|(line, count)| match count {
+                Some(count) => format!("{:>2} | {}", count, line),
+                None => format!(" ? | {}", line),
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let summary = t!(
+            locale,
+            "countfile.summary",
+            lines = lines.len(),
+            uncountable = counts.iter().filter(|count| count.is_none()).count(),
+            haikus = haikus.len()
+        );
+        let result = respond(ctx, command, |message| {
+            message.create_embed(|embed| {
+                embed.title(
+                    attachment
+                        .map(|attachment| attachment.filename.clone())
+                        .unwrap_or_default(),
+                );
+                // Embed descriptions are limited to 4096 characters
+                embed.description(format!(
+                    "{}\n```\n{}\n```",
+                    summary,
+                    line_counts.chars().take(3800).collect::<String>()
+                ));
+                for haiku in haikus.iter().take(MAX_HAIKUS_SHOWN) {
+                    embed.field(t!(locale, "countfile.haiku"), haiku.join("\n"), false);
+                }
+                embed
+            })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send countfile message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+fn is_text_file(attachment: &Attachment) -> bool {
+    let filename = attachment.filename.to_lowercase();
+    filename.ends_with(".txt") || filename.ends_with(".md")
+}
+
+/// Accept either a bare message id or a message link, which ends with the message id
+fn parse_message_id(link: &str) -> Option<MessageId> {
+    link.trim()
+        .rsplit('/')
+        .next()
+        .map(|id| id.parse::<u64>().ok())
+        .flatten()
+        .map(MessageId)
+}
+
+async fn find_latest_attachment(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Option<Message> {
+    let messages = with_retry(&ctx.http, || {
+        command
+            .channel_id
+            .messages(&ctx.http, |retriever| retriever.limit(MESSAGE_SEARCH_LIMIT))
+    })
+    .await
+    .ok()?;
+    messages.into_iter().find(|message| {
+        message.author.id == command.user.id && message.attachments.iter().any(is_text_file)
+    })
+}
@@ -0,0 +1,71 @@
+use crate::{
+    critique::{critique_haiku, CritiqueError},
+    framework::response::respond,
+    i18n::guild_locale,
+    repository::repository,
+};
+use serenity::{
+    async_trait, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Get a short, friendly critique of a haiku from this server
+#[derive(Command)]
+#[name = "critique"]
+pub struct CritiqueCommand {
+    /// Id of the haiku to critique
+    id: i64,
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for CritiqueCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let haiku_and_id = match (self.id, command.guild_id) {
+            (id, Some(server_id)) => repository.get_haiku(server_id, id),
+            _ => None,
+        };
+        let (id, haiku) = match haiku_and_id {
+            Some(haiku_and_id) => haiku_and_id,
+            None => {
+                let result = respond(ctx, command, |message| {
+                    message.content(t!(locale, "errors.haiku_not_found", id = self.id))
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Could not send critique message: {:?}", why);
+                }
+                return Ok(());
+            }
+        };
+
+        let poem = haiku
+            .lines
+            .iter()
+            .map(|line| format!("> {}", line.content))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let content = match critique_haiku(&haiku).await {
+            Ok(critique) => format!("Haiku #{}\n{}\n\n{}", id, poem, critique),
+            Err(CritiqueError::NotConfigured) => t!(locale, "critique.disabled"),
+            Err(err) => {
+                println!("Error fetching critique: {:?}", err);
+                t!(locale, "critique.failed")
+            }
+        };
+        // The LLM can easily take longer than the 3 second response window,
+        // in which case this edits the response the framework deferred for us
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send critique message: {:?}", why);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    framework::{response::respond, spec::CommandSpec},
+    i18n::guild_locale,
+    posts::{delete_posts, find_posts},
+    repository::repository,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{interactions::application_command::ApplicationCommandInteraction, Permissions},
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Delete a haiku from this server by its id
+#[derive(Command)]
+#[name = "deletehaiku"]
+pub struct DeleteHaikuCommand {
+    /// Id of the haiku to delete
+    id: i64,
+}
+
+impl DeleteHaikuCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("deletehaiku").permissions(Permissions::MANAGE_MESSAGES)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for DeleteHaikuCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let deleted = match command.guild_id {
+            Some(server_id) => {
+                let posts = find_posts(&*repository, server_id, self.id);
+                let deleted = repository.delete_haiku(server_id, self.id);
+                if let (true, Some(posts)) = (deleted, posts) {
+                    delete_posts(ctx, &*repository, server_id, posts).await;
+                }
+                deleted
+            }
+            None => false,
+        };
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = if deleted {
+            t!(locale, "deletehaiku.deleted", id = self.id)
+        } else {
+            t!(locale, "errors.haiku_not_found", id = self.id)
+        };
+        let result = respond(ctx, command, |message| message.content(content)).await;
+        if let Err(why) = result {
+            println!("Could not send delete haiku message: {:?}", why);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    commands::contentwarning::is_moderator,
+    counting::{count_line_with, Uncountable, HAIKU_SYLLABLES},
+    framework::response::respond,
+    i18n::guild_locale,
+    models::HaikuEdit,
+    posts::refresh_posts,
+    repository::repository,
+};
+use chrono::Utc;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        id::GuildId,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Fix a line of a haiku you wrote, e.g. a typo. Moderators can edit any line. The haiku keeps a
+/// history of its edits.
+#[derive(Command)]
+#[name = "edithaiku"]
+pub struct EditHaikuCommand {
+    /// Id of the haiku
+    id: i64,
+    /// Which line to change, from 1 to 3
+    line: i64,
+    /// What the line should say, with the same number of syllables
+    text: String,
+}
+
+impl EditHaikuCommand {
+    /// Edit the line if it's allowed and still fits the haiku, saying what happened
+    async fn edit(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        server_id: GuildId,
+    ) -> String {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, Some(server_id));
+        if !(1..=3).contains(&self.line) {
+            return t!(locale, "edithaiku.invalid_line");
+        }
+        let position = self.line as usize - 1;
+        let haiku = match repository.get_haiku(server_id, self.id) {
+            Some((_, haiku)) => haiku,
+            None => return t!(locale, "errors.haiku_not_found", id = self.id),
+        };
+        if !is_moderator(command) && haiku.lines[position].author != command.user.id {
+            return t!(
+                locale,
+                "edithaiku.not_allowed",
+                id = self.id,
+                line = self.line
+            );
+        }
+        let text = self
+            .text
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if haiku.lines[position].content == text {
+            return t!(
+                locale,
+                "edithaiku.unchanged",
+                id = self.id,
+                line = self.line
+            );
+        }
+        let syllables = match count_line_with(&text, &repository.get_word_overrides(server_id)) {
+            Ok(count) => count.syllables(),
+            Err(Uncountable::NotAWord(word)) => {
+                return t!(locale, "explain.not_a_word", word = word)
+            }
+            Err(Uncountable::UnknownWord(word)) => {
+                return t!(locale, "explain.unknown_word", word = word)
+            }
+        };
+        if syllables != HAIKU_SYLLABLES[position] {
+            return t!(
+                locale,
+                "edithaiku.wrong_syllables",
+                line = self.line,
+                expected = HAIKU_SYLLABLES[position],
+                found = syllables
+            );
+        }
+        let edit = HaikuEdit {
+            position,
+            previous: haiku.lines[position].content.clone(),
+            content: text,
+            editor: command.user.id,
+            edited_at: Utc::now(),
+        };
+        if !repository.edit_haiku_line(server_id, self.id, &edit, syllables) {
+            return t!(locale, "errors.haiku_not_found", id = self.id);
+        }
+        println!(
+            "{} edited line {} of haiku {} in server {}",
+            command.user.id, self.line, self.id, server_id
+        );
+        refresh_posts(ctx, &*repository, server_id, self.id).await;
+        t!(
+            locale,
+            "edithaiku.edited",
+            id = self.id,
+            line = self.line,
+            content = edit.content
+        )
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for EditHaikuCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let content = self.edit(ctx, command, server_id).await;
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send edit haiku message: {:?}", why);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{
+    export::{export_haikus, ExportFormat},
+    formatting::format_number,
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Region},
+    repository::repository,
+    retry::with_retry,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    http::AttachmentType,
+    model::interactions::{
+        application_command::ApplicationCommandInteraction,
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+use std::borrow::Cow;
+
+/// Get a file of every haiku you've written a line of in this server, sent to your DMs
+#[derive(Command)]
+#[name = "exportmine"]
+pub struct ExportMineCommand {
+    /// The file format to export as
+    format: String,
+}
+
+impl ExportMineCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("exportmine").option(
+            OptionSpec::new("format")
+                .choices::<ExportFormat>()
+                .default("json"),
+        )
+    }
+}
+
+impl Choice for ExportFormat {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![("JSON", "json"), ("CSV", "csv")]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ExportMineCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = match command.guild_id {
+            None => t!(locale, "exportmine.guild_only"),
+            Some(server_id) => {
+                let mut haikus =
+                    repository.get_archived_haikus_by_author(server_id, command.user.id);
+                haikus.extend(repository.get_haikus_by_author(server_id, command.user.id));
+                haikus.sort_by_key(|(_, haiku)| haiku.timestamp);
+                if haikus.is_empty() {
+                    t!(locale, "exportmine.no_haikus")
+                } else {
+                    let format =
+                        ExportFormat::from_value(&self.format).unwrap_or(ExportFormat::Json);
+                    let exported = export_haikus(&haikus, format);
+                    let filename = format!("haikus-{}.{}", server_id, format.extension());
+                    let count =
+                        format_number(haikus.len() as i64, Region::from_discord(&command.locale));
+                    let result = with_retry(&ctx.http, || {
+                        command.user.direct_message(&ctx.http, |message| {
+                            message
+                                .content(t!(locale, "exportmine.message", count = count))
+                                .add_file(AttachmentType::Bytes {
+                                    data: Cow::Owned(exported.clone().into_bytes()),
+                                    filename: filename.clone(),
+                                })
+                        })
+                    })
+                    .await;
+                    match result {
+                        Ok(_) => t!(locale, "exportmine.sent", count = count),
+                        Err(why) => {
+                            println!("Could not send haiku export: {:?}", why);
+                            t!(locale, "exportmine.dm_failed")
+                        }
+                    }
+                }
+            }
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send export message: {:?}", why);
+        }
+        Ok(())
+    }
+}
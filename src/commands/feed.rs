@@ -0,0 +1,91 @@
+use crate::{
+    config::Config,
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::guild_locale,
+    repository::repository,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::{
+        application_command::ApplicationCommandInteraction,
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Publish the haikus you wrote on your own in this server as a feed, so you can show them on
+/// your own site
+#[derive(Command)]
+#[name = "feed"]
+pub struct FeedCommand {
+    /// Whether anyone with the link can see your haikus from this server
+    enabled: String,
+}
+
+impl FeedCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("feed").option(OptionSpec::new("enabled").choices::<bool>())
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for FeedCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let (server_id, enabled) = match (command.guild_id, bool::from_value(&self.enabled)) {
+            (Some(server_id), Some(enabled)) => (server_id, enabled),
+            _ => return Ok(()),
+        };
+        let public_url = {
+            let data = ctx.data.read().await;
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .web
+                .public_url
+                .clone()
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, Some(server_id));
+        repository.set_user_public_feed(server_id, command.user.id, enabled);
+        let allowed = repository
+            .get_server_config(server_id)
+            .map_or(false, |config| config.public_feeds);
+        let content = match (enabled, allowed, public_url) {
+            (false, _, _) => t!(locale, "feed.disabled"),
+            (true, false, _) => t!(locale, "feed.not_allowed"),
+            (true, true, Some(public_url)) => {
+                let base = format!(
+                    "{}/guilds/{}/authors/{}",
+                    public_url.trim_end_matches('/'),
+                    server_id,
+                    command.user.id
+                );
+                t!(
+                    locale,
+                    "feed.enabled_links",
+                    json = format!("{}/haikus", base),
+                    rss = format!("{}/feed.rss", base)
+                )
+            }
+            (true, true, None) => t!(locale, "feed.enabled"),
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send feed message: {:?}", why);
+        }
+        Ok(())
+    }
+}
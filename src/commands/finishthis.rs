@@ -0,0 +1,201 @@
+use crate::{
+    completion::{format_completions, CompletionGames},
+    config::Config,
+    formatting::{format_haiku, server_wants_plain_text, to_embed_data},
+    framework::{
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    models::Notification,
+    notifications::notify,
+    repository::repository,
+    retry::with_retry,
+};
+use chrono::Utc;
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        id::ChannelId,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+    },
+    prelude::{Mutex, TypeMapKey},
+    utils::Color,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+use std::{sync::Arc, time::Duration};
+
+const DEFAULT_MINUTES: i64 = 3;
+const MAX_MINUTES: i64 = 30;
+
+pub struct CompletionTracker;
+impl TypeMapKey for CompletionTracker {
+    type Value = Arc<Mutex<CompletionGames>>;
+}
+
+/// Post the first line of a haiku and see who can finish it first
+#[derive(Command)]
+#[name = "finishthis"]
+pub struct FinishThisCommand {
+    /// How many minutes everyone has to finish the haiku
+    minutes: i64,
+}
+
+impl FinishThisCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("finishthis").option(OptionSpec::new("minutes").default(DEFAULT_MINUTES))
+    }
+}
+
+/// The completion games running in every channel
+pub async fn completion_games(ctx: &Context) -> Arc<Mutex<CompletionGames>> {
+    ctx.data
+        .read()
+        .await
+        .get::<CompletionTracker>()
+        .expect("Expected CompletionTracker in TypeMap")
+        .clone()
+}
+
+async fn reply_privately(ctx: &Context, command: &ApplicationCommandInteraction, content: String) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(content)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not send finishthis message: {:?}", why);
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for FinishThisCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let can_read_messages = ctx
+            .data
+            .read()
+            .await
+            .get::<Config>()
+            .expect("Expected Config in TypeMap")
+            .intents
+            .message_content;
+        if !can_read_messages {
+            reply_privately(ctx, command, t!(locale, "finishthis.unavailable")).await;
+            return Ok(());
+        }
+        let prompt = match repository.get_random_line(server_id, &[0]) {
+            Some(prompt) => prompt,
+            None => {
+                reply_privately(ctx, command, t!(locale, "finishthis.no_haikus")).await;
+                return Ok(());
+            }
+        };
+        let first_line = prompt.line.content.clone();
+        let minutes = self.minutes.max(1).min(MAX_MINUTES);
+        let ends_at = Utc::now() + chrono::Duration::minutes(minutes);
+        let started = completion_games(ctx).await.lock().await.start(
+            server_id,
+            command.channel_id,
+            prompt,
+            ends_at,
+        );
+        if !started {
+            reply_privately(ctx, command, t!(locale, "finishthis.already_running")).await;
+            return Ok(());
+        }
+        tokio::spawn(finish_game(
+            ctx.clone(),
+            command.channel_id,
+            locale,
+            Duration::from_secs(minutes as u64 * 60),
+        ));
+        let result = respond(ctx, command, |message| {
+            message.content(t!(
+                locale,
+                "finishthis.started",
+                line = first_line,
+                minutes = minutes
+            ))
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send finishthis message: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+/// Wait for a game to run out of time, then post the completions alongside the original haiku
+/// and save each completion as a haiku of its own
+async fn finish_game(ctx: Context, channel_id: ChannelId, locale: Locale, after: Duration) {
+    tokio::time::sleep(after).await;
+    let game = match completion_games(&ctx).await.lock().await.finish(channel_id) {
+        Some(game) => game,
+        None => return,
+    };
+    let repository = repository(&ctx).await;
+    let mut completions = CreateEmbed::default();
+    completions
+        .title(t!(locale, "finishthis.finished"))
+        .description(format_completions(&game, locale))
+        .color(Color::DARK_GREEN);
+    let mut embeds = vec![completions];
+    let mut content = None;
+    if let Some((id, original)) = repository.get_haiku(game.server, game.prompt.haiku_id) {
+        let plain_text = server_wants_plain_text(&*repository, Some(game.server));
+        let formatted = format_haiku(to_embed_data(id, &original, locale, &ctx).await, plain_text)
+            .titled(t!(locale, "finishthis.original"));
+        content = formatted.content(None);
+        embeds.extend(formatted.embed());
+    }
+    let result = with_retry(&ctx.http, || {
+        channel_id.send_message(&ctx.http, |message| {
+            if let Some(content) = &content {
+                message.content(content);
+            }
+            message.set_embeds(embeds.clone())
+        })
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Failed to send finished completion game: {:?}", why);
+    }
+
+    for completion in &game.completions {
+        if repository
+            .save_haiku(&game.derivative(completion))
+            .is_none()
+        {
+            println!("Failed to save completion by {}", completion.author);
+        }
+    }
+    if let Some(winner) = game.completions.first() {
+        let content = t!(locale, "finishthis.won", line = game.prompt.line.content);
+        notify(
+            &ctx,
+            &*repository,
+            winner.author,
+            Notification::ContestWin,
+            &content,
+            None,
+        )
+        .await;
+    }
+}
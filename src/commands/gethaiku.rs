@@ -1,14 +1,13 @@
 use crate::{
-    database,
-    formatting::{format_haiku_embed, to_embed_data},
+    commands::buttons::{haiku_buttons, is_edited},
+    formatting::{format_haiku, to_embed_data, wants_plain_text},
+    framework::response::respond,
+    i18n::guild_locale,
+    repository::repository,
 };
 use serenity::{
-    async_trait,
-    builder::CreateEmbed,
-    client::Context,
-    model::interactions::{
-        application_command::ApplicationCommandInteraction, InteractionResponseType,
-    },
+    async_trait, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
 };
 use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
 use slash_helper_macros::Command;
@@ -28,27 +27,32 @@ impl ApplicationCommandInteractionHandler for GetHaikuCommand {
         ctx: &Context,
         command: &ApplicationCommandInteraction,
     ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
         let haiku_and_id = match (self.id, command.guild_id) {
-            (id, Some(server_id)) => {
-                let db_connection = database::establish_connection();
-                database::get_haiku(server_id, id, &db_connection)
-            }
+            (id, Some(server_id)) => repository.get_haiku(server_id, id),
             _ => None,
         };
         if let Some((id, haiku)) = haiku_and_id {
-            let embed_data = to_embed_data(id, &haiku, ctx).await;
-            command
-                .create_interaction_response(&ctx.http, |response| {
-                    response
-                        .kind(InteractionResponseType::ChannelMessageWithSource)
-                        .interaction_response_data(|message| {
-                            let mut embed = CreateEmbed::default();
-                            format_haiku_embed(embed_data, &mut embed);
-                            message.add_embed(embed)
-                        })
-                })
-                .await
-                .expect("Failed to send haiku msg");
+            let locale = guild_locale(&*repository, command.guild_id);
+            let embed_data = to_embed_data(id, &haiku, locale, ctx).await;
+            let plain_text = wants_plain_text(&*repository, command.user.id, command.guild_id);
+            let formatted = format_haiku(embed_data, plain_text);
+            let edited = is_edited(&*repository, haiku.server, id);
+            let result = respond(ctx, command, |message| {
+                if let Some(content) = formatted.content(None) {
+                    message
+                        .content(content)
+                        .allowed_mentions(|mentions| mentions.empty_parse());
+                }
+                if let Some(embed) = formatted.embed() {
+                    message.add_embed(embed);
+                }
+                message.components(|components| haiku_buttons(components, id, edited, locale))
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Failed to send haiku msg: {:?}", why);
+            }
         }
         Ok(())
     }
@@ -0,0 +1,62 @@
+use crate::{
+    formatting::{format_date, format_number},
+    framework::response::respond,
+    i18n::{guild_locale, Region},
+    repository::repository,
+};
+use serenity::{
+    async_trait, builder::CreateEmbed, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Show haiku counts from every server that shares them
+#[derive(Command)]
+#[name = "globalstats"]
+pub struct GlobalStatsCommand;
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for GlobalStatsCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let region = Region::from_discord(&command.locale);
+        let stats = repository.get_global_stats();
+
+        let mut embed = CreateEmbed::default();
+        embed.title(t!(locale, "globalstats.title"));
+        embed.field(
+            t!(locale, "globalstats.servers"),
+            format_number(stats.servers, region),
+            true,
+        );
+        embed.field(
+            t!(locale, "globalstats.haikus"),
+            format_number(stats.haikus, region),
+            true,
+        );
+        if let Some((day, haikus)) = stats.biggest_day {
+            embed.field(
+                t!(locale, "globalstats.biggest_day"),
+                t!(
+                    locale,
+                    "globalstats.day",
+                    day = format_date(day, region),
+                    haikus = format_number(haikus, region)
+                ),
+                true,
+            );
+        }
+        embed.footer(|footer| footer.text(t!(locale, "globalstats.footer")));
+        let result = respond(ctx, command, |message| message.add_embed(embed)).await;
+        if let Err(why) = result {
+            println!("Failed to send global stats: {:?}", why);
+        }
+        Ok(())
+    }
+}
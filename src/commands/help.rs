@@ -0,0 +1,378 @@
+use crate::{
+    framework::{
+        components::{custom_id, ComponentHandler},
+        options::get_option,
+        response::respond,
+        spec::{CommandSpec, CommandSpecs, OptionSpec},
+    },
+    i18n::{guild_locale, lookup, Locale},
+    repository::repository,
+    retry::with_retry,
+    RegisteredCommands,
+};
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        channel::Message,
+        interactions::{
+            application_command::{
+                ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandOption,
+                ApplicationCommandOptionType,
+            },
+            message_component::MessageComponentInteraction,
+            InteractionResponseType,
+        },
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+pub const HELP_COMPONENT_PREFIX: &str = "help";
+
+/// List the available commands and how to use them
+#[derive(Command)]
+#[name = "help"]
+pub struct HelpCommand;
+
+impl HelpCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("help").option(OptionSpec::extra(
+            "command",
+            ApplicationCommandOptionType::String,
+            "Show the details of a single command",
+        ))
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for HelpCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (mut commands, specs) = {
+            let data = ctx.data.read().await;
+            (
+                data.get::<RegisteredCommands>()
+                    .expect("Expected RegisteredCommands in TypeMap")
+                    .clone(),
+                data.get::<CommandSpecs>()
+                    .expect("Expected CommandSpecs in TypeMap")
+                    .clone(),
+            )
+        };
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        let locale = guild_locale(&*repository, command.guild_id);
+
+        if let Some(name) = get_option::<String>(command, "command") {
+            let name = name.trim_start_matches('/');
+            let selected = commands.iter().find(|command| command.name == name);
+            let result = respond(ctx, command, |message| match selected {
+                Some(selected) => message.create_embed(|embed| {
+                    format_command_details(selected, &specs, locale, embed);
+                    embed
+                }),
+                None => message.content(t!(locale, "help.no_such_command", name = name)),
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Could not send help message: {:?}", why);
+            }
+            return Ok(());
+        }
+
+        let mut embed = CreateEmbed::default();
+        format_command_list(&commands, &specs, locale, &mut embed);
+        let result = respond(ctx, command, |message| {
+            message.add_embed(embed);
+            message.components(|components| {
+                components.create_action_row(|row| {
+                    row.create_select_menu(|menu| {
+                        menu.custom_id(custom_id(HELP_COMPONENT_PREFIX, "select"))
+                            .placeholder(t!(locale, "help.placeholder"))
+                            .options(|options| {
+                                // Discord allows at most 25 options per select menu
+                                for command in commands.iter().take(25) {
+                                    options.create_option(|option| {
+                                        option
+                                            .label(format!("/{}", command.name))
+                                            .value(&command.name)
+                                            .description(command_description(
+                                                &specs,
+                                                locale,
+                                                &[command.name.as_str()],
+                                                &command.description,
+                                            ))
+                                    });
+                                }
+                                options
+                            })
+                    })
+                })
+            });
+            message
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send help message: {:?}", why);
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows the details of the command picked from the /help select menu
+pub struct HelpSelectHandler;
+
+#[async_trait]
+impl ComponentHandler for HelpSelectHandler {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        original_message: &mut Message,
+    ) {
+        let (commands, specs) = {
+            let data = ctx.data.read().await;
+            (
+                data.get::<RegisteredCommands>()
+                    .expect("Expected RegisteredCommands in TypeMap")
+                    .clone(),
+                data.get::<CommandSpecs>()
+                    .expect("Expected CommandSpecs in TypeMap")
+                    .clone(),
+            )
+        };
+        let selected = interaction
+            .data
+            .values
+            .first()
+            .map(|name| commands.iter().find(|command| &command.name == name))
+            .flatten();
+        if let Some(selected) = selected {
+            let mut embed = CreateEmbed::default();
+            let locale = guild_locale(&*repository(ctx).await, interaction.guild_id);
+            format_command_details(selected, &specs, locale, &mut embed);
+            let result = with_retry(&ctx.http, || {
+                interaction
+                    .channel_id
+                    .edit_message(&ctx.http, original_message.id, |message| {
+                        message.set_embeds(vec![embed.clone()])
+                    })
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Failed to show command details: {:?}", why);
+            }
+            let result = with_retry(&ctx.http, || {
+                interaction.create_interaction_response(&ctx.http, |response| {
+                    response.kind(InteractionResponseType::UpdateMessage)
+                })
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Failed to respond to component interaction: {:?}", why);
+            }
+        }
+    }
+}
+
+fn format_command_list<'a>(
+    commands: &[ApplicationCommand],
+    specs: &CommandSpecs,
+    locale: Locale,
+    embed: &'a mut CreateEmbed,
+) -> &'a mut CreateEmbed {
+    embed.title(t!(locale, "help.title"));
+    embed.description(
+        commands
+            .iter()
+            .map(|command| {
+                format!(
+                    "`/{}` - {}",
+                    command.name,
+                    command_description(
+                        specs,
+                        locale,
+                        &[command.name.as_str()],
+                        &command.description
+                    )
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+    );
+    embed.footer(|footer| footer.text(t!(locale, "help.footer")));
+    embed
+}
+
+fn format_command_details<'a>(
+    command: &ApplicationCommand,
+    specs: &CommandSpecs,
+    locale: Locale,
+    embed: &'a mut CreateEmbed,
+) -> &'a mut CreateEmbed {
+    embed.title(format!("/{}", command.name));
+    embed.description(command_description(
+        specs,
+        locale,
+        &[command.name.as_str()],
+        &command.description,
+    ));
+    let subcommands = subcommands(specs, locale, &[command.name.as_str()], &command.options);
+    if !subcommands.is_empty() {
+        for (usage, description, example) in subcommands {
+            embed.field(
+                format!("`{}`", usage),
+                format!(
+                    "{}\n{}: `{}`",
+                    description,
+                    t!(locale, "help.example"),
+                    example
+                ),
+                false,
+            );
+        }
+        return embed;
+    }
+    let prefix = format!("/{}", command.name);
+    embed.field(
+        t!(locale, "help.usage"),
+        format!("`{}`", format_usage(&prefix, &command.options, locale)),
+        false,
+    );
+    embed.field(
+        t!(locale, "help.example"),
+        format!("`{}`", format_example(&prefix, &command.options, locale)),
+        false,
+    );
+    for option in command.options.iter() {
+        embed.field(
+            if option.required {
+                option.name.clone()
+            } else {
+                format!("{} {}", option.name, t!(locale, "help.optional"))
+            },
+            format!(
+                "{}\n{}",
+                option.description,
+                t!(locale, "help.type", kind = option_type_name(option, locale))
+            ),
+            false,
+        );
+    }
+    embed
+}
+
+/// The description of a command in the given locale, or its registered description. The
+/// translations are under the command's own name, whatever it's registered as.
+fn command_description(
+    specs: &CommandSpecs,
+    locale: Locale,
+    path: &[&str],
+    description: &str,
+) -> String {
+    let mut path = path.to_vec();
+    path[0] = specs.original_name(path[0]);
+    lookup(locale, &format!("command_descriptions.{}", path.join(" ")))
+        .unwrap_or(description.to_owned())
+}
+
+/// Usage, description and an example of every subcommand, including those inside subcommand
+/// groups
+fn subcommands(
+    specs: &CommandSpecs,
+    locale: Locale,
+    path: &[&str],
+    options: &[ApplicationCommandOption],
+) -> Vec<(String, String, String)> {
+    options
+        .iter()
+        .flat_map(|option| {
+            let mut path = path.to_vec();
+            path.push(&option.name);
+            match option.kind {
+                ApplicationCommandOptionType::SubCommandGroup => {
+                    subcommands(specs, locale, &path, &option.options)
+                }
+                ApplicationCommandOptionType::SubCommand => {
+                    let prefix = format!("/{}", path.join(" "));
+                    vec![(
+                        format_usage(&prefix, &option.options, locale),
+                        command_description(specs, locale, &path, &option.description),
+                        format_example(&prefix, &option.options, locale),
+                    )]
+                }
+                _ => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+fn format_usage(prefix: &str, options: &[ApplicationCommandOption], locale: Locale) -> String {
+    let mut usage = prefix.to_owned();
+    for option in options.iter() {
+        let option_usage = format!("{}:<{}>", option.name, option_type_name(option, locale));
+        if option.required {
+            usage.push_str(&format!(" {}", option_usage));
+        } else {
+            usage.push_str(&format!(" [{}]", option_usage));
+        }
+    }
+    usage
+}
+
+/// The command filled in with its required options, or its first option if none are required
+fn format_example(prefix: &str, options: &[ApplicationCommandOption], locale: Locale) -> String {
+    let mut shown = options
+        .iter()
+        .filter(|option| option.required)
+        .collect::<Vec<&ApplicationCommandOption>>();
+    if shown.is_empty() {
+        shown.extend(options.first());
+    }
+    let mut example = prefix.to_owned();
+    for option in shown {
+        example.push_str(&format!(
+            " {}:{}",
+            option.name,
+            example_value(option, locale)
+        ));
+    }
+    example
+}
+
+/// The option's first choice if it has any, otherwise a made up value of the right type
+fn example_value(option: &ApplicationCommandOption, locale: Locale) -> String {
+    if let Some(choice) = option.choices.first() {
+        return choice.name.clone();
+    }
+    match option_type_key(option) {
+        "whole_number" => "3".to_owned(),
+        "number" => "0.5".to_owned(),
+        "boolean" => "True".to_owned(),
+        key => t!(locale, &format!("help.examples.{}", key)),
+    }
+}
+
+fn option_type_name(option: &ApplicationCommandOption, locale: Locale) -> String {
+    t!(locale, &format!("help.types.{}", option_type_key(option)))
+}
+
+fn option_type_key(option: &ApplicationCommandOption) -> &'static str {
+    match option.kind {
+        ApplicationCommandOptionType::String => "text",
+        ApplicationCommandOptionType::Integer => "whole_number",
+        ApplicationCommandOptionType::Number => "number",
+        ApplicationCommandOptionType::Boolean => "boolean",
+        ApplicationCommandOptionType::User => "user",
+        ApplicationCommandOptionType::Channel => "channel",
+        ApplicationCommandOptionType::Role => "role",
+        ApplicationCommandOptionType::Mentionable => "mentionable",
+        _ => "value",
+    }
+}
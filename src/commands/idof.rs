@@ -0,0 +1,118 @@
+use crate::{
+    detection::find_haiku_for_message, formatting::haiku_id_from_footer,
+    framework::response::respond, i18n::guild_locale, repository::repository, retry::with_retry,
+};
+use chrono::Utc;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        id::{ChannelId, GuildId, MessageId},
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Find the id of the haiku a message is part of, to use with /gethaiku
+#[derive(Command)]
+#[name = "idof"]
+pub struct IdOfCommand {
+    /// A link to the haiku's announcement, or to one of the messages it was made from
+    link: String,
+}
+
+/// The ids in a message link, e.g. https://discord.com/channels/1/2/3
+pub fn parse_message_link(link: &str) -> Option<(GuildId, ChannelId, MessageId)> {
+    let path = link.trim().trim_start_matches('<').trim_end_matches('>');
+    let path = path
+        .strip_prefix("https://")
+        .or_else(|| path.strip_prefix("http://"))
+        .unwrap_or(path);
+    let mut parts = path.split('/');
+    let host = parts.next()?;
+    let host = host
+        .strip_prefix("ptb.")
+        .or_else(|| host.strip_prefix("canary."))
+        .unwrap_or(host);
+    if !matches!(host, "discord.com" | "discordapp.com") || parts.next()? != "channels" {
+        return None;
+    }
+    let guild_id = parts.next()?.parse().ok()?;
+    let channel_id = parts.next()?.parse().ok()?;
+    let message_id = parts.next()?.parse().ok()?;
+    match parts.next() {
+        None => Some((
+            GuildId(guild_id),
+            ChannelId(channel_id),
+            MessageId(message_id),
+        )),
+        Some(_) => None,
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for IdOfCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let content = match parse_message_link(&self.link) {
+            None => t!(locale, "idof.invalid_link"),
+            Some((server_id, _, _)) if Some(server_id) != command.guild_id => {
+                t!(locale, "idof.other_server")
+            }
+            Some((server_id, channel_id, message_id)) => {
+                match with_retry(&ctx.http, || {
+                    ctx.http.get_message(channel_id.0, message_id.0)
+                })
+                .await
+                {
+                    Err(why) => {
+                        println!("Could not fetch message for /idof: {:?}", why);
+                        t!(locale, "idof.fetch_failed")
+                    }
+                    Ok(message) => {
+                        let id = if message.author.id == ctx.cache.current_user_id().await {
+                            message
+                                .embeds
+                                .iter()
+                                .filter_map(|embed| embed.footer.as_ref())
+                                .find_map(|footer| haiku_id_from_footer(&footer.text))
+                        } else {
+                            let haikus =
+                                repository.get_haikus_by_author(server_id, message.author.id);
+                            find_haiku_for_message(
+                                &haikus,
+                                channel_id,
+                                message.author.id,
+                                &message.content,
+                                message.timestamp.with_timezone(&Utc),
+                            )
+                        };
+                        match id {
+                            Some(id) => t!(locale, "idof.result", id = id),
+                            None => t!(locale, "idof.not_found"),
+                        }
+                    }
+                }
+            }
+        };
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send idof message: {:?}", why);
+        }
+        Ok(())
+    }
+}
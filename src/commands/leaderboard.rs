@@ -0,0 +1,189 @@
+use crate::{
+    formatting::format_number,
+    framework::{
+        pagination::{Page, Pages, Paginator},
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale, Region},
+    leaderboard::LeaderboardPeriod,
+    repository::repository,
+    schedule::server_timezone,
+};
+use chrono::{DateTime, Utc};
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        id::{GuildId, UserId},
+        interactions::application_command::ApplicationCommandInteraction,
+        misc::Mentionable,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// How many poets are listed on each page
+const POETS_PER_PAGE: i64 = 10;
+
+impl Choice for LeaderboardPeriod {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("This week", "week"),
+            ("This month", "month"),
+            ("This year", "year"),
+            ("All time", "alltime"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        LeaderboardPeriod::from_code(value)
+    }
+}
+
+/// Show who has written the most haikus in this server
+#[derive(Command)]
+#[name = "leaderboard"]
+pub struct LeaderboardCommand {
+    /// How far back to count haikus from
+    period: String,
+}
+
+impl LeaderboardCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("leaderboard").option(
+            OptionSpec::new("period")
+                .choices::<LeaderboardPeriod>()
+                .default(LeaderboardPeriod::Month.code()),
+        )
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for LeaderboardCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let (server_id, period) = match (
+            command.guild_id,
+            LeaderboardPeriod::from_value(&self.period),
+        ) {
+            (Some(server_id), Some(period)) => (server_id, period),
+            _ => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        // Fixed when the command is run, so paging through doesn't shift into the next period
+        let since = period.start(Utc::now(), server_timezone(&*repository, server_id));
+        let authors = repository.count_leaderboard_authors(server_id, since);
+        if authors == 0 {
+            let result = respond(ctx, command, |message| {
+                message.content(t!(locale, "leaderboard.no_haikus"))
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Could not send leaderboard message: {:?}", why);
+            }
+            return Ok(());
+        }
+
+        let pages = LeaderboardPage {
+            server_id,
+            period,
+            since,
+            page: 0,
+            authors,
+            locale,
+            region: Region::from_discord(&command.locale),
+        };
+        Paginator::new(pages, locale).send(ctx, command).await;
+        Ok(())
+    }
+}
+
+/// Which page of which leaderboard a message is showing. Only that page's poets are fetched.
+pub struct LeaderboardPage {
+    server_id: GuildId,
+    period: LeaderboardPeriod,
+    since: Option<DateTime<Utc>>,
+    page: i64,
+    /// How many poets are on the leaderboard, as of the last page loaded
+    authors: i64,
+    locale: Locale,
+    /// How numbers are written for whoever ran the command
+    region: Region,
+}
+
+#[async_trait]
+impl Pages for LeaderboardPage {
+    fn page_count(&self) -> usize {
+        ((self.authors + POETS_PER_PAGE - 1) / POETS_PER_PAGE) as usize
+    }
+
+    async fn show(&mut self, ctx: &Context, page: usize) -> Page {
+        let repository = repository(ctx).await;
+        self.page = page as i64;
+        self.authors = repository.count_leaderboard_authors(self.server_id, self.since);
+        let counts = repository.get_leaderboard(
+            self.server_id,
+            self.since,
+            self.page * POETS_PER_PAGE,
+            POETS_PER_PAGE,
+        );
+        let mut shown = Page::default();
+        self.format(
+            &counts,
+            shown.embed.get_or_insert_with(CreateEmbed::default),
+        );
+        shown
+    }
+}
+
+impl LeaderboardPage {
+    /// A ranked list of the poets on this page, ranks continuing from the previous pages
+    fn format<'a>(
+        &self,
+        counts: &[(UserId, i64)],
+        embed: &'a mut CreateEmbed,
+    ) -> &'a mut CreateEmbed {
+        let locale = self.locale;
+        let title = match self.period {
+            LeaderboardPeriod::Week => t!(locale, "leaderboard.title_week"),
+            LeaderboardPeriod::Month => t!(locale, "leaderboard.title_month"),
+            LeaderboardPeriod::Year => t!(locale, "leaderboard.title_year"),
+            LeaderboardPeriod::AllTime => t!(locale, "leaderboard.title_alltime"),
+        };
+        embed.title(title);
+        embed.description(
+            counts
+                .iter()
+                .enumerate()
+                .map(|(index, (author, count))| {
+                    t!(
+                        locale,
+                        "leaderboard.entry",
+                        rank = format_number(
+                            self.page * POETS_PER_PAGE + index as i64 + 1,
+                            self.region
+                        ),
+                        user = author.mention(),
+                        count = format_number(*count, self.region)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+        );
+        embed.footer(|footer| {
+            footer.text(t!(
+                locale,
+                "leaderboard.page",
+                page = self.page + 1,
+                pages = self.page_count()
+            ))
+        });
+        embed
+    }
+}
@@ -1,24 +1,599 @@
+#[cfg(feature = "critique")]
+use self::critique::CritiqueCommand;
 use self::{
-    count::CountCommand, gethaiku::GetHaikuCommand, random::RandomHaikuCommand,
-    search::SearchCommand, uptime::UptimeCommand,
+    about::AboutCommand,
+    admin::{
+        AdminMaintenanceCommand, AdminRegisterCommand, AdminReloadCommand, AdminRevalidateCommand,
+        AdminStatsCommand,
+    },
+    apitoken::{ApiTokenCreateCommand, ApiTokenListCommand, ApiTokenRevokeCommand},
+    buttons::{HaikuButtonHandler, HAIKU_COMPONENT_PREFIX},
+    chain::ChainStartCommand,
+    channelstats::ChannelStatsCommand,
+    config::{
+        ConfigAnnouncementCommand, ConfigArchiveCommand, ConfigBridgeCommand,
+        ConfigDetectionCommand, ConfigErrorsCommand, ConfigFeedsCommand, ConfigGlobalStatsCommand,
+        ConfigLanguageCommand, ConfigLaureateCommand, ConfigManualSaveCommand,
+        ConfigPlainTextCommand, ConfigRandomCommand, ConfigRetentionCommand,
+        ConfigSameAuthorCommand, ConfigSendersCommand, ConfigSyllablesCommand,
+        ConfigTimezoneCommand, ConfigWelcomeCommand,
+    },
+    contentwarning::ContentWarningCommand,
+    count::CountCommand,
+    countfile::CountFileCommand,
+    deletehaiku::DeleteHaikuCommand,
+    edithaiku::EditHaikuCommand,
+    exportmine::ExportMineCommand,
+    feed::FeedCommand,
+    finishthis::FinishThisCommand,
+    gethaiku::GetHaikuCommand,
+    globalstats::GlobalStatsCommand,
+    help::{HelpCommand, HelpSelectHandler, HELP_COMPONENT_PREFIX},
+    idof::IdOfCommand,
+    leaderboard::LeaderboardCommand,
+    notifications::{NotificationsSetCommand, NotificationsShowCommand},
+    plaintext::PlainTextCommand,
+    quiz::{QuizCommand, QuizLeaderboardCommand},
+    random::RandomHaikuCommand,
+    randomline::RandomLineCommand,
+    recheck::RecheckCommand,
+    scanhistory::ScanHistoryCommand,
+    search::SearchCommand,
+    translate::TranslateCommand,
+    uptime::UptimeCommand,
+    usage::UsageCommand,
+};
+use crate::{
+    config::{CommandsConfig, Config},
+    framework::{
+        components::ComponentRouter,
+        progress::{CancelHandler, PROGRESS_COMPONENT_PREFIX},
+        spec::{overwrite_commands, CommandSpecs},
+    },
+    RegisteredCommands,
 };
 use serenity::{
-    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+    builder::CreateApplicationCommand,
+    client::Context,
+    model::{
+        id::GuildId,
+        interactions::application_command::{ApplicationCommand, ApplicationCommandInteraction},
+    },
 };
-use slash_helper::{ApplicationCommandInteractionHandler, InvocationError, ParseError};
+use slash_helper::{ApplicationCommandInteractionHandler, Command, InvocationError, ParseError};
 use slash_helper_macros::Commands;
+use std::{
+    env::{self, VarError},
+    sync::Arc,
+};
 
+pub mod about;
+pub mod admin;
+pub mod apitoken;
+pub mod buttons;
+pub mod chain;
+pub mod channelstats;
+pub mod config;
+pub mod contentwarning;
 pub mod count;
+pub mod countfile;
+#[cfg(feature = "critique")]
+pub mod critique;
+pub mod deletehaiku;
+pub mod edithaiku;
+pub mod exportmine;
+pub mod feed;
+pub mod finishthis;
 pub mod gethaiku;
+pub mod globalstats;
+pub mod help;
+pub mod idof;
+pub mod leaderboard;
+pub mod notifications;
+pub mod plaintext;
+pub mod quiz;
 pub mod random;
+pub mod randomline;
+pub mod recheck;
+pub mod scanhistory;
 pub mod search;
+pub mod translate;
 pub mod uptime;
+pub mod usage;
 
 #[derive(Commands)]
 pub enum Commands {
     Uptime(UptimeCommand),
     Count(CountCommand),
+    CountFile(CountFileCommand),
     GetHaiku(GetHaikuCommand),
+    IdOf(IdOfCommand),
+    Recheck(RecheckCommand),
     RandomHaiku(RandomHaikuCommand),
+    RandomLine(RandomLineCommand),
     Search(SearchCommand),
+    ScanHistory(ScanHistoryCommand),
+    ChannelStats(ChannelStatsCommand),
+    Leaderboard(LeaderboardCommand),
+    ChainStart(ChainStartCommand),
+    FinishThis(FinishThisCommand),
+    Quiz(QuizCommand),
+    QuizLeaderboard(QuizLeaderboardCommand),
+    Help(HelpCommand),
+    About(AboutCommand),
+    Translate(TranslateCommand),
+    DeleteHaiku(DeleteHaikuCommand),
+    EditHaiku(EditHaikuCommand),
+    ContentWarning(ContentWarningCommand),
+    ExportMine(ExportMineCommand),
+    AdminReload(AdminReloadCommand),
+    AdminRegister(AdminRegisterCommand),
+    AdminMaintenance(AdminMaintenanceCommand),
+    AdminRevalidate(AdminRevalidateCommand),
+    AdminStats(AdminStatsCommand),
+    ConfigLanguage(ConfigLanguageCommand),
+    ConfigWelcome(ConfigWelcomeCommand),
+    ConfigRandom(ConfigRandomCommand),
+    ConfigLaureate(ConfigLaureateCommand),
+    ConfigRetention(ConfigRetentionCommand),
+    ConfigGlobalStats(ConfigGlobalStatsCommand),
+    ConfigAnnouncement(ConfigAnnouncementCommand),
+    ConfigArchive(ConfigArchiveCommand),
+    ConfigBridge(ConfigBridgeCommand),
+    ConfigDetection(ConfigDetectionCommand),
+    ConfigManualSave(ConfigManualSaveCommand),
+    ConfigSameAuthor(ConfigSameAuthorCommand),
+    ConfigSenders(ConfigSendersCommand),
+    ConfigSyllables(ConfigSyllablesCommand),
+    ConfigErrors(ConfigErrorsCommand),
+    ConfigTimezone(ConfigTimezoneCommand),
+    ConfigPlainText(ConfigPlainTextCommand),
+    ConfigFeeds(ConfigFeedsCommand),
+    GlobalStats(GlobalStatsCommand),
+    Usage(UsageCommand),
+    NotificationsShow(NotificationsShowCommand),
+    NotificationsSet(NotificationsSetCommand),
+    PlainText(PlainTextCommand),
+    Feed(FeedCommand),
+    ApiTokenCreate(ApiTokenCreateCommand),
+    ApiTokenList(ApiTokenListCommand),
+    ApiTokenRevoke(ApiTokenRevokeCommand),
+    #[cfg(feature = "critique")]
+    Critique(CritiqueCommand),
+}
+
+/// The guild to register commands in while testing, from $TEST_GUILD_ID
+pub fn test_guild_id() -> Option<GuildId> {
+    match env::var("TEST_GUILD_ID") {
+        Ok(id) => Some(GuildId(id.parse().expect("Invalid test guild id id"))),
+        Err(VarError::NotPresent) => None,
+        _ => panic!("Invalid guild id provided at $TEST_GUILD_ID"),
+    }
+}
+
+/// A command as slash-helper derives it from its struct, built without registering it
+fn build<C: Command>() -> CreateApplicationCommand {
+    let mut command = CreateApplicationCommand::default();
+    C::register(&mut command);
+    command
+}
+
+/// Every command as slash-helper derives it, before [CommandSpecs::apply] fills in the rest
+fn build_all() -> Vec<CreateApplicationCommand> {
+    vec![
+        build::<UptimeCommand>(),
+        build::<CountCommand>(),
+        build::<CountFileCommand>(),
+        build::<GetHaikuCommand>(),
+        build::<IdOfCommand>(),
+        build::<RecheckCommand>(),
+        build::<RandomHaikuCommand>(),
+        build::<RandomLineCommand>(),
+        build::<SearchCommand>(),
+        build::<ScanHistoryCommand>(),
+        build::<ChannelStatsCommand>(),
+        build::<LeaderboardCommand>(),
+        build::<ChainStartCommand>(),
+        build::<FinishThisCommand>(),
+        build::<QuizCommand>(),
+        build::<QuizLeaderboardCommand>(),
+        build::<HelpCommand>(),
+        build::<AboutCommand>(),
+        build::<TranslateCommand>(),
+        build::<DeleteHaikuCommand>(),
+        build::<EditHaikuCommand>(),
+        build::<ContentWarningCommand>(),
+        build::<ExportMineCommand>(),
+        build::<AdminReloadCommand>(),
+        build::<AdminRegisterCommand>(),
+        build::<AdminMaintenanceCommand>(),
+        build::<AdminRevalidateCommand>(),
+        build::<AdminStatsCommand>(),
+        build::<ConfigLanguageCommand>(),
+        build::<ConfigWelcomeCommand>(),
+        build::<ConfigRandomCommand>(),
+        build::<ConfigLaureateCommand>(),
+        build::<ConfigRetentionCommand>(),
+        build::<ConfigGlobalStatsCommand>(),
+        build::<ConfigAnnouncementCommand>(),
+        build::<ConfigArchiveCommand>(),
+        build::<ConfigBridgeCommand>(),
+        build::<ConfigDetectionCommand>(),
+        build::<ConfigManualSaveCommand>(),
+        build::<ConfigSameAuthorCommand>(),
+        build::<ConfigSendersCommand>(),
+        build::<ConfigSyllablesCommand>(),
+        build::<ConfigErrorsCommand>(),
+        build::<ConfigTimezoneCommand>(),
+        build::<ConfigPlainTextCommand>(),
+        build::<ConfigFeedsCommand>(),
+        build::<GlobalStatsCommand>(),
+        build::<UsageCommand>(),
+        build::<NotificationsShowCommand>(),
+        build::<NotificationsSetCommand>(),
+        build::<PlainTextCommand>(),
+        build::<FeedCommand>(),
+        build::<ApiTokenCreateCommand>(),
+        build::<ApiTokenListCommand>(),
+        build::<ApiTokenRevokeCommand>(),
+        #[cfg(feature = "critique")]
+        build::<CritiqueCommand>(),
+    ]
+}
+
+/// Register every command, either for a single guild or globally
+pub async fn register_all(ctx: &Context, guild_id: Option<GuildId>) -> Vec<ApplicationCommand> {
+    let specs = {
+        let data = ctx.data.read().await;
+        data.get::<CommandSpecs>()
+            .expect("Expected CommandSpecs in TypeMap")
+            .clone()
+    };
+    // The specs are applied before registering, so Discord only ever sees the finished commands
+    let commands = overwrite_commands(ctx, guild_id, specs.apply(build_all()))
+        .await
+        .expect("Unable to register commands");
+    {
+        let mut data = ctx.data.write().await;
+        data.insert::<RegisteredCommands>(commands.clone());
+    }
+    println!(
+        "Registered {} commands {}",
+        commands.len(),
+        match guild_id {
+            Some(id) => format!("for guild_id: {}", id),
+            None => "globally".to_owned(),
+        },
+    );
+    commands
+}
+
+/// Register every command again wherever the bot registers them, returning how many were registered
+pub async fn reregister_all(ctx: &Context) -> usize {
+    let guild_scoped = {
+        let data = ctx.data.read().await;
+        data.get::<Config>()
+            .expect("Expected Config in TypeMap")
+            .commands
+            .guild_scoped
+    };
+    match test_guild_id() {
+        Some(guild_id) => register_all(ctx, Some(guild_id)).await.len(),
+        None if guild_scoped => {
+            let mut registered = 0;
+            for guild_id in ctx.cache.guilds().await {
+                registered += register_all(ctx, Some(guild_id)).await.len();
+            }
+            registered
+        }
+        None => register_all(ctx, None).await.len(),
+    }
+}
+
+/// Metadata for command options beyond what slash-helper derives from the command structs
+pub fn command_specs() -> CommandSpecs {
+    CommandSpecs::new(vec![
+        AdminMaintenanceCommand::spec(),
+        AdminRegisterCommand::spec(),
+        AdminReloadCommand::spec(),
+        AdminRevalidateCommand::spec(),
+        AdminStatsCommand::spec(),
+        ApiTokenCreateCommand::spec(),
+        ApiTokenListCommand::spec(),
+        ApiTokenRevokeCommand::spec(),
+        ChainStartCommand::spec(),
+        ConfigAnnouncementCommand::spec(),
+        ConfigArchiveCommand::spec(),
+        ConfigBridgeCommand::spec(),
+        ConfigDetectionCommand::spec(),
+        ConfigErrorsCommand::spec(),
+        ConfigFeedsCommand::spec(),
+        ConfigGlobalStatsCommand::spec(),
+        ConfigLanguageCommand::spec(),
+        ConfigLaureateCommand::spec(),
+        ConfigManualSaveCommand::spec(),
+        ConfigPlainTextCommand::spec(),
+        ConfigRandomCommand::spec(),
+        ConfigRetentionCommand::spec(),
+        ConfigSameAuthorCommand::spec(),
+        ConfigSendersCommand::spec(),
+        ConfigSyllablesCommand::spec(),
+        ConfigTimezoneCommand::spec(),
+        ConfigWelcomeCommand::spec(),
+        ContentWarningCommand::spec(),
+        CountFileCommand::spec(),
+        DeleteHaikuCommand::spec(),
+        ExportMineCommand::spec(),
+        FeedCommand::spec(),
+        FinishThisCommand::spec(),
+        HelpCommand::spec(),
+        LeaderboardCommand::spec(),
+        NotificationsSetCommand::spec(),
+        NotificationsShowCommand::spec(),
+        PlainTextCommand::spec(),
+        RandomHaikuCommand::spec(),
+        RandomLineCommand::spec(),
+        RecheckCommand::spec(),
+        ScanHistoryCommand::spec(),
+        SearchCommand::spec(),
+        TranslateCommand::spec(),
+        UsageCommand::spec(),
+    ])
+    .group(&["admin"], "Tools for the bot's owner")
+    .group(
+        &["apitoken"],
+        "Let tools outside Discord add and delete this server's haikus through the HTTP API",
+    )
+    .group(&["chain"], "Write haikus together on a theme")
+    .group(&["config"], "View and change settings for this server")
+    .group(
+        &["notifications"],
+        "Choose which DMs the bot sends you about your haikus",
+    )
+}
+
+/// The commands moved under /haiku in umbrella mode, with their subcommand names
+const UMBRELLA_COMMANDS: &[(&str, &str)] = &[
+    ("gethaiku", "get"),
+    ("randomhaiku", "random"),
+    ("search", "search"),
+    ("count", "count"),
+    ("channelstats", "stats"),
+];
+
+/// The command specs, laid out and named as the config says
+pub fn configured_command_specs(config: &CommandsConfig) -> CommandSpecs {
+    let specs = command_specs();
+    let specs = if config.umbrella {
+        specs.umbrella("haiku", "Find, count and share haikus", UMBRELLA_COMMANDS)
+    } else {
+        specs
+    };
+    specs.named(config)
+}
+
+/// Route the components that commands attach to their messages back to the module that owns them
+pub fn component_router() -> ComponentRouter {
+    let router = ComponentRouter::default();
+    router.route(HELP_COMPONENT_PREFIX, Arc::new(HelpSelectHandler));
+    router.route(HAIKU_COMPONENT_PREFIX, Arc::new(HaikuButtonHandler));
+    router.route(PROGRESS_COMPONENT_PREFIX, Arc::new(CancelHandler));
+    router
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_all, command_specs, configured_command_specs, idof::parse_message_link};
+    use crate::{
+        config::CommandsConfig,
+        framework::{
+            options::get_option,
+            spec::{CommandSpec, CommandSpecs, OptionSpec},
+            testing::MockInteraction,
+        },
+    };
+    use serenity::model::{
+        id::{ChannelId, GuildId, MessageId},
+        Permissions,
+    };
+
+    #[test]
+    fn test_subcommands_are_flattened() {
+        let specs = command_specs();
+        let interaction = specs.prepare(&MockInteraction::new(&["admin", "stats"]).build());
+        assert_eq!(interaction.data.name, "admin-stats");
+        assert!(interaction.data.options.is_empty());
+
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["config", "language"])
+                .option("language", "de")
+                .build(),
+        );
+        assert_eq!(interaction.data.name, "config-language");
+        assert_eq!(specs.display_name("config-language"), "config language");
+        assert_eq!(specs.display_name("usage"), "usage");
+        assert_eq!(
+            get_option::<String>(&interaction, "language"),
+            Some("de".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_renamed_commands() {
+        let config = CommandsConfig {
+            prefix: "haiku-".to_owned(),
+            rename: vec![("search".to_owned(), "findhaiku".to_owned())]
+                .into_iter()
+                .collect(),
+            ..CommandsConfig::default()
+        };
+        let specs = command_specs().named(&config);
+        assert_eq!(specs.registered_name("config"), "haiku-config");
+        assert_eq!(specs.registered_name("search"), "findhaiku");
+        assert_eq!(
+            specs.display_name("config-language"),
+            "haiku-config language"
+        );
+
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["haiku-config", "language"])
+                .option("language", "de")
+                .build(),
+        );
+        assert_eq!(interaction.data.name, "config-language");
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["findhaiku"])
+                .option("keywords", "frog")
+                .build(),
+        );
+        assert_eq!(interaction.data.name, "search");
+        assert_eq!(specs.original_name("haiku-usage"), "usage");
+    }
+
+    #[test]
+    fn test_umbrella_command() {
+        let config = CommandsConfig {
+            umbrella: true,
+            ..CommandsConfig::default()
+        };
+        let specs = configured_command_specs(&config);
+        assert!(specs.top_level_names().contains(&"haiku"));
+        assert!(!specs.top_level_names().contains(&"gethaiku"));
+        assert_eq!(specs.display_name("gethaiku"), "haiku get");
+
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["haiku", "random"])
+                .option("hide_author", "on")
+                .build(),
+        );
+        assert_eq!(interaction.data.name, "randomhaiku");
+        assert_eq!(
+            get_option::<String>(&interaction, "hide_author"),
+            Some("on".to_owned())
+        );
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["haiku", "get"])
+                .option("id", 5)
+                .build(),
+        );
+        assert_eq!(interaction.data.name, "gethaiku");
+        assert_eq!(get_option::<i64>(&interaction, "id"), Some(5));
+    }
+
+    #[test]
+    fn test_defaults_are_filled_in() {
+        let specs = command_specs();
+        let interaction =
+            specs.prepare(&MockInteraction::new(&["translate"]).option("id", 5).build());
+        assert_eq!(get_option::<i64>(&interaction, "id"), Some(5));
+        assert_eq!(
+            get_option::<String>(&interaction, "language"),
+            Some("en".to_owned())
+        );
+
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["translate"])
+                .option("id", 5)
+                .option("language", "ja")
+                .build(),
+        );
+        assert_eq!(
+            get_option::<String>(&interaction, "language"),
+            Some("ja".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_permissions_are_checked() {
+        let specs = command_specs();
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["deletehaiku"])
+                .option("id", 5)
+                .build(),
+        );
+        assert_eq!(
+            specs.missing_permissions(&interaction),
+            Permissions::MANAGE_MESSAGES
+        );
+
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["deletehaiku"])
+                .option("id", 5)
+                .permissions(Permissions::MANAGE_MESSAGES)
+                .build(),
+        );
+        assert!(specs.missing_permissions(&interaction).is_empty());
+
+        let interaction = specs.prepare(
+            &MockInteraction::new(&["deletehaiku"])
+                .option("id", 5)
+                .permissions(Permissions::ADMINISTRATOR)
+                .build(),
+        );
+        assert!(specs.missing_permissions(&interaction).is_empty());
+    }
+
+    #[test]
+    fn test_owner_only_commands() {
+        let specs = command_specs();
+        let admin = specs.prepare(&MockInteraction::new(&["admin", "reload"]).build());
+        assert!(specs.is_owner_only(&admin));
+        let search = specs.prepare(
+            &MockInteraction::new(&["search"])
+                .option("keywords", "frog")
+                .direct_message()
+                .build(),
+        );
+        assert!(!specs.is_owner_only(&search));
+        assert!(specs.missing_permissions(&search).is_empty());
+    }
+
+    #[test]
+    fn test_specs_match_commands() {
+        let config = CommandsConfig {
+            umbrella: true,
+            ..CommandsConfig::default()
+        };
+        // Every spec and umbrella entry is for a command and options that exist
+        let commands = configured_command_specs(&config).apply(build_all());
+        assert!(commands.iter().any(|command| command.0["name"] == "haiku"));
+    }
+
+    #[test]
+    #[should_panic(expected = "has an option langauge")]
+    fn test_spec_for_unknown_option() {
+        CommandSpecs::new(vec![
+            CommandSpec::new("translate").option(OptionSpec::new("langauge"))
+        ])
+        .apply(build_all());
+    }
+
+    #[test]
+    #[should_panic(expected = "/gethaiku-typo, which isn't a command")]
+    fn test_spec_for_unknown_command() {
+        CommandSpecs::new(vec![CommandSpec::new("gethaiku-typo")]).apply(build_all());
+    }
+
+    #[test]
+    fn test_parse_message_link() {
+        let ids = Some((GuildId(1), ChannelId(2), MessageId(3)));
+        assert_eq!(
+            parse_message_link("https://discord.com/channels/1/2/3"),
+            ids
+        );
+        assert_eq!(
+            parse_message_link("<https://canary.discordapp.com/channels/1/2/3>"),
+            ids
+        );
+        assert_eq!(parse_message_link("https://discord.com/channels/1/2"), None);
+        assert_eq!(
+            parse_message_link("https://discord.com/channels/1/2/3/4"),
+            None
+        );
+        assert_eq!(
+            parse_message_link("https://example.com/channels/1/2/3"),
+            None
+        );
+        assert_eq!(parse_message_link("42"), None);
+    }
 }
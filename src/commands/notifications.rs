@@ -0,0 +1,140 @@
+use crate::{
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    models::{Notification, UserPreferences},
+    repository::repository,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::{
+        application_command::ApplicationCommandInteraction,
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+impl Choice for Notification {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("When a haiku of mine is found", "detected"),
+            ("When someone bookmarks a haiku of mine", "bookmarked"),
+            (
+                "When I win a contest, like becoming laureate",
+                "contest_win",
+            ),
+            ("When a haiku of mine gets lots of votes", "vote_threshold"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        Notification::from_code(value)
+    }
+}
+
+fn notification_name(notification: Notification, locale: Locale) -> String {
+    match notification {
+        Notification::Detected => t!(locale, "notifications.name_detected"),
+        Notification::Bookmarked => t!(locale, "notifications.name_bookmarked"),
+        Notification::ContestWin => t!(locale, "notifications.name_contest_win"),
+        Notification::VoteThreshold => t!(locale, "notifications.name_vote_threshold"),
+    }
+}
+
+fn format_preferences(preferences: &UserPreferences, locale: Locale) -> String {
+    let mut lines = vec![t!(locale, "notifications.title")];
+    lines.extend(Notification::all().iter().map(|notification| {
+        let state = if preferences.wants(*notification) {
+            t!(locale, "notifications.on")
+        } else {
+            t!(locale, "notifications.off")
+        };
+        format!("{}: {}", notification_name(*notification, locale), state)
+    }));
+    lines.join("\n")
+}
+
+/// Only the person who ran the command needs to see their preferences
+async fn reply(ctx: &Context, command: &ApplicationCommandInteraction, content: String) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(content)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not send notifications message: {:?}", why);
+    }
+}
+
+/// See which DMs the bot sends you about your haikus
+#[derive(Command)]
+#[name = "notifications-show"]
+pub struct NotificationsShowCommand;
+
+impl NotificationsShowCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("notifications-show").subcommand(&["notifications", "show"])
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for NotificationsShowCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let preferences = repository.get_user_preferences(command.user.id);
+        reply(ctx, command, format_preferences(&preferences, locale)).await;
+        Ok(())
+    }
+}
+
+/// Choose whether the bot DMs you about something that happens to your haikus
+#[derive(Command)]
+#[name = "notifications-set"]
+pub struct NotificationsSetCommand {
+    /// What to be told about
+    notification: String,
+    /// Whether to be told about it
+    enabled: String,
+}
+
+impl NotificationsSetCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("notifications-set")
+            .subcommand(&["notifications", "set"])
+            .option(OptionSpec::new("notification").choices::<Notification>())
+            .option(OptionSpec::new("enabled").choices::<bool>())
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for NotificationsSetCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let (notification, enabled) = match (
+            Notification::from_value(&self.notification),
+            bool::from_value(&self.enabled),
+        ) {
+            (Some(notification), Some(enabled)) => (notification, enabled),
+            _ => return Ok(()),
+        };
+        repository.set_user_notification(command.user.id, notification, enabled);
+        let locale = guild_locale(&*repository, command.guild_id);
+        let preferences = repository.get_user_preferences(command.user.id);
+        reply(ctx, command, format_preferences(&preferences, locale)).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::guild_locale,
+    repository::repository,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::{
+        application_command::ApplicationCommandInteraction,
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Whether someone sees haikus as plain text, or goes by what the server chose
+enum PlainTextMode {
+    On,
+    Off,
+    ServerDefault,
+}
+
+impl Choice for PlainTextMode {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("Plain text", "on"),
+            ("Embeds", "off"),
+            ("Whatever this server uses", "default"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "on" => Some(PlainTextMode::On),
+            "off" => Some(PlainTextMode::Off),
+            "default" => Some(PlainTextMode::ServerDefault),
+            _ => None,
+        }
+    }
+}
+
+/// Choose whether haikus shown to you are plain text instead of embeds, which some screen readers
+/// handle better
+#[derive(Command)]
+#[name = "plaintext"]
+pub struct PlainTextCommand {
+    /// How haikus are shown to you
+    mode: String,
+}
+
+impl PlainTextCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("plaintext").option(OptionSpec::new("mode").choices::<PlainTextMode>())
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for PlainTextCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let mode = match PlainTextMode::from_value(&self.mode) {
+            Some(mode) => mode,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let (plain_text, content) = match mode {
+            PlainTextMode::On => (Some(true), t!(locale, "plaintext.on")),
+            PlainTextMode::Off => (Some(false), t!(locale, "plaintext.off")),
+            PlainTextMode::ServerDefault => (None, t!(locale, "plaintext.default")),
+        };
+        repository.set_user_plain_text(command.user.id, plain_text);
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send plaintext message: {:?}", why);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    commands::random::next_random_haiku,
+    formatting::{format_anonymous_haiku, to_embed_data, wants_plain_text},
+    framework::response::respond,
+    i18n::{guild_locale, Locale},
+    repository::repository,
+    retry::with_retry,
+    MessageComponentInteractionHandlers,
+};
+use rand::seq::SliceRandom;
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        channel::Message,
+        id::{GuildId, UserId},
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+        misc::Mentionable,
+    },
+    prelude::Mutex,
+};
+use slash_helper::{
+    ApplicationCommandInteractionHandler, InvocationError, MessageComponentInteractionHandler,
+};
+use slash_helper_macros::Command;
+use std::{collections::HashSet, sync::Arc};
+
+/// How many wrong answers are offered alongside the right one
+const DECOYS: usize = 3;
+/// How many of the server's poets decoys are picked from
+const DECOY_POOL: i64 = 50;
+/// How many people are shown on the quiz leaderboard
+const QUIZ_LEADERBOARD_SIZE: i64 = 10;
+
+async fn reply_privately(ctx: &Context, command: &ApplicationCommandInteraction, content: String) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(content)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not send quiz message: {:?}", why);
+    }
+}
+
+/// The name someone goes by in a server, if they're still in it
+async fn display_name(ctx: &Context, server_id: GuildId, user_id: UserId) -> Option<String> {
+    server_id
+        .member(ctx, user_id)
+        .await
+        .ok()
+        .map(|member| member.display_name().to_string())
+}
+
+/// Guess who wrote a random haiku from this server
+#[derive(Command)]
+#[name = "quiz"]
+pub struct QuizCommand;
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for QuizCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let (id, haiku) = match next_random_haiku(ctx, &*repository, server_id, None).await {
+            Some(haiku_and_id) => haiku_and_id,
+            None => {
+                reply_privately(ctx, command, t!(locale, "quiz.no_haikus")).await;
+                return Ok(());
+            }
+        };
+
+        // Only the first line's author is offered, and nobody else who wrote a line is a decoy
+        let author = haiku.lines[0].author;
+        let mut candidates = repository
+            .get_leaderboard(server_id, None, 0, DECOY_POOL)
+            .into_iter()
+            .map(|(poet, _)| poet)
+            .filter(|poet| haiku.lines.iter().all(|line| line.author != *poet))
+            .collect::<Vec<UserId>>();
+        candidates.shuffle(&mut rand::thread_rng());
+        let mut choices = vec![(
+            author,
+            display_name(ctx, server_id, author)
+                .await
+                .unwrap_or_else(|| t!(locale, "haiku.unknown_user")),
+        )];
+        for candidate in candidates {
+            if choices.len() > DECOYS {
+                break;
+            }
+            // Poets who've left can't be told apart from each other, so aren't used as decoys
+            if let Some(name) = display_name(ctx, server_id, candidate).await {
+                choices.push((candidate, name));
+            }
+        }
+        if choices.len() <= DECOYS {
+            reply_privately(ctx, command, t!(locale, "quiz.not_enough_poets")).await;
+            return Ok(());
+        }
+        choices.shuffle(&mut rand::thread_rng());
+
+        let plain_text = wants_plain_text(&*repository, command.user.id, command.guild_id);
+        let formatted =
+            format_anonymous_haiku(to_embed_data(id, &haiku, locale, ctx).await, plain_text)
+                .titled(t!(locale, "quiz.question"));
+        let result = respond(ctx, command, |message| {
+            if let Some(content) = formatted.content(None) {
+                message
+                    .content(content)
+                    .allowed_mentions(|mentions| mentions.empty_parse());
+            }
+            if let Some(embed) = formatted.embed() {
+                message.add_embed(embed);
+            }
+            message.components(|components| {
+                components.create_action_row(|row| {
+                    for (poet, name) in &choices {
+                        row.create_button(|button| {
+                            button
+                                .custom_id(poet)
+                                .label(name)
+                                .style(ButtonStyle::Primary)
+                        });
+                    }
+                    row
+                })
+            })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to send quiz: {:?}", why);
+            return Ok(());
+        }
+        let data = ctx.data.read().await;
+        let handlers = data
+            .get::<MessageComponentInteractionHandlers>()
+            .expect("Expected Handlers in TypeMap");
+        handlers.insert(
+            command.id,
+            Arc::new(Mutex::new(Box::new(QuizRound {
+                server_id,
+                author,
+                guessed: HashSet::new(),
+                locale,
+            }))),
+        );
+        Ok(())
+    }
+}
+
+/// A quiz question that's waiting for guesses. Everyone gets one guess at each question.
+pub struct QuizRound {
+    server_id: GuildId,
+    author: UserId,
+    guessed: HashSet<UserId>,
+    locale: Locale,
+}
+
+#[async_trait]
+impl MessageComponentInteractionHandler for QuizRound {
+    async fn invoke(
+        &mut self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        _original_message: &mut Message,
+    ) {
+        let guess = match interaction.data.custom_id.parse::<u64>() {
+            Ok(guess) => UserId(guess),
+            Err(_) => return,
+        };
+        let locale = self.locale;
+        let content = if !self.guessed.insert(interaction.user.id) {
+            t!(locale, "quiz.already_guessed")
+        } else {
+            let correct = guess == self.author;
+            repository(ctx)
+                .await
+                .record_quiz_answer(self.server_id, interaction.user.id, correct);
+            if correct {
+                t!(locale, "quiz.correct", author = self.author.mention())
+            } else {
+                t!(locale, "quiz.wrong", author = self.author.mention())
+            }
+        };
+        let result = with_retry(&ctx.http, || {
+            interaction.create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(&content)
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to respond to quiz guess: {:?}", why);
+        }
+    }
+}
+
+/// Show who is best at guessing who wrote haikus with /quiz
+#[derive(Command)]
+#[name = "quizleaderboard"]
+pub struct QuizLeaderboardCommand;
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for QuizLeaderboardCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let scores = repository.get_quiz_leaderboard(server_id, QUIZ_LEADERBOARD_SIZE);
+        if scores.is_empty() {
+            reply_privately(ctx, command, t!(locale, "quiz.no_scores")).await;
+            return Ok(());
+        }
+        let mut embed = CreateEmbed::default();
+        embed.title(t!(locale, "quiz.leaderboard_title"));
+        embed.description(
+            scores
+                .iter()
+                .enumerate()
+                .map(|(index, score)| {
+                    t!(
+                        locale,
+                        "quiz.leaderboard_entry",
+                        rank = index + 1,
+                        user = score.user.mention(),
+                        correct = score.correct,
+                        answered = score.answered
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+        );
+        let result = respond(ctx, command, |message| message.add_embed(embed)).await;
+        if let Err(why) = result {
+            println!("Failed to send quiz leaderboard: {:?}", why);
+        }
+        Ok(())
+    }
+}
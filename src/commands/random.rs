@@ -1,22 +1,105 @@
 use crate::{
-    database,
-    formatting::{format_haiku_embed, to_embed_data},
+    commands::buttons::{haiku_buttons, hidden_author_buttons, is_edited},
+    formatting::{format_anonymous_haiku, format_haiku, to_embed_data, wants_plain_text},
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    models::Haiku,
+    repository::{repository, HaikuRepository},
+    schedule::{calendar_period, server_timezone},
+    shuffle::ShuffleHistory,
 };
+use chrono::{DateTime, Utc};
 use serenity::{
     async_trait,
-    builder::CreateEmbed,
     client::Context,
-    model::interactions::{
-        application_command::ApplicationCommandInteraction, InteractionResponseType,
+    model::{
+        id::GuildId,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
     },
+    prelude::{Mutex, TypeMapKey},
 };
 use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
 use slash_helper_macros::Command;
+use std::{convert::TryFrom, ops::Range, sync::Arc};
+
+pub struct RandomHaikuHistory;
+impl TypeMapKey for RandomHaikuHistory {
+    type Value = Arc<Mutex<ShuffleHistory>>;
+}
+
+/// A random haiku from the server, avoiding those shown recently, optionally only from those
+/// written in a stretch of time
+pub async fn next_random_haiku(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    written: Option<&Range<DateTime<Utc>>>,
+) -> Option<(i64, Haiku)> {
+    let history = {
+        let data = ctx.data.read().await;
+        data.get::<RandomHaikuHistory>()
+            .expect("Expected RandomHaikuHistory in TypeMap")
+            .clone()
+    };
+    let mut history = history.lock().await;
+    history.next_haiku(repository, server_id, written)
+}
 
 /// Fetch a random haiku from this server
 #[derive(Command)]
 #[name = "randomhaiku"]
-pub struct RandomHaikuCommand;
+pub struct RandomHaikuCommand {
+    /// Hide who wrote it until someone presses "Reveal author", for guessing games
+    hide_author: String,
+    /// Only pick from haikus written in this year
+    year: i64,
+    /// Only pick from haikus written in this month of the year (1 to 12)
+    month: i64,
+}
+
+impl RandomHaikuCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("randomhaiku")
+            .option(
+                OptionSpec::new("hide_author")
+                    .choices::<bool>()
+                    .default("off"),
+            )
+            .option(OptionSpec::new("year").default(0))
+            .option(OptionSpec::new("month").default(0))
+    }
+
+    /// The year or month to pick from in the server's timezone, None for any time, or a message
+    /// saying what's wrong with the options
+    fn written(
+        &self,
+        repository: &dyn HaikuRepository,
+        server_id: GuildId,
+        locale: Locale,
+    ) -> Result<Option<Range<DateTime<Utc>>>, String> {
+        let month = match self.month {
+            0 => None,
+            month => Some(u32::try_from(month).unwrap_or(0)),
+        };
+        match (self.year, month) {
+            (0, None) => Ok(None),
+            (0, Some(_)) => Err(t!(locale, "randomhaiku.month_needs_year")),
+            (year, month) => i32::try_from(year)
+                .ok()
+                .and_then(|year| {
+                    calendar_period(year, month, server_timezone(repository, server_id))
+                })
+                .map(Some)
+                .ok_or_else(|| t!(locale, "randomhaiku.invalid_period")),
+        }
+    }
+}
 
 #[async_trait]
 impl ApplicationCommandInteractionHandler for RandomHaikuCommand {
@@ -25,27 +108,63 @@ impl ApplicationCommandInteractionHandler for RandomHaikuCommand {
         ctx: &Context,
         command: &ApplicationCommandInteraction,
     ) -> Result<(), InvocationError> {
-        let haiku_and_id = if let Some(server_id) = command.guild_id {
-            let db_connection = database::establish_connection();
-            database::get_random_haiku(server_id, &db_connection)
-        } else {
-            None
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let written = self.written(&*repository, server_id, locale);
+        let haiku_and_id = match &written {
+            Ok(written) => next_random_haiku(ctx, &*repository, server_id, written.as_ref()).await,
+            Err(_) => None,
         };
         if let Some((id, haiku)) = haiku_and_id {
-            let embed_data = to_embed_data(id, &haiku, ctx).await;
-            command
-                .create_interaction_response(&ctx.http, |response| {
-                    response
-                        .kind(InteractionResponseType::ChannelMessageWithSource)
-                        .interaction_response_data(|message| {
-                            let mut embed = CreateEmbed::default();
-                            format_haiku_embed(embed_data, &mut embed);
-                            message.add_embed(embed);
-                            message
-                        })
+            let embed_data = to_embed_data(id, &haiku, locale, ctx).await;
+            let plain_text = wants_plain_text(&*repository, command.user.id, command.guild_id);
+            let hide_author = bool::from_value(&self.hide_author).unwrap_or(false);
+            let formatted = if hide_author {
+                format_anonymous_haiku(embed_data, plain_text)
+            } else {
+                format_haiku(embed_data, plain_text)
+            };
+            let edited = is_edited(&*repository, haiku.server, id);
+            let result = respond(ctx, command, |message| {
+                if let Some(content) = formatted.content(None) {
+                    message
+                        .content(content)
+                        .allowed_mentions(|mentions| mentions.empty_parse());
+                }
+                if let Some(embed) = formatted.embed() {
+                    message.add_embed(embed);
+                }
+                message.components(|components| {
+                    if hide_author {
+                        hidden_author_buttons(components, id, edited, locale)
+                    } else {
+                        haiku_buttons(components, id, edited, locale)
+                    }
                 })
-                .await
-                .expect("Failed to send haiku msg");
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Failed to send haiku msg: {:?}", why);
+            }
+        } else {
+            let content = match written {
+                Err(content) => content,
+                Ok(Some(_)) => t!(locale, "randomhaiku.none_written"),
+                Ok(None) => return Ok(()),
+            };
+            let result = respond(ctx, command, |message| {
+                message
+                    .content(content)
+                    .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+            })
+            .await;
+            if let Err(why) = result {
+                println!("Failed to send random haiku message: {:?}", why);
+            }
         }
         Ok(())
     }
@@ -0,0 +1,113 @@
+use crate::{
+    commands::buttons::reveal_button,
+    framework::{
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::guild_locale,
+    repository::repository,
+};
+use serenity::{
+    async_trait, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Which lines of a haiku /randomline can pick from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineLength {
+    Any,
+    /// The first and last lines
+    Five,
+    /// The middle line
+    Seven,
+}
+
+impl LineLength {
+    fn positions(self) -> &'static [usize] {
+        match self {
+            LineLength::Any => &[0, 1, 2],
+            LineLength::Five => &[0, 2],
+            LineLength::Seven => &[1],
+        }
+    }
+}
+
+impl Choice for LineLength {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("Any line", "any"),
+            ("5 syllables", "5"),
+            ("7 syllables", "7"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "any" => Some(LineLength::Any),
+            "5" => Some(LineLength::Five),
+            "7" => Some(LineLength::Seven),
+            _ => None,
+        }
+    }
+}
+
+/// Get a random line from this server's haikus as a prompt to write your own
+#[derive(Command)]
+#[name = "randomline"]
+pub struct RandomLineCommand {
+    /// How long the line should be
+    syllables: String,
+}
+
+impl RandomLineCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("randomline").option(
+            OptionSpec::new("syllables")
+                .choices::<LineLength>()
+                .default("any"),
+        )
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for RandomLineCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let length = LineLength::from_value(&self.syllables).unwrap_or(LineLength::Any);
+        let line = command
+            .guild_id
+            .and_then(|server_id| repository.get_random_line(server_id, length.positions()));
+        let result = match line {
+            Some(line) => {
+                let content = match line.position {
+                    0 => t!(locale, "randomline.first", line = line.line.content),
+                    1 => t!(locale, "randomline.middle", line = line.line.content),
+                    _ => t!(locale, "randomline.last", line = line.line.content),
+                };
+                respond(ctx, command, |message| {
+                    message
+                        .content(content)
+                        .components(|components| reveal_button(components, line.haiku_id, locale))
+                })
+                .await
+            }
+            None => {
+                respond(ctx, command, |message| {
+                    message.content(t!(locale, "buttons.no_haikus"))
+                })
+                .await
+            }
+        };
+        if let Err(why) = result {
+            println!("Failed to send random line: {:?}", why);
+        }
+        Ok(())
+    }
+}
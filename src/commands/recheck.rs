@@ -0,0 +1,190 @@
+use crate::{
+    commands::idof::parse_message_link,
+    config::Config,
+    counting::{count_line_with, WordOverrides},
+    detection::{
+        explain::explain,
+        pipeline::{fetch_earlier, IncomingMessage},
+    },
+    framework::{
+        options::get_option,
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    repository::repository,
+    retry::with_retry,
+    HaikuTracker,
+};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::interactions::{
+        application_command::{ApplicationCommandInteraction, ApplicationCommandOptionType},
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+const MAX_COUNTS_LENGTH: usize = 1500;
+const MAX_EXPLANATION_LENGTH: usize = 1800;
+
+/// Check a message and the two before it for a haiku again, showing how each line was counted
+#[derive(Command)]
+#[name = "recheck"]
+pub struct RecheckCommand {
+    /// A link to the message the haiku should end in
+    link: String,
+}
+
+impl RecheckCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("recheck").option(OptionSpec::extra(
+            "verbose",
+            ApplicationCommandOptionType::Boolean,
+            "Explain each step: what the filters did and how every word was counted",
+        ))
+    }
+}
+
+/// Each line of the messages as the detector sees them, with its syllables
+fn format_line_counts(
+    messages: &[Option<IncomingMessage>],
+    overrides: &WordOverrides,
+    locale: Locale,
+) -> String {
+    messages
+        .iter()
+        .flat_map(|message| match message {
+            Some(message) => message
+                .content
+                .lines()
+                .map(|line| match count_line_with(line, overrides) {
+                    Ok(count) => format!("{:>2} | {}", count.syllables(), line),
+                    Err(_) => format!(" ? | {}", line),
+                })
+                .collect::<Vec<String>>(),
+            None => vec![format!(" - | {}", t!(locale, "recheck.skipped"))],
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Check the message a link points to, saving any haiku it ends, and describe what was found
+async fn recheck(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    link: &str,
+    verbose: bool,
+) -> String {
+    let repository = repository(ctx).await;
+    let locale = guild_locale(&*repository, command.guild_id);
+    let (can_read_messages, pipeline) = {
+        let data = ctx.data.read().await;
+        (
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .intents
+                .message_content,
+            data.get::<HaikuTracker>()
+                .expect("Expected HaikuTracker in TypeMap")
+                .clone(),
+        )
+    };
+    if !can_read_messages {
+        return t!(locale, "recheck.unavailable");
+    }
+    let (server_id, channel_id, message_id) = match parse_message_link(link) {
+        Some((server_id, _, _)) if Some(server_id) != command.guild_id => {
+            return t!(locale, "recheck.other_server")
+        }
+        Some(ids) => ids,
+        None => return t!(locale, "recheck.invalid_link"),
+    };
+    let message = match with_retry(&ctx.http, || {
+        ctx.http.get_message(channel_id.0, message_id.0)
+    })
+    .await
+    {
+        Ok(message) => IncomingMessage::in_server(&message, server_id),
+        Err(why) => {
+            println!("Could not fetch message for /recheck: {:?}", why);
+            return t!(locale, "recheck.fetch_failed");
+        }
+    };
+    let earlier = fetch_earlier(ctx, server_id, channel_id, message_id).await;
+
+    let found = pipeline.find_requested(&*repository, earlier.clone(), message.clone());
+    let details = if verbose {
+        let messages = earlier
+            .iter()
+            .chain(Some(&message))
+            .cloned()
+            .collect::<Vec<IncomingMessage>>();
+        explain(&pipeline, &*repository, &messages, &found, locale)
+            .chars()
+            .take(MAX_EXPLANATION_LENGTH)
+            .collect::<String>()
+    } else {
+        let window = earlier
+            .iter()
+            .chain(Some(&message))
+            .map(|message| pipeline.prepare(&*repository, message.clone()))
+            .collect::<Vec<Option<IncomingMessage>>>();
+        format!(
+            "```\n{}\n```",
+            format_line_counts(&window, &repository.get_word_overrides(server_id), locale)
+                .chars()
+                .take(MAX_COUNTS_LENGTH)
+                .collect::<String>()
+        )
+    };
+    let outcome = if found.is_empty() {
+        t!(locale, "recheck.not_haiku")
+    } else if !repository.is_detection_enabled(server_id, channel_id) {
+        // Saving is left to the manual save role there
+        t!(locale, "recheck.detection_disabled")
+    } else {
+        // Saved haikus are announced by the pipeline's hooks, like detected ones
+        let saved = pipeline
+            .run_requested(ctx, &*repository, earlier, message)
+            .await;
+        let ids = saved
+            .iter()
+            .map(|saved| format!("#{}", saved.id))
+            .collect::<Vec<String>>()
+            .join(", ");
+        if saved.is_empty() {
+            t!(locale, "recheck.not_saved")
+        } else if saved.iter().all(|saved| saved.flagged) {
+            t!(locale, "recheck.flagged", ids = ids)
+        } else {
+            t!(locale, "recheck.saved", ids = ids)
+        }
+    };
+    // Messages are limited to 2000 characters
+    format!("{}\n{}", details, outcome)
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for RecheckCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let verbose = get_option::<bool>(command, "verbose").unwrap_or(false);
+        let content = recheck(ctx, command, &self.link, verbose).await;
+        let result = respond(ctx, command, |message| {
+            message
+                .content(content)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send recheck message: {:?}", why);
+        }
+        Ok(())
+    }
+}
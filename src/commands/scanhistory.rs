@@ -0,0 +1,296 @@
+use crate::{
+    bulk::{bulk, Bucket},
+    config::Config,
+    detection::pipeline::IncomingMessage,
+    formatting::parse_duration,
+    framework::{
+        checks::missing_channel_permissions,
+        options::get_option,
+        progress::Progress,
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale},
+    models::HistoryScan,
+    repository::repository,
+    HaikuTracker,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashSet;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        channel::PartialChannel,
+        id::ChannelId,
+        interactions::{
+            application_command::{ApplicationCommandInteraction, ApplicationCommandOptionType},
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+        misc::Mentionable,
+        Permissions,
+    },
+    prelude::TypeMapKey,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+use std::sync::Arc;
+
+/// Discord won't send more than this many messages at once
+const PAGE_SIZE: u64 = 100;
+
+/// The channels being scanned
+pub struct HistoryScans;
+impl TypeMapKey for HistoryScans {
+    type Value = Arc<DashSet<ChannelId>>;
+}
+
+/// Look back through a channel's history for haikus sent before the bot joined. Running it again
+/// on a channel carries on from where its last scan stopped.
+#[derive(Command)]
+#[name = "scanhistory"]
+pub struct ScanHistoryCommand {
+    /// How far back to look, e.g. 30d, 2w, 6mo or 1y
+    duration: String,
+}
+
+impl ScanHistoryCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("scanhistory")
+            .option(OptionSpec::extra(
+                "channel",
+                ApplicationCommandOptionType::Channel,
+                "The channel to scan, or leave it out for this one",
+            ))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+async fn reply_privately(ctx: &Context, command: &ApplicationCommandInteraction, content: String) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(content)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not send scanhistory message: {:?}", why);
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for ScanHistoryCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let server_id = match command.guild_id {
+            Some(server_id) => server_id,
+            None => return Ok(()),
+        };
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let channel_id = get_option::<PartialChannel>(command, "channel")
+            .map(|channel| channel.id)
+            .unwrap_or(command.channel_id);
+        let (can_read_messages, scans) = {
+            let data = ctx.data.read().await;
+            (
+                data.get::<Config>()
+                    .expect("Expected Config in TypeMap")
+                    .intents
+                    .message_content,
+                data.get::<HistoryScans>()
+                    .expect("Expected HistoryScans in TypeMap")
+                    .clone(),
+            )
+        };
+        if !can_read_messages {
+            reply_privately(ctx, command, t!(locale, "scanhistory.unavailable")).await;
+            return Ok(());
+        }
+        let duration = match parse_duration(&self.duration) {
+            Some(duration) => duration,
+            None => {
+                let content = t!(
+                    locale,
+                    "scanhistory.invalid_duration",
+                    duration = self.duration
+                );
+                reply_privately(ctx, command, content).await;
+                return Ok(());
+            }
+        };
+        let missing = missing_channel_permissions(
+            ctx,
+            channel_id,
+            Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+        )
+        .await;
+        if !missing.is_empty() {
+            let content = t!(
+                locale,
+                "scanhistory.missing_permissions",
+                permissions = missing
+            );
+            reply_privately(ctx, command, content).await;
+            return Ok(());
+        }
+        if !scans.insert(channel_id) {
+            reply_privately(ctx, command, t!(locale, "scanhistory.already_running")).await;
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let until = now - duration;
+        let scan = match repository.get_history_scan(channel_id) {
+            Some(scan) if scan.server == server_id => HistoryScan { until, ..scan },
+            _ => HistoryScan {
+                server: server_id,
+                channel: channel_id,
+                before: None,
+                until,
+                messages: 0,
+                haikus: 0,
+            },
+        };
+        let done = scan
+            .before
+            .map_or(0, |before| scanned_seconds(now, before.created_at()));
+        let total = scanned_seconds(now, until);
+        match Progress::start(
+            ctx,
+            command,
+            false,
+            locale,
+            describe(&scan, locale),
+            done,
+            total,
+        )
+        .await
+        {
+            Some(progress) => {
+                tokio::spawn(scan_history(ctx.clone(), scan, now, progress, locale));
+            }
+            None => {
+                scans.remove(&channel_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How far back from when the scan started a point in the channel's history is, which is what its
+/// progress is measured in
+fn scanned_seconds(now: DateTime<Utc>, time: DateTime<Utc>) -> u64 {
+    now.signed_duration_since(time).num_seconds().max(0) as u64
+}
+
+fn describe(scan: &HistoryScan, locale: Locale) -> String {
+    t!(
+        locale,
+        "scanhistory.progress",
+        channel = scan.channel.mention(),
+        messages = scan.messages,
+        haikus = scan.haikus
+    )
+}
+
+/// Check a channel's messages for haikus a page at a time, newest first, saving how far it's got
+/// after each page, until the scan reaches its start date or the start of the channel, or is
+/// cancelled
+async fn scan_history(
+    ctx: Context,
+    mut scan: HistoryScan,
+    now: DateTime<Utc>,
+    progress: Arc<Progress>,
+    locale: Locale,
+) {
+    let repository = repository(&ctx).await;
+    let bulk = bulk(&ctx).await;
+    let pipeline = {
+        let data = ctx.data.read().await;
+        data.get::<HaikuTracker>()
+            .expect("Expected HaikuTracker in TypeMap")
+            .clone()
+    };
+    let bot_id = ctx.cache.current_user_id().await;
+    let mut fetch_before = scan.before;
+    // Newest first. The oldest two wait for the next page, which has the messages before them.
+    let mut unchecked: Vec<IncomingMessage> = Vec::new();
+    let finished = loop {
+        if progress.is_cancelled() {
+            break false;
+        }
+        let page = bulk
+            .run(&ctx.http, Bucket::ChannelHistory(scan.channel), || {
+                scan.channel
+                    .messages(&ctx.http, |retriever| match fetch_before {
+                        Some(before) => retriever.before(before).limit(PAGE_SIZE),
+                        None => retriever.limit(PAGE_SIZE),
+                    })
+            })
+            .await;
+        let page = match page {
+            Ok(page) => page,
+            Err(why) => {
+                println!("Could not fetch history of {}: {:?}", scan.channel, why);
+                break false;
+            }
+        };
+        let done = page.len() < PAGE_SIZE as usize
+            || page.last().map_or(true, |oldest| {
+                oldest.timestamp.with_timezone(&Utc) < scan.until
+            });
+        fetch_before = page.last().map(|oldest| oldest.id);
+        unchecked.extend(
+            page.iter()
+                .filter(|message| message.timestamp.with_timezone(&Utc) >= scan.until)
+                .filter(|message| message.author.id != bot_id)
+                .map(|message| IncomingMessage::in_server(message, scan.server)),
+        );
+        let waiting = if done { 0 } else { 2 };
+        while unchecked.len() > waiting {
+            let message = unchecked.remove(0);
+            let earlier = unchecked.iter().take(2).rev().cloned().collect();
+            scan.before = Some(message.id);
+            scan.messages += 1;
+            scan.haikus += pipeline
+                .detect_history(&*repository, earlier, message)
+                .len() as i64;
+        }
+        repository.save_history_scan(&scan);
+        if done {
+            break true;
+        }
+        if let Some(oldest) = page.last() {
+            let scanned = scanned_seconds(now, oldest.timestamp.with_timezone(&Utc));
+            progress.update(scanned, describe(&scan, locale));
+        }
+    };
+
+    let content = if finished {
+        repository.delete_history_scan(scan.channel);
+        t!(
+            locale,
+            "scanhistory.finished",
+            channel = scan.channel.mention(),
+            messages = scan.messages,
+            haikus = scan.haikus
+        )
+    } else {
+        t!(
+            locale,
+            "scanhistory.stopped",
+            channel = scan.channel.mention(),
+            messages = scan.messages,
+            haikus = scan.haikus
+        )
+    };
+    progress.finish(&ctx, content).await;
+    let data = ctx.data.read().await;
+    data.get::<HistoryScans>()
+        .expect("Expected HistoryScans in TypeMap")
+        .remove(&scan.channel);
+}
@@ -1,33 +1,110 @@
 use crate::{
-    database,
-    formatting::{format_haiku_embed, to_embed_data},
+    commands::buttons::{haiku_buttons, is_edited},
+    database::{KeywordMatch, SearchOrder, SearchTerms},
+    formatting::{format_haiku, format_number, to_embed_data, wants_plain_text},
+    framework::{
+        options::get_option,
+        pagination::{Page, Pages, Paginator},
+        response::respond,
+        spec::{Choice, CommandSpec, OptionSpec},
+    },
+    i18n::{guild_locale, Locale, Region},
     models::Haiku,
-    MessageComponentInteractionHandlers,
+    repository::repository,
 };
 use serenity::{
     async_trait,
-    builder::CreateEmbed,
     client::Context,
     model::{
-        channel::Message,
-        interactions::{
-            application_command::ApplicationCommandInteraction,
-            message_component::{ButtonStyle, MessageComponentInteraction},
-            InteractionResponseType,
+        channel::PartialChannel,
+        interactions::application_command::{
+            ApplicationCommandInteraction, ApplicationCommandOptionType,
         },
+        misc::Mentionable,
+        user::User,
     },
 };
-use slash_helper::{
-    ApplicationCommandInteractionHandler, InvocationError, MessageComponentInteractionHandler,
-};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
 use slash_helper_macros::Command;
 
+/// How many authors are listed when only counting the results
+const MAX_AUTHORS_SHOWN: usize = 10;
+
 /// Search for a haiku
 #[derive(Command)]
 #[name = "search"]
 pub struct SearchCommand {
-    /// A set of keywords to search for, separated by spaces
+    /// A set of keywords to search for, separated by spaces. Put a - before words to exclude
     keywords: String,
+    /// How to order the results
+    sort: String,
+    /// Whether results need to contain all of the keywords or just one
+    keyword_match: String,
+}
+
+impl SearchCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("search")
+            .option(
+                OptionSpec::new("sort")
+                    .choices::<SearchOrder>()
+                    .default("relevance"),
+            )
+            .option(
+                OptionSpec::new("keyword_match")
+                    .name("match")
+                    .choices::<KeywordMatch>()
+                    .default("any"),
+            )
+            .option(OptionSpec::extra(
+                "author",
+                ApplicationCommandOptionType::User,
+                "Only show haikus with a line written by this user",
+            ))
+            .option(OptionSpec::extra(
+                "channel",
+                ApplicationCommandOptionType::Channel,
+                "Only show haikus from this channel",
+            ))
+            .option(OptionSpec::extra(
+                "count_only",
+                ApplicationCommandOptionType::Boolean,
+                "Only show how many haikus match, and who wrote them",
+            ))
+    }
+}
+
+impl Choice for SearchOrder {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("Best match", "relevance"),
+            ("Newest first", "newest"),
+            ("Oldest first", "oldest"),
+        ]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "relevance" => Some(SearchOrder::Relevance),
+            "newest" => Some(SearchOrder::Newest),
+            "oldest" => Some(SearchOrder::Oldest),
+            _ => None,
+        }
+    }
+}
+
+impl Choice for KeywordMatch {
+    fn choices() -> Vec<(&'static str, &'static str)> {
+        vec![("Any keyword", "any"), ("All keywords", "all")]
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "any" => Some(KeywordMatch::Any),
+            "all" => Some(KeywordMatch::All),
+            _ => None,
+        }
+    }
 }
 
 #[async_trait]
@@ -37,147 +114,100 @@ impl ApplicationCommandInteractionHandler for SearchCommand {
         ctx: &Context,
         command: &ApplicationCommandInteraction,
     ) -> Result<(), InvocationError> {
-        let keywords = self
-            .keywords
-            .split_whitespace()
-            .map(|word| word.to_owned())
-            .collect::<Vec<String>>();
+        let repository = repository(ctx).await;
+        let terms = SearchTerms::parse(
+            &self.keywords,
+            KeywordMatch::from_value(&self.keyword_match).unwrap_or(KeywordMatch::Any),
+        );
 
         if let Some(server_id) = command.guild_id {
-            let db_connection = database::establish_connection();
-            let search_results = database::search_haikus(server_id, keywords, &db_connection);
+            let locale = guild_locale(&*repository, command.guild_id);
+            let order = SearchOrder::from_value(&self.sort).unwrap_or(SearchOrder::Relevance);
+            let author = get_option::<User>(command, "author").map(|user| user.id);
+            let channel =
+                get_option::<PartialChannel>(command, "channel").map(|channel| channel.id);
+            if get_option::<bool>(command, "count_only").unwrap_or(false) {
+                let counts = repository.count_search_results(server_id, &terms, author, channel);
+                let region = Region::from_discord(&command.locale);
+                let mut lines = vec![t!(
+                    locale,
+                    "search.count",
+                    count = format_number(counts.total, region)
+                )];
+                lines.extend(counts.by_author.iter().take(MAX_AUTHORS_SHOWN).map(
+                    |(author, count)| {
+                        t!(
+                            locale,
+                            "search.count_by_author",
+                            author = author.mention(),
+                            count = format_number(*count, region)
+                        )
+                    },
+                ));
+                let result = respond(ctx, command, |message| {
+                    message
+                        .content(lines.join("\n"))
+                        .allowed_mentions(|mentions| mentions.empty_parse())
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Could not send search count message: {:?}", why);
+                }
+                return Ok(());
+            }
+            let search_results =
+                repository.search_haikus(server_id, &terms, order, author, channel);
             if search_results.is_empty() {
-                command
-                    .create_interaction_response(&ctx.http, |response| {
-                        response
-                            .kind(InteractionResponseType::ChannelMessageWithSource)
-                            .interaction_response_data(|message| {
-                                message.content("No haikus found for search terms.")
-                            })
-                    })
-                    .await
-                    .expect("Could not send search results message");
+                let result = respond(ctx, command, |message| {
+                    message.content(t!(locale, "search.no_results"))
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Could not send search results message: {:?}", why);
+                }
             } else {
-                let search_index = 0;
-                let (id, haiku) = search_results.get(search_index).unwrap();
-                let embed_data = to_embed_data(*id, &haiku, ctx).await;
-                command
-                    .create_interaction_response(&ctx.http, |response| {
-                        response
-                            .kind(InteractionResponseType::ChannelMessageWithSource)
-                            .interaction_response_data(|message| {
-                                let mut embed = CreateEmbed::default();
-                                format_haiku_embed(embed_data, &mut embed);
-                                message.add_embed(embed);
-                                message.content(format!(
-                                    "Search result {}/{}",
-                                    search_index + 1,
-                                    search_results.len()
-                                ));
-                                message.components(|components| {
-                                    components.create_action_row(|row| {
-                                        row.create_button(|button| {
-                                            button
-                                                .custom_id("previous")
-                                                .label("Previous")
-                                                .style(ButtonStyle::Primary)
-                                                .disabled(search_index < 1)
-                                        })
-                                        .create_button(
-                                            |button| {
-                                                button
-                                                    .custom_id("next")
-                                                    .label("Next")
-                                                    .style(ButtonStyle::Primary)
-                                                    .disabled(
-                                                        search_index >= search_results.len() - 1,
-                                                    )
-                                            },
-                                        )
-                                    })
-                                });
-                                message
-                            })
-                    })
-                    .await
-                    .expect("Failed to send search results");
-                let handler = Box::new(SearchReactionHandler {
-                    search_index,
-                    search_results,
-                });
-                let data = ctx.data.read().await;
-                let handlers = data
-                    .get::<MessageComponentInteractionHandlers>()
-                    .expect("Expected Handlers in TypeMap");
-                handlers.insert(command.id, handler);
+                let pages = SearchResults {
+                    results: search_results,
+                    locale,
+                    plain_text: wants_plain_text(&*repository, command.user.id, command.guild_id),
+                };
+                Paginator::new(pages, locale).send(ctx, command).await;
             }
         }
         Ok(())
     }
 }
 
-pub struct SearchReactionHandler {
-    search_index: usize,
-    search_results: Vec<(i64, Haiku)>,
+/// One search result per page, with the usual buttons for the haiku
+pub struct SearchResults {
+    results: Vec<(i64, Haiku)>,
+    locale: Locale,
+    plain_text: bool,
 }
 
 #[async_trait]
-impl MessageComponentInteractionHandler for SearchReactionHandler {
-    async fn invoke(
-        &mut self,
-        ctx: &Context,
-        interaction: &MessageComponentInteraction,
-        original_message: &mut Message,
-    ) {
-        let new_index = match interaction.data.custom_id.as_str() {
-            "next" => Some(self.search_index + 1),
-            "previous" => self.search_index.checked_sub(1),
-            _ => None,
+impl Pages for SearchResults {
+    fn page_count(&self) -> usize {
+        self.results.len()
+    }
+
+    async fn show(&mut self, ctx: &Context, page: usize) -> Page {
+        let (id, haiku) = &self.results[page];
+        let embed_data = to_embed_data(*id, haiku, self.locale, ctx).await;
+        let formatted = format_haiku(embed_data, self.plain_text);
+        let header = t!(
+            self.locale,
+            "search.result",
+            index = page + 1,
+            total = self.results.len()
+        );
+        let mut shown = Page {
+            content: formatted.content(Some(&header)),
+            embed: formatted.embed(),
+            ..Page::default()
         };
-        if let Some((new_index, (id, haiku))) = new_index
-            .map(|i| self.search_results.get(i).map(|haiku| (i, haiku)))
-            .flatten()
-        {
-            let embed_data = to_embed_data(*id, &haiku, ctx).await;
-            original_message
-                .edit(&ctx.http, |message| {
-                    message
-                        .set_embeds(Vec::new())
-                        .add_embed(|embed| format_haiku_embed(embed_data, embed))
-                        .content(format!(
-                            "Search result {}/{}",
-                            new_index + 1,
-                            self.search_results.len()
-                        ))
-                        .components(|components| {
-                            components.create_action_row(|row| {
-                                row.create_button(|button| {
-                                    button
-                                        .custom_id("previous")
-                                        .label("Previous")
-                                        .style(ButtonStyle::Primary)
-                                        .disabled(new_index < 1)
-                                })
-                                .create_button(|button| {
-                                    button
-                                        .custom_id("next")
-                                        .label("Next")
-                                        .style(ButtonStyle::Primary)
-                                        .disabled(new_index >= self.search_results.len() - 1)
-                                })
-                            })
-                        });
-                    message
-                })
-                .await
-                .expect("Failed to send search results");
-            self.search_index = new_index;
-            interaction
-                .create_interaction_response(&ctx.http, |response| {
-                    response.kind(InteractionResponseType::UpdateMessage)
-                })
-                .await
-                .expect("Failed to respond to component interaction");
-        }
+        let edited = is_edited(&*repository(ctx).await, haiku.server, *id);
+        haiku_buttons(&mut shown.components, *id, edited, self.locale);
+        shown
     }
 }
@@ -0,0 +1,116 @@
+use crate::{
+    framework::{
+        response::respond,
+        spec::{CommandSpec, OptionSpec},
+    },
+    i18n::guild_locale,
+    repository::repository,
+    translation::provider_from_env,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serenity::{
+    async_trait, builder::CreateEmbed, client::Context,
+    model::interactions::application_command::ApplicationCommandInteraction,
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+/// Translate a haiku from this server into another language
+#[derive(Command)]
+#[name = "translate"]
+pub struct TranslateCommand {
+    /// Id of the haiku to translate
+    id: i64,
+    /// Language code to translate into, e.g. fr, de, ja, pt-BR
+    language: String,
+}
+
+impl TranslateCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("translate").option(
+            OptionSpec::new("language")
+                .description(
+                    "Language code to translate into, e.g. fr, de, ja, pt-BR (default: en)",
+                )
+                .default("en"),
+        )
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for TranslateCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        lazy_static! {
+            static ref LANGUAGE_CODE_REGEX: Regex =
+                Regex::new(r"^[[:alpha:]]{2,3}(-[[:alpha:]]{2,4})?$").unwrap();
+        }
+        let locale = guild_locale(&*repository, command.guild_id);
+        let haiku_and_id = match (self.id, command.guild_id) {
+            (id, Some(server_id)) => repository.get_haiku(server_id, id),
+            _ => None,
+        };
+        let provider = provider_from_env().ok();
+        let error = match (&haiku_and_id, &provider) {
+            (None, _) => Some(t!(locale, "errors.haiku_not_found", id = self.id)),
+            (_, None) => Some(t!(locale, "translate.disabled")),
+            _ if !LANGUAGE_CODE_REGEX.is_match(&self.language) => Some(t!(
+                locale,
+                "translate.invalid_language",
+                language = self.language
+            )),
+            _ => None,
+        };
+        if let Some(error) = error {
+            let result = respond(ctx, command, |message| message.content(error)).await;
+            if let Err(why) = result {
+                println!("Could not send translation message: {:?}", why);
+            }
+            return Ok(());
+        }
+        let (id, haiku) = haiku_and_id.unwrap();
+        let provider = provider.unwrap();
+
+        let original = haiku
+            .lines
+            .iter()
+            .map(|line| line.content.clone())
+            .collect::<Vec<String>>()
+            .join("\n");
+        match provider.translate(&original, &self.language).await {
+            Ok(translation) => {
+                let mut embed = CreateEmbed::default();
+                embed.title(t!(locale, "haiku.id", id = id));
+                embed.field(t!(locale, "translate.original"), &original, true);
+                embed.field(
+                    t!(locale, "translate.translation", language = self.language),
+                    translation,
+                    true,
+                );
+                embed.footer(|footer| footer.text(t!(locale, "translate.footer")));
+                // Translation providers can be slow to respond, in which case
+                // this edits the response the framework deferred for us
+                let result = respond(ctx, command, |message| message.add_embed(embed)).await;
+                if let Err(why) = result {
+                    println!("Could not send translation message: {:?}", why);
+                }
+            }
+            Err(err) => {
+                println!("Error fetching translation: {:?}", err);
+                let result = respond(ctx, command, |message| {
+                    message.content(t!(locale, "translate.failed"))
+                })
+                .await;
+                if let Err(why) = result {
+                    println!("Could not send translation message: {:?}", why);
+                }
+            }
+        }
+        Ok(())
+    }
+}
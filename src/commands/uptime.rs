@@ -1,17 +1,23 @@
-use crate::UptimeStart;
+use crate::{
+    database,
+    formatting::{format_bytes, format_duration},
+    framework::response::respond,
+    i18n::guild_locale,
+    repository::repository,
+    runtime_stats::{memory_usage, RuntimeStats},
+    ShardManagerContainer, UptimeStart,
+};
 
 use chrono::Utc;
 use serenity::{
     async_trait,
-    client::Context,
-    model::interactions::{
-        application_command::ApplicationCommandInteraction, InteractionResponseType,
-    },
+    client::{bridge::gateway::ShardId, Context},
+    model::interactions::application_command::ApplicationCommandInteraction,
 };
 use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
 use slash_helper_macros::Command;
 
-/// Show how long since the bot was last restarted
+/// Show how long since the bot was last restarted, along with runtime diagnostics
 #[derive(Command)]
 #[name = "uptime"]
 pub struct UptimeCommand;
@@ -23,31 +29,78 @@ impl ApplicationCommandInteractionHandler for UptimeCommand {
         ctx: &Context,
         command: &ApplicationCommandInteraction,
     ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
         let data = ctx.data.read().await;
         let uptime_start_lock = data
             .get::<UptimeStart>()
             .expect("Expected HaikuTracker in TypeMap")
             .clone();
         let uptime = Utc::now().signed_duration_since(uptime_start_lock);
-        let days = uptime.num_days();
-        let uptime = uptime - chrono::Duration::days(days);
-        let hrs = uptime.num_hours();
-        let uptime = uptime - chrono::Duration::hours(hrs);
-        let mins = uptime.num_minutes();
+        let runtime_stats = data
+            .get::<RuntimeStats>()
+            .expect("Expected RuntimeStats in TypeMap")
+            .clone();
+        let shard_manager = data
+            .get::<ShardManagerContainer>()
+            .expect("Expected ShardManagerContainer in TypeMap")
+            .clone();
+        let latency = {
+            let manager = shard_manager.lock().await;
+            let runners = manager.runners.lock().await;
+            runners
+                .get(&ShardId(ctx.shard_id))
+                .map(|runner| runner.latency)
+                .flatten()
+        };
+        let database_size = {
+            let db_connection = database::establish_connection();
+            database::get_database_size(&db_connection)
+        };
 
-        command
-            .create_interaction_response(&ctx.http, |response| {
-                response
-                    .kind(InteractionResponseType::ChannelMessageWithSource)
-                    .interaction_response_data(|message| {
-                        message.content(format!(
-                            "Uptime: {} days, {} hours, {} minutes",
-                            days, hrs, mins
-                        ))
-                    })
-            })
-            .await
-            .expect("Could not send uptime message");
+        let locale = guild_locale(&*repository, command.guild_id);
+        let diagnostics = vec![
+            t!(
+                locale,
+                "uptime.uptime",
+                uptime = format_duration(uptime, locale)
+            ),
+            t!(
+                locale,
+                "uptime.latency",
+                latency = latency
+                    .map(|latency| format!("{}ms", latency.as_millis()))
+                    .unwrap_or(t!(locale, "uptime.unknown"))
+            ),
+            t!(
+                locale,
+                "uptime.memory",
+                memory = memory_usage()
+                    .map(format_bytes)
+                    .unwrap_or(t!(locale, "uptime.unknown"))
+            ),
+            t!(
+                locale,
+                "uptime.database_size",
+                size = format_bytes(database_size as u64)
+            ),
+            t!(
+                locale,
+                "uptime.messages",
+                count = runtime_stats.messages_processed()
+            ),
+            t!(
+                locale,
+                "uptime.haikus",
+                count = runtime_stats.haikus_detected()
+            ),
+        ];
+        let result = respond(ctx, command, |message| {
+            message.content(diagnostics.join("\n"))
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not send uptime message: {:?}", why);
+        }
         Ok(())
     }
 }
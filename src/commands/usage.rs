@@ -0,0 +1,161 @@
+use crate::{
+    database::CommandUsage,
+    formatting::format_number,
+    framework::{
+        response::respond,
+        spec::{CommandSpec, CommandSpecs, OptionSpec},
+    },
+    i18n::{guild_locale, Locale, Region},
+    repository::repository,
+    runtime_stats::RuntimeStats,
+};
+use chrono::{Duration, Utc};
+use serenity::{
+    async_trait,
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags,
+        },
+        Permissions,
+    },
+};
+use slash_helper::{ApplicationCommandInteractionHandler, InvocationError};
+use slash_helper_macros::Command;
+
+const DEFAULT_DAYS: i64 = 30;
+const MAX_DAYS: i64 = 365;
+/// How many commands and hours are listed
+const TOP_COMMANDS: usize = 10;
+const TOP_HOURS: usize = 3;
+
+/// See which commands this server uses most, and when
+#[derive(Command)]
+#[name = "usage"]
+pub struct UsageCommand {
+    /// How many days back to look
+    days: i64,
+}
+
+impl UsageCommand {
+    pub fn spec() -> CommandSpec {
+        CommandSpec::new("usage")
+            .option(OptionSpec::new("days").default(DEFAULT_DAYS))
+            .permissions(Permissions::MANAGE_GUILD)
+    }
+}
+
+#[async_trait]
+impl ApplicationCommandInteractionHandler for UsageCommand {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+    ) -> Result<(), InvocationError> {
+        let repository = repository(ctx).await;
+        let locale = guild_locale(&*repository, command.guild_id);
+        let mut embed = CreateEmbed::default();
+        match command.guild_id {
+            Some(server_id) => {
+                let days = self.days.max(1).min(MAX_DAYS);
+                let since = Utc::now() - Duration::days(days);
+                let usage = repository.get_command_usage(server_id, since);
+                let hours = repository.count_command_usage_by_hour(server_id, since);
+                let region = Region::from_discord(&command.locale);
+                format_usage(&usage, &hours, days, locale, region, &mut embed);
+            }
+            None => {
+                embed.description(t!(locale, "usage.guild_only"));
+            }
+        }
+        let result = respond(ctx, command, |message| {
+            message
+                .add_embed(embed)
+                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to send command usage: {:?}", why);
+        }
+        Ok(())
+    }
+}
+
+fn format_usage<'a>(
+    usage: &[CommandUsage],
+    hours: &[(u32, i64)],
+    days: i64,
+    locale: Locale,
+    region: Region,
+    embed: &'a mut CreateEmbed,
+) -> &'a mut CreateEmbed {
+    embed.title(t!(locale, "usage.title", days = days));
+    if usage.is_empty() {
+        embed.description(t!(locale, "usage.no_usage"));
+        return embed;
+    }
+    embed.field(
+        t!(locale, "usage.commands"),
+        usage
+            .iter()
+            .take(TOP_COMMANDS)
+            .enumerate()
+            .map(|(index, usage)| {
+                t!(
+                    locale,
+                    "usage.command_entry",
+                    rank = index + 1,
+                    command = usage.command,
+                    uses = format_number(usage.uses, region),
+                    latency = format_number(usage.average_latency_ms, region)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        false,
+    );
+    embed.field(
+        t!(locale, "usage.hours"),
+        hours
+            .iter()
+            .take(TOP_HOURS)
+            .map(|(hour, uses)| {
+                t!(
+                    locale,
+                    "usage.hour_entry",
+                    hour = format!("{:02}:00", hour),
+                    uses = format_number(*uses, region)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        false,
+    );
+    embed
+}
+
+/// Count a command towards /usage and the metrics endpoint, with how long it took to answer
+pub async fn record_usage(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    latency: std::time::Duration,
+) {
+    let name = {
+        let data = ctx.data.read().await;
+        let name = data
+            .get::<CommandSpecs>()
+            .expect("Expected CommandSpecs in TypeMap")
+            .display_name(&command.data.name);
+        data.get::<RuntimeStats>()
+            .expect("Expected RuntimeStats in TypeMap")
+            .record_command(&name, latency);
+        name
+    };
+    if let Some(server_id) = command.guild_id {
+        repository(ctx)
+            .await
+            .record_command_usage(server_id, &name, latency.as_millis() as i64);
+    }
+}
@@ -0,0 +1,280 @@
+//! The completion mini-game: the bot posts the first line of a stored haiku and a channel races
+//! to finish it with a 7 and a 5 syllable line. Completions are kept as haikus of their own.
+
+use crate::{
+    counting::{is_haiku, split_into_haiku, NotHaiku},
+    i18n::Locale,
+    models::{Haiku, HaikuLine, StoredLine},
+};
+use chrono::{DateTime, Utc};
+use serenity::model::{
+    id::{ChannelId, GuildId, UserId},
+    misc::Mentionable,
+};
+use std::collections::HashMap;
+
+/// How many completions are shown when a game ends, so it fits in one message
+const MAX_COMPLETIONS_SHOWN: usize = 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub author: UserId,
+    /// The second and third lines
+    pub lines: [String; 2],
+    pub sent_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionGame {
+    /// The stored line being completed, and the haiku it came from
+    pub prompt: StoredLine,
+    pub server: GuildId,
+    pub channel: ChannelId,
+    pub ends_at: DateTime<Utc>,
+    /// In the order they were sent, so the first is the winner
+    pub completions: Vec<Completion>,
+}
+
+impl CompletionGame {
+    /// A completion as a haiku, with the prompt's first line and author kept
+    pub fn derivative(&self, completion: &Completion) -> Haiku {
+        let [line_2, line_3] = completion.lines.clone();
+        Haiku {
+            lines: [
+                self.prompt.line.clone(),
+                HaikuLine {
+                    author: completion.author,
+                    author_name: None,
+                    content: line_2,
+                },
+                HaikuLine {
+                    author: completion.author,
+                    author_name: None,
+                    content: line_3,
+                },
+            ],
+            timestamp: completion.sent_at,
+            channel: self.channel,
+            server: self.server,
+            source: None,
+        }
+    }
+}
+
+/// The completion game running in each channel, if any
+#[derive(Default)]
+pub struct CompletionGames {
+    games: HashMap<ChannelId, CompletionGame>,
+}
+
+impl CompletionGames {
+    pub fn new() -> Self {
+        CompletionGames::default()
+    }
+
+    /// Start a game in a channel, returning false if there's already one running there
+    pub fn start(
+        &mut self,
+        server: GuildId,
+        channel: ChannelId,
+        prompt: StoredLine,
+        ends_at: DateTime<Utc>,
+    ) -> bool {
+        if self.games.contains_key(&channel) {
+            return false;
+        }
+        self.games.insert(
+            channel,
+            CompletionGame {
+                prompt,
+                server,
+                channel,
+                ends_at,
+                completions: Vec::new(),
+            },
+        );
+        true
+    }
+
+    /// Add a message to the channel's game if it finishes the prompt before the game ends,
+    /// returning its place (counting from 1)
+    pub fn submit(
+        &mut self,
+        channel: ChannelId,
+        author: UserId,
+        content: &str,
+        sent_at: DateTime<Utc>,
+    ) -> Option<usize> {
+        let game = self.games.get_mut(&channel)?;
+        if sent_at >= game.ends_at {
+            return None;
+        }
+        let lines = parse_completion(&game.prompt.line.content, content).ok()?;
+        game.completions.push(Completion {
+            author,
+            lines,
+            sent_at,
+        });
+        Some(game.completions.len())
+    }
+
+    /// End the game in a channel, returning it
+    pub fn finish(&mut self, channel: ChannelId) -> Option<CompletionGame> {
+        self.games.remove(&channel)
+    }
+}
+
+/// The two lines finishing `first_line`, written either one per line or on a single line
+pub fn parse_completion(first_line: &str, content: &str) -> Result<[String; 2], NotHaiku> {
+    let lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<String>>();
+    if lines.len() == 1 {
+        // The first line is already 5 syllables, so the split puts it back on its own
+        let [_, line_2, line_3] = split_into_haiku(&format!("{} {}", first_line, lines[0]))?;
+        return Ok([line_2.text(), line_3.text()]);
+    }
+    let mut haiku = vec![first_line.to_owned()];
+    haiku.extend(lines);
+    is_haiku(&haiku)?;
+    Ok([haiku[1].clone(), haiku[2].clone()])
+}
+
+/// A finished game's completions, winner first
+pub fn format_completions(game: &CompletionGame, locale: Locale) -> String {
+    if game.completions.is_empty() {
+        return t!(locale, "finishthis.no_completions");
+    }
+    let mut completions = game
+        .completions
+        .iter()
+        .take(MAX_COMPLETIONS_SHOWN)
+        .enumerate()
+        .map(|(index, completion)| {
+            let heading = if index == 0 {
+                t!(
+                    locale,
+                    "finishthis.winner",
+                    author = completion.author.mention()
+                )
+            } else {
+                t!(
+                    locale,
+                    "finishthis.completion",
+                    number = index + 1,
+                    author = completion.author.mention()
+                )
+            };
+            format!(
+                "{}\n*{}*\n{}",
+                heading,
+                game.prompt.line.content,
+                completion.lines.join("\n")
+            )
+        })
+        .collect::<Vec<String>>();
+    if game.completions.len() > MAX_COMPLETIONS_SHOWN {
+        completions.push(t!(
+            locale,
+            "finishthis.more_completions",
+            count = game.completions.len() - MAX_COMPLETIONS_SHOWN
+        ));
+    }
+    completions.join("\n\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_completions, parse_completion, CompletionGames};
+    use crate::{
+        counting::NotHaiku,
+        i18n::Locale,
+        models::{HaikuLine, StoredLine},
+    };
+    use chrono::{Duration, Utc};
+    use serenity::model::id::{ChannelId, GuildId, UserId};
+
+    const FIRST_LINE: &str = "An old silent pond";
+    const COMPLETION: &str = "A frog jumps into the pond\nSplash! Silence again";
+
+    fn prompt() -> StoredLine {
+        StoredLine {
+            haiku_id: 7,
+            position: 0,
+            line: HaikuLine {
+                author: UserId(1),
+                author_name: None,
+                content: FIRST_LINE.to_owned(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_completion() {
+        assert_eq!(
+            parse_completion(FIRST_LINE, COMPLETION).unwrap(),
+            [
+                "A frog jumps into the pond".to_owned(),
+                "Splash! Silence again".to_owned()
+            ]
+        );
+        assert_eq!(
+            parse_completion(FIRST_LINE, &COMPLETION.replace('\n', " ")).unwrap()[1],
+            "Splash! Silence again"
+        );
+        // A whole haiku isn't a completion
+        assert_eq!(
+            parse_completion(FIRST_LINE, &format!("{}\n{}", FIRST_LINE, COMPLETION)),
+            Err(NotHaiku::WrongLineCount(4))
+        );
+        // Lines the wrong way round
+        assert!(parse_completion(
+            FIRST_LINE,
+            "Splash! Silence again\nA frog jumps into the pond"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_completion_games() {
+        let mut games = CompletionGames::new();
+        let now = Utc::now();
+        let ends_at = now + Duration::minutes(5);
+        assert!(games.start(GuildId(1), ChannelId(1), prompt(), ends_at));
+        assert!(!games.start(GuildId(1), ChannelId(1), prompt(), ends_at));
+
+        assert_eq!(
+            games.submit(ChannelId(1), UserId(100), COMPLETION, now),
+            Some(1)
+        );
+        assert_eq!(games.submit(ChannelId(1), UserId(200), "hello", now), None);
+        assert_eq!(
+            games.submit(ChannelId(2), UserId(200), COMPLETION, now),
+            None
+        );
+        // Too late
+        assert_eq!(
+            games.submit(ChannelId(1), UserId(200), COMPLETION, ends_at),
+            None
+        );
+        assert_eq!(
+            games.submit(ChannelId(1), UserId(200), COMPLETION, now),
+            Some(2)
+        );
+
+        let game = games.finish(ChannelId(1)).unwrap();
+        assert_eq!(game.completions.len(), 2);
+        let derivative = game.derivative(&game.completions[1]);
+        assert_eq!(derivative.lines[0].author, UserId(1));
+        assert_eq!(derivative.lines[0].content, FIRST_LINE);
+        assert_eq!(derivative.lines[2].author, UserId(200));
+        assert_eq!(derivative.server, GuildId(1));
+        let formatted = format_completions(&game, Locale::English);
+        assert!(formatted.contains("<@100>"));
+        assert!(formatted.contains("<@200>"));
+        assert!(games.finish(ChannelId(1)).is_none());
+    }
+}
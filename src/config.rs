@@ -0,0 +1,296 @@
+use haikubot::detection::{
+    noise::NoiseRules,
+    pipeline::{LowConfidence, UrlPolicy},
+};
+use serde::Deserialize;
+use serenity::{client::bridge::gateway::GatewayIntents, prelude::TypeMapKey};
+use std::{collections::HashMap, env, fs, io::ErrorKind, net::SocketAddr, sync::Arc};
+
+/// Bot-wide settings, read from the TOML file at $HAIKUBOT_CONFIG (default: config.toml).
+/// Every setting has a default so the file is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub presence: PresenceConfig,
+    pub commands: CommandsConfig,
+    pub guilds: GuildsConfig,
+    pub web: WebConfig,
+    pub maintenance: MaintenanceConfig,
+    pub intents: IntentsConfig,
+    pub save_queue: SaveQueueConfig,
+    pub detection: DetectionConfig,
+}
+
+impl TypeMapKey for Config {
+    type Value = Arc<Config>;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    pub enabled: bool,
+    /// How long to show each activity for before moving on to the next
+    pub interval_secs: u64,
+    /// Activities to cycle through. The text may contain {haiku_count} and {recent_line}, which is
+    /// only ever taken from servers that have opted in to the global stats.
+    pub activities: Vec<ActivityConfig>,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        PresenceConfig {
+            enabled: true,
+            interval_secs: 300,
+            activities: vec![
+                ActivityConfig {
+                    kind: ActivityKind::Watching,
+                    text: "for haikus".to_owned(),
+                },
+                ActivityConfig {
+                    kind: ActivityKind::Playing,
+                    text: "with {haiku_count} haikus collected".to_owned(),
+                },
+                ActivityConfig {
+                    kind: ActivityKind::Listening,
+                    text: "{recent_line}".to_owned(),
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CommandsConfig {
+    /// Register commands separately for each guild instead of globally.
+    /// Guild commands update instantly, whereas global commands can take an hour to propagate.
+    pub guild_scoped: bool,
+    /// Register the everyday commands as subcommands of /haiku, e.g. /haiku get instead of
+    /// /gethaiku, so the bot takes up less of the command list
+    pub umbrella: bool,
+    /// Put in front of every top level command's name, e.g. "haiku-" for /haiku-search, for
+    /// servers where other bots already have commands with the same names
+    pub prefix: String,
+    /// Register top level commands under other names, e.g. `search = "findhaiku"`, instead of
+    /// with the prefix
+    pub rename: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GuildsConfig {
+    /// Delete a guild's haikus this many days after the bot is removed from it.
+    /// Data is kept forever if unset.
+    pub purge_after_days: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    /// Address to serve HTTP endpoints such as /healthz on, e.g. "0.0.0.0:8080".
+    /// No HTTP server is started if unset.
+    pub address: Option<SocketAddr>,
+    /// Where the HTTP endpoints can be reached from outside, e.g. "https://haikubot.example.com",
+    /// so /feed can link people to their feeds
+    pub public_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Periodically vacuum and analyze the database and prune stale in-memory state
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// How long the pagination buttons on search and stats messages keep working
+    pub component_ttl_hours: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        MaintenanceConfig {
+            enabled: true,
+            interval_hours: 24,
+            component_ttl_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SaveQueueConfig {
+    /// Save haikus and count votes in batches from a background writer
+    pub enabled: bool,
+    /// How many writes can wait before anything queueing another has to wait too
+    pub capacity: usize,
+    /// The most writes made in one batch
+    pub max_batch: usize,
+}
+
+impl Default for SaveQueueConfig {
+    fn default() -> Self {
+        SaveQueueConfig {
+            enabled: true,
+            capacity: 256,
+            max_batch: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    /// Lines sent further apart than this in a channel can't form a haiku together. 0 turns it off.
+    pub max_gap_minutes: u64,
+    /// Whether links are stripped before counting, or messages with links skipped entirely
+    pub urls: UrlPolicy,
+    /// Lines with fewer words than this are left out of haikus
+    pub min_words: usize,
+    /// Leave out lines that are just laughing, e.g. "ahahaha" or "lol"
+    pub skip_laughter: bool,
+    /// Leave out lines that are one or two characters repeated, e.g. "aaaaaa" or "!!!!!"
+    pub skip_repeated_characters: bool,
+    /// Leave out lines with nothing but emoji and punctuation
+    pub skip_emoji_only: bool,
+    /// Haikus whose words' syllables were found with less confidence than this on average, from 0
+    /// to 1, are flagged or skipped. 0 saves everything.
+    pub min_confidence: f64,
+    pub low_confidence: LowConfidence,
+    /// Where each channel's most recent lines are saved, so a haiku begun before a restart can
+    /// still be finished after it. Empty turns this off.
+    pub checkpoint_file: String,
+    /// How often the recent lines are saved
+    pub checkpoint_secs: u64,
+    /// How many messages sent while the bot was offline are fetched and checked for haikus in
+    /// each channel with recent lines, up to 100. 0 turns this off.
+    pub backfill_messages: u64,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        let noise = NoiseRules::default();
+        DetectionConfig {
+            max_gap_minutes: 10,
+            urls: UrlPolicy::Strip,
+            min_words: noise.min_words,
+            skip_laughter: noise.laughter,
+            skip_repeated_characters: noise.repeated_characters,
+            skip_emoji_only: noise.emoji_only,
+            min_confidence: 0.0,
+            low_confidence: LowConfidence::Flag,
+            checkpoint_file: "recent_lines.json".to_owned(),
+            checkpoint_secs: 60,
+            backfill_messages: 0,
+        }
+    }
+}
+
+impl DetectionConfig {
+    pub fn max_gap(&self) -> Option<chrono::Duration> {
+        match self.max_gap_minutes {
+            0 => None,
+            minutes => Some(chrono::Duration::minutes(minutes as i64)),
+        }
+    }
+
+    pub fn noise_rules(&self) -> NoiseRules {
+        NoiseRules {
+            min_words: self.min_words,
+            laughter: self.skip_laughter,
+            repeated_characters: self.skip_repeated_characters,
+            emoji_only: self.skip_emoji_only,
+        }
+    }
+}
+
+/// Which privileged gateway intents to ask for. Each has to be enabled in the developer portal
+/// first, so deployments that can't get one can turn it off and lose only what depends on it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct IntentsConfig {
+    /// Needed to read messages, so haiku detection and chains are turned off without it
+    pub message_content: bool,
+    /// Needed to show authors' names, avatars and colours on haiku embeds
+    pub server_members: bool,
+    /// Nothing uses members' presences, but it can be turned back on
+    pub presences: bool,
+}
+
+impl Default for IntentsConfig {
+    fn default() -> Self {
+        IntentsConfig {
+            message_content: true,
+            server_members: true,
+            presences: false,
+        }
+    }
+}
+
+impl IntentsConfig {
+    /// The intents to connect with: the unprivileged ones the bot always needs, plus the
+    /// privileged ones that are turned on
+    pub fn gateway_intents(&self) -> GatewayIntents {
+        let mut intents = GatewayIntents::GUILDS
+            | GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::GUILD_MESSAGE_REACTIONS;
+        if self.message_content {
+            intents |= GatewayIntents::MESSAGE_CONTENT;
+        }
+        if self.server_members {
+            intents |= GatewayIntents::GUILD_MEMBERS;
+        }
+        if self.presences {
+            intents |= GatewayIntents::GUILD_PRESENCES;
+        }
+        intents
+    }
+
+    /// The names of the privileged intents that are turned on, as the developer portal shows them
+    pub fn privileged_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.presences {
+            names.push("Presence");
+        }
+        if self.server_members {
+            names.push("Server Members");
+        }
+        if self.message_content {
+            names.push("Message Content");
+        }
+        names
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActivityConfig {
+    pub kind: ActivityKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Playing,
+    Listening,
+    Watching,
+    Competing,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl Config {
+    pub fn path() -> String {
+        env::var("HAIKUBOT_CONFIG").unwrap_or("config.toml".to_owned())
+    }
+
+    pub fn load() -> Result<Config, ConfigError> {
+        match fs::read_to_string(Config::path()) {
+            Ok(contents) => toml::from_str(&contents).map_err(ConfigError::Parse),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(ConfigError::Io(err)),
+        }
+    }
+}
@@ -1,82 +1,601 @@
-use cached::proc_macro::cached;
+use cached::{proc_macro::cached, Cached, SizedCache};
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::io::BufRead;
-use std::{fs::File, io::BufReader};
+use std::io::{self, BufRead};
+use std::{collections::HashMap, fs::File, io::BufReader, sync::Mutex};
+use unicode_segmentation::UnicodeSegmentation;
 
+/// The CMU pronouncing dictionary, which words' syllables are counted from
+const DICTIONARY_PATH: &str = "cmu_dict.txt";
+
+/// How many syllables each line of a haiku has
+pub const HAIKU_SYLLABLES: [usize; 3] = [5, 7, 5];
+
+/// How many recently counted lines are remembered. Chat repeats itself a lot, and lines are
+/// counted again by /recheck and revalidation.
+const LINE_CACHE_SIZE: usize = 5000;
+
+/// British spellings and their American ones, which the dictionary is more likely to have. Most
+/// are stems, so e.g. "colours" and "organisation" are covered too.
+const SPELLING_VARIANTS: &[(&str, &str)] = &[
+    // -our / -or
+    ("armour", "armor"),
+    ("behaviour", "behavior"),
+    ("colour", "color"),
+    ("favour", "favor"),
+    ("flavour", "flavor"),
+    ("harbour", "harbor"),
+    ("honour", "honor"),
+    ("humour", "humor"),
+    ("labour", "labor"),
+    ("neighbour", "neighbor"),
+    ("odour", "odor"),
+    ("rumour", "rumor"),
+    ("savour", "savor"),
+    ("vapour", "vapor"),
+    ("vigour", "vigor"),
+    // -re / -er
+    ("centre", "center"),
+    ("fibre", "fiber"),
+    ("litre", "liter"),
+    ("lustre", "luster"),
+    ("meagre", "meager"),
+    ("metre", "meter"),
+    ("sombre", "somber"),
+    ("spectre", "specter"),
+    ("theatre", "theater"),
+    // -ise / -ize and -yse / -yze
+    ("analys", "analyz"),
+    ("apologis", "apologiz"),
+    ("criticis", "criticiz"),
+    ("emphasis", "emphasiz"),
+    ("maximis", "maximiz"),
+    ("memoris", "memoriz"),
+    ("minimis", "minimiz"),
+    ("organis", "organiz"),
+    ("paralys", "paralyz"),
+    ("prioritis", "prioritiz"),
+    ("realis", "realiz"),
+    ("recognis", "recogniz"),
+    ("summaris", "summariz"),
+    // -ll- / -l-
+    ("cancell", "cancel"),
+    ("travell", "travel"),
+    // Others
+    ("aeroplane", "airplane"),
+    ("catalogue", "catalog"),
+    ("cheque", "check"),
+    ("defence", "defense"),
+    ("dialogue", "dialog"),
+    ("grey", "gray"),
+    ("jewellery", "jewelry"),
+    ("licence", "license"),
+    ("manoeuvre", "maneuver"),
+    ("mould", "mold"),
+    ("offence", "offense"),
+    ("plough", "plow"),
+    ("programme", "program"),
+    ("pyjama", "pajama"),
+    ("sceptic", "skeptic"),
+    ("tyre", "tire"),
+];
+
+/// Interjections and sounds the dictionary doesn't have, or doesn't count how they're said in chat
+const INTERJECTIONS: &[(&str, usize)] = &[
+    ("aww", 1),
+    ("bleh", 1),
+    ("brr", 1),
+    ("erm", 1),
+    ("ew", 1),
+    ("eww", 1),
+    ("grr", 1),
+    ("hmm", 1),
+    ("hmph", 1),
+    ("meh", 1),
+    ("mhm", 2),
+    ("mm", 1),
+    ("mmm", 1),
+    ("oof", 1),
+    ("pfft", 1),
+    ("psst", 1),
+    ("shh", 1),
+    ("tsk", 1),
+    ("ugh", 1),
+    ("yeet", 1),
+    ("zzz", 1),
+];
+
+/// Letters that can be vowels when guessing how a name is said. "y" is only a vowel when it isn't
+/// followed by one.
+const VOWELS: &str = "aeiouyàáâäåèéêëìíîïòóôöøùúûüý";
+
+/// Vowels with a diaeresis, and the combining diaeresis
+const DIAERESES: &str = "äëïöüÿ\u{308}";
+
+/// Vowels written together that are usually said separately in names, e.g. the "io" in "Orion"
+const SPLIT_VOWELS: &[&str] = &["eo", "ia", "io", "iu", "ua", "uo"];
+
+lazy_static! {
+    static ref LINE_CACHE: Mutex<SizedCache<String, Result<LineCount, Uncountable>>> =
+        Mutex::new(SizedCache::with_size(LINE_CACHE_SIZE));
+}
+
+/// Syllable counts a server has set for words the counter gets wrong, e.g. members' names, keyed
+/// by the word in lowercase
+pub type WordOverrides = HashMap<String, usize>;
+
+/// Why a word couldn't be counted
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Uncountable;
+pub enum Uncountable {
+    /// Something other than a word, e.g. an emoji, a link or a mention
+    NotAWord(String),
+    /// A word that isn't in the dictionary
+    UnknownWord(String),
+}
 
-pub fn count_line(line: &str) -> Result<usize, Uncountable> {
-    let word_syllables = line
-        .split_whitespace()
-        .map(|word| count_word(word))
-        .collect::<Result<Vec<usize>, Uncountable>>()?;
-    Ok(word_syllables.into_iter().sum())
+/// Why some text isn't a haiku
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotHaiku {
+    /// A haiku has three lines
+    WrongLineCount(usize),
+    Uncountable(Uncountable),
+    /// A line (counting from 0) has the wrong number of syllables
+    WrongSyllables {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
 }
-fn count_word(word: &str) -> Result<usize, Uncountable> {
-    match count_word_strict(word.to_owned()) {
-        Ok(count) => Ok(count),
-        // Try again after trimming punctuation
-        Err(_) => count_word_strict(word.trim_matches(|c: char| !c.is_alphanumeric()).to_owned()),
+
+impl From<Uncountable> for NotHaiku {
+    fn from(uncountable: Uncountable) -> Self {
+        NotHaiku::Uncountable(uncountable)
     }
 }
 
-#[cached(size = 1000)]
-fn count_word_strict(word: String) -> Result<usize, Uncountable> {
+/// A word as it was written, and its syllables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordCount {
+    pub word: String,
+    pub syllables: usize,
+}
+
+/// The syllables of each word in a line
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LineCount {
+    pub words: Vec<WordCount>,
+}
+
+impl LineCount {
+    pub fn syllables(&self) -> usize {
+        self.words.iter().map(|word| word.syllables).sum()
+    }
+
+    /// The line's words separated by single spaces
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|word| word.word.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+/// How sure the counter is of a word's syllables, least sure first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Guessed from how a name is spelled
+    Guessed,
+    /// Found after spelling the word another way, e.g. "colour" as "color" or "sooo" as "so"
+    Respelled,
+    /// Found as written, ignoring punctuation, or set by the server
+    Known,
+}
+
+impl Confidence {
+    /// How likely the count is to be right, from 0 to 1
+    pub fn score(self) -> f64 {
+        match self {
+            Confidence::Guessed => 0.5,
+            Confidence::Respelled => 0.8,
+            Confidence::Known => 1.0,
+        }
+    }
+}
+
+/// How a single word was counted, for explaining why some text is or isn't a haiku
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordDiagnostic {
+    /// The word as it was written
+    pub word: String,
+    /// What was looked up in the dictionary: the word itself, or the word without punctuation or
+    /// spelled another way
+    pub looked_up: String,
+    pub syllables: Result<usize, Uncountable>,
+    /// How the syllables were found, or None if they couldn't be
+    pub confidence: Option<Confidence>,
+}
+
+pub fn diagnose_word(word: &str) -> WordDiagnostic {
+    diagnose_word_with(word, &WordOverrides::new())
+}
+
+/// Like [diagnose_word], but with a server's overrides taking priority
+pub fn diagnose_word_with(word: &str, overrides: &WordOverrides) -> WordDiagnostic {
+    let trimmed = word.trim_matches(|c: char| !c.is_alphanumeric());
+    if let Some(&syllables) = overrides.get(&trimmed.to_lowercase()) {
+        return WordDiagnostic {
+            word: word.to_owned(),
+            looked_up: trimmed.to_owned(),
+            syllables: Ok(syllables),
+            confidence: Some(Confidence::Known),
+        };
+    }
+    // Try again after trimming punctuation, then with the word spelled other ways
+    let found = std::iter::once(word.to_owned())
+        .chain(spellings(trimmed))
+        .find_map(|spelling| lookup(&spelling).map(|count| (spelling, count)));
+    if let Some((spelling, syllables)) = found {
+        let confidence = if spelling == word || spelling == trimmed {
+            Confidence::Known
+        } else {
+            Confidence::Respelled
+        };
+        return WordDiagnostic {
+            word: word.to_owned(),
+            looked_up: spelling,
+            syllables: Ok(syllables),
+            confidence: Some(confidence),
+        };
+    }
+    let (syllables, confidence) = match guess_proper_noun(trimmed) {
+        Some(syllables) => (Ok(syllables), Some(Confidence::Guessed)),
+        None if is_word(trimmed) => (Err(Uncountable::UnknownWord(trimmed.to_owned())), None),
+        None => (Err(Uncountable::NotAWord(word.to_owned())), None),
+    };
+    WordDiagnostic {
+        word: word.to_owned(),
+        looked_up: trimmed.to_owned(),
+        syllables,
+        confidence,
+    }
+}
+
+/// How sure the counter is of some lines' syllables, from 0 to 1: the average of their words'
+/// [Confidence::score]s, with words that couldn't be counted scoring 0
+pub fn confidence(lines: &[String], overrides: &WordOverrides) -> f64 {
+    let scores = lines
+        .iter()
+        .flat_map(|line| diagnose_line_with(line, overrides))
+        .map(|word| word.confidence.map_or(0.0, Confidence::score))
+        .collect::<Vec<f64>>();
+    if scores.is_empty() {
+        return 0.0;
+    }
+    scores.iter().sum::<f64>() / scores.len() as f64
+}
+
+/// Ways a word might be found: as written, spelled the American way, and without stretched out
+/// letters, e.g. "sooooo" as "soo" and then "so"
+fn spellings(word: &str) -> Vec<String> {
+    let mut spellings = vec![word.to_owned()];
+    spellings.extend(american_spelling(word));
+    for &max_run in &[2, 1] {
+        let collapsed = collapse_elongation(word, max_run);
+        if collapsed != word {
+            spellings.extend(american_spelling(&collapsed));
+            spellings.push(collapsed);
+        }
+    }
+    spellings
+}
+
+/// Shorten runs of three or more of the same letter to `max_run` letters
+fn collapse_elongation(word: &str, max_run: usize) -> String {
+    let characters = word.chars().collect::<Vec<char>>();
+    let mut collapsed = String::new();
+    let mut start = 0;
+    while start < characters.len() {
+        let run = characters[start..]
+            .iter()
+            .take_while(|&&c| c == characters[start])
+            .count();
+        let kept = if run >= 3 && characters[start].is_alphabetic() {
+            max_run
+        } else {
+            run
+        };
+        collapsed.push_str(&characters[start].to_string().repeat(kept));
+        start += run;
+    }
+    collapsed
+}
+
+/// How many syllables a word has, checking interjections before the dictionary
+fn lookup(word: &str) -> Option<usize> {
+    let lowercase = word.to_lowercase();
+    INTERJECTIONS
+        .iter()
+        .find(|(interjection, _)| *interjection == lowercase)
+        .map(|&(_, syllables)| syllables)
+        .or_else(|| lookup_syllables(word.to_owned()))
+}
+
+/// A guess at how many syllables a capitalized word the dictionary doesn't have, e.g. "Skyrim"
+/// or "Ystharion", is said with: one for each group of vowels, except for a silent final "e".
+/// Words in all caps are left alone, since acronyms are usually spelled out.
+fn guess_proper_noun(word: &str) -> Option<usize> {
+    if !is_word(word)
+        || !word.chars().next()?.is_uppercase()
+        || !word.chars().any(char::is_lowercase)
+    {
+        return None;
+    }
+    // Letters written with combining accents are a single grapheme, and count as their base letter
+    let word = word.to_lowercase();
+    let graphemes = word.graphemes(true).collect::<Vec<&str>>();
+    let letters = graphemes
+        .iter()
+        .filter_map(|grapheme| grapheme.chars().next())
+        .collect::<Vec<char>>();
+    let is_vowel = |index: usize| match letters[index] {
+        'y' => !matches!(
+            letters.get(index + 1),
+            Some(&next) if next != 'y' && VOWELS.contains(next)
+        ),
+        letter => VOWELS.contains(letter),
+    };
+    let mut syllables = 0;
+    for index in 0..letters.len() {
+        if !is_vowel(index) {
+            continue;
+        }
+        // A diaeresis, as in "Zoë", marks a vowel that's said separately
+        let starts_group = index == 0
+            || !is_vowel(index - 1)
+            || graphemes[index].contains(|c| DIAERESES.contains(c));
+        let pair = letters[index.saturating_sub(1)..=index]
+            .iter()
+            .collect::<String>();
+        if starts_group || SPLIT_VOWELS.contains(&pair.as_str()) {
+            syllables += 1;
+        }
+    }
+    // e.g. "Hyrule", but not "Gable"
+    let length = letters.len();
+    if syllables > 1
+        && letters[length - 1] == 'e'
+        && !is_vowel(length - 2)
+        && !(letters[length - 2] == 'l' && length > 2 && !is_vowel(length - 3))
+    {
+        syllables -= 1;
+    }
+    if syllables == 0 {
+        None
+    } else {
+        Some(syllables)
+    }
+}
+
+/// The word spelled the American way, if it's a British spelling from [SPELLING_VARIANTS]
+fn american_spelling(word: &str) -> Option<String> {
+    let word = word.to_lowercase();
+    SPELLING_VARIANTS
+        .iter()
+        .find(|(british, _)| word.starts_with(british))
+        .map(|(british, american)| format!("{}{}", american, &word[british.len()..]))
+}
+
+pub fn count_word(word: &str) -> Result<usize, Uncountable> {
+    diagnose_word(word).syllables
+}
+
+/// How each word in a line was counted. Unlike [count_line], this carries on past words that
+/// can't be counted.
+pub fn diagnose_line(line: &str) -> Vec<WordDiagnostic> {
+    diagnose_line_with(line, &WordOverrides::new())
+}
+
+/// Like [diagnose_line], but with a server's overrides taking priority
+pub fn diagnose_line_with(line: &str, overrides: &WordOverrides) -> Vec<WordDiagnostic> {
+    line.split_whitespace()
+        .map(|word| diagnose_word_with(word, overrides))
+        .collect()
+}
+
+/// Like [count_line], but with a server's overrides taking priority
+pub fn count_line_with(line: &str, overrides: &WordOverrides) -> Result<LineCount, Uncountable> {
+    // Only lines counted without overrides are cached
+    if overrides.is_empty() {
+        return count_line(line);
+    }
+    count_words(line, overrides)
+}
+
+pub fn count_line(line: &str) -> Result<LineCount, Uncountable> {
+    // Lines are counted word by word, so spacing can't change the count
+    let normalized = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if let Some(count) = LINE_CACHE
+        .lock()
+        .expect("Line cache lock poisoned")
+        .cache_get(&normalized)
+    {
+        return count.clone();
+    }
+    let count = count_words(&normalized, &WordOverrides::new());
+    LINE_CACHE
+        .lock()
+        .expect("Line cache lock poisoned")
+        .cache_set(normalized, count.clone());
+    count
+}
+
+/// How often [count_line] has found a line in its cache since the bot started, and how often it
+/// had to count one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub fn line_cache_stats() -> CacheStats {
+    let cache = LINE_CACHE.lock().expect("Line cache lock poisoned");
+    CacheStats {
+        hits: cache.cache_hits().unwrap_or(0),
+        misses: cache.cache_misses().unwrap_or(0),
+    }
+}
+
+/// Forget every word and line counted so far, so edits to the dictionary are picked up without a
+/// restart. The dictionary is checked first, and nothing is forgotten if it can't be read.
+/// Returns how many words the dictionary has.
+pub fn reload_dictionary() -> io::Result<usize> {
+    let mut entries = 0;
+    for line in BufReader::new(File::open(DICTIONARY_PATH)?).lines() {
+        // Lines starting with ;;; are comments
+        if !line?.starts_with(";;;") {
+            entries += 1;
+        }
+    }
+    LOOKUP_SYLLABLES
+        .lock()
+        .expect("Syllable cache lock poisoned")
+        .cache_clear();
+    LINE_CACHE
+        .lock()
+        .expect("Line cache lock poisoned")
+        .cache_clear();
+    Ok(entries)
+}
+
+fn count_words(line: &str, overrides: &WordOverrides) -> Result<LineCount, Uncountable> {
+    let words = line
+        .split_whitespace()
+        .map(|word| {
+            let syllables = diagnose_word_with(word, overrides).syllables;
+            syllables.map(|syllables| WordCount {
+                word: word.to_owned(),
+                syllables,
+            })
+        })
+        .collect::<Result<Vec<WordCount>, Uncountable>>()?;
+    Ok(LineCount { words })
+}
+
+fn is_word(word: &str) -> bool {
     lazy_static! {
         static ref WORD_REGEX: Regex = Regex::new(r"^[\w']+$").unwrap();
     }
-    if !WORD_REGEX.is_match(&word) {
-        Err(Uncountable)
-    } else {
-        let file = File::open("cmu_dict.txt").unwrap();
-        let reader = BufReader::new(file);
-        let line: Option<String> = reader
-            .lines()
-            .filter_map(Result::ok)
-            .filter(|line| line.starts_with(&format!("{} ", word.to_uppercase())))
-            .next();
-        if let Some(line) = line {
-            lazy_static! {
-                // Match a whitespace char (don't include the word itself), then phoneme followed by stress
-                static ref STRESS_REGEX: Regex = Regex::new(r"\s(?:[[:alpha:]]+([[:digit:]]))").unwrap();
-            }
-            Ok(STRESS_REGEX.captures_iter(&line).count())
-        } else {
-            Err(Uncountable)
+    WORD_REGEX.is_match(word)
+}
+
+#[cached(size = 1000)]
+fn lookup_syllables(word: String) -> Option<usize> {
+    if !is_word(&word) {
+        return None;
+    }
+    let file = File::open(DICTIONARY_PATH).unwrap();
+    let reader = BufReader::new(file);
+    let line: Option<String> = reader
+        .lines()
+        .map_while(Result::ok)
+        .find(|line| line.starts_with(&format!("{} ", word.to_uppercase())));
+    line.map(|line| {
+        lazy_static! {
+            // Match a whitespace char (don't include the word itself), then phoneme followed by stress
+            static ref STRESS_REGEX: Regex = Regex::new(r"\s(?:[[:alpha:]]+([[:digit:]]))").unwrap();
         }
+        STRESS_REGEX.captures_iter(&line).count()
+    })
+}
+
+/// Check three lines are a haiku, returning the count of each line if they are
+pub fn is_haiku(lines: &[String]) -> Result<[LineCount; 3], NotHaiku> {
+    is_haiku_with(lines, &WordOverrides::new())
+}
+
+/// Like [is_haiku], but with a server's overrides taking priority
+pub fn is_haiku_with(
+    lines: &[String],
+    overrides: &WordOverrides,
+) -> Result<[LineCount; 3], NotHaiku> {
+    if lines.len() != 3 {
+        return Err(NotHaiku::WrongLineCount(lines.len()));
     }
+    let counts = [
+        count_line_with(&lines[0], overrides)?,
+        count_line_with(&lines[1], overrides)?,
+        count_line_with(&lines[2], overrides)?,
+    ];
+    check_syllables(counts)
 }
 
-pub fn is_haiku(lines: &[String]) -> bool {
-    count_line(&lines[0]) == Ok(5)
-        && count_line(&lines[1]) == Ok(7)
-        && count_line(&lines[2]) == Ok(5)
+/// Split a single line into the three lines of a haiku, if it is one
+pub fn split_into_haiku(line: &str) -> Result<[LineCount; 3], NotHaiku> {
+    split_into_haiku_with(line, &WordOverrides::new())
 }
 
-pub fn is_haiku_single(line: &str) -> Result<Option<[String; 3]>, Uncountable> {
+/// Like [split_into_haiku], but with a server's overrides taking priority
+pub fn split_into_haiku_with(
+    line: &str,
+    overrides: &WordOverrides,
+) -> Result<[LineCount; 3], NotHaiku> {
     let mut syllable_count = 0;
-    let mut lines = [Vec::new(), Vec::new(), Vec::new()];
+    let mut lines = [
+        LineCount::default(),
+        LineCount::default(),
+        LineCount::default(),
+    ];
     for word in line.split_whitespace() {
-        syllable_count += count_word(word)?;
+        let syllables = diagnose_word_with(word, overrides).syllables?;
+        syllable_count += syllables;
+        let word = WordCount {
+            word: word.to_owned(),
+            syllables,
+        };
         match syllable_count {
-            x if x <= 5 => lines[0].push(word.to_owned()),
-            x if x <= 12 => lines[1].push(word.to_owned()),
-            _ => lines[2].push(word.to_owned()),
+            x if x <= 5 => lines[0].words.push(word),
+            x if x <= 12 => lines[1].words.push(word),
+            _ => lines[2].words.push(word),
         }
     }
-    let lines = [lines[0].join(" "), lines[1].join(" "), lines[2].join(" ")];
-    if is_haiku(&lines) {
-        Ok(Some(lines))
-    } else {
-        Ok(None)
+    check_syllables(lines)
+}
+
+fn check_syllables(lines: [LineCount; 3]) -> Result<[LineCount; 3], NotHaiku> {
+    for (line, expected) in HAIKU_SYLLABLES.iter().enumerate() {
+        let found = lines[line].syllables();
+        if found != *expected {
+            return Err(NotHaiku::WrongSyllables {
+                line,
+                expected: *expected,
+                found,
+            });
+        }
     }
+    Ok(lines)
 }
 
 #[cfg(test)]
 mod test {
-    use super::{count_line, count_word, is_haiku, is_haiku_single, Uncountable};
+    use super::{
+        american_spelling, collapse_elongation, confidence, count_line, count_line_with,
+        count_word, diagnose_line, guess_proper_noun, is_haiku, line_cache_stats,
+        reload_dictionary, split_into_haiku, Confidence, NotHaiku, Uncountable, WordCount,
+        WordDiagnostic, WordOverrides,
+    };
+
+    /// The share of the corpus that must be counted correctly. Raise this as counting improves.
+    const CORPUS_ACCURACY_THRESHOLD: f64 = 0.85;
+
+    fn syllables(line: &str) -> Result<usize, Uncountable> {
+        count_line(line).map(|count| count.syllables())
+    }
+
+    fn split(line: &str) -> Result<[String; 3], NotHaiku> {
+        let [line_1, line_2, line_3] = split_into_haiku(line)?;
+        Ok([line_1.text(), line_2.text(), line_3.text()])
+    }
 
     #[test]
     fn test_count_word() {
@@ -87,56 +606,236 @@ mod test {
         assert_eq!(count_word("A"), Ok(1));
         assert_eq!(count_word("Don't"), Ok(1));
         assert_eq!(count_word("'Allo"), Ok(2));
-        assert_eq!(count_word("Allo"), Err(Uncountable));
-        assert_eq!(count_word("X Y Z"), Err(Uncountable));
-        assert_eq!(count_word("XYZ"), Err(Uncountable));
-        assert_eq!(count_word("#$&^%&"), Err(Uncountable));
+        assert_eq!(
+            count_word("allo"),
+            Err(Uncountable::UnknownWord("allo".to_owned()))
+        );
+        assert_eq!(
+            count_word("X Y Z"),
+            Err(Uncountable::NotAWord("X Y Z".to_owned()))
+        );
+        assert_eq!(
+            count_word("XYZ"),
+            Err(Uncountable::UnknownWord("XYZ".to_owned()))
+        );
+        assert_eq!(
+            count_word("#$&^%&"),
+            Err(Uncountable::NotAWord("#$&^%&".to_owned()))
+        );
     }
 
     #[test]
     fn test_count_line() {
-        assert_eq!(count_line("A B C"), Ok(3));
-        assert_eq!(count_line("Abundant haiku"), Ok(5));
-        assert_eq!(count_line("Uses a dictionary"), Ok(7));
-        assert_eq!(count_line("Database to come"), Ok(5));
-        assert_eq!(count_line("'Allo 'allo"), Ok(4));
-        assert_eq!(count_line("'Hello there'"), Ok(3));
-        assert_eq!(count_line("\"Hello there.\" said General Kenobi."), Ok(10));
+        assert_eq!(syllables("A B C"), Ok(3));
+        assert_eq!(syllables("Abundant haiku"), Ok(5));
+        assert_eq!(syllables("Uses a dictionary"), Ok(7));
+        assert_eq!(syllables("Database to come"), Ok(5));
+        assert_eq!(syllables("'Allo 'allo"), Ok(4));
+        assert_eq!(syllables("'Hello there'"), Ok(3));
+        assert_eq!(syllables("\"Hello there.\" said General Kenobi."), Ok(10));
+        assert_eq!(
+            count_line("Abundant haiku").map(|count| count.words),
+            Ok(vec![
+                WordCount {
+                    word: "Abundant".to_owned(),
+                    syllables: 3
+                },
+                WordCount {
+                    word: "haiku".to_owned(),
+                    syllables: 2
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_spelling_variants() {
+        assert_eq!(american_spelling("Colours"), Some("colors".to_owned()));
+        assert_eq!(
+            american_spelling("organisation"),
+            Some("organization".to_owned())
+        );
+        assert_eq!(american_spelling("color"), None);
+        for (british, american) in &[
+            ("colours", "colors"),
+            ("Flavour", "Flavor"),
+            ("organised", "organized"),
+            ("analyse", "analyze"),
+            ("litres", "liters"),
+            ("Pyjamas", "Pajamas"),
+            ("sceptical", "skeptical"),
+        ] {
+            assert!(count_word(american).is_ok(), "{}", american);
+            assert_eq!(count_word(british), count_word(american), "{}", british);
+        }
+        assert_eq!(diagnose_line("vapour")[0].looked_up, "vapor".to_owned());
+    }
+
+    #[test]
+    fn test_interjections() {
+        assert_eq!(collapse_elongation("sooooo", 1), "so");
+        assert_eq!(collapse_elongation("Heyyyy!!!", 2), "Heyy!!!");
+        assert_eq!(collapse_elongation("good", 1), "good");
+        for word in &[
+            "hmm", "pfft", "Shh!", "ugh", "hmmmmm", "sooooo", "NOOOOO", "yessss",
+        ] {
+            assert_eq!(count_word(word), Ok(1), "{}", word);
+        }
+        assert_eq!(count_word("mhm"), Ok(2));
+        assert_eq!(syllables("that's sooooo goooood"), Ok(3));
+        assert_eq!(diagnose_line("coooool")[0].looked_up, "cool".to_owned());
+    }
+
+    #[test]
+    fn test_proper_nouns() {
+        for (name, syllables) in &[
+            ("Skyrim", 2),
+            ("Ystharion", 4),
+            ("Zelda", 2),
+            ("Hyrule", 2),
+            ("Gable", 2),
+            ("Mario", 3),
+            ("Zoë", 2),
+            ("Zoe\u{308}", 2),
+        ] {
+            assert_eq!(guess_proper_noun(name), Some(*syllables), "{}", name);
+        }
+        // Acronyms and words that aren't capitalized aren't guessed
+        assert_eq!(guess_proper_noun("XYZ"), None);
+        assert_eq!(guess_proper_noun("ystharion"), None);
+        assert_eq!(guess_proper_noun("Brr"), None);
+        assert_eq!(syllables("Ystharion plays Skyrim"), Ok(7));
+        assert_eq!(
+            count_word("ystharion"),
+            Err(Uncountable::UnknownWord("ystharion".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_word_overrides() {
+        let mut overrides = WordOverrides::new();
+        overrides.insert("ystharion".to_owned(), 5);
+        overrides.insert("pond".to_owned(), 2);
+        let count = |line| count_line_with(line, &overrides).map(|count| count.syllables());
+        assert_eq!(count("Ystharion, pond!"), Ok(7));
+        assert_eq!(count("ystharion"), Ok(5));
+        assert_eq!(syllables("Ystharion, pond!"), Ok(5));
+        assert_eq!(syllables("pond"), Ok(1));
+    }
+
+    #[test]
+    fn test_line_cache() {
+        let line = "The cache remembers lines";
+        let counted = count_line(line);
+        // Other tests count lines at the same time, so only a lower bound can be checked
+        let before = line_cache_stats();
+        assert_eq!(count_line("  The cache  remembers lines "), counted);
+        assert!(line_cache_stats().hits > before.hits);
+    }
+
+    #[test]
+    fn test_reload_dictionary() {
+        assert_eq!(count_word("pond"), Ok(1));
+        assert!(reload_dictionary().unwrap() > 100_000);
+        assert_eq!(count_word("pond"), Ok(1));
+    }
+
+    #[test]
+    fn test_diagnose_line() {
+        assert_eq!(
+            diagnose_line("'Hello XYZ $$"),
+            vec![
+                WordDiagnostic {
+                    word: "'Hello".to_owned(),
+                    looked_up: "Hello".to_owned(),
+                    syllables: Ok(2),
+                    confidence: Some(Confidence::Known)
+                },
+                WordDiagnostic {
+                    word: "XYZ".to_owned(),
+                    looked_up: "XYZ".to_owned(),
+                    syllables: Err(Uncountable::UnknownWord("XYZ".to_owned())),
+                    confidence: None
+                },
+                WordDiagnostic {
+                    word: "$$".to_owned(),
+                    looked_up: "".to_owned(),
+                    syllables: Err(Uncountable::NotAWord("$$".to_owned())),
+                    confidence: None
+                }
+            ]
+        );
+        assert_eq!(diagnose_line(""), vec![]);
+    }
+
+    #[test]
+    fn test_confidence() {
+        let confidences = diagnose_line("Ystharion's colours sooo bright")
+            .into_iter()
+            .map(|word| word.confidence)
+            .collect::<Vec<Option<Confidence>>>();
+        assert_eq!(
+            confidences,
+            vec![
+                Some(Confidence::Guessed),
+                Some(Confidence::Respelled),
+                Some(Confidence::Respelled),
+                Some(Confidence::Known)
+            ]
+        );
+        let overrides = WordOverrides::new();
+        let haiku = |lines: [&str; 3]| {
+            confidence(
+                &lines
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect::<Vec<String>>(),
+                &overrides,
+            )
+        };
+        assert_eq!(
+            haiku([
+                "An old silent pond",
+                "A frog jumps into the pond",
+                "Splash! Silence again"
+            ]),
+            1.0
+        );
+        assert_eq!(haiku(["Skyrim", "Ystharion", "pond"]), 2.0 / 3.0);
+        assert_eq!(haiku(["", "", ""]), 0.0);
     }
 
     #[test]
-    fn test_haiku_single() {
+    fn test_split_into_haiku() {
         assert_eq!(
-            is_haiku_single(
-                "The last winter leaves Clinging to the black branches Explode into birds"
-            ),
-            Ok(Some([
+            split("The last winter leaves Clinging to the black branches Explode into birds"),
+            Ok([
                 "The last winter leaves".to_owned(),
                 "Clinging to the black branches".to_owned(),
                 "Explode into birds".to_owned()
-            ]))
+            ])
         );
         assert_eq!(
-            is_haiku_single(
-                "The last winter leaves, clinging to the black branches, explode into birds."
-            ),
-            Ok(Some([
+            split("The last winter leaves, clinging to the black branches, explode into birds."),
+            Ok([
                 "The last winter leaves,".to_owned(),
                 "clinging to the black branches,".to_owned(),
                 "explode into birds.".to_owned()
-            ]))
+            ])
         );
         assert_eq!(
-            is_haiku_single(
-                "The last spring leaves Clinging to the black branches Explode into birds"
-            ),
-            Ok(None)
+            split("The last spring leaves Clinging to the black branches Explode into birds"),
+            Err(NotHaiku::WrongSyllables {
+                line: 0,
+                expected: 5,
+                found: 4
+            })
         );
         assert_eq!(
-            is_haiku_single(
-                "The last ^%^$&^ leaves Clinging to the black branches Explode into birds"
-            ),
-            Err(Uncountable)
+            split("The last ^%^$&^ leaves Clinging to the black branches Explode into birds"),
+            Err(NotHaiku::Uncountable(Uncountable::NotAWord(
+                "^%^$&^".to_owned()
+            )))
         );
     }
 
@@ -146,21 +845,83 @@ mod test {
             "The last winter leaves".to_owned(),
             "Clinging to the black branches".to_owned(),
             "Explode into birds".to_owned()
-        ]));
+        ])
+        .is_ok());
         assert!(is_haiku(&[
             "The last 'winter' leaves.".to_owned(),
             "Clinging to the black branches.".to_owned(),
             "Explode into birds".to_owned()
-        ]));
-        assert!(!is_haiku(&[
-            "The last spring leaves".to_owned(),
-            "Clinging to the black branches".to_owned(),
-            "Explode into birds".to_owned()
-        ]));
-        assert!(!is_haiku(&[
-            "The last $^%$^ leaves".to_owned(),
-            "Clinging to the black branches".to_owned(),
-            "Explode into birds".to_owned()
-        ]));
+        ])
+        .is_ok());
+        assert_eq!(
+            is_haiku(&[
+                "The last spring leaves".to_owned(),
+                "Clinging to the black branches".to_owned(),
+                "Explode into birds".to_owned()
+            ]),
+            Err(NotHaiku::WrongSyllables {
+                line: 0,
+                expected: 5,
+                found: 4
+            })
+        );
+        assert_eq!(
+            is_haiku(&[
+                "The last $^%$^ leaves".to_owned(),
+                "Clinging to the black branches".to_owned(),
+                "Explode into birds".to_owned()
+            ]),
+            Err(NotHaiku::Uncountable(Uncountable::NotAWord(
+                "$^%$^".to_owned()
+            )))
+        );
+        assert_eq!(
+            is_haiku(&[
+                "The last winter leaves".to_owned(),
+                "Clinging to the black branches".to_owned()
+            ]),
+            Err(NotHaiku::WrongLineCount(2))
+        );
+        assert_eq!(is_haiku(&[]), Err(NotHaiku::WrongLineCount(0)));
+    }
+
+    #[test]
+    fn test_corpus_accuracy() {
+        let corpus = include_str!("../corpus/syllables.txt")
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (count, phrase) = line.split_at(line.find('|').expect("Missing '|' in corpus"));
+                (
+                    count.parse::<usize>().expect("Invalid count in corpus"),
+                    &phrase[1..],
+                )
+            })
+            .collect::<Vec<(usize, &str)>>();
+        let misses = corpus
+            .iter()
+            .filter(|(expected, phrase)| syllables(phrase) != Ok(*expected))
+            .collect::<Vec<_>>();
+        for (expected, phrase) in misses.iter() {
+            println!(
+                "Miscounted \"{}\": expected {}, got {:?}",
+                phrase,
+                expected,
+                syllables(phrase)
+            );
+        }
+        let accuracy = (corpus.len() - misses.len()) as f64 / corpus.len() as f64;
+        println!(
+            "Counted {}/{} corpus phrases correctly ({:.1}%)",
+            corpus.len() - misses.len(),
+            corpus.len(),
+            accuracy * 100.0
+        );
+        assert!(
+            accuracy >= CORPUS_ACCURACY_THRESHOLD,
+            "Counting accuracy {:.1}% is below the threshold of {:.1}%",
+            accuracy * 100.0,
+            CORPUS_ACCURACY_THRESHOLD * 100.0
+        );
     }
 }
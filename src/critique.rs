@@ -0,0 +1,74 @@
+use crate::models::Haiku;
+use serde_json::{json, Value};
+use std::env;
+
+const DEFAULT_API_URL: &str = "https://api.openai.com/v1/chat/completions";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+const SYSTEM_PROMPT: &str = "You are a kind and encouraging poetry teacher. \
+    Give a short, friendly critique of the haiku you are given in no more than three sentences. \
+    Mention one thing that works well and one gentle suggestion. \
+    Do not rewrite the haiku and do not comment on its syllable count.";
+
+#[derive(Debug)]
+pub enum CritiqueError {
+    NotConfigured,
+    Request(reqwest::Error),
+    InvalidResponse,
+}
+
+impl From<reqwest::Error> for CritiqueError {
+    fn from(err: reqwest::Error) -> Self {
+        CritiqueError::Request(err)
+    }
+}
+
+/// Settings for the LLM endpoint, read from the environment.
+/// Critiques are disabled unless $CRITIQUE_API_KEY is set.
+pub struct CritiqueConfig {
+    pub api_url: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+impl CritiqueConfig {
+    pub fn from_env() -> Option<Self> {
+        let api_key = env::var("CRITIQUE_API_KEY").ok()?;
+        Some(CritiqueConfig {
+            api_url: env::var("CRITIQUE_API_URL").unwrap_or(DEFAULT_API_URL.to_owned()),
+            api_key,
+            model: env::var("CRITIQUE_MODEL").unwrap_or(DEFAULT_MODEL.to_owned()),
+        })
+    }
+}
+
+/// Ask the configured OpenAI-compatible chat completions endpoint for a critique of the haiku
+pub async fn critique_haiku(haiku: &Haiku) -> Result<String, CritiqueError> {
+    let config = CritiqueConfig::from_env().ok_or(CritiqueError::NotConfigured)?;
+    let poem = haiku
+        .lines
+        .iter()
+        .map(|line| line.content.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let response = reqwest::Client::new()
+        .post(&config.api_url)
+        .bearer_auth(&config.api_key)
+        .json(&json!({
+            "model": config.model,
+            "max_tokens": 200,
+            "messages": [
+                { "role": "system", "content": SYSTEM_PROMPT },
+                { "role": "user", "content": poem },
+            ],
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+    response["choices"][0]["message"]["content"]
+        .as_str()
+        .map(|critique| critique.trim().to_owned())
+        .filter(|critique| !critique.is_empty())
+        .ok_or(CritiqueError::InvalidResponse)
+}
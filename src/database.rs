@@ -1,28 +1,153 @@
+use crate::counting::WordOverrides;
 use crate::models::*;
 use crate::Haiku;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use diesel::pg::PgConnection;
-use diesel::{pg::Pg, prelude::*};
-use diesel_full_text_search::{
-    plainto_tsquery, to_tsvector, ts_rank_cd, TsQuery, TsQueryExtensions, TsVectorExtensions,
+use diesel::{
+    dsl::sql,
+    expression::SqlLiteral,
+    pg::Pg,
+    prelude::*,
+    sql_types::{Array, BigInt, Date, Double, Nullable, Text, Timestamp},
 };
+use diesel_full_text_search::{plainto_tsquery, ts_rank_cd, TsQuery, TsQueryExtensions, TsVector};
 use rand::Rng;
-use serenity::model::id::GuildId;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::env;
+use std::ops::Range;
 
 pub fn establish_connection() -> PgConnection {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     PgConnection::establish(&database_url).expect(&format!("Error connecting to {}", database_url))
 }
 
-pub fn save_haiku(haiku: &Haiku, database_connection: &PgConnection) -> i64 {
-    use crate::schema::haikus;
-    let new_haiku = NewHaikuDTO::from(haiku);
-    let haiku: HaikuDTO = diesel::insert_into(haikus::table)
-        .values(&new_haiku)
-        .get_result(database_connection)
-        .expect("Error saving haiku");
-    haiku.id
+/// Connect to `DATABASE_URL`, or say why that isn't possible right now, e.g. the server is down
+pub fn try_establish_connection() -> ConnectionResult<PgConnection> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    PgConnection::establish(&database_url)
+}
+
+/// The version of the newest migration in migrations/, which should be bumped alongside every
+/// new migration so the bot can tell when the database hasn't been migrated yet
+pub const LATEST_MIGRATION: &str = "20261017120000";
+
+/// The version of the newest migration the diesel CLI has run against the database, if any
+pub fn latest_applied_migration(database_connection: &PgConnection) -> QueryResult<Option<String>> {
+    diesel::select(sql::<Nullable<Text>>(
+        "(SELECT MAX(version) FROM __diesel_schema_migrations)",
+    ))
+    .get_result::<Option<String>>(database_connection)
+}
+
+/// Check the database can be connected to and queried, without panicking
+pub fn check_connection() -> bool {
+    let database_url = match env::var("DATABASE_URL") {
+        Ok(database_url) => database_url,
+        Err(_) => return false,
+    };
+    PgConnection::establish(&database_url)
+        .map(|connection| {
+            diesel::select(sql::<BigInt>("1"))
+                .execute(&connection)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Store a haiku and its lines with the next number in its server, returning that number, or None
+/// if a haiku from the same messages is already saved
+pub fn save_haiku(haiku: &Haiku, database_connection: &PgConnection) -> Option<i64> {
+    use crate::schema::{haiku_lines, haiku_numbers, haikus};
+    let server_id = i64::try_from(*haiku.server.as_u64()).unwrap();
+    // Run as a savepoint when saving a batch, so a haiku that's already saved gives its number back
+    let saved = database_connection.transaction::<_, diesel::result::Error, _>(|| {
+        // Locks the server's row until the save is committed, so numbers are never given out twice
+        let haiku_number = diesel::insert_into(haiku_numbers::table)
+            .values((
+                haiku_numbers::server.eq(server_id),
+                haiku_numbers::last_number.eq(1),
+            ))
+            .on_conflict(haiku_numbers::server)
+            .do_update()
+            .set(haiku_numbers::last_number.eq(haiku_numbers::last_number + 1))
+            .returning(haiku_numbers::last_number)
+            .get_result::<i64>(database_connection)?;
+        let inserted = diesel::insert_into(haikus::table)
+            .values(&NewHaikuDTO::from_haiku(haiku_number, haiku))
+            .on_conflict_do_nothing()
+            .execute(database_connection)?;
+        if inserted == 0 {
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+        diesel::insert_into(haiku_lines::table)
+            .values(&NewHaikuLineDTO::from_haiku(haiku_number, haiku))
+            .execute(database_connection)?;
+        Ok(haiku_number)
+    });
+    match saved {
+        Ok(haiku_number) => Some(haiku_number),
+        Err(diesel::result::Error::RollbackTransaction) => None,
+        Err(why) => panic!("Error saving haiku: {:?}", why),
+    }
+}
+
+/// Store several haikus in one transaction, returning their ids in the same order
+/// (None for any from messages that are already saved)
+pub fn save_haikus(to_save: &[Haiku], database_connection: &PgConnection) -> Vec<Option<i64>> {
+    database_connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            Ok(to_save
+                .iter()
+                .map(|haiku| save_haiku(haiku, database_connection))
+                .collect())
+        })
+        .expect("Error saving haikus")
+}
+
+/// Haikus along with their lines, which are fetched in one query. Any haiku missing a line is
+/// left out.
+fn with_lines(dtos: Vec<HaikuDTO>, database_connection: &PgConnection) -> Vec<(i64, Haiku)> {
+    use crate::schema::haiku_lines::dsl::*;
+    let numbers = dtos.iter().map(|dto| dto.number).collect::<Vec<i64>>();
+    let mut lines = HashMap::new();
+    for line in haiku_lines
+        .filter(haiku_id.eq_any(&numbers))
+        .load::<HaikuLineDTO>(database_connection)
+        .expect("Error fetching haiku lines")
+    {
+        lines
+            .entry((line.server, line.haiku_id))
+            .or_insert_with(Vec::new)
+            .push(line);
+    }
+    dtos.into_iter()
+        .filter_map(|dto| {
+            let found = lines.remove(&(dto.server, dto.number)).unwrap_or_default();
+            dto.with_lines(found)
+        })
+        .collect()
+}
+
+/// The authors of each line of some of a server's haikus, in the order of the haikus' ids
+fn line_authors(
+    server_id: GuildId,
+    ids: &[i64],
+    database_connection: &PgConnection,
+) -> Vec<[UserId; 3]> {
+    use crate::schema::haiku_lines::dsl::*;
+    let user = |user_id: i64| UserId::from(u64::try_from(user_id).unwrap());
+    haiku_lines
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(haiku_id.eq_any(ids))
+        .order((haiku_id, position))
+        .select(author)
+        .load::<i64>(database_connection)
+        .expect("Error fetching haiku authors")
+        .chunks_exact(3)
+        .map(|authors| [user(authors[0]), user(authors[1]), user(authors[2])])
+        .collect()
 }
 
 pub fn get_haiku(
@@ -33,31 +158,576 @@ pub fn get_haiku(
     use crate::schema::haikus::dsl::*;
     let results = haikus
         .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
-        .filter(id.eq(haiku_id))
+        .filter(number.eq(haiku_id))
         .load::<HaikuDTO>(database_connection)
         .expect("Error fetching haiku");
-    results.into_iter().next().map(|dto| dto.into())
+    with_lines(results, database_connection).into_iter().next()
 }
 
-pub fn get_random_haiku(
+/// Every haiku from every server, oldest first
+pub fn get_all_haikus(database_connection: &PgConnection) -> Vec<(i64, Haiku)> {
+    use crate::schema::haikus::dsl::*;
+    let results = haikus
+        .order(id.asc())
+        .load::<HaikuDTO>(database_connection)
+        .expect("Error fetching haikus");
+    with_lines(results, database_connection)
+}
+
+/// Mark a haiku as needing review, e.g. because it no longer counts as a haiku
+pub fn flag_haiku(server_id: GuildId, haiku_id: i64, database_connection: &PgConnection) {
+    use crate::schema::haikus::dsl::*;
+    diesel::update(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(number.eq(haiku_id)),
+    )
+    .set(flagged.eq(true))
+    .execute(database_connection)
+    .expect("Error flagging haiku");
+}
+
+/// Give a haiku a content warning or take it away, returning whether the haiku exists
+pub fn set_haiku_sensitive(
     server_id: GuildId,
+    haiku_id: i64,
+    is_sensitive: bool,
     database_connection: &PgConnection,
-) -> Option<(i64, Haiku)> {
+) -> bool {
     use crate::schema::haikus::dsl::*;
-    let count = haikus
+    let updated = diesel::update(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(number.eq(haiku_id)),
+    )
+    .set(sensitive.eq(is_sensitive))
+    .execute(database_connection)
+    .expect("Error setting content warning");
+    updated > 0
+}
+
+pub fn is_haiku_sensitive(
+    server_id: GuildId,
+    haiku_id: i64,
+    database_connection: &PgConnection,
+) -> bool {
+    use crate::schema::haikus::dsl::*;
+    haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(number.eq(haiku_id))
+        .select(sensitive)
+        .first::<bool>(database_connection)
+        .optional()
+        .expect("Error fetching content warning")
+        .unwrap_or(false)
+}
+
+/// Change one line of a haiku and record the edit, returning whether the haiku exists
+pub fn edit_haiku_line(
+    server_id: GuildId,
+    haiku_id: i64,
+    edit: &HaikuEdit,
+    syllables: usize,
+    database_connection: &PgConnection,
+) -> bool {
+    use crate::schema::{haiku_edits, haiku_lines};
+    let server_id_value = i64::try_from(*server_id.as_u64()).unwrap();
+    let edited = database_connection.transaction::<_, diesel::result::Error, _>(|| {
+        let updated = diesel::update(
+            haiku_lines::table
+                .filter(haiku_lines::server.eq(server_id_value))
+                .filter(haiku_lines::haiku_id.eq(haiku_id))
+                .filter(haiku_lines::position.eq(i64::try_from(edit.position).unwrap())),
+        )
+        .set((
+            haiku_lines::content.eq(&edit.content),
+            haiku_lines::syllables.eq(i64::try_from(syllables).unwrap()),
+        ))
+        .execute(database_connection)?;
+        if updated == 0 {
+            return Ok(false);
+        }
+        diesel::insert_into(haiku_edits::table)
+            .values(&NewHaikuEditDTO::new(server_id, haiku_id, edit))
+            .execute(database_connection)?;
+        Ok(true)
+    });
+    edited.expect("Error editing haiku")
+}
+
+/// Every edit made to a haiku, oldest first
+pub fn get_haiku_edits(
+    server_id: GuildId,
+    haiku: i64,
+    database_connection: &PgConnection,
+) -> Vec<HaikuEdit> {
+    use crate::schema::haiku_edits::dsl::*;
+    haiku_edits
         .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(haiku_id.eq(haiku))
+        .order(id.asc())
+        .load::<HaikuEditDTO>(database_connection)
+        .expect("Error fetching haiku edits")
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+/// Remember which message in the server's archive channel is a haiku's mirror
+pub fn set_mirror_message(
+    server_id: GuildId,
+    haiku_id: i64,
+    message: Option<MessageId>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::haikus::dsl::*;
+    diesel::update(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(number.eq(haiku_id)),
+    )
+    .set(mirror_message.eq(message.map(|message| i64::try_from(*message.as_u64()).unwrap())))
+    .execute(database_connection)
+    .expect("Error saving mirror message");
+}
+
+pub fn get_mirror_message(
+    server_id: GuildId,
+    haiku_id: i64,
+    database_connection: &PgConnection,
+) -> Option<MessageId> {
+    use crate::schema::haikus::dsl::*;
+    haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(number.eq(haiku_id))
+        .select(mirror_message)
+        .first::<Option<i64>>(database_connection)
+        .optional()
+        .expect("Error fetching mirror message")
+        .flatten()
+        .map(|message| MessageId::from(u64::try_from(message).unwrap()))
+}
+
+/// Remember which message announced a haiku in the channel it was written in
+pub fn set_announcement_message(
+    server_id: GuildId,
+    haiku_id: i64,
+    message: Option<MessageId>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::haikus::dsl::*;
+    diesel::update(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(number.eq(haiku_id)),
+    )
+    .set(announcement_message.eq(message.map(|message| i64::try_from(*message.as_u64()).unwrap())))
+    .execute(database_connection)
+    .expect("Error saving announcement message");
+}
+
+pub fn get_announcement_message(
+    server_id: GuildId,
+    haiku_id: i64,
+    database_connection: &PgConnection,
+) -> Option<MessageId> {
+    use crate::schema::haikus::dsl::*;
+    haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(number.eq(haiku_id))
+        .select(announcement_message)
+        .first::<Option<i64>>(database_connection)
+        .optional()
+        .expect("Error fetching announcement message")
+        .flatten()
+        .map(|message| MessageId::from(u64::try_from(message).unwrap()))
+}
+
+/// Record that a haiku has just been shown by /randomhaiku
+pub fn mark_haiku_shown(server_id: GuildId, haiku_id: i64, database_connection: &PgConnection) {
+    use crate::schema::haikus::dsl::*;
+    diesel::update(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(number.eq(haiku_id)),
+    )
+    .set(last_shown_at.eq(Utc::now().naive_utc()))
+    .execute(database_connection)
+    .expect("Error marking haiku as shown");
+}
+
+/// Count someone's vote for a haiku, returning how many votes the haiku now has, or None if
+/// they'd already voted for it or there's no such haiku
+pub fn vote_for_haiku(
+    server_id: GuildId,
+    haiku_id: i64,
+    user_id: UserId,
+    database_connection: &PgConnection,
+) -> Option<i64> {
+    use crate::schema::{haiku_votes, haikus};
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let haiku = haikus::table
+        .filter(haikus::server.eq(server_id))
+        .filter(haikus::number.eq(haiku_id));
+    // Run as a savepoint when voting in a batch
+    database_connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            if !diesel::select(diesel::dsl::exists(haiku))
+                .get_result::<bool>(database_connection)?
+            {
+                return Ok(None);
+            }
+            let inserted = diesel::insert_into(haiku_votes::table)
+                .values((
+                    haiku_votes::server.eq(server_id),
+                    haiku_votes::haiku_id.eq(haiku_id),
+                    haiku_votes::user_id.eq(i64::try_from(*user_id.as_u64()).unwrap()),
+                    haiku_votes::voted_at.eq(Utc::now().naive_utc()),
+                ))
+                .on_conflict_do_nothing()
+                .execute(database_connection)?;
+            if inserted == 0 {
+                return Ok(None);
+            }
+            // Locks the haiku's row, so votes cast at the same time each see their own count
+            diesel::update(haiku)
+                .set(haikus::votes.eq(haikus::votes + 1))
+                .returning(haikus::votes)
+                .get_result::<i64>(database_connection)
+                .map(Some)
+        })
+        .expect("Error voting for haiku")
+}
+
+/// Count several votes at once, given as (server, haiku id, voter), returning what
+/// [vote_for_haiku] would for each in the same order
+pub fn add_votes(
+    new_votes: &[(GuildId, i64, UserId)],
+    database_connection: &PgConnection,
+) -> Vec<Option<i64>> {
+    database_connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            Ok(new_votes
+                .iter()
+                .map(|(server_id, haiku_id, user_id)| {
+                    vote_for_haiku(*server_id, *haiku_id, *user_id, database_connection)
+                })
+                .collect())
+        })
+        .expect("Error voting for haikus")
+}
+
+/// Delete a haiku, returning whether it existed
+pub fn delete_haiku(server_id: GuildId, haiku_id: i64, database_connection: &PgConnection) -> bool {
+    use crate::schema::haikus::dsl::*;
+    let deleted = diesel::delete(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(number.eq(haiku_id)),
+    )
+    .execute(database_connection)
+    .expect("Error deleting haiku");
+    deleted > 0
+}
+
+pub fn count_all_haikus(database_connection: &PgConnection) -> i64 {
+    use crate::schema::haikus::dsl::*;
+    haikus
+        .count()
+        .get_result::<i64>(database_connection)
+        .expect("Error counting haikus")
+}
+
+/// How many haikus in a server have at least one line written by the author
+pub fn count_haikus_by_author(
+    server_id: GuildId,
+    author_id: UserId,
+    database_connection: &PgConnection,
+) -> i64 {
+    use crate::schema::haiku_lines::dsl::*;
+    haiku_lines
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(author.eq(i64::try_from(*author_id.as_u64()).unwrap()))
+        .select(sql::<BigInt>("COUNT(DISTINCT haiku_id)"))
+        .get_result::<i64>(database_connection)
+        .expect("Error counting haikus")
+}
+
+/// How many haikus each author has a line in since a point in time, most first
+pub fn count_haikus_by_author_since(
+    server_id: GuildId,
+    since: DateTime<Utc>,
+    database_connection: &PgConnection,
+) -> Vec<(UserId, i64)> {
+    get_leaderboard(server_id, Some(since), 0, i64::MAX, database_connection)
+}
+
+/// The authors of each haiku in a server since a point in time, with each author counted once
+/// per haiku however many lines they wrote
+const HAIKU_AUTHORS_SQL: &str = "SELECT DISTINCT haiku_lines.haiku_id, haiku_lines.author \
+     FROM haiku_lines JOIN haikus \
+     ON haikus.server = haiku_lines.server AND haikus.number = haiku_lines.haiku_id \
+     WHERE haikus.server = $1 AND haikus.timestamp >= $2";
+
+#[derive(QueryableByName)]
+struct AuthorCount {
+    #[sql_type = "BigInt"]
+    author: i64,
+    #[sql_type = "BigInt"]
+    haikus: i64,
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// One page of the authors with the most haikus since a point in time (or ever), counted by the
+/// database rather than loading every haiku
+pub fn get_leaderboard(
+    server_id: GuildId,
+    since: Option<DateTime<Utc>>,
+    offset: i64,
+    limit: i64,
+    database_connection: &PgConnection,
+) -> Vec<(UserId, i64)> {
+    diesel::sql_query(format!(
+        "SELECT author, COUNT(*) AS haikus FROM ({}) AS haiku_authors \
+         GROUP BY author ORDER BY haikus DESC, author ASC OFFSET $3 LIMIT $4",
+        HAIKU_AUTHORS_SQL
+    ))
+    .bind::<BigInt, _>(i64::try_from(*server_id.as_u64()).unwrap())
+    .bind::<Timestamp, _>(leaderboard_since(since))
+    .bind::<BigInt, _>(offset)
+    .bind::<BigInt, _>(limit)
+    .load::<AuthorCount>(database_connection)
+    .expect("Error fetching leaderboard")
+    .into_iter()
+    .map(|row| (UserId::from(u64::try_from(row.author).unwrap()), row.haikus))
+    .collect()
+}
+
+/// How many authors are on a leaderboard, for working out how many pages it has
+pub fn count_leaderboard_authors(
+    server_id: GuildId,
+    since: Option<DateTime<Utc>>,
+    database_connection: &PgConnection,
+) -> i64 {
+    diesel::sql_query(format!(
+        "SELECT COUNT(DISTINCT author) AS count FROM ({}) AS haiku_authors",
+        HAIKU_AUTHORS_SQL
+    ))
+    .bind::<BigInt, _>(i64::try_from(*server_id.as_u64()).unwrap())
+    .bind::<Timestamp, _>(leaderboard_since(since))
+    .get_result::<Count>(database_connection)
+    .expect("Error counting leaderboard authors")
+    .count
+}
+
+fn leaderboard_since(since: Option<DateTime<Utc>>) -> NaiveDateTime {
+    since
+        .map(|since| since.naive_utc())
+        .unwrap_or_else(|| NaiveDateTime::from_timestamp(0, 0))
+}
+
+/// How many haikus each channel in a server has produced, most first
+pub fn count_haikus_by_channel(
+    server_id: GuildId,
+    database_connection: &PgConnection,
+) -> Vec<(ChannelId, i64)> {
+    use crate::schema::haikus::dsl::*;
+    use diesel::dsl::count_star;
+    haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .group_by(channel)
+        .select((channel, count_star()))
+        .order((count_star().desc(), channel.asc()))
+        .load::<(i64, i64)>(database_connection)
+        .expect("Error counting haikus by channel")
+        .into_iter()
+        .map(|(channel_id, count)| (ChannelId::from(u64::try_from(channel_id).unwrap()), count))
+        .collect()
+}
+
+pub fn count_haikus(server_id: GuildId, database_connection: &PgConnection) -> i64 {
+    use crate::schema::haikus::dsl::*;
+    haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .count()
+        .get_result::<i64>(database_connection)
+        .expect("Error counting haikus")
+}
+
+pub fn get_latest_haiku(database_connection: &PgConnection) -> Option<(i64, Haiku)> {
+    use crate::schema::{haikus::dsl::*, server_configs};
+    let shared_servers = server_configs::table
+        .select(server_configs::server)
+        .filter(server_configs::global_stats_enabled.eq(true))
+        .filter(server_configs::left_at.is_null());
+    let results = haikus
+        .filter(server.eq_any(shared_servers))
+        .order(timestamp.desc())
+        .limit(1)
+        .load::<HaikuDTO>(database_connection)
+        .expect("Error fetching haiku");
+    with_lines(results, database_connection).into_iter().next()
+}
+
+pub fn get_database_size(database_connection: &PgConnection) -> i64 {
+    diesel::select(sql::<BigInt>("pg_database_size(current_database())"))
+        .get_result::<i64>(database_connection)
+        .expect("Error fetching database size")
+}
+
+/// A random line from a server's haikus, from one of the given positions (0 to 2)
+pub fn get_random_line(
+    server_id: GuildId,
+    positions: &[usize],
+    database_connection: &PgConnection,
+) -> Option<StoredLine> {
+    use crate::schema::{haiku_lines::dsl::*, haikus};
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let positions = positions
+        .iter()
+        .map(|line| i64::try_from(*line).unwrap())
+        .collect::<Vec<i64>>();
+    let in_server = || {
+        haiku_lines
+            .filter(server.eq(server_id))
+            .filter(position.eq_any(&positions))
+            .filter(
+                haiku_id.ne_all(
+                    haikus::table
+                        .filter(haikus::server.eq(server_id))
+                        .filter(haikus::sensitive.eq(true))
+                        .select(haikus::number),
+                ),
+            )
+    };
+    let count = in_server()
+        .count()
+        .get_result::<i64>(database_connection)
+        .expect("Error counting haiku lines");
+    if count == 0 {
+        return None;
+    }
+    let offset = rand::thread_rng().gen_range(0, count);
+    in_server()
+        .order((haiku_id, position))
+        .offset(offset)
+        .first::<HaikuLineDTO>(database_connection)
+        .optional()
+        .expect("Error fetching haiku line")
+        .map(|line| line.into())
+}
+
+/// How likely each haiku is to be picked by /randomhaiku
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomWeighting {
+    /// Every haiku is as likely as any other
+    Uniform,
+    /// The longer since a haiku was last shown (or saved, if it never has been), the more likely
+    LeastRecentlyShown,
+    /// The more votes a haiku has, the more likely
+    MostVoted,
+}
+
+impl RandomWeighting {
+    pub fn code(self) -> &'static str {
+        match self {
+            RandomWeighting::Uniform => "uniform",
+            RandomWeighting::LeastRecentlyShown => "least_recently_shown",
+            RandomWeighting::MostVoted => "most_voted",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "uniform" => Some(RandomWeighting::Uniform),
+            "least_recently_shown" => Some(RandomWeighting::LeastRecentlyShown),
+            "most_voted" => Some(RandomWeighting::MostVoted),
+            _ => None,
+        }
+    }
+
+    /// A haiku's weight, relative to the others. Every haiku has a weight of at least 1, so none
+    /// are left out entirely.
+    pub fn weight(self, hours_since_shown: f64, votes: i64) -> f64 {
+        match self {
+            RandomWeighting::Uniform => 1.0,
+            RandomWeighting::LeastRecentlyShown => hours_since_shown.max(0.0) + 1.0,
+            RandomWeighting::MostVoted => votes.max(0) as f64 + 1.0,
+        }
+    }
+
+    /// [RandomWeighting::weight] as an SQL expression over the haikus table
+    fn weight_sql(self) -> &'static str {
+        match self {
+            RandomWeighting::Uniform => "1",
+            RandomWeighting::LeastRecentlyShown => {
+                "GREATEST(CAST(EXTRACT(EPOCH FROM (NOW() AT TIME ZONE 'UTC') \
+                 - COALESCE(last_shown_at, timestamp)) AS DOUBLE PRECISION) / 3600, 0) + 1"
+            }
+            RandomWeighting::MostVoted => "GREATEST(votes, 0) + 1",
+        }
+    }
+}
+
+/// The haikus /randomhaiku may pick from
+fn random_candidates(
+    server_id: GuildId,
+    exclude: &[i64],
+    written: Option<&Range<DateTime<Utc>>>,
+) -> crate::schema::haikus::BoxedQuery<'static, Pg> {
+    use crate::schema::haikus::dsl::*;
+    let query = haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(number.ne_all(exclude))
+        .filter(sensitive.eq(false))
+        .into_boxed();
+    match written {
+        Some(written) => query
+            .filter(timestamp.ge(written.start.naive_utc()))
+            .filter(timestamp.lt(written.end.naive_utc())),
+        None => query,
+    }
+}
+
+pub fn get_random_haiku(
+    server_id: GuildId,
+    exclude: &[i64],
+    weighting: RandomWeighting,
+    written: Option<&Range<DateTime<Utc>>>,
+    database_connection: &PgConnection,
+) -> Option<(i64, Haiku)> {
+    if weighting != RandomWeighting::Uniform {
+        // Weighted sampling: the smallest -ln(u) / weight wins, where u is uniform in (0, 1]
+        let results = random_candidates(server_id, exclude, written)
+            .order(sql::<Double>(&format!(
+                "-LN(1 - RANDOM()) / ({})",
+                weighting.weight_sql()
+            )))
+            .limit(1)
+            .load::<HaikuDTO>(database_connection)
+            .expect("Error fetching haiku");
+        return with_lines(results, database_connection).into_iter().next();
+    }
+    let count = random_candidates(server_id, exclude, written)
         .count()
         .get_result::<i64>(database_connection)
         .expect("Error fetching haiku");
+    if count == 0 {
+        return None;
+    }
     for _ in 0..10 {
         let haiku_id = rand::thread_rng().gen_range(0, count);
-        let results = haikus
-            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        let results = random_candidates(server_id, exclude, written)
             .offset(haiku_id)
             .limit(1)
             .load::<HaikuDTO>(database_connection)
             .expect("Error fetching haiku");
-        let haiku = results.into_iter().next().map(|dto| dto.into());
+        let haiku = with_lines(results, database_connection).into_iter().next();
         if let Some(haiku) = haiku {
             return Some(haiku);
         }
@@ -66,7 +736,8 @@ pub fn get_random_haiku(
 }
 
 fn get_search_query(
-    keywords: &Vec<String>,
+    keywords: &[String],
+    mode: KeywordMatch,
 ) -> Option<Box<dyn BoxableExpression<crate::schema::haikus::table, Pg, SqlType = TsQuery>>> {
     keywords
         .iter()
@@ -76,35 +747,1254 @@ fn get_search_query(
                 as Box<
                     dyn BoxableExpression<crate::schema::haikus::table, Pg, SqlType = TsQuery>,
                 >),
-            Some(query) => Some(Box::new(query.or(next))
-                as Box<
-                    dyn BoxableExpression<crate::schema::haikus::table, Pg, SqlType = TsQuery>,
-                >),
+            Some(query) => match mode {
+                KeywordMatch::Any => Some(Box::new(query.or(next))
+                    as Box<
+                        dyn BoxableExpression<crate::schema::haikus::table, Pg, SqlType = TsQuery>,
+                    >),
+                KeywordMatch::All => Some(Box::new(query.and(next))
+                    as Box<
+                        dyn BoxableExpression<crate::schema::haikus::table, Pg, SqlType = TsQuery>,
+                    >),
+            },
         })
 }
 
+/// Whether a haiku has to contain every keyword of a search, or just one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordMatch {
+    All,
+    Any,
+}
+
+/// The words to search for, and the words a result must not contain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchTerms {
+    pub keywords: Vec<String>,
+    pub excluded: Vec<String>,
+    pub mode: KeywordMatch,
+}
+
+impl SearchTerms {
+    /// Split a search into words, those starting with `-` being excluded, e.g. `frog pond -splash`
+    pub fn parse(search: &str, mode: KeywordMatch) -> Self {
+        let mut keywords = Vec::new();
+        let mut excluded = Vec::new();
+        for word in search.split_whitespace() {
+            match word.strip_prefix('-') {
+                // A lone - isn't a word either way
+                Some("") => {}
+                Some(word) => excluded.push(word.to_owned()),
+                None => keywords.push(word.to_owned()),
+            }
+        }
+        SearchTerms {
+            keywords,
+            excluded,
+            mode,
+        }
+    }
+}
+
+/// How search results are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOrder {
+    Relevance,
+    Newest,
+    Oldest,
+}
+
+/// How many haikus matched a search, in total and for each author with a line in them
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SearchCounts {
+    pub total: i64,
+    /// Most haikus first
+    pub by_author: Vec<(UserId, i64)>,
+}
+
+impl SearchCounts {
+    /// Count up the authors of each matching haiku, counting each author once per haiku
+    pub fn from_authors(haikus: impl IntoIterator<Item = [UserId; 3]>) -> Self {
+        let mut total = 0;
+        let mut by_author = HashMap::new();
+        for authors in haikus {
+            total += 1;
+            for (index, author) in authors.iter().enumerate() {
+                if !authors[..index].contains(author) {
+                    *by_author.entry(*author).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut by_author = by_author.into_iter().collect::<Vec<(UserId, i64)>>();
+        by_author.sort_by_key(|(author, count)| (std::cmp::Reverse(*count), *author));
+        SearchCounts { total, by_author }
+    }
+}
+
+/// Where a list of haikus, newest first, left off: the last haiku listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HaikuCursor {
+    pub timestamp: DateTime<Utc>,
+    pub id: i64,
+}
+
+/// Which haikus to list, narrowed down the way /search does
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HaikuFilter {
+    pub terms: Option<SearchTerms>,
+    /// Only haikus written at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only haikus written before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Only haikus that come after this one, newest first
+    pub after: Option<HaikuCursor>,
+}
+
+/// A haiku's lines as one document to search, in the order they're written
+fn search_document() -> SqlLiteral<TsVector> {
+    sql::<TsVector>(
+        "(SELECT to_tsvector(string_agg(haiku_lines.content, ' ' ORDER BY haiku_lines.position)) \
+         FROM haiku_lines \
+         WHERE haiku_lines.server = haikus.server AND haiku_lines.haiku_id = haikus.number)",
+    )
+}
+
+/// Haikus in a server matching the search terms, optionally only from one author or channel.
+/// None if there are no keywords to search for.
+fn filter_search(
+    server_id: GuildId,
+    terms: &SearchTerms,
+    author_id: Option<UserId>,
+    channel_id: Option<ChannelId>,
+) -> Option<crate::schema::haikus::BoxedQuery<'static, Pg>> {
+    use crate::schema::{haiku_lines, haikus::dsl::*};
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let search_query = get_search_query(&terms.keywords, terms.mode)?;
+    let query = haikus
+        .filter(server.eq(server_id))
+        .filter(search_query.matches(search_document()))
+        .into_boxed();
+    let query = match get_search_query(&terms.excluded, KeywordMatch::Any) {
+        Some(excluded) => query.filter(diesel::dsl::not(excluded.matches(search_document()))),
+        None => query,
+    };
+    let query = match author_id {
+        Some(author_id) => query.filter(
+            number.eq_any(
+                haiku_lines::table
+                    .filter(haiku_lines::server.eq(server_id))
+                    .filter(haiku_lines::author.eq(i64::try_from(*author_id.as_u64()).unwrap()))
+                    .select(haiku_lines::haiku_id),
+            ),
+        ),
+        None => query,
+    };
+    let query = match channel_id {
+        Some(channel_id) => query.filter(channel.eq(i64::try_from(*channel_id.as_u64()).unwrap())),
+        None => query,
+    };
+    Some(query)
+}
+
 pub fn search_haikus(
     server_id: GuildId,
-    keywords: Vec<String>,
+    terms: &SearchTerms,
+    order: SearchOrder,
+    author_id: Option<UserId>,
+    channel_id: Option<ChannelId>,
     database_connection: &PgConnection,
 ) -> Vec<(i64, Haiku)> {
     use crate::schema::haikus::dsl::*;
-    let search_fields = to_tsvector(message_0)
-        .concat(to_tsvector(message_1))
-        .concat(to_tsvector(message_2));
-    let search_query = get_search_query(&keywords);
-    if let Some(search_query) = search_query {
-        let search_query_2 = get_search_query(&keywords).unwrap();
-        let query = haikus
-            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
-            .filter(search_query.matches(search_fields))
-            .order(ts_rank_cd(search_fields, search_query_2).desc())
-            .limit(5);
+    if let Some(query) = filter_search(server_id, terms, author_id, channel_id) {
+        let search_query = get_search_query(&terms.keywords, terms.mode).unwrap();
+        let query = match order {
+            SearchOrder::Relevance => {
+                query.order(ts_rank_cd(search_document(), search_query).desc())
+            }
+            SearchOrder::Newest => query.order(timestamp.desc()),
+            SearchOrder::Oldest => query.order(timestamp.asc()),
+        }
+        .limit(5);
         let result = query
             .load::<HaikuDTO>(database_connection)
             .expect("Error searching for haikus");
-        result.into_iter().map(|dto| dto.into()).collect()
+        with_lines(result, database_connection)
     } else {
         Vec::new()
     }
 }
+
+/// How many haikus match a search, without fetching them
+pub fn count_search_results(
+    server_id: GuildId,
+    terms: &SearchTerms,
+    author_id: Option<UserId>,
+    channel_id: Option<ChannelId>,
+    database_connection: &PgConnection,
+) -> SearchCounts {
+    use crate::schema::haikus::dsl::*;
+    match filter_search(server_id, terms, author_id, channel_id) {
+        Some(query) => {
+            let ids = query
+                .select(number)
+                .load::<i64>(database_connection)
+                .expect("Error counting search results");
+            SearchCounts::from_authors(line_authors(server_id, &ids, database_connection))
+        }
+        None => SearchCounts::default(),
+    }
+}
+
+/// Every haiku in a server with at least one line written by the author, oldest first
+pub fn get_haikus_by_author(
+    server_id: GuildId,
+    author_id: UserId,
+    database_connection: &PgConnection,
+) -> Vec<(i64, Haiku)> {
+    use crate::schema::{haiku_lines, haikus::dsl::*};
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let results = haikus
+        .filter(server.eq(server_id))
+        .filter(
+            number.eq_any(
+                haiku_lines::table
+                    .filter(haiku_lines::server.eq(server_id))
+                    .filter(haiku_lines::author.eq(i64::try_from(*author_id.as_u64()).unwrap()))
+                    .select(haiku_lines::haiku_id),
+            ),
+        )
+        .order(timestamp.asc())
+        .load::<HaikuDTO>(database_connection)
+        .expect("Error fetching haikus");
+    with_lines(results, database_connection)
+}
+
+/// The most recent haikus in a server that the author wrote every line of and that match the
+/// filter, leaving out those with a content warning, newest first
+pub fn get_public_haikus_by_author(
+    server_id: GuildId,
+    author_id: UserId,
+    filter: &HaikuFilter,
+    limit: i64,
+    database_connection: &PgConnection,
+) -> Vec<(i64, Haiku)> {
+    use crate::schema::{haiku_lines, haikus::dsl::*};
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let author_id = i64::try_from(*author_id.as_u64()).unwrap();
+    let mut query = haikus
+        .filter(server.eq(server_id))
+        .filter(sensitive.eq(false))
+        .filter(
+            number.eq_any(
+                haiku_lines::table
+                    .filter(haiku_lines::server.eq(server_id))
+                    .filter(haiku_lines::author.eq(author_id))
+                    .select(haiku_lines::haiku_id),
+            ),
+        )
+        .filter(
+            number.ne_all(
+                haiku_lines::table
+                    .filter(haiku_lines::server.eq(server_id))
+                    .filter(haiku_lines::author.ne(author_id))
+                    .select(haiku_lines::haiku_id),
+            ),
+        )
+        .into_boxed();
+    if let Some(terms) = &filter.terms {
+        if let Some(search_query) = get_search_query(&terms.keywords, terms.mode) {
+            query = query.filter(search_query.matches(search_document()));
+        }
+        if let Some(excluded) = get_search_query(&terms.excluded, KeywordMatch::Any) {
+            query = query.filter(diesel::dsl::not(excluded.matches(search_document())));
+        }
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(timestamp.ge(since.naive_utc()));
+    }
+    if let Some(until) = filter.until {
+        query = query.filter(timestamp.lt(until.naive_utc()));
+    }
+    if let Some(after) = filter.after {
+        let after_timestamp = after.timestamp.naive_utc();
+        query = query.filter(
+            timestamp
+                .lt(after_timestamp)
+                .or(timestamp.eq(after_timestamp).and(number.lt(after.id))),
+        );
+    }
+    let results = query
+        .order((timestamp.desc(), number.desc()))
+        .limit(limit)
+        .load::<HaikuDTO>(database_connection)
+        .expect("Error fetching haikus");
+    with_lines(results, database_connection)
+}
+
+/// Every archived haiku in a server with at least one line written by the author, oldest first
+pub fn get_archived_haikus_by_author(
+    server_id: GuildId,
+    author_id: UserId,
+    database_connection: &PgConnection,
+) -> Vec<(i64, Haiku)> {
+    use crate::schema::archived_haikus::dsl::*;
+    let author_id = i64::try_from(*author_id.as_u64()).unwrap();
+    archived_haikus
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(
+            author_0
+                .eq(author_id)
+                .or(author_1.eq(author_id))
+                .or(author_2.eq(author_id)),
+        )
+        .order(timestamp.asc())
+        .load::<ArchivedHaikuDTO>(database_connection)
+        .expect("Error fetching archived haikus")
+        .into_iter()
+        .map(|dto| dto.into())
+        .collect()
+}
+
+/// What happens to a server's haikus once they're older than its retention window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionAction {
+    /// Move them to the archive, where they can still be exported
+    Archive,
+    Delete,
+}
+
+impl RetentionAction {
+    pub fn code(self) -> &'static str {
+        match self {
+            RetentionAction::Archive => "archive",
+            RetentionAction::Delete => "delete",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "archive" => Some(RetentionAction::Archive),
+            "delete" => Some(RetentionAction::Delete),
+            _ => None,
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct ArchivedNumber {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+/// Move a server's haikus saved before a point in time to the archive, returning how many moved
+pub fn archive_haikus_before(
+    server_id: GuildId,
+    before: DateTime<Utc>,
+    database_connection: &PgConnection,
+) -> usize {
+    use crate::schema::haikus;
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    database_connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            // The archive keeps a haiku's lines alongside it, as they were before lines had their
+            // own table
+            let archived = diesel::sql_query(format!(
+                "INSERT INTO archived_haikus (id, channel, server, timestamp, \
+                 author_0, author_1, author_2, message_0, message_1, message_2) \
+                 SELECT haikus.number, haikus.channel, haikus.server, haikus.timestamp, \
+                 line_0.author, line_1.author, line_2.author, \
+                 line_0.content, line_1.content, line_2.content \
+                 FROM haikus {} {} {} \
+                 WHERE haikus.server = $1 AND haikus.timestamp < $2 \
+                 RETURNING id",
+                join_line(0),
+                join_line(1),
+                join_line(2)
+            ))
+            .bind::<BigInt, _>(server_id)
+            .bind::<Timestamp, _>(before.naive_utc())
+            .load::<ArchivedNumber>(database_connection)?
+            .into_iter()
+            .map(|archived| archived.id)
+            .collect::<Vec<i64>>();
+            // Only what made it into the archive is deleted, and their lines go with them
+            diesel::delete(
+                haikus::table
+                    .filter(haikus::server.eq(server_id))
+                    .filter(haikus::number.eq_any(&archived)),
+            )
+            .execute(database_connection)
+        })
+        .expect("Error archiving haikus")
+}
+
+/// SQL joining one of a haiku's lines on to it, as line_{position}
+fn join_line(position: usize) -> String {
+    format!(
+        "JOIN haiku_lines line_{0} ON line_{0}.server = haikus.server \
+         AND line_{0}.haiku_id = haikus.number AND line_{0}.position = {0}",
+        position
+    )
+}
+
+/// Delete a server's haikus saved before a point in time, returning how many were deleted
+pub fn delete_haikus_before(
+    server_id: GuildId,
+    before: DateTime<Utc>,
+    database_connection: &PgConnection,
+) -> usize {
+    use crate::schema::haikus::dsl::*;
+    diesel::delete(
+        haikus
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(timestamp.lt(before.naive_utc())),
+    )
+    .execute(database_connection)
+    .expect("Error deleting old haikus")
+}
+
+/// Reclaim space from deleted rows and refresh the query planner's statistics
+pub fn run_maintenance(database_connection: &PgConnection) {
+    diesel::sql_query("VACUUM ANALYZE")
+        .execute(database_connection)
+        .expect("Error running database maintenance");
+}
+
+/// Create the default config for a server, or mark it as active again if it has rejoined
+pub fn create_server_config(server_id: GuildId, database_connection: &PgConnection) {
+    use crate::schema::server_configs::dsl::*;
+    let new_config = NewServerConfigDTO {
+        server: i64::try_from(*server_id.as_u64()).unwrap(),
+        joined_at: Utc::now().naive_utc(),
+    };
+    diesel::insert_into(server_configs)
+        .values(&new_config)
+        .on_conflict(server)
+        .do_update()
+        .set(left_at.eq(None::<NaiveDateTime>))
+        .execute(database_connection)
+        .expect("Error creating server config");
+}
+
+pub fn get_server_config(
+    server_id: GuildId,
+    database_connection: &PgConnection,
+) -> Option<ServerConfig> {
+    use crate::schema::server_configs::dsl::*;
+    let results = server_configs
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .load::<ServerConfigDTO>(database_connection)
+        .expect("Error fetching server config");
+    results.into_iter().next().map(|dto| dto.into())
+}
+
+pub fn set_server_locale(server_id: GuildId, new_locale: &str, database_connection: &PgConnection) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(locale.eq(new_locale))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_welcome(server_id: GuildId, enabled: bool, database_connection: &PgConnection) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(welcome_enabled.eq(enabled))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+/// Record that a server has reached a milestone, returning false if it already had
+pub fn set_server_random_weighting(
+    server_id: GuildId,
+    weighting: RandomWeighting,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(random_weighting.eq(weighting.code()))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+/// Set or clear the laureate role, forgetting who held the previous one
+pub fn set_server_laureate_role(
+    server_id: GuildId,
+    role: Option<RoleId>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set((
+            laureate_role.eq(role.map(|role| i64::try_from(*role.as_u64()).unwrap())),
+            laureate.eq(None::<i64>),
+        ))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_laureate(
+    server_id: GuildId,
+    user: Option<UserId>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(laureate.eq(user.map(|user| i64::try_from(*user.as_u64()).unwrap())))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+/// Set how many years a server's haikus are kept for (forever if None) and what happens after
+pub fn set_server_retention(
+    server_id: GuildId,
+    years: Option<i64>,
+    action: RetentionAction,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set((
+            retention_years.eq(years),
+            retention_action.eq(action.code()),
+        ))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_global_stats(
+    server_id: GuildId,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(global_stats_enabled.eq(enabled))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+/// Set or clear the text posted with each announced haiku
+pub fn set_server_announcement_template(
+    server_id: GuildId,
+    template: Option<&str>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(announcement_template.eq(template))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_manual_save(
+    server_id: GuildId,
+    emoji: Option<&str>,
+    role: Option<RoleId>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set((
+            manual_save_emoji.eq(emoji),
+            manual_save_role.eq(role.map(|role| i64::try_from(*role.as_u64()).unwrap())),
+        ))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_senders(
+    server_id: GuildId,
+    bots: bool,
+    webhooks: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set((
+            detect_bot_messages.eq(bots),
+            detect_webhook_messages.eq(webhooks),
+        ))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_bridge_pattern(
+    server_id: GuildId,
+    pattern: Option<&str>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(bridge_pattern.eq(pattern))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_same_author(
+    server_id: GuildId,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(same_author_only.eq(enabled))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_error_channel(
+    server_id: GuildId,
+    channel: Option<ChannelId>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(error_channel.eq(channel.map(|channel| i64::try_from(*channel.as_u64()).unwrap())))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_archive(
+    server_id: GuildId,
+    archive: Option<&ArchiveWebhook>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set((
+            archive_channel
+                .eq(archive.map(|archive| i64::try_from(*archive.channel.as_u64()).unwrap())),
+            archive_webhook
+                .eq(archive.map(|archive| i64::try_from(*archive.webhook.as_u64()).unwrap())),
+            archive_webhook_token.eq(archive.map(|archive| archive.token.clone())),
+        ))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_timezone(
+    server_id: GuildId,
+    new_timezone: &str,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(timezone.eq(new_timezone))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_plain_text(
+    server_id: GuildId,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(plain_text.eq(enabled))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn set_server_public_feeds(
+    server_id: GuildId,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(public_feeds.eq(enabled))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+/// Turn automatic haiku detection on or off in a channel
+pub fn set_channel_detection(
+    server_id: GuildId,
+    channel_id: ChannelId,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::detection_disabled_channels::dsl::*;
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let channel_id = i64::try_from(*channel_id.as_u64()).unwrap();
+    if enabled {
+        diesel::delete(
+            detection_disabled_channels
+                .filter(server.eq(server_id))
+                .filter(channel.eq(channel_id)),
+        )
+        .execute(database_connection)
+    } else {
+        diesel::insert_into(detection_disabled_channels)
+            .values((server.eq(server_id), channel.eq(channel_id)))
+            .on_conflict_do_nothing()
+            .execute(database_connection)
+    }
+    .expect("Error updating channel detection");
+}
+
+pub fn is_detection_enabled(
+    server_id: GuildId,
+    channel_id: ChannelId,
+    database_connection: &PgConnection,
+) -> bool {
+    use crate::schema::detection_disabled_channels::dsl::*;
+    let disabled = detection_disabled_channels
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(channel.eq(i64::try_from(*channel_id.as_u64()).unwrap()))
+        .count()
+        .get_result::<i64>(database_connection)
+        .expect("Error fetching channel detection");
+    disabled == 0
+}
+
+/// Counts across every server that has opted in to sharing them, with nothing that could say
+/// which server or person they came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalStats {
+    pub servers: i64,
+    pub haikus: i64,
+    /// The day (UTC) the most haikus were found, and how many
+    pub biggest_day: Option<(NaiveDate, i64)>,
+}
+
+#[derive(QueryableByName)]
+struct DayCount {
+    #[sql_type = "Date"]
+    day: NaiveDate,
+    #[sql_type = "BigInt"]
+    haikus: i64,
+}
+
+pub fn get_global_stats(database_connection: &PgConnection) -> GlobalStats {
+    let servers = {
+        use crate::schema::server_configs::dsl::*;
+        server_configs
+            .select(server)
+            .filter(global_stats_enabled.eq(true))
+            .filter(left_at.is_null())
+            .load::<i64>(database_connection)
+            .expect("Error fetching global stats servers")
+    };
+    let haikus = {
+        use crate::schema::haikus::dsl::*;
+        haikus
+            .filter(server.eq_any(&servers))
+            .count()
+            .get_result::<i64>(database_connection)
+            .expect("Error counting global haikus")
+    };
+    let biggest_day = diesel::sql_query(
+        "SELECT CAST(timestamp AS DATE) AS day, COUNT(*) AS haikus FROM haikus \
+         WHERE server = ANY($1) GROUP BY day ORDER BY haikus DESC, day ASC LIMIT 1",
+    )
+    .bind::<Array<BigInt>, _>(&servers)
+    .get_result::<DayCount>(database_connection)
+    .optional()
+    .expect("Error fetching biggest day")
+    .map(|row| (row.day, row.haikus));
+    GlobalStats {
+        servers: servers.len() as i64,
+        haikus,
+        biggest_day,
+    }
+}
+
+/// The configs of the servers the bot is still in that have a retention window
+pub fn get_retention_servers(database_connection: &PgConnection) -> Vec<ServerConfig> {
+    use crate::schema::server_configs::dsl::*;
+    server_configs
+        .filter(retention_years.is_not_null())
+        .filter(left_at.is_null())
+        .load::<ServerConfigDTO>(database_connection)
+        .expect("Error fetching server configs")
+        .into_iter()
+        .map(|dto| dto.into())
+        .collect()
+}
+
+/// The configs of the servers the bot is still in that have a laureate role
+pub fn get_laureate_servers(database_connection: &PgConnection) -> Vec<ServerConfig> {
+    use crate::schema::server_configs::dsl::*;
+    server_configs
+        .filter(laureate_role.is_not_null())
+        .filter(left_at.is_null())
+        .load::<ServerConfigDTO>(database_connection)
+        .expect("Error fetching server configs")
+        .into_iter()
+        .map(|dto| dto.into())
+        .collect()
+}
+
+pub fn record_milestone(
+    server_id: GuildId,
+    milestone: i64,
+    database_connection: &PgConnection,
+) -> bool {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(
+        server_configs
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(last_milestone.lt(milestone)),
+    )
+    .set(last_milestone.eq(milestone))
+    .execute(database_connection)
+    .expect("Error updating server config")
+        > 0
+}
+
+pub fn mark_server_departed(server_id: GuildId, database_connection: &PgConnection) {
+    use crate::schema::server_configs::dsl::*;
+    diesel::update(server_configs.filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap())))
+        .set(left_at.eq(Some(Utc::now().naive_utc())))
+        .execute(database_connection)
+        .expect("Error updating server config");
+}
+
+pub fn get_servers_departed_before(
+    before: DateTime<Utc>,
+    database_connection: &PgConnection,
+) -> Vec<GuildId> {
+    use crate::schema::server_configs::dsl::*;
+    server_configs
+        .select(server)
+        .filter(left_at.lt(before.naive_utc()))
+        .load::<i64>(database_connection)
+        .expect("Error fetching departed servers")
+        .into_iter()
+        .map(|id| GuildId::from(u64::try_from(id).unwrap()))
+        .collect()
+}
+
+/// How often a command was used, and how long it took to answer on average
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandUsage {
+    pub command: String,
+    pub uses: i64,
+    pub average_latency_ms: i64,
+}
+
+pub fn record_command_usage(
+    server_id: GuildId,
+    command_name: &str,
+    latency_ms: i64,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::command_usage;
+    let usage = NewCommandUsageDTO {
+        server: i64::try_from(*server_id.as_u64()).unwrap(),
+        command: command_name.to_owned(),
+        used_at: Utc::now().naive_utc(),
+        latency_ms,
+    };
+    diesel::insert_into(command_usage::table)
+        .values(&usage)
+        .execute(database_connection)
+        .expect("Error recording command usage");
+}
+
+/// Each command used in a server since a point in time, most used first
+pub fn get_command_usage(
+    server_id: GuildId,
+    since: DateTime<Utc>,
+    database_connection: &PgConnection,
+) -> Vec<CommandUsage> {
+    use crate::schema::command_usage::dsl::*;
+    use diesel::dsl::count_star;
+    command_usage
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(used_at.ge(since.naive_utc()))
+        .group_by(command)
+        .select((
+            command,
+            count_star(),
+            sql::<BigInt>("CAST(AVG(latency_ms) AS BIGINT)"),
+        ))
+        .order((count_star().desc(), command.asc()))
+        .load::<(String, i64, i64)>(database_connection)
+        .expect("Error fetching command usage")
+        .into_iter()
+        .map(|(name, uses, average_latency_ms)| CommandUsage {
+            command: name,
+            uses,
+            average_latency_ms,
+        })
+        .collect()
+}
+
+/// How many commands were used in each hour of the day (UTC) since a point in time, busiest first
+pub fn count_command_usage_by_hour(
+    server_id: GuildId,
+    since: DateTime<Utc>,
+    database_connection: &PgConnection,
+) -> Vec<(u32, i64)> {
+    use crate::schema::command_usage::dsl::*;
+    use diesel::dsl::count_star;
+    let hour = || sql::<BigInt>("CAST(EXTRACT(HOUR FROM used_at) AS BIGINT)");
+    command_usage
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(used_at.ge(since.naive_utc()))
+        .group_by(hour())
+        .select((hour(), count_star()))
+        .order((count_star().desc(), hour().asc()))
+        .load::<(i64, i64)>(database_connection)
+        .expect("Error counting command usage by hour")
+        .into_iter()
+        .map(|(hour_of_day, uses)| (hour_of_day as u32, uses))
+        .collect()
+}
+
+/// How many people have voted for a haiku, or 0 if it doesn't exist
+pub fn count_votes(server_id: GuildId, haiku: i64, database_connection: &PgConnection) -> i64 {
+    use crate::schema::haiku_votes::dsl::*;
+    haiku_votes
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(haiku_id.eq(haiku))
+        .count()
+        .get_result::<i64>(database_connection)
+        .expect("Error counting haiku votes")
+}
+
+pub fn get_user_preferences(
+    user: UserId,
+    database_connection: &PgConnection,
+) -> Option<UserPreferences> {
+    use crate::schema::user_preferences::dsl::*;
+    user_preferences
+        .filter(user_id.eq(i64::try_from(*user.as_u64()).unwrap()))
+        .first::<UserPreferencesDTO>(database_connection)
+        .optional()
+        .expect("Error fetching user preferences")
+        .map(|dto| dto.into())
+}
+
+pub fn set_user_notification(
+    user: UserId,
+    notification: Notification,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::user_preferences::dsl::*;
+    let user = i64::try_from(*user.as_u64()).unwrap();
+    database_connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(user_preferences)
+                .values(user_id.eq(user))
+                .on_conflict_do_nothing()
+                .execute(database_connection)?;
+            let preferences = user_preferences.filter(user_id.eq(user));
+            match notification {
+                Notification::Detected => diesel::update(preferences)
+                    .set(notify_detected.eq(enabled))
+                    .execute(database_connection),
+                Notification::Bookmarked => diesel::update(preferences)
+                    .set(notify_bookmarked.eq(enabled))
+                    .execute(database_connection),
+                Notification::ContestWin => diesel::update(preferences)
+                    .set(notify_contest_win.eq(enabled))
+                    .execute(database_connection),
+                Notification::VoteThreshold => diesel::update(preferences)
+                    .set(notify_vote_threshold.eq(enabled))
+                    .execute(database_connection),
+            }?;
+            Ok(())
+        })
+        .expect("Error updating user preferences");
+}
+
+pub fn set_user_plain_text(
+    user: UserId,
+    enabled: Option<bool>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::user_preferences::dsl::*;
+    let user = i64::try_from(*user.as_u64()).unwrap();
+    diesel::insert_into(user_preferences)
+        .values((user_id.eq(user), plain_text.eq(enabled)))
+        .on_conflict(user_id)
+        .do_update()
+        .set(plain_text.eq(enabled))
+        .execute(database_connection)
+        .expect("Error updating user preferences");
+}
+
+/// Choose whether the haikus someone wrote on their own in a server are public
+pub fn set_user_public_feed(
+    server_id: GuildId,
+    user: UserId,
+    enabled: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::public_feeds::dsl::*;
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let user = i64::try_from(*user.as_u64()).unwrap();
+    if enabled {
+        diesel::insert_into(public_feeds)
+            .values((server.eq(server_id), user_id.eq(user)))
+            .on_conflict_do_nothing()
+            .execute(database_connection)
+    } else {
+        diesel::delete(
+            public_feeds
+                .filter(server.eq(server_id))
+                .filter(user_id.eq(user)),
+        )
+        .execute(database_connection)
+    }
+    .expect("Error updating public feed");
+}
+
+pub fn has_public_feed(
+    server_id: GuildId,
+    user: UserId,
+    database_connection: &PgConnection,
+) -> bool {
+    use crate::schema::public_feeds::dsl::*;
+    let found = public_feeds
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .filter(user_id.eq(i64::try_from(*user.as_u64()).unwrap()))
+        .count()
+        .get_result::<i64>(database_connection)
+        .expect("Error fetching public feed");
+    found > 0
+}
+
+/// Count someone's answer to a /quiz question
+pub fn record_quiz_answer(
+    server_id: GuildId,
+    user: UserId,
+    was_correct: bool,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::quiz_scores::dsl::*;
+    let new_score = NewQuizScoreDTO {
+        server: i64::try_from(*server_id.as_u64()).unwrap(),
+        user_id: i64::try_from(*user.as_u64()).unwrap(),
+        correct: was_correct as i64,
+        answered: 1,
+    };
+    diesel::insert_into(quiz_scores)
+        .values(&new_score)
+        .on_conflict((server, user_id))
+        .do_update()
+        .set((
+            correct.eq(correct + was_correct as i64),
+            answered.eq(answered + 1),
+        ))
+        .execute(database_connection)
+        .expect("Error recording quiz answer");
+}
+
+/// The people with the most correct /quiz answers in a server, best first
+pub fn get_quiz_leaderboard(
+    server_id: GuildId,
+    limit: i64,
+    database_connection: &PgConnection,
+) -> Vec<QuizScore> {
+    use crate::schema::quiz_scores::dsl::*;
+    quiz_scores
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .order((correct.desc(), answered.asc(), user_id.asc()))
+        .limit(limit)
+        .load::<QuizScoreDTO>(database_connection)
+        .expect("Error fetching quiz leaderboard")
+        .into_iter()
+        .map(|dto| dto.into())
+        .collect()
+}
+
+/// Set how many syllables a word has in a server, or go back to counting it normally
+pub fn set_word_override(
+    server_id: GuildId,
+    override_word: &str,
+    override_syllables: Option<usize>,
+    database_connection: &PgConnection,
+) {
+    use crate::schema::word_overrides::dsl::*;
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    let override_word = override_word.to_lowercase();
+    match override_syllables {
+        Some(override_syllables) => diesel::insert_into(word_overrides)
+            .values((
+                server.eq(server_id),
+                word.eq(&override_word),
+                syllables.eq(override_syllables as i64),
+            ))
+            .on_conflict((server, word))
+            .do_update()
+            .set(syllables.eq(override_syllables as i64))
+            .execute(database_connection),
+        None => diesel::delete(
+            word_overrides
+                .filter(server.eq(server_id))
+                .filter(word.eq(&override_word)),
+        )
+        .execute(database_connection),
+    }
+    .expect("Error updating word override");
+}
+
+pub fn get_word_overrides(server_id: GuildId, database_connection: &PgConnection) -> WordOverrides {
+    use crate::schema::word_overrides::dsl::*;
+    word_overrides
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .select((word, syllables))
+        .load::<(String, i64)>(database_connection)
+        .expect("Error fetching word overrides")
+        .into_iter()
+        .map(|(override_word, override_syllables)| (override_word, override_syllables as usize))
+        .collect()
+}
+
+pub fn get_job_last_run(job: &str, database_connection: &PgConnection) -> Option<DateTime<Utc>> {
+    use crate::schema::scheduled_jobs::dsl::*;
+    scheduled_jobs
+        .filter(name.eq(job))
+        .select(last_run_at)
+        .first::<NaiveDateTime>(database_connection)
+        .optional()
+        .expect("Error fetching scheduled job")
+        .map(|last_run| DateTime::from_utc(last_run, Utc))
+}
+
+pub fn record_job_run(job: &str, finished_at: DateTime<Utc>, database_connection: &PgConnection) {
+    use crate::schema::scheduled_jobs::dsl::*;
+    diesel::insert_into(scheduled_jobs)
+        .values((name.eq(job), last_run_at.eq(finished_at.naive_utc())))
+        .on_conflict(name)
+        .do_update()
+        .set(last_run_at.eq(finished_at.naive_utc()))
+        .execute(database_connection)
+        .expect("Error recording scheduled job");
+}
+
+pub fn get_history_scan(
+    channel_id: ChannelId,
+    database_connection: &PgConnection,
+) -> Option<HistoryScan> {
+    use crate::schema::history_scans::dsl::*;
+    history_scans
+        .filter(channel.eq(i64::try_from(*channel_id.as_u64()).unwrap()))
+        .first::<HistoryScanDTO>(database_connection)
+        .optional()
+        .expect("Error fetching history scan")
+        .map(|dto| dto.into())
+}
+
+pub fn save_history_scan(scan: &HistoryScan, database_connection: &PgConnection) {
+    use crate::schema::history_scans::dsl::*;
+    let scan = NewHistoryScanDTO::from(scan);
+    diesel::insert_into(history_scans)
+        .values(&scan)
+        .on_conflict(channel)
+        .do_update()
+        .set(&scan)
+        .execute(database_connection)
+        .expect("Error saving history scan");
+}
+
+pub fn delete_history_scan(channel_id: ChannelId, database_connection: &PgConnection) {
+    use crate::schema::history_scans::dsl::*;
+    diesel::delete(history_scans.filter(channel.eq(i64::try_from(*channel_id.as_u64()).unwrap())))
+        .execute(database_connection)
+        .expect("Error deleting history scan");
+}
+
+pub fn create_api_token(
+    token: &ApiToken,
+    token_hash: &str,
+    database_connection: &PgConnection,
+) -> i64 {
+    use crate::schema::api_tokens::dsl::*;
+    diesel::insert_into(api_tokens)
+        .values(&NewApiTokenDTO::new(token, token_hash))
+        .returning(id)
+        .get_result(database_connection)
+        .expect("Error saving API token")
+}
+
+/// The token with a hash, from whichever server it was made in
+pub fn get_api_token(
+    token_hash_value: &str,
+    database_connection: &PgConnection,
+) -> Option<(i64, ApiToken)> {
+    use crate::schema::api_tokens::dsl::*;
+    api_tokens
+        .filter(token_hash.eq(token_hash_value))
+        .first::<ApiTokenDTO>(database_connection)
+        .optional()
+        .expect("Error fetching API token")
+        .map(|dto| dto.into())
+}
+
+pub fn get_api_tokens(
+    server_id: GuildId,
+    database_connection: &PgConnection,
+) -> Vec<(i64, ApiToken)> {
+    use crate::schema::api_tokens::dsl::*;
+    api_tokens
+        .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+        .order(id.asc())
+        .load::<ApiTokenDTO>(database_connection)
+        .expect("Error fetching API tokens")
+        .into_iter()
+        .map(|dto| dto.into())
+        .collect()
+}
+
+pub fn delete_api_token(
+    server_id: GuildId,
+    token_id: i64,
+    database_connection: &PgConnection,
+) -> bool {
+    use crate::schema::api_tokens::dsl::*;
+    let deleted = diesel::delete(
+        api_tokens
+            .filter(server.eq(i64::try_from(*server_id.as_u64()).unwrap()))
+            .filter(id.eq(token_id)),
+    )
+    .execute(database_connection)
+    .expect("Error deleting API token");
+    deleted > 0
+}
+
+/// Delete everything stored for a server
+pub fn purge_server_data(server_id: GuildId, database_connection: &PgConnection) {
+    use crate::schema::{
+        api_tokens, archived_haikus, command_usage, detection_disabled_channels, haiku_numbers,
+        haikus, history_scans, public_feeds, quiz_scores, server_configs, word_overrides,
+    };
+    let server_id = i64::try_from(*server_id.as_u64()).unwrap();
+    database_connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(haikus::table.filter(haikus::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(archived_haikus::table.filter(archived_haikus::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(server_configs::table.filter(server_configs::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(command_usage::table.filter(command_usage::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(quiz_scores::table.filter(quiz_scores::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(
+                detection_disabled_channels::table
+                    .filter(detection_disabled_channels::server.eq(server_id)),
+            )
+            .execute(database_connection)?;
+            diesel::delete(word_overrides::table.filter(word_overrides::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(history_scans::table.filter(history_scans::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(haiku_numbers::table.filter(haiku_numbers::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(api_tokens::table.filter(api_tokens::server.eq(server_id)))
+                .execute(database_connection)?;
+            diesel::delete(public_feeds::table.filter(public_feeds::server.eq(server_id)))
+                .execute(database_connection)?;
+            Ok(())
+        })
+        .expect("Error purging server data");
+}
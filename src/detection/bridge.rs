@@ -0,0 +1,90 @@
+//! Crediting the real author of messages relayed by a bridge bot, which posts everyone's messages
+//! as itself, formatted like `<nick> message`
+
+use regex::{Regex, RegexBuilder};
+
+/// Longer patterns are almost certainly a mistake
+pub const MAX_PATTERN_LENGTH: usize = 200;
+/// Keeps a pattern from compiling into something huge
+const MAX_COMPILED_SIZE: usize = 1 << 16;
+/// The capture group holding the author's name
+pub const NICK_GROUP: &str = "nick";
+/// The capture group holding what they said. Without it, everything after the match is used.
+pub const MESSAGE_GROUP: &str = "message";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BridgePatternError {
+    Invalid(String),
+    MissingNick,
+    TooLong,
+}
+
+/// Compile a server's bridge pattern, checking it can pick out an author
+pub fn parse_bridge_pattern(pattern: &str) -> Result<Regex, BridgePatternError> {
+    if pattern.chars().count() > MAX_PATTERN_LENGTH {
+        return Err(BridgePatternError::TooLong);
+    }
+    let regex = RegexBuilder::new(pattern)
+        .size_limit(MAX_COMPILED_SIZE)
+        .build()
+        .map_err(|why| BridgePatternError::Invalid(why.to_string()))?;
+    if !regex.capture_names().any(|name| name == Some(NICK_GROUP)) {
+        return Err(BridgePatternError::MissingNick);
+    }
+    Ok(regex)
+}
+
+/// The author's name and what they said, if the message was relayed in the bridge's format
+pub fn parse_bridged(pattern: &Regex, content: &str) -> Option<(String, String)> {
+    let captures = pattern.captures(content)?;
+    let nick = captures.name(NICK_GROUP)?.as_str().trim();
+    let message = match captures.name(MESSAGE_GROUP) {
+        Some(message) => message.as_str(),
+        None => &content[captures.get(0)?.end()..],
+    };
+    if nick.is_empty() {
+        return None;
+    }
+    Some((nick.to_owned(), message.trim().to_owned()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_bridge_pattern, parse_bridged, BridgePatternError};
+
+    #[test]
+    fn test_parse_bridge_pattern() {
+        assert!(parse_bridge_pattern(r"^<(?P<nick>[^>]+)> ").is_ok());
+        assert!(parse_bridge_pattern(r"^\[(?P<nick>\w+)\] (?P<message>.*)$").is_ok());
+        assert_eq!(
+            parse_bridge_pattern(r"^<([^>]+)> ").unwrap_err(),
+            BridgePatternError::MissingNick
+        );
+        assert!(matches!(
+            parse_bridge_pattern(r"^<(?P<nick>[^>]+> "),
+            Err(BridgePatternError::Invalid(_))
+        ));
+        assert_eq!(
+            parse_bridge_pattern(&format!("(?P<nick>{})", "a".repeat(200))).unwrap_err(),
+            BridgePatternError::TooLong
+        );
+    }
+
+    #[test]
+    fn test_parse_bridged() {
+        let pattern = parse_bridge_pattern(r"^<(?P<nick>[^>]+)> ").unwrap();
+        assert_eq!(
+            parse_bridged(&pattern, "<basho> An old silent pond"),
+            Some(("basho".to_owned(), "An old silent pond".to_owned()))
+        );
+        assert_eq!(parse_bridged(&pattern, "basho: An old silent pond"), None);
+
+        let pattern = parse_bridge_pattern(r"^\[(?P<nick>[^\]]*)\] (?P<message>.*)$").unwrap();
+        assert_eq!(
+            parse_bridged(&pattern, "[buson] Explode into birds"),
+            Some(("buson".to_owned(), "Explode into birds".to_owned()))
+        );
+        // Nobody to credit
+        assert_eq!(parse_bridged(&pattern, "[] Explode into birds"), None);
+    }
+}
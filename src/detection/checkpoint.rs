@@ -0,0 +1,141 @@
+//! Saving the detector's recent lines now and then, so that restarting partway through a haiku
+//! doesn't forget the lines already sent
+
+use super::{ChannelLines, Detector};
+use crate::models::HaikuLine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, MessageId, UserId};
+
+#[derive(Serialize, Deserialize)]
+struct LineCheckpoint {
+    author: u64,
+    author_name: Option<String>,
+    content: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChannelCheckpoint {
+    channel: u64,
+    /// Oldest first, like [super::RecentLines]
+    lines: Vec<Option<LineCheckpoint>>,
+    messages: Vec<Option<u64>>,
+    last_sent_at: Option<String>,
+}
+
+impl ChannelCheckpoint {
+    fn new(channel: ChannelId, recent: &ChannelLines) -> Self {
+        ChannelCheckpoint {
+            channel: channel.0,
+            lines: recent
+                .lines
+                .iter()
+                .map(|line| {
+                    line.as_ref().map(|line| LineCheckpoint {
+                        author: line.author.0,
+                        author_name: line.author_name.clone(),
+                        content: line.content.clone(),
+                    })
+                })
+                .collect(),
+            messages: recent.messages.iter().map(|id| id.map(|id| id.0)).collect(),
+            last_sent_at: recent.last_sent_at.map(|sent_at| sent_at.to_rfc3339()),
+        }
+    }
+
+    fn restore(self) -> Option<(ChannelId, ChannelLines)> {
+        let mut recent = ChannelLines::default();
+        if self.lines.len() != recent.lines.len() || self.messages.len() != recent.messages.len() {
+            return None;
+        }
+        for (restored, line) in recent.lines.iter_mut().zip(self.lines) {
+            *restored = line.map(|line| HaikuLine {
+                author: UserId(line.author),
+                author_name: line.author_name,
+                content: line.content,
+            });
+        }
+        for (restored, id) in recent.messages.iter_mut().zip(self.messages) {
+            *restored = id.map(MessageId);
+        }
+        recent.last_sent_at = match self.last_sent_at {
+            Some(sent_at) => Some(
+                DateTime::parse_from_rfc3339(&sent_at)
+                    .ok()?
+                    .with_timezone(&Utc),
+            ),
+            None => None,
+        };
+        Some((ChannelId(self.channel), recent))
+    }
+}
+
+impl Detector {
+    /// Every channel's recent lines, as JSON to be handed back to [Detector::restore]
+    pub fn checkpoint(&self) -> String {
+        let channels = self
+            .channels
+            .iter()
+            .map(|(channel, recent)| ChannelCheckpoint::new(*channel, recent))
+            .collect::<Vec<ChannelCheckpoint>>();
+        serde_json::to_string(&channels).expect("Unable to serialize recent lines")
+    }
+
+    /// Pick up where a checkpoint left off, keeping lines seen since for channels that have
+    /// had messages already. Channels that can't be read are skipped.
+    pub fn restore(&mut self, checkpoint: &str) -> serde_json::Result<()> {
+        let channels = serde_json::from_str::<Vec<ChannelCheckpoint>>(checkpoint)?;
+        for (channel, recent) in channels.into_iter().filter_map(ChannelCheckpoint::restore) {
+            if !self.channels.contains_key(&channel) {
+                self.make_room();
+                self.channels.insert(channel, recent);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        counting::WordOverrides,
+        detection::{pipeline::IncomingMessage, pipeline::Sender, Detector},
+    };
+    use chrono::Utc;
+    use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+    fn message(id: u64, content: &str) -> IncomingMessage {
+        IncomingMessage {
+            id: MessageId(id),
+            server: GuildId(1),
+            channel: ChannelId(10),
+            author: UserId(100),
+            author_name: None,
+            sender: Sender::Person,
+            content: content.to_owned(),
+            sent_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_restore_checkpoint() {
+        let overrides = WordOverrides::new();
+        let mut detector = Detector::new();
+        detector.find_haikus(&message(1, "An old silent pond"), &overrides);
+        detector.find_haikus(&message(2, "A frog jumps into the pond"), &overrides);
+
+        let mut restarted = Detector::new();
+        restarted
+            .restore(&detector.checkpoint())
+            .expect("Invalid checkpoint");
+        let haikus = restarted.find_haikus(&message(3, "Splash! Silence again"), &overrides);
+        assert_eq!(haikus.len(), 1);
+        assert_eq!(haikus[0].lines[0].content, "An old silent pond");
+        assert_eq!(
+            haikus[0].source.map(|source| source.messages),
+            Some([MessageId(1), MessageId(2), MessageId(3)])
+        );
+
+        assert!(Detector::new().restore("not json").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Step-by-step explanations of what detection made of a few messages, for /recheck verbose:true
+
+use super::pipeline::{DetectionPipeline, IncomingMessage};
+use crate::{
+    counting::{diagnose_line_with, is_haiku_with, NotHaiku, Uncountable, WordOverrides},
+    i18n::Locale,
+    models::Haiku,
+    repository::HaikuRepository,
+};
+
+/// Explain what each pre-filter did to a message and the (up to two) messages before it, oldest
+/// first, how each of their words was counted with the server's overrides, and why `found` does
+/// or doesn't have a haiku in it
+pub fn explain(
+    pipeline: &DetectionPipeline,
+    repository: &dyn HaikuRepository,
+    messages: &[IncomingMessage],
+    found: &[Haiku],
+    locale: Locale,
+) -> String {
+    let overrides = match messages.last() {
+        Some(message) => repository.get_word_overrides(message.server),
+        None => WordOverrides::new(),
+    };
+    let mut sections = Vec::new();
+    let mut lines = Vec::new();
+    let mut requested_kept = false;
+    for (index, message) in messages.iter().enumerate() {
+        let (prepared, steps) = pipeline.trace(repository, message.clone());
+        let mut section = vec![t!(
+            locale,
+            "explain.message",
+            number = index + 1,
+            total = messages.len()
+        )];
+        for step in steps {
+            section.push(match (step.kept, step.rewritten) {
+                (false, _) => t!(locale, "explain.skipped", filter = step.filter),
+                (true, Some(content)) => {
+                    t!(
+                        locale,
+                        "explain.rewritten",
+                        filter = step.filter,
+                        content = content
+                    )
+                }
+                (true, None) => t!(locale, "explain.unchanged", filter = step.filter),
+            });
+        }
+        if let Some(prepared) = prepared {
+            for line in prepared.content.lines() {
+                section.push(format_words(line, &overrides));
+                lines.push(line.to_owned());
+            }
+            requested_kept = index + 1 == messages.len();
+        }
+        sections.push(section.join("\n"));
+    }
+    sections.push(verdict(&lines, requested_kept, found, &overrides, locale));
+    sections.join("\n\n")
+}
+
+/// A line's words with the syllables counted for each, and the total
+fn format_words(line: &str, overrides: &WordOverrides) -> String {
+    let words = diagnose_line_with(line, overrides);
+    let counts = words
+        .iter()
+        .map(|word| match &word.syllables {
+            Ok(syllables) => format!("{} {}", word.word, syllables),
+            Err(_) => format!("{} ?", word.word),
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    let total = words
+        .iter()
+        .map(|word| word.syllables.clone().ok())
+        .sum::<Option<usize>>()
+        .map_or("?".to_owned(), |total| total.to_string());
+    format!("`{}`: {} = {}", line, counts, total)
+}
+
+fn verdict(
+    lines: &[String],
+    requested_kept: bool,
+    found: &[Haiku],
+    overrides: &WordOverrides,
+    locale: Locale,
+) -> String {
+    if !found.is_empty() {
+        return t!(locale, "explain.found", count = found.len());
+    }
+    if !requested_kept {
+        return t!(locale, "explain.requested_skipped");
+    }
+    match is_haiku_with(&lines[lines.len().saturating_sub(3)..], overrides) {
+        // The lines fit, so the detector must have forgotten the earlier ones
+        Ok(_) => t!(locale, "explain.too_far_apart"),
+        Err(NotHaiku::WrongLineCount(count)) => t!(locale, "explain.too_few_lines", count = count),
+        Err(NotHaiku::WrongSyllables {
+            line,
+            expected,
+            found,
+        }) => t!(
+            locale,
+            "explain.wrong_syllables",
+            line = line + 1,
+            expected = expected,
+            found = found
+        ),
+        Err(NotHaiku::Uncountable(Uncountable::NotAWord(word))) => {
+            t!(locale, "explain.not_a_word", word = word)
+        }
+        Err(NotHaiku::Uncountable(Uncountable::UnknownWord(word))) => {
+            t!(locale, "explain.unknown_word", word = word)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::explain;
+    use crate::{
+        detection::pipeline::{DetectionPipeline, IncomingMessage, Sender, StripMarkdown},
+        i18n::Locale,
+        repository::MemoryRepository,
+    };
+    use chrono::Utc;
+    use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+    fn messages(lines: [&str; 3]) -> Vec<IncomingMessage> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(index, content)| IncomingMessage {
+                id: MessageId(index as u64 + 1),
+                server: GuildId(1),
+                channel: ChannelId(10),
+                author: UserId(100),
+                author_name: None,
+                sender: Sender::Person,
+                content: content.to_string(),
+                sent_at: Utc::now(),
+            })
+            .collect()
+    }
+
+    fn explain_messages(lines: [&str; 3]) -> String {
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new().filter(StripMarkdown);
+        let mut messages = messages(lines);
+        let message = messages.pop().unwrap();
+        let found = pipeline.find_requested(&repository, messages.clone(), message.clone());
+        messages.push(message);
+        explain(&pipeline, &repository, &messages, &found, Locale::English)
+    }
+
+    #[test]
+    fn test_explain_haiku() {
+        let explanation = explain_messages([
+            "An old silent pond",
+            "A frog jumps into the **pond**",
+            "Splash! Silence again",
+        ]);
+        assert!(explanation.contains("StripMarkdown"));
+        assert!(explanation.contains("A frog jumps into the pond"));
+        assert!(explanation.contains("`An old silent pond`: An 1, old 1, silent 2, pond 1 = 5"));
+        assert!(explanation.ends_with(&t!(Locale::English, "explain.found", count = 1)));
+    }
+
+    #[test]
+    fn test_explain_not_haiku() {
+        let explanation = explain_messages([
+            "An old silent pond",
+            "A frog jumps into the pond",
+            "Splash! Silence",
+        ]);
+        assert!(explanation.ends_with(&t!(
+            Locale::English,
+            "explain.wrong_syllables",
+            line = 3,
+            expected = 5,
+            found = 3
+        )));
+
+        let explanation = explain_messages([
+            "An old silent pond",
+            "A frog jumps into the pond",
+            "Splash! xyzzy",
+        ]);
+        assert!(explanation.contains("xyzzy ? = ?"));
+        assert!(explanation.ends_with(&t!(
+            Locale::English,
+            "explain.unknown_word",
+            word = "xyzzy"
+        )));
+    }
+}
@@ -0,0 +1,236 @@
+pub mod bridge;
+pub mod checkpoint;
+pub mod explain;
+pub mod noise;
+pub mod pipeline;
+
+use crate::{
+    counting::{is_haiku_with, split_into_haiku_with, WordOverrides},
+    models::{Haiku, HaikuLine, HaikuSource},
+    repository::HaikuRepository,
+};
+use chrono::{DateTime, Duration, Utc};
+use pipeline::IncomingMessage;
+use serenity::model::id::{ChannelId, MessageId, UserId};
+use std::collections::HashMap;
+
+/// How many channels' recent lines are kept, after which the channel that's been quiet longest
+/// is forgotten
+const MAX_CHANNELS: usize = 10_000;
+
+/// The last three lines sent in a channel, oldest first
+pub type RecentLines = [Option<HaikuLine>; 3];
+
+/// Add a line to a channel's recent lines, then check whether it completes a haiku, either on
+/// its own or as the last of the three recent lines
+pub fn on_line(
+    recent: &mut RecentLines,
+    line: HaikuLine,
+    overrides: &WordOverrides,
+) -> Option<[HaikuLine; 3]> {
+    recent[0] = recent[1].take();
+    recent[1] = recent[2].take();
+    recent[2] = Some(line.clone());
+    if let Ok(lines) = split_into_haiku_with(&line.content, overrides) {
+        let author = line.author;
+        let [line_1, line_2, line_3] = lines;
+        return Some([
+            HaikuLine {
+                author,
+                author_name: line.author_name.clone(),
+                content: line_1.text(),
+            },
+            HaikuLine {
+                author,
+                author_name: line.author_name.clone(),
+                content: line_2.text(),
+            },
+            HaikuLine {
+                author,
+                author_name: line.author_name.clone(),
+                content: line_3.text(),
+            },
+        ]);
+    }
+    match recent {
+        [Some(line_1), Some(line_2), Some(line_3)] => {
+            let line_contents = [
+                line_1.content.clone(),
+                line_2.content.clone(),
+                line_3.content.clone(),
+            ];
+            if is_haiku_with(&line_contents, overrides).is_ok() {
+                Some([line_1.clone(), line_2.clone(), line_3.clone()])
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A channel's recent lines and the messages they came from
+#[derive(Default)]
+struct ChannelLines {
+    lines: RecentLines,
+    messages: [Option<MessageId>; 3],
+    last_sent_at: Option<DateTime<Utc>>,
+}
+
+/// Tracks the recent lines of every channel, saving the haikus they form
+#[derive(Default)]
+pub struct Detector {
+    channels: HashMap<ChannelId, ChannelLines>,
+    /// Lines sent further apart than this are from different conversations, so never share a haiku
+    max_gap: Option<Duration>,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Detector::default()
+    }
+
+    /// Forget a channel's recent lines when its next message comes more than `max_gap` later
+    pub fn max_gap(mut self, max_gap: Option<Duration>) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Check each line of a message for haikus, saving any that are found along with their ids.
+    /// Haikus that were already saved from the same message, e.g. because it was received twice,
+    /// are left out.
+    pub fn on_message(
+        &mut self,
+        repository: &dyn HaikuRepository,
+        message: &IncomingMessage,
+    ) -> Vec<(i64, Haiku)> {
+        let overrides = repository.get_word_overrides(message.server);
+        self.find_haikus(message, &overrides)
+            .into_iter()
+            .filter_map(|haiku| Some((repository.save_haiku(&haiku)?, haiku)))
+            .collect()
+    }
+
+    /// Check each line of a message for haikus without saving them, counting words with the
+    /// server's overrides
+    pub fn find_haikus(
+        &mut self,
+        message: &IncomingMessage,
+        overrides: &WordOverrides,
+    ) -> Vec<Haiku> {
+        if !self.channels.contains_key(&message.channel) {
+            self.make_room();
+        }
+        let recent = self.channels.entry(message.channel).or_default();
+        if let (Some(max_gap), Some(last_sent_at)) = (self.max_gap, recent.last_sent_at) {
+            if message.sent_at.signed_duration_since(last_sent_at) > max_gap {
+                *recent = ChannelLines::default();
+            }
+        }
+        recent.last_sent_at = Some(message.sent_at);
+        let (server, channel, author, id) =
+            (message.server, message.channel, message.author, message.id);
+        message
+            .content
+            .lines()
+            .enumerate()
+            .filter_map(|(line_number, content)| {
+                recent.messages = [recent.messages[1], recent.messages[2], Some(id)];
+                let lines = on_line(
+                    &mut recent.lines,
+                    HaikuLine {
+                        author,
+                        author_name: message.author_name.clone(),
+                        content: content.to_owned(),
+                    },
+                    overrides,
+                )?;
+                // A haiku split out of a single line ends with part of it rather than all of it
+                let messages = if lines[2].content != content {
+                    [id, id, id]
+                } else {
+                    match recent.messages {
+                        [Some(message_0), Some(message_1), Some(message_2)] => {
+                            [message_0, message_1, message_2]
+                        }
+                        _ => [id, id, id],
+                    }
+                };
+                Some(Haiku {
+                    lines,
+                    timestamp: Utc::now(),
+                    channel,
+                    server,
+                    source: Some(HaikuSource {
+                        messages,
+                        line: line_number,
+                    }),
+                })
+            })
+            .collect()
+    }
+
+    /// The newest message seen in each channel whose recent lines are kept
+    pub fn last_seen(&self) -> Vec<(ChannelId, MessageId)> {
+        self.channels
+            .iter()
+            .filter_map(|(channel, recent)| Some((*channel, recent.messages[2]?)))
+            .collect()
+    }
+
+    /// Forget the quietest channel if there are already as many as can be kept
+    fn make_room(&mut self) {
+        if self.channels.len() < MAX_CHANNELS {
+            return;
+        }
+        let quietest = self
+            .channels
+            .iter()
+            .min_by_key(|(_, recent)| recent.last_sent_at)
+            .map(|(channel, _)| *channel);
+        if let Some(channel) = quietest {
+            self.channels.remove(&channel);
+        }
+    }
+}
+
+/// Find which saved haiku a message was part of, picking the haiku saved closest to when the
+/// message was sent if its lines have been used in more than one
+pub fn find_haiku_for_message(
+    haikus: &[(i64, Haiku)],
+    channel: ChannelId,
+    author: UserId,
+    content: &str,
+    sent_at: DateTime<Utc>,
+) -> Option<i64> {
+    haikus
+        .iter()
+        .filter(|(_, haiku)| haiku.channel == channel)
+        .filter(|(_, haiku)| {
+            haiku
+                .lines
+                .iter()
+                .any(|line| line.author == author && contains_line(content, &line.content))
+        })
+        .min_by_key(|(_, haiku)| {
+            haiku
+                .timestamp
+                .signed_duration_since(sent_at)
+                .num_milliseconds()
+                .abs()
+        })
+        .map(|(id, _)| *id)
+}
+
+/// Whether one of the message's lines contains the haiku line's words, in order
+fn contains_line(content: &str, line: &str) -> bool {
+    let line = line.split_whitespace().collect::<Vec<&str>>();
+    !line.is_empty()
+        && content.lines().any(|content| {
+            content
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .windows(line.len())
+                .any(|words| words == line.as_slice())
+        })
+}
@@ -0,0 +1,155 @@
+//! Lines that are just noise, like laughter, held keys or emoji, which shouldn't end up in haikus
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Shorter runs of the same couple of characters can be real words, e.g. "noon"
+const MIN_REPEATED_LENGTH: usize = 5;
+
+/// Which kinds of line are left out of haikus. Blank lines are never noise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoiseRules {
+    /// Lines with fewer words than this are noise
+    pub min_words: usize,
+    /// e.g. "ahahahaha", "hehe lol" or "xD"
+    pub laughter: bool,
+    /// e.g. "aaaaaaa" or "!!!!!!"
+    pub repeated_characters: bool,
+    /// Lines with nothing but emoji and punctuation
+    pub emoji_only: bool,
+}
+
+impl Default for NoiseRules {
+    fn default() -> Self {
+        NoiseRules {
+            min_words: 1,
+            laughter: true,
+            repeated_characters: true,
+            emoji_only: true,
+        }
+    }
+}
+
+impl NoiseRules {
+    pub fn is_noise(&self, line: &str) -> bool {
+        if line.trim().is_empty() {
+            return false;
+        }
+        let line = remove_custom_emoji(line);
+        (self.emoji_only && is_emoji_only(&line))
+            || (self.laughter && is_laughter(&line))
+            || (self.repeated_characters && is_repeated_characters(&line))
+            || line.split_whitespace().count() < self.min_words
+    }
+}
+
+/// Remove Discord's custom emoji, written like <:name:id>, whose names could otherwise be counted
+/// as words
+fn remove_custom_emoji(line: &str) -> String {
+    lazy_static! {
+        static ref CUSTOM_EMOJI_REGEX: Regex = Regex::new(r"<a?:\w+:\d+>").unwrap();
+    }
+    CUSTOM_EMOJI_REGEX.replace_all(line, " ").into_owned()
+}
+
+/// Whether a line has no letters or numbers in it
+pub fn is_emoji_only(line: &str) -> bool {
+    !line.chars().any(char::is_alphanumeric)
+}
+
+/// Whether every word in a line is laughing
+pub fn is_laughter(line: &str) -> bool {
+    lazy_static! {
+        static ref LAUGHTER_REGEX: Regex = Regex::new(
+            r"^(?:a?(?:h+[aeiou]+){2,}h*|l+o+l+[lo]*|lmf?a+o+|rofl|x+d+|(?:j[ae]){2,}j?|k{3,})$"
+        )
+        .unwrap();
+    }
+    let mut words = line
+        .split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .peekable();
+    words.peek().is_some() && words.all(|word| LAUGHTER_REGEX.is_match(&word))
+}
+
+/// Whether a line is one or two characters held down or repeated, ignoring case and spaces
+pub fn is_repeated_characters(line: &str) -> bool {
+    let mut characters = line
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<char>>();
+    if characters.len() < MIN_REPEATED_LENGTH {
+        return false;
+    }
+    characters.sort_unstable();
+    characters.dedup();
+    characters.len() <= 2
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_emoji_only, is_laughter, is_repeated_characters, NoiseRules};
+
+    #[test]
+    fn test_laughter() {
+        for line in &[
+            "ahahahaha",
+            "HAHAHA!!",
+            "hehe lol",
+            "lmaooo",
+            "xD",
+            "jajaja",
+            "kkkkk",
+        ] {
+            assert!(is_laughter(line), "{}", line);
+        }
+        for line in &["hi", "ha", "oh haha that's good", "hello", "lollipop", ""] {
+            assert!(!is_laughter(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_repeated_characters() {
+        for line in &["aaaaaaa", "!!!!!!", "AAAAaaaa", "noooooo", "zz zz zz"] {
+            assert!(is_repeated_characters(line), "{}", line);
+        }
+        for line in &["noon", "aaah!", "An old silent pond", "sooooo good"] {
+            assert!(!is_repeated_characters(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_emoji_only() {
+        assert!(is_emoji_only("😂😂😂"));
+        assert!(is_emoji_only("🐸 ... 💦"));
+        assert!(!is_emoji_only("🐸 jumps"));
+        assert!(!is_emoji_only("2 frogs"));
+    }
+
+    #[test]
+    fn test_noise_rules() {
+        let rules = NoiseRules::default();
+        assert!(rules.is_noise("<:pepe_laugh:123456> <a:frog_dance:789>"));
+        assert!(rules.is_noise("hahaha"));
+        assert!(!rules.is_noise("An old silent pond"));
+        assert!(!rules.is_noise(""));
+        assert!(!rules.is_noise("Splash!"));
+
+        let rules = NoiseRules {
+            min_words: 2,
+            laughter: false,
+            repeated_characters: false,
+            emoji_only: false,
+        };
+        assert!(rules.is_noise("Splash!"));
+        assert!(rules.is_noise("<:frog:123> Splash!"));
+        assert!(!rules.is_noise("hahaha hahaha"));
+        assert!(!rules.is_noise("😂 😂"));
+        assert!(!rules.is_noise("   "));
+    }
+}
@@ -0,0 +1,1049 @@
+//! The steps a message goes through to become saved haikus: pre-filters decide whether a message
+//! is checked at all (and can tidy its content), the detector finds and saves haikus, and
+//! post-save hooks act on each haiku that was saved
+
+use super::{
+    bridge::{parse_bridge_pattern, parse_bridged},
+    noise::NoiseRules,
+    Detector,
+};
+use crate::{counting::confidence, models::Haiku, repository::HaikuRepository};
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, MessageId, UserId},
+    },
+};
+use std::sync::Mutex;
+
+/// What kind of account sent a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sender {
+    Person,
+    Bot,
+    /// e.g. a bridge relaying messages from IRC or Matrix
+    Webhook,
+}
+
+/// A message as the pipeline sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingMessage {
+    pub id: MessageId,
+    pub server: GuildId,
+    pub channel: ChannelId,
+    pub author: UserId,
+    /// The name a webhook posted the message under, since its author isn't a member to look up
+    pub author_name: Option<String>,
+    pub sender: Sender,
+    pub content: String,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl IncomingMessage {
+    /// Only messages sent in servers can contain haikus
+    pub fn from_message(message: &Message) -> Option<Self> {
+        Some(IncomingMessage::in_server(message, message.guild_id?))
+    }
+
+    /// Messages fetched over HTTP don't say which server they were sent in, so it has to be given
+    pub fn in_server(message: &Message, server: GuildId) -> Self {
+        let sender = if message.webhook_id.is_some() {
+            Sender::Webhook
+        } else if message.author.bot {
+            Sender::Bot
+        } else {
+            Sender::Person
+        };
+        IncomingMessage {
+            id: message.id,
+            server,
+            channel: message.channel_id,
+            author: message.author.id,
+            author_name: message.webhook_id.map(|_| message.author.name.clone()),
+            sender,
+            content: message.content.clone(),
+            sent_at: message.timestamp.with_timezone(&Utc),
+        }
+    }
+}
+
+/// The (up to two) messages sent in a channel before a message, oldest first, which may hold the
+/// first lines of a haiku ending in it
+pub async fn fetch_earlier(
+    ctx: &Context,
+    server: GuildId,
+    channel: ChannelId,
+    message: MessageId,
+) -> Vec<IncomingMessage> {
+    // Discord sends the newest first
+    channel
+        .messages(&ctx.http, |retriever| retriever.before(message).limit(2))
+        .await
+        .unwrap_or_else(|why| {
+            println!("Failed to fetch messages before {}: {:?}", message, why);
+            Vec::new()
+        })
+        .iter()
+        .rev()
+        .map(|message| IncomingMessage::in_server(message, server))
+        .collect()
+}
+
+/// A haiku the pipeline found and saved
+#[derive(Debug, Clone)]
+pub struct SavedHaiku {
+    pub id: i64,
+    pub haiku: Haiku,
+    /// Held for review because the counter wasn't sure of it, so the hooks aren't run for it
+    pub flagged: bool,
+}
+
+/// What happens to haikus the counter isn't sure enough of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LowConfidence {
+    /// Save them flagged for review, without announcing them
+    Flag,
+    /// Don't save them
+    Skip,
+}
+
+/// Runs before detection. Returns false to skip the message entirely, and may rewrite its content.
+pub trait PreFilter: Send + Sync {
+    fn filter(&self, repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool;
+
+    /// Shown when explaining what detection did with a message
+    fn name(&self) -> &'static str {
+        let path = std::any::type_name::<Self>();
+        path.rsplit("::").next().unwrap_or(path)
+    }
+}
+
+/// What a pre-filter did to a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterStep {
+    pub filter: &'static str,
+    /// False if the filter skipped the message
+    pub kept: bool,
+    /// The message's content afterwards, if the filter changed it
+    pub rewritten: Option<String>,
+}
+
+/// Runs for every haiku that was saved, in the order the hooks were added
+#[async_trait]
+pub trait PostSaveHook: Send + Sync {
+    async fn on_saved(&self, ctx: &Context, repository: &dyn HaikuRepository, saved: &SavedHaiku);
+}
+
+pub struct DetectionPipeline {
+    filters: Vec<Box<dyn PreFilter>>,
+    detector: Mutex<Detector>,
+    max_gap: Option<Duration>,
+    min_confidence: f64,
+    low_confidence: LowConfidence,
+    hooks: Vec<Box<dyn PostSaveHook>>,
+}
+
+impl DetectionPipeline {
+    pub fn new() -> Self {
+        DetectionPipeline {
+            filters: Vec::new(),
+            detector: Mutex::new(Detector::new()),
+            max_gap: None,
+            min_confidence: 0.0,
+            low_confidence: LowConfidence::Flag,
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Stop lines sent more than `max_gap` apart in a channel from forming a haiku together
+    pub fn max_gap(mut self, max_gap: Option<Duration>) -> Self {
+        self.detector = Mutex::new(Detector::new().max_gap(max_gap));
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Treat haikus whose words' average confidence (see [crate::counting::confidence]) is below
+    /// `min_confidence` as `low_confidence` says
+    pub fn min_confidence(mut self, min_confidence: f64, low_confidence: LowConfidence) -> Self {
+        self.min_confidence = min_confidence;
+        self.low_confidence = low_confidence;
+        self
+    }
+
+    pub fn filter(mut self, filter: impl PreFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn hook(mut self, hook: impl PostSaveHook + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Run a message through the pre-filters, returning it as the detector would see it, or None
+    /// if it would be skipped
+    pub fn prepare(
+        &self,
+        repository: &dyn HaikuRepository,
+        mut message: IncomingMessage,
+    ) -> Option<IncomingMessage> {
+        if self
+            .filters
+            .iter()
+            .all(|filter| filter.filter(repository, &mut message))
+        {
+            Some(message)
+        } else {
+            None
+        }
+    }
+
+    /// Like [DetectionPipeline::prepare], but recording what each filter did along the way
+    pub fn trace(
+        &self,
+        repository: &dyn HaikuRepository,
+        mut message: IncomingMessage,
+    ) -> (Option<IncomingMessage>, Vec<FilterStep>) {
+        let mut steps = Vec::new();
+        for filter in self.filters.iter() {
+            let before = message.content.clone();
+            let kept = filter.filter(repository, &mut message);
+            steps.push(FilterStep {
+                filter: filter.name(),
+                kept,
+                rewritten: Some(message.content.clone()).filter(|content| *content != before),
+            });
+            if !kept {
+                return (None, steps);
+            }
+        }
+        (Some(message), steps)
+    }
+
+    /// Filter a message and save any haikus it completes, without running the hooks
+    pub fn detect(
+        &self,
+        repository: &dyn HaikuRepository,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        self.detect_dated(repository, message, false)
+    }
+
+    /// Like [DetectionPipeline::detect], for a message sent while the bot was offline: its haikus
+    /// are dated when it was sent rather than now
+    pub fn detect_missed(
+        &self,
+        repository: &dyn HaikuRepository,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        self.detect_dated(repository, message, true)
+    }
+
+    fn detect_dated(
+        &self,
+        repository: &dyn HaikuRepository,
+        message: IncomingMessage,
+        when_sent: bool,
+    ) -> Vec<SavedHaiku> {
+        let message = match self.prepare(repository, message) {
+            Some(message) => message,
+            None => return Vec::new(),
+        };
+        let overrides = repository.get_word_overrides(message.server);
+        let mut haikus = self
+            .detector
+            .lock()
+            .expect("Detector lock poisoned")
+            .find_haikus(&message, &overrides);
+        if when_sent {
+            for haiku in haikus.iter_mut() {
+                haiku.timestamp = message.sent_at;
+            }
+        }
+        self.save(repository, message.server, haikus)
+    }
+
+    /// The detector's recent lines, see [Detector::checkpoint]
+    pub fn checkpoint(&self) -> String {
+        self.detector
+            .lock()
+            .expect("Detector lock poisoned")
+            .checkpoint()
+    }
+
+    /// Pick up the recent lines from a checkpoint, see [Detector::restore]
+    pub fn restore(&self, checkpoint: &str) -> serde_json::Result<()> {
+        self.detector
+            .lock()
+            .expect("Detector lock poisoned")
+            .restore(checkpoint)
+    }
+
+    /// See [Detector::last_seen]
+    pub fn last_seen(&self) -> Vec<(ChannelId, MessageId)> {
+        self.detector
+            .lock()
+            .expect("Detector lock poisoned")
+            .last_seen()
+    }
+
+    /// Run a message through the whole pipeline, returning the haikus that were saved
+    pub async fn run(
+        &self,
+        ctx: &Context,
+        repository: &dyn HaikuRepository,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        let saved = self.detect(repository, message);
+        self.run_hooks(ctx, repository, &saved).await;
+        saved
+    }
+
+    /// Check a message sent while the bot was offline, see [DetectionPipeline::detect_missed],
+    /// running the hooks for each haiku that was saved
+    pub async fn run_missed(
+        &self,
+        ctx: &Context,
+        repository: &dyn HaikuRepository,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        let saved = self.detect_missed(repository, message);
+        self.run_hooks(ctx, repository, &saved).await;
+        saved
+    }
+
+    /// Filter a message and the (up to two) messages sent before it, oldest first, and find any
+    /// haikus that end in the message without saving them.
+    /// The channel's recent lines are left alone, since these messages may be old.
+    pub fn find_requested(
+        &self,
+        repository: &dyn HaikuRepository,
+        earlier: Vec<IncomingMessage>,
+        message: IncomingMessage,
+    ) -> Vec<Haiku> {
+        let mut detector = Detector::new().max_gap(self.max_gap);
+        let overrides = repository.get_word_overrides(message.server);
+        let requested = message.id;
+        let mut found = Vec::new();
+        for message in earlier.into_iter().chain(Some(message)) {
+            let message = match self.prepare(repository, message) {
+                Some(message) => message,
+                None => continue,
+            };
+            let haikus = detector.find_haikus(&message, &overrides);
+            // Earlier messages only provide the first lines
+            if message.id == requested {
+                found = haikus;
+            }
+        }
+        found
+    }
+
+    /// Like [DetectionPipeline::find_requested], but saving the haikus that are found, without
+    /// running the hooks
+    pub fn detect_requested(
+        &self,
+        repository: &dyn HaikuRepository,
+        earlier: Vec<IncomingMessage>,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        let server = message.server;
+        let found = self.find_requested(repository, earlier, message);
+        self.save(repository, server, found)
+    }
+
+    /// Like [DetectionPipeline::detect_requested], for a message from a channel's history: its
+    /// haikus are dated when it was sent rather than now
+    pub fn detect_history(
+        &self,
+        repository: &dyn HaikuRepository,
+        earlier: Vec<IncomingMessage>,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        let (server, sent_at) = (message.server, message.sent_at);
+        let found = self
+            .find_requested(repository, earlier, message)
+            .into_iter()
+            .map(|haiku| Haiku {
+                timestamp: sent_at,
+                ..haiku
+            })
+            .collect();
+        self.save(repository, server, found)
+    }
+
+    /// Check a message someone has asked to be saved, along with the messages before it, running
+    /// the hooks for each haiku that was saved
+    pub async fn run_requested(
+        &self,
+        ctx: &Context,
+        repository: &dyn HaikuRepository,
+        earlier: Vec<IncomingMessage>,
+        message: IncomingMessage,
+    ) -> Vec<SavedHaiku> {
+        let saved = self.detect_requested(repository, earlier, message);
+        self.run_hooks(ctx, repository, &saved).await;
+        saved
+    }
+
+    /// Save a haiku that was written somewhere else, e.g. sent through the HTTP API, as the
+    /// server's settings allow, without running the hooks. None if the settings leave it out.
+    pub fn save_submitted(
+        &self,
+        repository: &dyn HaikuRepository,
+        haiku: Haiku,
+    ) -> Option<SavedHaiku> {
+        let server = haiku.server;
+        self.save(repository, server, vec![haiku]).pop()
+    }
+
+    /// Run the hooks for haikus that were saved without them, other than those held for review
+    pub async fn run_hooks(
+        &self,
+        ctx: &Context,
+        repository: &dyn HaikuRepository,
+        saved: &[SavedHaiku],
+    ) {
+        for haiku in saved.iter().filter(|saved| !saved.flagged) {
+            for hook in self.hooks.iter() {
+                hook.on_saved(ctx, repository, haiku).await;
+            }
+        }
+    }
+
+    /// Save the haikus the server's settings allow, skipping any that were already saved, and
+    /// flagging or skipping those the counter isn't sure of
+    fn save(
+        &self,
+        repository: &dyn HaikuRepository,
+        server: GuildId,
+        haikus: Vec<Haiku>,
+    ) -> Vec<SavedHaiku> {
+        if haikus.is_empty() {
+            return Vec::new();
+        }
+        let same_author_only = repository
+            .get_server_config(server)
+            .map_or(false, |config| config.same_author_only);
+        let overrides = repository.get_word_overrides(server);
+        haikus
+            .into_iter()
+            .filter(|haiku| !same_author_only || has_one_author(haiku))
+            .filter_map(|haiku| {
+                let lines = haiku
+                    .lines
+                    .iter()
+                    .map(|line| line.content.clone())
+                    .collect::<Vec<String>>();
+                let flagged = confidence(&lines, &overrides) < self.min_confidence;
+                if flagged && self.low_confidence == LowConfidence::Skip {
+                    return None;
+                }
+                let id = repository.save_haiku(&haiku)?;
+                if flagged {
+                    println!(
+                        "Flagged haiku #{} in {} for review: low confidence",
+                        id, server
+                    );
+                    repository.flag_haiku(server, id);
+                }
+                Some(SavedHaiku { id, haiku, flagged })
+            })
+            .collect()
+    }
+}
+
+/// Whether every line was written by the same person. Bridged lines count as written by whoever
+/// the bridge credited them to.
+fn has_one_author(haiku: &Haiku) -> bool {
+    let [first, rest @ ..] = &haiku.lines;
+    rest.iter()
+        .all(|line| line.author == first.author && line.author_name == first.author_name)
+}
+
+impl Default for DetectionPipeline {
+    fn default() -> Self {
+        DetectionPipeline::new()
+    }
+}
+
+/// Discord formatting that doesn't change what's being said
+const MARKDOWN_MARKERS: &[&str] = &["**", "__", "~~", "||", "*"];
+
+/// Remove bold, italic, underline, strikethrough and spoiler markers from text
+pub fn strip_markdown(content: &str) -> String {
+    MARKDOWN_MARKERS
+        .iter()
+        .fold(content.to_owned(), |content, marker| {
+            content.replace(marker, "")
+        })
+}
+
+/// Saves haikus without the formatting they were written with
+pub struct StripMarkdown;
+
+impl PreFilter for StripMarkdown {
+    fn filter(&self, _repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+        message.content = strip_markdown(&message.content);
+        true
+    }
+}
+
+/// Remove fenced code blocks and quoted lines from text, leaving what the sender wrote themselves
+pub fn strip_quotes_and_code(content: &str) -> String {
+    let mut kept = Vec::new();
+    let mut in_code_block = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if in_code_block {
+            in_code_block = !line.contains("```");
+        } else if trimmed.starts_with("```") {
+            // A block can open and close on the same line
+            in_code_block = trimmed.matches("```").count() == 1;
+        } else if trimmed == ">>>" || trimmed.starts_with(">>> ") {
+            // Quotes everything after it
+            break;
+        } else if !(trimmed == ">" || trimmed.starts_with("> ")) {
+            kept.push(line);
+        }
+    }
+    kept.join("\n")
+}
+
+/// Leaves out code blocks and quotes of other messages, since they aren't the sender's own lines.
+/// Messages with nothing else in them are skipped.
+pub struct StripQuotesAndCode;
+
+impl PreFilter for StripQuotesAndCode {
+    fn filter(&self, _repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+        message.content = strip_quotes_and_code(&message.content);
+        !message.content.trim().is_empty()
+    }
+}
+
+/// What to do with links, which can't be counted. Attachments aren't part of a message's text, so
+/// messages that are only attachments are always skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlPolicy {
+    /// Count them like any other text, so lines with links are never haikus
+    Keep,
+    /// Count lines as if their links weren't there, keeping the text of masked links
+    Strip,
+    /// Skip messages with links entirely
+    Skip,
+}
+
+/// Remove links from text, leaving the text of masked links like [text](url)
+pub fn strip_urls(content: &str) -> String {
+    lazy_static! {
+        static ref MASKED_LINK_REGEX: Regex =
+            Regex::new(r"\[([^\]\n]*)\]\(<?https?://[^)\s]*>?\)").unwrap();
+        static ref URL_REGEX: Regex = Regex::new(r"<?\bhttps?://\S+").unwrap();
+    }
+    URL_REGEX
+        .replace_all(&MASKED_LINK_REGEX.replace_all(content, "$1"), "")
+        .into_owned()
+}
+
+impl PreFilter for UrlPolicy {
+    fn filter(&self, _repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+        if message.content.trim().is_empty() {
+            return false;
+        }
+        match self {
+            UrlPolicy::Keep => true,
+            UrlPolicy::Skip => strip_urls(&message.content) == message.content,
+            UrlPolicy::Strip => {
+                let stripped = strip_urls(&message.content);
+                if stripped == message.content {
+                    return true;
+                }
+                // Lines that were only links are left out rather than left blank
+                let lines = message
+                    .content
+                    .lines()
+                    .zip(stripped.lines())
+                    .filter(|(original, stripped)| {
+                        original.trim().is_empty() || !stripped.trim().is_empty()
+                    })
+                    .map(|(_, stripped)| stripped.trim())
+                    .collect::<Vec<&str>>();
+                message.content = lines.join("\n");
+                !message.content.trim().is_empty()
+            }
+        }
+    }
+}
+
+/// Skips messages from bots and webhooks unless the server has chosen to allow them
+pub struct AllowedSenders;
+
+impl PreFilter for AllowedSenders {
+    fn filter(&self, repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+        // Only looked up for the rare messages that need it
+        let config = || repository.get_server_config(message.server);
+        match message.sender {
+            Sender::Person => true,
+            Sender::Bot => config().map_or(false, |config| config.detect_bot_messages),
+            Sender::Webhook => config().map_or(false, |config| config.detect_webhook_messages),
+        }
+    }
+}
+
+/// Credits bridged messages to the person the bridge relayed them from, using the server's bridge
+/// pattern. Only bots and webhooks are treated as bridges.
+pub struct BridgeNicks;
+
+impl PreFilter for BridgeNicks {
+    fn filter(&self, repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+        if message.sender == Sender::Person {
+            return true;
+        }
+        let pattern = match repository
+            .get_server_config(message.server)
+            .and_then(|config| config.bridge_pattern)
+            .and_then(|pattern| parse_bridge_pattern(&pattern).ok())
+        {
+            Some(pattern) => pattern,
+            None => return true,
+        };
+        if let Some((nick, content)) = parse_bridged(&pattern, &message.content) {
+            message.author_name = Some(nick);
+            message.content = content;
+        }
+        true
+    }
+}
+
+/// Leaves noise out of messages, so it never becomes part of a haiku or splits one up. Messages
+/// that are all noise are skipped.
+impl PreFilter for NoiseRules {
+    fn filter(&self, _repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+        if !message.content.lines().any(|line| self.is_noise(line)) {
+            return true;
+        }
+        let lines = message
+            .content
+            .lines()
+            .filter(|line| !self.is_noise(line))
+            .collect::<Vec<&str>>();
+        if lines.iter().all(|line| line.trim().is_empty()) {
+            return false;
+        }
+        message.content = lines.join("\n");
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        strip_markdown, strip_quotes_and_code, strip_urls, AllowedSenders, BridgeNicks,
+        DetectionPipeline, IncomingMessage, LowConfidence, PreFilter, Sender, StripMarkdown,
+        StripQuotesAndCode, UrlPolicy,
+    };
+    use crate::{
+        detection::noise::NoiseRules,
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use chrono::{TimeZone, Utc};
+    use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+    const HAIKU: &str = "An old silent pond\nA frog jumps into the pond\nSplash! Silence again";
+
+    fn message(channel: u64, content: &str) -> IncomingMessage {
+        IncomingMessage {
+            id: MessageId(channel),
+            server: GuildId(1),
+            channel: ChannelId(channel),
+            author: UserId(100),
+            author_name: None,
+            sender: Sender::Person,
+            content: content.to_owned(),
+            sent_at: Utc::now(),
+        }
+    }
+
+    struct OnlyChannel(ChannelId);
+
+    impl PreFilter for OnlyChannel {
+        fn filter(&self, _repository: &dyn HaikuRepository, message: &mut IncomingMessage) -> bool {
+            message.channel == self.0
+        }
+    }
+
+    #[test]
+    fn test_strip_markdown() {
+        assert_eq!(
+            strip_markdown("**An old** *silent* ~~pond~~ ||frog|| __jumps__"),
+            "An old silent pond frog jumps"
+        );
+        assert_eq!(strip_markdown("snake_case stays"), "snake_case stays");
+    }
+
+    #[test]
+    fn test_pre_filters() {
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new()
+            .filter(OnlyChannel(ChannelId(10)))
+            .filter(StripMarkdown);
+
+        assert!(pipeline.detect(&repository, message(11, HAIKU)).is_empty());
+        let saved = pipeline.detect(&repository, message(10, &HAIKU.replace("pond", "**pond**")));
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].haiku.lines[0].content, "An old silent pond");
+        assert_eq!(repository.count_haikus(GuildId(1)), 1);
+    }
+
+    #[test]
+    fn test_allowed_senders() {
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(1));
+        let pipeline = DetectionPipeline::new().filter(AllowedSenders);
+        let webhook = IncomingMessage {
+            author_name: Some("alice (IRC)".to_owned()),
+            sender: Sender::Webhook,
+            ..message(10, HAIKU)
+        };
+        let bot = IncomingMessage {
+            sender: Sender::Bot,
+            ..message(11, HAIKU)
+        };
+
+        assert!(pipeline.detect(&repository, webhook.clone()).is_empty());
+        assert!(pipeline.detect(&repository, bot.clone()).is_empty());
+
+        repository.set_server_senders(GuildId(1), false, true);
+        let saved = pipeline.detect(&repository, webhook);
+        assert_eq!(saved.len(), 1);
+        assert_eq!(
+            saved[0].haiku.lines[2].author_name.as_deref(),
+            Some("alice (IRC)")
+        );
+        assert!(pipeline.detect(&repository, bot).is_empty());
+        assert_eq!(pipeline.detect(&repository, message(12, HAIKU)).len(), 1);
+    }
+
+    #[test]
+    fn test_bridge_nicks() {
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(1));
+        repository.set_server_bridge_pattern(GuildId(1), Some(r"^<(?P<nick>[^>]+)> "));
+        let pipeline = DetectionPipeline::new().filter(BridgeNicks);
+        let bridged = |id: u64, content: &str| IncomingMessage {
+            id: MessageId(id),
+            sender: Sender::Bot,
+            ..message(10, content)
+        };
+
+        assert!(pipeline
+            .detect(&repository, bridged(1, "<basho> An old silent pond"))
+            .is_empty());
+        pipeline.detect(
+            &repository,
+            bridged(2, "<buson> A frog jumps into the pond"),
+        );
+        let saved = pipeline.detect(&repository, bridged(3, "<basho> Splash! Silence again"));
+        assert_eq!(saved.len(), 1);
+        let lines = &saved[0].haiku.lines;
+        assert_eq!(lines[0].content, "An old silent pond");
+        assert_eq!(lines[0].author_name.as_deref(), Some("basho"));
+        assert_eq!(lines[1].author_name.as_deref(), Some("buson"));
+
+        // People can't pass their messages off as someone else's
+        let mut person = message(11, "<basho> An old silent pond");
+        assert!(BridgeNicks.filter(&repository, &mut person));
+        assert_eq!(person.author_name, None);
+        assert_eq!(person.content, "<basho> An old silent pond");
+    }
+
+    #[test]
+    fn test_detect_requested() {
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new().filter(StripMarkdown);
+        let line = |id: u64, content: &str| IncomingMessage {
+            id: MessageId(id),
+            ..message(10, content)
+        };
+
+        // A haiku that ended earlier isn't the one asked for
+        let saved = pipeline.detect_requested(
+            &repository,
+            vec![line(1, "hello"), line(2, HAIKU)],
+            line(3, "Splash! Silence again"),
+        );
+        assert!(saved.is_empty());
+
+        let saved = pipeline.detect_requested(
+            &repository,
+            vec![
+                line(4, "An old silent pond"),
+                line(5, "A frog jumps into the **pond**"),
+            ],
+            line(6, "Splash! Silence again"),
+        );
+        assert_eq!(saved.len(), 1);
+        assert_eq!(
+            saved[0].haiku.lines[1].content,
+            "A frog jumps into the pond"
+        );
+        assert_eq!(
+            saved[0].haiku.source.as_ref().unwrap().messages,
+            [MessageId(4), MessageId(5), MessageId(6)]
+        );
+        assert_eq!(repository.count_haikus(GuildId(1)), 1);
+
+        // Asking again doesn't save it twice
+        let saved = pipeline.detect_requested(
+            &repository,
+            vec![
+                line(4, "An old silent pond"),
+                line(5, "A frog jumps into the pond"),
+            ],
+            line(6, "Splash! Silence again"),
+        );
+        assert!(saved.is_empty());
+    }
+
+    #[test]
+    fn test_low_confidence() {
+        // Both names are guessed, so the counter is only 90% sure of this
+        let haiku = "An old silent pond\nYstharion plays Skyrim\nSplash! Silence again";
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new().min_confidence(0.95, LowConfidence::Flag);
+        let saved = pipeline.detect(&repository, message(10, haiku));
+        assert_eq!(saved.len(), 1);
+        assert!(saved[0].flagged);
+        assert!(repository.is_flagged(GuildId(1), saved[0].id));
+
+        let pipeline = DetectionPipeline::new().min_confidence(0.95, LowConfidence::Skip);
+        assert!(pipeline.detect(&repository, message(11, haiku)).is_empty());
+        assert_eq!(repository.count_haikus(GuildId(1)), 1);
+
+        // The server knows better
+        repository.set_word_override(GuildId(1), "ystharion", Some(4));
+        repository.set_word_override(GuildId(1), "skyrim", Some(2));
+        let saved = pipeline.detect(&repository, message(12, haiku));
+        assert_eq!(saved.len(), 1);
+        assert!(!saved[0].flagged);
+        assert!(!repository.is_flagged(GuildId(1), saved[0].id));
+    }
+
+    #[test]
+    fn test_missed_haikus_are_dated_when_sent() {
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new();
+        let sent_at = Utc.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        let saved = pipeline.detect_missed(
+            &repository,
+            IncomingMessage {
+                sent_at,
+                ..message(10, HAIKU)
+            },
+        );
+        assert_eq!(saved.len(), 1);
+        let (_, haiku) = repository.get_haiku(GuildId(1), saved[0].id).unwrap();
+        assert_eq!(haiku.timestamp, sent_at);
+    }
+
+    #[test]
+    fn test_save_submitted() {
+        let haiku = |lines: [&str; 3]| HaikuBuilder::new().lines(lines).build();
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new().min_confidence(0.95, LowConfidence::Skip);
+        let saved = pipeline
+            .save_submitted(
+                &repository,
+                haiku([
+                    "An old silent pond",
+                    "A frog jumps into the pond",
+                    "Splash! Silence again",
+                ]),
+            )
+            .unwrap();
+        assert!(!saved.flagged);
+        assert_eq!(repository.get_haiku(GuildId(1), saved.id).unwrap().0, 1);
+
+        let unsure = haiku([
+            "An old silent pond",
+            "Ystharion plays Skyrim",
+            "Splash! Silence again",
+        ]);
+        assert!(pipeline.save_submitted(&repository, unsure).is_none());
+        assert_eq!(repository.count_haikus(GuildId(1)), 1);
+    }
+
+    #[test]
+    fn test_same_author_only() {
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(1));
+        repository.set_server_same_author(GuildId(1), true);
+        let pipeline = DetectionPipeline::new();
+        let line = |id: u64, author: u64, content: &str| IncomingMessage {
+            id: MessageId(id),
+            author: UserId(author),
+            ..message(10, content)
+        };
+
+        pipeline.detect(&repository, line(1, 100, "An old silent pond"));
+        pipeline.detect(&repository, line(2, 200, "A frog jumps into the pond"));
+        assert!(pipeline
+            .detect(&repository, line(3, 100, "Splash! Silence again"))
+            .is_empty());
+
+        pipeline.detect(&repository, line(4, 100, "An old silent pond"));
+        pipeline.detect(&repository, line(5, 100, "A frog jumps into the pond"));
+        assert_eq!(
+            pipeline
+                .detect(&repository, line(6, 100, "Splash! Silence again"))
+                .len(),
+            1
+        );
+        assert_eq!(pipeline.detect(&repository, line(7, 200, HAIKU)).len(), 1);
+
+        // Bridged lines are told apart by who they were relayed from
+        let bridged = |id: u64, nick: &str, content: &str| IncomingMessage {
+            author_name: Some(nick.to_owned()),
+            ..line(id, 300, content)
+        };
+        pipeline.detect(&repository, bridged(8, "basho", "An old silent pond"));
+        pipeline.detect(
+            &repository,
+            bridged(9, "buson", "A frog jumps into the pond"),
+        );
+        assert!(pipeline
+            .detect(&repository, bridged(10, "basho", "Splash! Silence again"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_noise_rules() {
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new().filter(NoiseRules::default());
+        let line = |id: u64, content: &str| IncomingMessage {
+            id: MessageId(id),
+            ..message(10, content)
+        };
+
+        let mut laughing = line(1, "hahaha");
+        assert!(!NoiseRules::default().filter(&repository, &mut laughing));
+        let mut mixed = line(2, "An old silent pond\n😂😂😂");
+        assert!(NoiseRules::default().filter(&repository, &mut mixed));
+        assert_eq!(mixed.content, "An old silent pond");
+
+        // Noise between a haiku's lines doesn't interrupt it
+        pipeline.detect(&repository, line(3, "An old silent pond"));
+        assert!(pipeline
+            .detect(&repository, line(4, "ahahahaha"))
+            .is_empty());
+        pipeline.detect(&repository, line(5, "A frog jumps into the pond"));
+        pipeline.detect(&repository, line(6, "<:frog:123456>"));
+        let saved = pipeline.detect(&repository, line(7, "Splash! Silence again"));
+        assert_eq!(saved.len(), 1);
+        assert_eq!(
+            saved[0].haiku.source.as_ref().unwrap().messages,
+            [MessageId(3), MessageId(5), MessageId(7)]
+        );
+    }
+
+    #[test]
+    fn test_strip_urls() {
+        assert_eq!(
+            strip_urls("An old pond https://example.com/pond?frog=1 silent"),
+            "An old pond  silent"
+        );
+        assert_eq!(strip_urls("see <https://example.com>"), "see ");
+        assert_eq!(
+            strip_urls("[An old silent pond](https://example.com/pond)"),
+            "An old silent pond"
+        );
+        assert_eq!(strip_urls("no links here"), "no links here");
+    }
+
+    #[test]
+    fn test_url_policy() {
+        let repository = MemoryRepository::new();
+        let linked = message(
+            10,
+            "An old silent pond https://example.com\nhttps://example.com/frog\nA frog",
+        );
+
+        assert!(UrlPolicy::Keep.filter(&repository, &mut linked.clone()));
+        assert!(!UrlPolicy::Skip.filter(&repository, &mut linked.clone()));
+        assert!(UrlPolicy::Skip.filter(&repository, &mut message(10, HAIKU)));
+        let mut stripped = linked;
+        assert!(UrlPolicy::Strip.filter(&repository, &mut stripped));
+        assert_eq!(stripped.content, "An old silent pond\nA frog");
+
+        // Nothing left to count, e.g. a message that's only an attachment
+        for policy in &[UrlPolicy::Keep, UrlPolicy::Strip, UrlPolicy::Skip] {
+            assert!(!policy.filter(&repository, &mut message(10, "")));
+        }
+        assert!(!UrlPolicy::Strip.filter(&repository, &mut message(10, "https://example.com")));
+
+        let pipeline = DetectionPipeline::new().filter(UrlPolicy::Strip);
+        let saved = pipeline.detect(
+            &repository,
+            message(
+                10,
+                "An old silent pond\nA frog jumps into the pond <https://example.com/frog.gif>\nSplash! Silence again",
+            ),
+        );
+        assert_eq!(saved.len(), 1);
+        assert_eq!(
+            saved[0].haiku.lines[1].content,
+            "A frog jumps into the pond"
+        );
+    }
+
+    #[test]
+    fn test_strip_quotes_and_code() {
+        assert_eq!(
+            strip_quotes_and_code("> An old silent pond\nA frog jumps into the pond"),
+            "A frog jumps into the pond"
+        );
+        assert_eq!(
+            strip_quotes_and_code("look at this\n```rust\nlet pond = old();\n```\nneat"),
+            "look at this\nneat"
+        );
+        assert_eq!(
+            strip_quotes_and_code("```one line of code```\nSplash!"),
+            "Splash!"
+        );
+        assert_eq!(
+            strip_quotes_and_code("so true\n>>> An old silent pond\nA frog jumps into the pond"),
+            "so true"
+        );
+        // Not quotes
+        assert_eq!(strip_quotes_and_code(">_> hmm\n2 > 1"), ">_> hmm\n2 > 1");
+    }
+
+    #[test]
+    fn test_quotes_and_code_are_skipped() {
+        let repository = MemoryRepository::new();
+        let pipeline = DetectionPipeline::new().filter(StripQuotesAndCode);
+
+        assert!(pipeline
+            .detect(&repository, message(10, &format!("```\n{}\n```", HAIKU)))
+            .is_empty());
+        let quoted = HAIKU
+            .lines()
+            .map(|line| format!("> {}", line))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let mut reply = message(10, &format!("{}\nlovely", quoted));
+        assert!(StripQuotesAndCode.filter(&repository, &mut reply));
+        assert_eq!(reply.content, "lovely");
+        assert!(!StripQuotesAndCode.filter(&repository, &mut message(10, &quoted)));
+        assert_eq!(repository.count_haikus(GuildId(1)), 0);
+    }
+}
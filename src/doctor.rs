@@ -0,0 +1,378 @@
+use crate::{
+    commands,
+    config::{CommandsConfig, Config},
+    database,
+};
+use serde_json::Value;
+use serenity::{http::Http, model::id::GuildId};
+use std::env;
+
+/// Privileged intents the bot can ask for, with the application flags that mean each one is enabled
+/// (the second flag is the limited version given to bots in fewer than 100 servers)
+const PRIVILEGED_INTENTS: &[(&str, u64, u64)] = &[
+    ("Presence", 1 << 12, 1 << 13),
+    ("Server Members", 1 << 14, 1 << 15),
+    ("Message Content", 1 << 18, 1 << 19),
+];
+
+const APPLICATION_URL: &str = "https://discord.com/api/v9/oauth2/applications/@me";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The outcome of one check, with what to do about it if it didn't pass
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn line(&self) -> String {
+        let label = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "ERROR",
+        };
+        format!("[{}] {}: {}", label, self.name, self.detail)
+    }
+}
+
+/// The names of the wanted privileged intents that aren't enabled in the application's flags
+pub fn missing_intents(flags: u64, wanted: &[&str]) -> Vec<&'static str> {
+    PRIVILEGED_INTENTS
+        .iter()
+        .filter(|(name, _, _)| wanted.contains(name))
+        .filter(|(_, full, limited)| flags & (full | limited) == 0)
+        .map(|(name, _, _)| *name)
+        .collect()
+}
+
+/// Check everything the bot needs before it can run. Checking the registered commands is slower
+/// and they are registered on startup anyway, so it's only done when asked for.
+pub async fn run_checks(check_commands: bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let config = match Config::load() {
+        Ok(config) => {
+            checks.push(Check::new("Config", Status::Ok, Config::path()));
+            Some(config)
+        }
+        Err(why) => {
+            checks.push(Check::new(
+                "Config",
+                Status::Error,
+                format!(
+                    "Could not load {}, fix or remove it: {:?}",
+                    Config::path(),
+                    why
+                ),
+            ));
+            None
+        }
+    };
+
+    let application_id = match env::var("DISCORD_USER_ID").map(|id| id.parse::<u64>()) {
+        Ok(Ok(id)) => Some(id),
+        Ok(Err(_)) => {
+            checks.push(Check::new(
+                "Application id",
+                Status::Error,
+                "$DISCORD_USER_ID is not a number, copy the application id from the developer portal",
+            ));
+            None
+        }
+        Err(_) => {
+            checks.push(Check::new(
+                "Application id",
+                Status::Error,
+                "Set $DISCORD_USER_ID to the application id from the developer portal",
+            ));
+            None
+        }
+    };
+
+    let test_guild = match env::var("TEST_GUILD_ID") {
+        Ok(id) => match id.parse::<u64>() {
+            Ok(id) => Some(GuildId(id)),
+            Err(_) => {
+                checks.push(Check::new(
+                    "Test guild",
+                    Status::Error,
+                    "$TEST_GUILD_ID is not a number, copy the server id or unset it",
+                ));
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    match env::var("DISCORD_TOKEN") {
+        Ok(token) => {
+            let config = config.unwrap_or_default();
+            check_discord(&mut checks, &token, application_id, &config).await;
+            if check_commands {
+                if let Some(application_id) = application_id {
+                    check_registered_commands(
+                        &mut checks,
+                        &token,
+                        application_id,
+                        test_guild,
+                        &config.commands,
+                    )
+                    .await;
+                }
+            }
+        }
+        Err(_) => checks.push(Check::new(
+            "Discord token",
+            Status::Error,
+            "Set $DISCORD_TOKEN to the bot token from the developer portal",
+        )),
+    }
+
+    check_database(&mut checks).await;
+    checks
+}
+
+async fn check_discord(
+    checks: &mut Vec<Check>,
+    token: &str,
+    application_id: Option<u64>,
+    config: &Config,
+) {
+    let http = Http::new_with_token(token);
+    match http.get_current_user().await {
+        Ok(user) => checks.push(Check::new(
+            "Discord token",
+            Status::Ok,
+            format!("logged in as {}", user.tag()),
+        )),
+        Err(why) => {
+            checks.push(Check::new(
+                "Discord token",
+                Status::Error,
+                format!(
+                    "Discord rejected the token ({}), reset it in the developer portal and update $DISCORD_TOKEN",
+                    why
+                ),
+            ));
+            return;
+        }
+    }
+
+    if let (Ok(info), Some(application_id)) =
+        (http.get_current_application_info().await, application_id)
+    {
+        if info.id.0 == application_id {
+            checks.push(Check::new(
+                "Application id",
+                Status::Ok,
+                application_id.to_string(),
+            ));
+        } else {
+            checks.push(Check::new(
+                "Application id",
+                Status::Error,
+                format!(
+                    "$DISCORD_USER_ID is {} but the token belongs to application {}",
+                    application_id, info.id
+                ),
+            ));
+        }
+    }
+
+    let flags = async {
+        reqwest::Client::new()
+            .get(APPLICATION_URL)
+            .header("Authorization", format!("Bot {}", token))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await
+    }
+    .await
+    .map(|application| application["flags"].as_u64().unwrap_or(0));
+    match flags {
+        Ok(flags) => {
+            let missing = missing_intents(flags, &config.intents.privileged_names());
+            if missing.is_empty() {
+                checks.push(Check::new(
+                    "Intents",
+                    Status::Ok,
+                    "every configured privileged intent is enabled",
+                ));
+            } else {
+                checks.push(Check::new(
+                    "Intents",
+                    Status::Error,
+                    format!(
+                        "Enable the {} intent(s) under Bot > Privileged Gateway Intents in the developer portal, or turn it off under [intents] in the config",
+                        missing.join(", ")
+                    ),
+                ));
+            }
+        }
+        Err(why) => checks.push(Check::new(
+            "Intents",
+            Status::Warning,
+            format!("Could not fetch the application's intents: {}", why),
+        )),
+    }
+}
+
+async fn check_registered_commands(
+    checks: &mut Vec<Check>,
+    token: &str,
+    application_id: u64,
+    test_guild: Option<GuildId>,
+    config: &CommandsConfig,
+) {
+    let http = Http::new_with_token_application_id(token, application_id);
+    let registered = match test_guild {
+        Some(guild_id) => http.get_guild_application_commands(guild_id.0).await,
+        None if config.guild_scoped => {
+            checks.push(Check::new(
+                "Commands",
+                Status::Ok,
+                "registered separately in each server as it's loaded",
+            ));
+            return;
+        }
+        None => http.get_global_application_commands().await,
+    };
+    let registered = match registered {
+        Ok(registered) => registered,
+        Err(why) => {
+            checks.push(Check::new(
+                "Commands",
+                Status::Warning,
+                format!("Could not fetch the registered commands: {}", why),
+            ));
+            return;
+        }
+    };
+    let specs = commands::configured_command_specs(config);
+    let missing = specs
+        .top_level_names()
+        .into_iter()
+        .map(|name| specs.registered_name(name))
+        .filter(|name| !registered.iter().any(|command| command.name == *name))
+        .collect::<Vec<&str>>();
+    if missing.is_empty() {
+        checks.push(Check::new(
+            "Commands",
+            Status::Ok,
+            format!("{} registered", registered.len()),
+        ));
+    } else {
+        checks.push(Check::new(
+            "Commands",
+            Status::Warning,
+            format!(
+                "/{} not registered yet, they will be when the bot next starts (global commands can take an hour to show up)",
+                missing.join(", /")
+            ),
+        ));
+    }
+}
+
+async fn check_database(checks: &mut Vec<Check>) {
+    if env::var("DATABASE_URL").is_err() {
+        checks.push(Check::new(
+            "Database",
+            Status::Error,
+            "Set $DATABASE_URL to the Postgres connection string",
+        ));
+        return;
+    }
+    let reachable = tokio::task::spawn_blocking(database::check_connection)
+        .await
+        .unwrap_or(false);
+    if !reachable {
+        checks.push(Check::new(
+            "Database",
+            Status::Error,
+            "Could not connect to $DATABASE_URL, check Postgres is running and the URL is right",
+        ));
+        return;
+    }
+    checks.push(Check::new("Database", Status::Ok, "connected"));
+
+    let applied = tokio::task::spawn_blocking(|| {
+        database::latest_applied_migration(&database::establish_connection())
+    })
+    .await
+    .expect("Checking migrations panicked");
+    let check = match applied {
+        Ok(Some(version)) if version.as_str() > database::LATEST_MIGRATION => Check::new(
+            "Migrations",
+            Status::Warning,
+            format!(
+                "The database is at {}, newer than this build's {}, so some things may not work",
+                version,
+                database::LATEST_MIGRATION
+            ),
+        ),
+        Ok(Some(version)) if version.as_str() == database::LATEST_MIGRATION => Check::new(
+            "Migrations",
+            Status::Ok,
+            format!("up to date ({})", version),
+        ),
+        Ok(Some(version)) => Check::new(
+            "Migrations",
+            Status::Error,
+            format!(
+                "The database is at {} but this build needs {}, run `diesel migration run`",
+                version,
+                database::LATEST_MIGRATION
+            ),
+        ),
+        Ok(None) | Err(_) => Check::new(
+            "Migrations",
+            Status::Error,
+            "No migrations have been run, run `diesel setup` then `diesel migration run`",
+        ),
+    };
+    checks.push(check);
+}
+
+/// Print each check, returning whether all of them passed well enough for the bot to run
+pub fn report(checks: &[Check]) -> bool {
+    for check in checks.iter() {
+        println!("{}", check.line());
+    }
+    checks.iter().all(|check| check.status != Status::Error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::missing_intents;
+
+    #[test]
+    fn test_missing_intents() {
+        let all = ["Presence", "Server Members", "Message Content"];
+        assert_eq!(missing_intents(0, &all), all.to_vec());
+        // Limited intents count too
+        assert_eq!(
+            missing_intents((1 << 13) | (1 << 14), &all),
+            vec!["Message Content"]
+        );
+        assert!(missing_intents((1 << 12) | (1 << 15) | (1 << 19), &all).is_empty());
+        // Intents turned off in the config aren't needed
+        assert!(missing_intents(1 << 18, &["Message Content"]).is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+//! Telling a server's admins when something goes wrong, in the channel they chose with
+//! /config errors, rather than only in the bot's logs
+
+use crate::{
+    database,
+    i18n::guild_locale,
+    repository::{repository, HaikuRepository},
+    retry::with_retry,
+};
+use serenity::{client::Context, model::id::GuildId};
+
+/// Post a problem in the server's error channel, if it has one
+pub async fn report_error(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    content: &str,
+) {
+    let channel = match repository
+        .get_server_config(server_id)
+        .and_then(|config| config.error_channel)
+    {
+        Some(channel) => channel,
+        None => return,
+    };
+    let result = with_retry(&ctx.http, || {
+        channel.send_message(&ctx.http, |msg| {
+            msg.content(content)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not report error in {}: {:?}", server_id, why);
+    }
+}
+
+/// Warn the servers with an error channel if the database has migrations this build doesn't know
+/// about, e.g. after rolling back to an older version, since some things may not work
+pub async fn report_migration_problems(ctx: Context, servers: Vec<GuildId>) {
+    let applied = tokio::task::spawn_blocking(|| {
+        database::latest_applied_migration(&database::establish_connection())
+    })
+    .await
+    .expect("Checking migrations panicked");
+    let version = match applied {
+        Ok(Some(version)) if version.as_str() > database::LATEST_MIGRATION => version,
+        _ => return,
+    };
+    let repository = repository(&ctx).await;
+    for server_id in servers {
+        let locale = guild_locale(&*repository, Some(server_id));
+        let content = t!(
+            locale,
+            "error_channel.migrations_ahead",
+            version = version,
+            expected = database::LATEST_MIGRATION
+        );
+        report_error(&ctx, &*repository, server_id, &content).await;
+    }
+}
@@ -0,0 +1,123 @@
+//! Exporting haikus to files people can keep
+
+use crate::models::Haiku;
+use serde_json::json;
+
+/// The file formats haikus can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Write haikus out in the given format, one entry per haiku with each line's author
+pub fn export_haikus(haikus: &[(i64, Haiku)], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => export_json(haikus),
+        ExportFormat::Csv => export_csv(haikus),
+    }
+}
+
+fn export_json(haikus: &[(i64, Haiku)]) -> String {
+    let haikus = haikus
+        .iter()
+        .map(|(id, haiku)| {
+            json!({
+                "id": id,
+                "timestamp": haiku.timestamp.to_rfc3339(),
+                "channel": haiku.channel.to_string(),
+                "lines": haiku
+                    .lines
+                    .iter()
+                    .map(|line| json!({
+                        "author": line.author.to_string(),
+                        "content": line.content,
+                    }))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string_pretty(&haikus).expect("Unable to serialize haikus")
+}
+
+fn export_csv(haikus: &[(i64, Haiku)]) -> String {
+    let mut csv =
+        String::from("id,timestamp,channel,author_0,line_0,author_1,line_1,author_2,line_2\n");
+    for (id, haiku) in haikus {
+        let mut fields = vec![
+            id.to_string(),
+            haiku.timestamp.to_rfc3339(),
+            haiku.channel.to_string(),
+        ];
+        for line in haiku.lines.iter() {
+            fields.push(line.author.to_string());
+            fields.push(csv_field(&line.content));
+        }
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a field if it contains anything that would break the row apart
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export_haikus, ExportFormat};
+    use crate::{models::Haiku, test_support::HaikuBuilder};
+    use chrono::{TimeZone, Utc};
+    use serde_json::Value;
+
+    fn haiku() -> Haiku {
+        HaikuBuilder::new()
+            .lines([
+                "An old silent pond",
+                "A frog jumps into the pond, \"splash!\"",
+                "Silence again",
+            ])
+            .authors([100, 200, 100])
+            .at(Utc.ymd(2021, 1, 1).and_hms(12, 0, 0))
+            .build()
+    }
+
+    #[test]
+    fn test_export_json() {
+        let exported = export_haikus(&[(7, haiku())], ExportFormat::Json);
+        let exported: Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(exported[0]["id"], 7);
+        assert_eq!(exported[0]["timestamp"], "2021-01-01T12:00:00+00:00");
+        assert_eq!(exported[0]["channel"], "10");
+        assert_eq!(exported[0]["lines"][1]["author"], "200");
+        assert_eq!(
+            exported[0]["lines"][1]["content"],
+            "A frog jumps into the pond, \"splash!\""
+        );
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let exported = export_haikus(&[(7, haiku())], ExportFormat::Csv);
+        assert_eq!(
+            exported,
+            "id,timestamp,channel,author_0,line_0,author_1,line_1,author_2,line_2\n\
+             7,2021-01-01T12:00:00+00:00,10,100,An old silent pond,\
+             200,\"A frog jumps into the pond, \"\"splash!\"\"\",100,Silence again\n"
+        );
+    }
+}
@@ -1,10 +1,24 @@
 use std::{collections::HashSet, env};
 
-use crate::models::Haiku;
-use chrono::{DateTime, Utc};
+use crate::{
+    build_info::REPOSITORY_URL,
+    i18n::{Locale, Ordinals, Region},
+    models::{Haiku, HaikuEdit},
+    repository::{repository, HaikuRepository},
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use lazy_static::lazy_static;
-use serenity::{builder::CreateEmbed, client::Context, model::id::UserId, utils::Color};
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        id::{GuildId, UserId},
+        misc::Mentionable,
+    },
+    utils::Color,
+};
 
+#[derive(Clone)]
 pub struct EmbedData {
     haiku_lines: Vec<String>,
     haiku_id: i64,
@@ -13,15 +27,19 @@ pub struct EmbedData {
     unique_authors: Vec<String>,
     primary_author_color: Option<Color>,
     primary_author_icon: Option<String>,
+    locale: Locale,
+    content_warning: bool,
 }
 
-pub async fn to_embed_data(id: i64, haiku: &Haiku, ctx: &Context) -> EmbedData {
-    let (authors, lines): (Vec<UserId>, Vec<String>) = haiku
-        .lines
-        .to_vec()
-        .into_iter()
-        .map(|line| (line.author, line.content.clone()))
-        .unzip();
+/// What a haiku embed shows about one of the haiku's authors
+pub struct AuthorDetails {
+    pub id: UserId,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub color: Option<Color>,
+}
+
+pub async fn to_embed_data(id: i64, haiku: &Haiku, locale: Locale, ctx: &Context) -> EmbedData {
     let members = ctx
         .cache
         .guild_channel(haiku.channel)
@@ -30,47 +48,112 @@ pub async fn to_embed_data(id: i64, haiku: &Haiku, ctx: &Context) -> EmbedData {
         .members(&ctx.cache)
         .await
         .unwrap();
-    let primary_author = members.iter().find(|member| member.user.id == authors[0]);
     lazy_static! {
         static ref BOT_USER_ID: String = env::var("DISCORD_USER_ID")
             .expect("Expected a user id in the environment")
             .parse()
             .expect("Invalid user id");
     }
-    let primary_author_icon = match primary_author {
-        Some(author) => author.user.avatar_url(),
-        None => None,
-    };
-    let primary_author_color = match primary_author {
-        Some(author) => author.colour(&ctx.cache).await,
-        None => None,
-    };
+    let mut authors = Vec::new();
+    for member in members
+        .iter()
+        .filter(|member| haiku.lines.iter().any(|line| line.author == member.user.id))
+    {
+        authors.push(AuthorDetails {
+            id: member.user.id,
+            display_name: member.display_name().to_string(),
+            avatar_url: member.user.avatar_url(),
+            color: member.colour(&ctx.cache).await,
+        });
+    }
+
+    let bot_member = ctx.cache.current_user().await;
+    let bot_icon_url = bot_member.avatar_url();
+    let sensitive = repository(ctx).await.is_haiku_sensitive(haiku.server, id);
+    build_embed_data(id, haiku, locale, &authors, bot_icon_url).with_content_warning(sensitive)
+}
+
+/// The data for a haiku's embed, given what's known about its authors.
+/// Authors missing from `authors` are shown by the name their line was posted under if it was
+/// kept (e.g. for webhooks), otherwise as unknown, e.g. because they've left the server.
+pub fn build_embed_data(
+    id: i64,
+    haiku: &Haiku,
+    locale: Locale,
+    authors: &[AuthorDetails],
+    bot_icon_url: Option<String>,
+) -> EmbedData {
+    let lines = haiku
+        .lines
+        .iter()
+        .map(|line| line.content.clone())
+        .collect();
+    let primary_author = authors
+        .iter()
+        .find(|author| author.id == haiku.lines[0].author);
 
-    // Deduplicate retaining order
-    let mut unique_authors = authors.clone();
+    // Deduplicate retaining order. One webhook can post as several people, so its names are kept
+    // apart.
+    let mut unique_authors = haiku
+        .lines
+        .iter()
+        .map(|line| (line.author, line.author_name.as_deref()))
+        .collect::<Vec<(UserId, Option<&str>)>>();
     let mut unique_authors_set = HashSet::new();
     unique_authors.retain(|x| unique_authors_set.insert(x.clone()));
 
     let unique_authors = unique_authors
         .into_iter()
-        .map(
-            |author| match members.iter().find(|member| member.user.id == author) {
-                Some(author) => author.display_name().to_string(),
-                None => "Unknown User".to_owned(),
-            },
-        )
+        .map(|(author_id, author_name)| {
+            match (
+                authors.iter().find(|author| author.id == author_id),
+                author_name,
+            ) {
+                (Some(author), _) => author.display_name.clone(),
+                (None, Some(author_name)) => author_name.to_owned(),
+                (None, None) => t!(locale, "haiku.unknown_user"),
+            }
+        })
         .collect();
 
-    let bot_member = ctx.cache.current_user().await;
-    let bot_icon_url = bot_member.avatar_url();
     EmbedData {
         haiku_lines: lines,
         haiku_id: id,
         haiku_timestamp: haiku.timestamp,
         bot_icon_url,
         unique_authors,
-        primary_author_color,
-        primary_author_icon,
+        primary_author_color: primary_author.map(|author| author.color).flatten(),
+        primary_author_icon: primary_author
+            .map(|author| author.avatar_url.clone())
+            .flatten(),
+        locale,
+        content_warning: false,
+    }
+}
+
+/// A line hidden behind a spoiler, so it's only read by people who choose to
+fn spoiler(line: &str) -> String {
+    format!("||{}||", line)
+}
+
+impl EmbedData {
+    /// Hide the haiku's lines behind spoilers under a warning, if it needs one
+    pub fn with_content_warning(mut self, content_warning: bool) -> Self {
+        if content_warning && !self.content_warning {
+            self.haiku_lines = self.haiku_lines.iter().map(|line| spoiler(line)).collect();
+            self.content_warning = true;
+        }
+        self
+    }
+
+    /// The haiku's lines, under its content warning if it has one
+    fn body(&self) -> Vec<String> {
+        let mut body = Vec::new();
+        if self.content_warning {
+            body.push(t!(self.locale, "haiku.content_warning"));
+        }
+        body.extend(self.haiku_lines.iter().cloned());
+        body
     }
 }
 
@@ -80,9 +163,9 @@ pub fn format_haiku_embed(embed_data: EmbedData, embed: &mut CreateEmbed) -> &mu
         .primary_author_icon
         .clone()
         .unwrap_or("https://cdn.discordapp.com/embed/avatars/0.png".to_owned());
-    embed.title("A beautiful haiku has been created!");
-    embed.description(embed_data.haiku_lines.join("\n"));
-    embed.url("https://github.com/bumblepie/haikubot-rs");
+    embed.title(t!(embed_data.locale, "haiku.title"));
+    embed.description(embed_data.body().join("\n"));
+    embed.url(REPOSITORY_URL);
     embed.color(embed_data.primary_author_color.unwrap_or_default());
     embed.timestamp(&embed_data.haiku_timestamp);
     embed.footer(|footer| {
@@ -91,7 +174,7 @@ pub fn format_haiku_embed(embed_data: EmbedData, embed: &mut CreateEmbed) -> &mu
                 .bot_icon_url
                 .unwrap_or("https://cdn.discordapp.com/embed/avatars/0.png".to_owned()),
         );
-        footer.text(format!("Haiku #{}", embed_data.haiku_id));
+        footer.text(t!(embed_data.locale, "haiku.id", id = embed_data.haiku_id));
         footer
     });
     embed.author(|author| {
@@ -101,3 +184,845 @@ pub fn format_haiku_embed(embed_data: EmbedData, embed: &mut CreateEmbed) -> &mu
     });
     embed
 }
+
+/// A haiku embed with its authors hidden, for guessing who wrote it
+pub fn format_anonymous_haiku_embed(
+    embed_data: EmbedData,
+    embed: &mut CreateEmbed,
+) -> &mut CreateEmbed {
+    let locale = embed_data.locale;
+    format_haiku_embed(embed_data, embed);
+    embed.color(Color::default());
+    embed.author(|author| {
+        author.name(t!(locale, "haiku.hidden_author"));
+        author.icon_url("https://cdn.discordapp.com/embed/avatars/0.png");
+        author
+    });
+    embed
+}
+
+/// A haiku ready to send, as an embed or as plain text for people whose screen readers handle
+/// embeds poorly
+pub enum FormattedHaiku {
+    Embed(CreateEmbed),
+    Text(String),
+}
+
+impl FormattedHaiku {
+    /// The message's text, with anything that goes before the haiku. Send it without allowing
+    /// mentions, as plain text haikus would ping anyone mentioned in their lines.
+    pub fn content(&self, before: Option<&str>) -> Option<String> {
+        match (self, before) {
+            (FormattedHaiku::Text(text), Some(before)) => Some(format!("{}\n\n{}", before, text)),
+            (FormattedHaiku::Text(text), None) => Some(text.clone()),
+            (FormattedHaiku::Embed(_), before) => before.map(str::to_owned),
+        }
+    }
+
+    /// Give the haiku another title, e.g. a quiz's question
+    pub fn titled(mut self, title: String) -> Self {
+        match &mut self {
+            FormattedHaiku::Embed(embed) => {
+                embed.title(title);
+            }
+            FormattedHaiku::Text(text) => {
+                let rest = text.split_once('\n').map_or("", |(_, rest)| rest);
+                *text = format!("**{}**\n{}", title, rest);
+            }
+        }
+        self
+    }
+
+    pub fn embed(&self) -> Option<CreateEmbed> {
+        match self {
+            FormattedHaiku::Embed(embed) => Some(embed.clone()),
+            FormattedHaiku::Text(_) => None,
+        }
+    }
+}
+
+/// What a message the bot posted has around its haiku, so the haiku can be shown again as it is
+/// now without changing the rest
+#[derive(Debug, PartialEq, Eq)]
+pub struct Framing {
+    /// The text before the haiku, e.g. the server's announcement text
+    pub before: Option<String>,
+    /// The haiku's title, which may be a milestone's or a quiz's rather than the usual one
+    pub title: Option<String>,
+}
+
+/// Read the framing back out of a posted haiku's text and, unless it was sent as plain text, the
+/// title of its embed
+pub fn haiku_framing(content: &str, plain_text: bool, embed_title: Option<&str>) -> Framing {
+    if !plain_text {
+        return Framing {
+            before: Some(content.to_owned()).filter(|content| !content.is_empty()),
+            title: embed_title.map(str::to_owned),
+        };
+    }
+    // A haiku's text has no blank lines, so the last one is where it starts
+    let (before, text) = match content.rsplit_once("\n\n") {
+        Some((before, text)) => (Some(before.to_owned()), text),
+        None => (None, content),
+    };
+    let title = text
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("**"))
+        .and_then(|line| line.strip_suffix("**"))
+        .map(str::to_owned);
+    Framing { before, title }
+}
+
+/// Everything a haiku embed says, in the order a screen reader would read it
+fn haiku_text(title: String, authors: String, embed_data: &EmbedData) -> String {
+    let mut text = vec![format!("**{}**", title)];
+    text.extend(embed_data.body());
+    text.push(t!(embed_data.locale, "haiku.by", authors = authors));
+    text.push(t!(embed_data.locale, "haiku.id", id = embed_data.haiku_id));
+    text.join("\n")
+}
+
+pub fn format_haiku(embed_data: EmbedData, plain_text: bool) -> FormattedHaiku {
+    if plain_text {
+        let title = t!(embed_data.locale, "haiku.title");
+        let authors = embed_data.unique_authors.join(", ");
+        return FormattedHaiku::Text(haiku_text(title, authors, &embed_data));
+    }
+    let mut embed = CreateEmbed::default();
+    format_haiku_embed(embed_data, &mut embed);
+    FormattedHaiku::Embed(embed)
+}
+
+/// A haiku with its authors hidden, for guessing who wrote it
+pub fn format_anonymous_haiku(embed_data: EmbedData, plain_text: bool) -> FormattedHaiku {
+    if plain_text {
+        let title = t!(embed_data.locale, "haiku.title");
+        let authors = t!(embed_data.locale, "haiku.hidden_author");
+        return FormattedHaiku::Text(haiku_text(title, authors, &embed_data));
+    }
+    let mut embed = CreateEmbed::default();
+    format_anonymous_haiku_embed(embed_data, &mut embed);
+    FormattedHaiku::Embed(embed)
+}
+
+/// A haiku celebrating the server reaching a milestone with it
+pub fn format_milestone(milestone: i64, embed_data: EmbedData, plain_text: bool) -> FormattedHaiku {
+    if plain_text {
+        let locale = embed_data.locale;
+        let title = t!(
+            locale,
+            "milestone.title",
+            count = format_ordinal(milestone, Region::for_locale(locale))
+        );
+        let authors = embed_data.unique_authors.join(", ");
+        return FormattedHaiku::Text(haiku_text(title, authors, &embed_data));
+    }
+    let mut embed = CreateEmbed::default();
+    format_milestone_embed(milestone, embed_data, &mut embed);
+    FormattedHaiku::Embed(embed)
+}
+
+/// Whether haikus posted for everyone in a server, like announcements, are plain text
+pub fn server_wants_plain_text(
+    repository: &dyn HaikuRepository,
+    server_id: Option<GuildId>,
+) -> bool {
+    server_id
+        .and_then(|server_id| repository.get_server_config(server_id))
+        .map_or(false, |config| config.plain_text)
+}
+
+/// Whether someone wants haikus as plain text, going by the server's default if they haven't
+/// chosen for themselves
+pub fn wants_plain_text(
+    repository: &dyn HaikuRepository,
+    user_id: UserId,
+    server_id: Option<GuildId>,
+) -> bool {
+    repository
+        .get_user_preferences(user_id)
+        .plain_text
+        .unwrap_or_else(|| server_wants_plain_text(repository, server_id))
+}
+
+/// A haiku as plain text quote that can be pasted anywhere, ending with its id. The lines of a
+/// haiku with a content warning stay behind spoilers.
+pub fn format_haiku_quote(id: i64, haiku: &Haiku, locale: Locale, sensitive: bool) -> String {
+    let mut quote = haiku
+        .lines
+        .iter()
+        .map(|line| {
+            if sensitive {
+                format!("> {}", spoiler(&line.content))
+            } else {
+                format!("> {}", line.content)
+            }
+        })
+        .collect::<Vec<String>>();
+    quote.push(format!("- {}", t!(locale, "haiku.id", id = id)));
+    quote.join("\n")
+}
+
+/// Discord's limit on the length of a message
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// A haiku's edits, newest first, each shown as a diff of the whole haiku with who made it and
+/// when. The oldest edits are left out if they don't all fit in a message.
+pub fn format_edit_history(
+    id: i64,
+    haiku: &Haiku,
+    edits: &[HaikuEdit],
+    locale: Locale,
+    sensitive: bool,
+) -> String {
+    let mut lines = haiku
+        .lines
+        .iter()
+        .map(|line| line.content.clone())
+        .collect::<Vec<String>>();
+    let mut history = t!(locale, "history.title", id = id);
+    for (shown, edit) in edits.iter().rev().enumerate() {
+        let diff = lines
+            .iter()
+            .enumerate()
+            .map(|(position, line)| {
+                if position == edit.position {
+                    format!("- {}\n+ {}", edit.previous, edit.content)
+                } else {
+                    format!("  {}", line)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let diff = format!("```diff\n{}\n```", diff);
+        let entry = format!(
+            "\n{}\n{}",
+            t!(
+                locale,
+                "history.edit",
+                editor = edit.editor.mention(),
+                line = edit.position + 1,
+                time = format!("<t:{}:f>", edit.edited_at.timestamp())
+            ),
+            if sensitive { spoiler(&diff) } else { diff }
+        );
+        let more = format!(
+            "\n{}",
+            t!(locale, "history.more", count = edits.len() - shown)
+        );
+        let last = shown + 1 == edits.len();
+        let needed = history.len() + entry.len() + if last { 0 } else { more.len() };
+        if needed > MAX_MESSAGE_LENGTH {
+            history.push_str(&more);
+            break;
+        }
+        history.push_str(&entry);
+        lines[edit.position] = edit.previous.clone();
+    }
+    history
+}
+
+/// Read a haiku's id back out of the footer of its embed
+pub fn haiku_id_from_footer(footer: &str) -> Option<i64> {
+    footer.rsplit('#').next()?.trim().parse().ok()
+}
+
+/// A haiku embed celebrating the server reaching a milestone with it
+pub fn format_milestone_embed(
+    milestone: i64,
+    embed_data: EmbedData,
+    embed: &mut CreateEmbed,
+) -> &mut CreateEmbed {
+    let locale = embed_data.locale;
+    format_haiku_embed(embed_data, embed);
+    embed.title(t!(
+        locale,
+        "milestone.title",
+        count = format_ordinal(milestone, Region::for_locale(locale))
+    ));
+    embed.color(Color::GOLD);
+    embed
+}
+
+/// The placeholders an announcement template can fill in
+pub const TEMPLATE_PLACEHOLDERS: &[&str] = &[
+    "author", "authors", "id", "line1", "line2", "line3", "channel",
+];
+
+/// The longest announcement template accepted, leaving room in the message for the haiku itself
+pub const MAX_TEMPLATE_LENGTH: usize = 500;
+
+/// Why an announcement template can't be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    /// A `{` without a `}` after it
+    Unclosed,
+    TooLong,
+}
+
+enum TemplatePart<'a> {
+    Text(String),
+    Placeholder(&'a str),
+}
+
+/// Split a template into text and `{placeholders}`. `{{` and `}}` are literal braces.
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(index) = rest.find(|c| c == '{' || c == '}') {
+        text.push_str(&rest[..index]);
+        let brace = &rest[index..index + 1];
+        rest = &rest[index + 1..];
+        if rest.starts_with(brace) {
+            text.push_str(brace);
+            rest = &rest[1..];
+        } else if brace == "}" {
+            text.push('}');
+        } else {
+            let end = rest.find('}').ok_or(TemplateError::Unclosed)?;
+            let name = &rest[..end];
+            if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+                return Err(TemplateError::UnknownPlaceholder(name.to_owned()));
+            }
+            if !text.is_empty() {
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+            }
+            parts.push(TemplatePart::Placeholder(name));
+            rest = &rest[end + 1..];
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Check a template can be rendered before it's saved
+pub fn validate_template(template: &str) -> Result<(), TemplateError> {
+    if template.chars().count() > MAX_TEMPLATE_LENGTH {
+        return Err(TemplateError::TooLong);
+    }
+    parse_template(template).map(|_| ())
+}
+
+/// Text from a haiku that can't format the rest of the message or ping anyone
+fn escape_template_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // A zero-width space stops @everyone, @here and <@id> from being read as mentions
+            '@' => escaped.push_str("@\u{200B}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A server's announcement template filled in for a haiku. Mentions are shown but shouldn't
+/// ping anyone, so the message should be sent with no allowed mentions.
+pub fn render_template(template: &str, id: i64, haiku: &Haiku) -> Result<String, TemplateError> {
+    let mut authors = haiku
+        .lines
+        .iter()
+        .map(|line| line.author)
+        .collect::<Vec<UserId>>();
+    let mut seen = HashSet::new();
+    authors.retain(|author| seen.insert(*author));
+    Ok(parse_template(template)?
+        .into_iter()
+        .map(|part| match part {
+            TemplatePart::Text(text) => text,
+            TemplatePart::Placeholder("author") => haiku.lines[0].author.mention().to_string(),
+            TemplatePart::Placeholder("authors") => authors
+                .iter()
+                .map(|author| author.mention().to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            TemplatePart::Placeholder("id") => id.to_string(),
+            TemplatePart::Placeholder("line1") => escape_template_value(&haiku.lines[0].content),
+            TemplatePart::Placeholder("line2") => escape_template_value(&haiku.lines[1].content),
+            TemplatePart::Placeholder("line3") => escape_template_value(&haiku.lines[2].content),
+            TemplatePart::Placeholder("channel") => haiku.channel.mention().to_string(),
+            TemplatePart::Placeholder(_) => String::new(),
+        })
+        .collect())
+}
+
+pub fn format_duration(duration: Duration, locale: Locale) -> String {
+    let days = duration.num_days();
+    let duration = duration - Duration::days(days);
+    let hrs = duration.num_hours();
+    let duration = duration - Duration::hours(hrs);
+    let mins = duration.num_minutes();
+    t!(
+        locale,
+        "duration.format",
+        days = days,
+        hours = hrs,
+        minutes = mins
+    )
+}
+
+/// Read a length of time like "30d", "2w", "6mo" or "1y", up to 100 years. Months count as 30
+/// days and years as 365.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim().to_lowercase();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = text.split_at(split);
+    let amount = amount.parse::<i64>().ok().filter(|amount| *amount > 0)?;
+    let days = match unit.trim() {
+        "d" | "day" | "days" => 1,
+        "w" | "week" | "weeks" => 7,
+        "mo" | "month" | "months" => 30,
+        "y" | "year" | "years" => 365,
+        _ => return None,
+    };
+    let days = amount.checked_mul(days).filter(|days| *days <= 100 * 365)?;
+    Some(Duration::days(days))
+}
+
+/// A whole number with its digits grouped the way they are in the region, e.g. 1,234 or 1.234
+pub fn format_number(number: i64, region: Region) -> String {
+    let digits = number.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            grouped.push_str(region.thousands_separator);
+        }
+        grouped.push(digit);
+    }
+    if number < 0 {
+        grouped.insert(0, '-');
+    }
+    grouped
+}
+
+/// A position like 1st, 2nd or 3rd, the way it's written in the region
+pub fn format_ordinal(number: i64, region: Region) -> String {
+    let formatted = format_number(number, region);
+    match region.ordinals {
+        Ordinals::English => {
+            let suffix = match (number.abs() % 10, number.abs() % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{}{}", formatted, suffix)
+        }
+        Ordinals::French if number == 1 => format!("{}er", formatted),
+        Ordinals::French => format!("{}e", formatted),
+        Ordinals::Period => format!("{}.", formatted),
+        Ordinals::Plain => formatted,
+    }
+}
+
+pub fn format_date(date: NaiveDate, region: Region) -> String {
+    date.format(region.date_format).to_string()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        build_embed_data, format_anonymous_haiku_embed, format_date, format_edit_history,
+        format_haiku, format_haiku_embed, format_haiku_quote, format_number, format_ordinal,
+        haiku_framing, haiku_id_from_footer, parse_duration, render_template, validate_template,
+        wants_plain_text, AuthorDetails, TemplateError, MAX_TEMPLATE_LENGTH,
+    };
+    use crate::{
+        i18n::{Locale, Region},
+        models::{Haiku, HaikuEdit},
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use insta::assert_json_snapshot;
+    use serde_json::Value;
+    use serenity::{
+        builder::CreateEmbed,
+        model::id::{GuildId, UserId},
+        utils::Color,
+    };
+
+    fn author(id: u64, name: &str, avatar: bool, color: Option<u32>) -> AuthorDetails {
+        AuthorDetails {
+            id: UserId(id),
+            display_name: name.to_owned(),
+            avatar_url: if avatar {
+                Some(format!("https://example.com/{}.png", id))
+            } else {
+                None
+            },
+            color: color.map(Color::new),
+        }
+    }
+
+    /// Each line with its author, found in channel 20 of server 10 at the time in the snapshots
+    fn haiku(lines: [(u64, &str); 3]) -> Haiku {
+        HaikuBuilder::new()
+            .server(10)
+            .channel(20)
+            .authors([lines[0].0, lines[1].0, lines[2].0])
+            .lines([lines[0].1, lines[1].1, lines[2].1])
+            .at(Utc.ymd(2021, 3, 14).and_hms(15, 9, 26))
+            .build()
+    }
+
+    fn embed(lines: [(u64, &str); 3], authors: &[AuthorDetails]) -> Value {
+        let haiku = haiku(lines);
+        let embed_data = build_embed_data(
+            42,
+            &haiku,
+            Locale::English,
+            authors,
+            Some("https://example.com/bot.png".to_owned()),
+        );
+        let mut embed = CreateEmbed::default();
+        format_haiku_embed(embed_data, &mut embed);
+        serde_json::to_value(&embed.0).unwrap()
+    }
+
+    #[test]
+    fn test_single_author() {
+        assert_json_snapshot!(
+            "single_author",
+            embed(
+                [
+                    (1, "An old silent pond"),
+                    (1, "A frog jumps into the pond"),
+                    (1, "Splash! Silence again."),
+                ],
+                &[author(1, "Basho", true, Some(0x3498db))],
+            )
+        );
+    }
+
+    #[test]
+    fn test_webhook_author_names() {
+        let mut haiku = haiku([(1, "a"), (5, "b"), (5, "c")]);
+        haiku.lines[1].author_name = Some("alice (IRC)".to_owned());
+        haiku.lines[2].author_name = Some("bob (IRC)".to_owned());
+        let embed_data = build_embed_data(42, &haiku, Locale::English, &[], None);
+        assert_eq!(
+            embed_data.unique_authors,
+            vec!["Unknown User", "alice (IRC)", "bob (IRC)"]
+        );
+    }
+
+    #[test]
+    fn test_multiple_authors() {
+        assert_json_snapshot!(
+            "multiple_authors",
+            embed(
+                [
+                    (2, "The last winter leaves"),
+                    (1, "Clinging to the black branches"),
+                    (2, "Explode into birds"),
+                ],
+                &[
+                    author(1, "Basho", true, Some(0x3498db)),
+                    author(2, "Buson", false, None),
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_long_lines() {
+        assert_json_snapshot!(
+            "long_lines",
+            embed(
+                [
+                    (1, "Unbelievable extraordinary"),
+                    (1, "Incomprehensibility notwithstanding"),
+                    (1, "Antidisestablishmentarianism"),
+                ],
+                &[author(1, "Basho", true, None)],
+            )
+        );
+    }
+
+    #[test]
+    fn test_missing_member() {
+        assert_json_snapshot!(
+            "missing_member",
+            embed(
+                [
+                    (1, "An old silent pond"),
+                    (3, "A frog jumps into the pond"),
+                    (1, "Splash! Silence again."),
+                ],
+                &[author(3, "Issa", true, Some(0xe67e22))],
+            )
+        );
+    }
+
+    #[test]
+    fn test_haiku_id_from_footer() {
+        let embed = embed(
+            [
+                (1, "An old silent pond"),
+                (1, "A frog jumps into the pond"),
+                (1, "Splash! Silence again"),
+            ],
+            &[],
+        );
+        let footer = embed["footer"]["text"].as_str().unwrap();
+        assert_eq!(haiku_id_from_footer(footer), Some(42));
+        assert_eq!(haiku_id_from_footer("Haiku #"), None);
+        assert_eq!(haiku_id_from_footer("Something else"), None);
+    }
+
+    #[test]
+    fn test_format_haiku_quote() {
+        let haiku = haiku([
+            (1, "An old silent pond"),
+            (1, "A frog jumps into the pond"),
+            (1, "Splash! Silence again."),
+        ]);
+        assert_eq!(
+            format_haiku_quote(42, &haiku, Locale::English, false),
+            "> An old silent pond\n> A frog jumps into the pond\n> Splash! Silence again.\n- Haiku #42"
+        );
+        assert_eq!(
+            format_haiku_quote(42, &haiku, Locale::English, true),
+            "> ||An old silent pond||\n> ||A frog jumps into the pond||\n> ||Splash! Silence again.||\n- Haiku #42"
+        );
+    }
+
+    #[test]
+    fn test_format_edit_history() {
+        let haiku = haiku([
+            (1, "An old silent pond"),
+            (1, "A frog leaps into the pond"),
+            (1, "Splash! Silence again."),
+        ]);
+        let edit = |position: usize, previous: &str, content: &str, minute: u32| HaikuEdit {
+            position,
+            previous: previous.to_owned(),
+            content: content.to_owned(),
+            editor: UserId(2),
+            edited_at: Utc.ymd(2021, 3, 15).and_hms(0, minute, 0),
+        };
+        let edits = vec![
+            edit(0, "An old quiet pond", "An old silent pond", 0),
+            edit(
+                1,
+                "A frog jumps into the pond",
+                "A frog leaps into the pond",
+                1,
+            ),
+        ];
+        assert_eq!(
+            format_edit_history(42, &haiku, &edits, Locale::English, false),
+            "**Edit history of haiku #42**\n\
+             <@2> changed line 2 on <t:1615766460:f>\n\
+             ```diff\n  An old silent pond\n- A frog jumps into the pond\n+ A frog leaps into the pond\n  Splash! Silence again.\n```\n\
+             <@2> changed line 1 on <t:1615766400:f>\n\
+             ```diff\n- An old quiet pond\n+ An old silent pond\n  A frog jumps into the pond\n  Splash! Silence again.\n```"
+        );
+        assert!(
+            format_edit_history(42, &haiku, &edits, Locale::English, true)
+                .contains("||```diff\n  An old silent pond")
+        );
+
+        let long = "a".repeat(500);
+        let edits = (0..10)
+            .map(|minute| edit(0, &long, "An old silent pond", minute))
+            .collect::<Vec<HaikuEdit>>();
+        let history = format_edit_history(42, &haiku, &edits, Locale::English, false);
+        assert!(history.len() <= 2000);
+        assert!(history.ends_with("...and 7 older edits"));
+    }
+
+    #[test]
+    fn test_anonymous_embed() {
+        let haiku = haiku([
+            (1, "An old silent pond"),
+            (1, "A frog jumps into the pond"),
+            (1, "Splash! Silence again."),
+        ]);
+        let embed_data = build_embed_data(
+            42,
+            &haiku,
+            Locale::English,
+            &[author(1, "Basho", true, Some(0x1abc9c))],
+            None,
+        );
+        let mut embed = CreateEmbed::default();
+        format_anonymous_haiku_embed(embed_data, &mut embed);
+        let embed = serde_json::to_value(&embed.0).unwrap();
+        assert_eq!(embed["author"]["name"], "Who wrote this?");
+        assert!(!embed.to_string().contains("Basho"));
+        assert!(!embed.to_string().contains("https://example.com/1.png"));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let haiku = haiku([
+            (1, "The last winter leaves"),
+            (2, "Clinging to the black branches"),
+            (1, "Explode into birds"),
+        ]);
+        let embed_data = build_embed_data(
+            42,
+            &haiku,
+            Locale::English,
+            &[
+                author(1, "Basho", true, None),
+                author(2, "Buson", true, None),
+            ],
+            None,
+        );
+        let formatted = format_haiku(embed_data.clone(), true);
+        assert!(formatted.embed().is_none());
+        assert_eq!(
+            formatted.content(None).unwrap(),
+            "**A beautiful haiku has been created!**\nThe last winter leaves\nClinging to the black branches\nExplode into birds\nBy Basho, Buson\nHaiku #42"
+        );
+        assert_eq!(
+            formatted.titled("Who wrote this?".to_owned()).content(Some("Look!")).unwrap(),
+            "Look!\n\n**Who wrote this?**\nThe last winter leaves\nClinging to the black branches\nExplode into birds\nBy Basho, Buson\nHaiku #42"
+        );
+
+        assert_eq!(
+            format_haiku(embed_data.clone().with_content_warning(true), true)
+                .content(None)
+                .unwrap(),
+            "**A beautiful haiku has been created!**\nContent warning\n||The last winter leaves||\n||Clinging to the black branches||\n||Explode into birds||\nBy Basho, Buson\nHaiku #42"
+        );
+
+        // What was around the haiku survives it being shown again
+        let announced = format_haiku(embed_data, true)
+            .titled("This server's 100th haiku!".to_owned())
+            .content(Some("New haiku!\n\nWell done"))
+            .unwrap();
+        let framing = haiku_framing(&announced, true, None);
+        assert_eq!(framing.before.as_deref(), Some("New haiku!\n\nWell done"));
+        assert_eq!(framing.title.as_deref(), Some("This server's 100th haiku!"));
+        let framing = haiku_framing("", false, Some("A beautiful haiku has been created!"));
+        assert_eq!(framing.before, None);
+        assert_eq!(
+            framing.title.as_deref(),
+            Some("A beautiful haiku has been created!")
+        );
+
+        // People's own choice wins over the server's default
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(10));
+        assert!(!wants_plain_text(&repository, UserId(1), Some(GuildId(10))));
+        repository.set_server_plain_text(GuildId(10), true);
+        assert!(wants_plain_text(&repository, UserId(1), Some(GuildId(10))));
+        assert!(!wants_plain_text(&repository, UserId(1), None));
+        repository.set_user_plain_text(UserId(1), Some(false));
+        assert!(!wants_plain_text(&repository, UserId(1), Some(GuildId(10))));
+        repository.set_user_plain_text(UserId(2), Some(true));
+        assert!(wants_plain_text(&repository, UserId(2), None));
+        repository.set_user_plain_text(UserId(1), None);
+        assert!(wants_plain_text(&repository, UserId(1), Some(GuildId(10))));
+    }
+
+    #[test]
+    fn test_render_template() {
+        let haiku = haiku([
+            (1, "An old *silent* pond"),
+            (2, "A frog jumps into the pond"),
+            (1, "Splash! @everyone"),
+        ]);
+        assert_eq!(
+            render_template("New haiku #{id} by {authors} in {channel}!", 42, &haiku).unwrap(),
+            "New haiku #42 by <@1>, <@2> in <#20>!"
+        );
+        assert_eq!(
+            render_template("{{{author}}} said {line1}, then {line3}", 42, &haiku).unwrap(),
+            "{<@1>} said An old \\*silent\\* pond, then Splash! @\u{200B}everyone"
+        );
+        assert_eq!(
+            render_template("{line4}", 42, &haiku),
+            Err(TemplateError::UnknownPlaceholder("line4".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_validate_template() {
+        assert_eq!(validate_template("Haiku #{id} }:)"), Ok(()));
+        assert_eq!(
+            validate_template("By {author"),
+            Err(TemplateError::Unclosed)
+        );
+        assert_eq!(
+            validate_template(&"a".repeat(MAX_TEMPLATE_LENGTH + 1)),
+            Err(TemplateError::TooLong)
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Some(Duration::days(30)));
+        assert_eq!(parse_duration(" 2 weeks "), Some(Duration::days(14)));
+        assert_eq!(parse_duration("6mo"), Some(Duration::days(180)));
+        assert_eq!(parse_duration("1Y"), Some(Duration::days(365)));
+        assert_eq!(parse_duration("0d"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("3 fortnights"), None);
+        assert_eq!(parse_duration("1000y"), None);
+    }
+
+    #[test]
+    fn test_regional_formats() {
+        let us = Region::from_discord("en-US");
+        let german = Region::from_discord("de");
+        let french = Region::from_discord("fr");
+        assert_eq!(format_number(1234567, us), "1,234,567");
+        assert_eq!(format_number(1234, german), "1.234");
+        assert_eq!(format_number(-1234, french), "-1\u{202f}234");
+        assert_eq!(format_number(999, us), "999");
+
+        let ordinals = [1, 2, 3, 4, 11, 12, 13, 21, 112, 1000]
+            .iter()
+            .map(|number| format_ordinal(*number, us))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            ordinals,
+            vec!["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "112th", "1,000th"]
+        );
+        assert_eq!(format_ordinal(1000, german), "1.000.");
+        assert_eq!(format_ordinal(1, french), "1er");
+        assert_eq!(format_ordinal(3, Region::from_discord("ja")), "3");
+
+        let date = NaiveDate::from_ymd(2021, 3, 14);
+        assert_eq!(format_date(date, us), "03/14/2021");
+        assert_eq!(
+            format_date(date, Region::from_discord("en-GB")),
+            "14/03/2021"
+        );
+        assert_eq!(format_date(date, german), "14.03.2021");
+        assert_eq!(Region::from_discord("xx"), us);
+        assert_eq!(Region::for_locale(Locale::German), german);
+    }
+}
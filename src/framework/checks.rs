@@ -0,0 +1,49 @@
+use crate::retry::with_retry;
+use serenity::{
+    client::Context,
+    model::{
+        id::{ChannelId, UserId},
+        Permissions,
+    },
+};
+
+/// Whether the user owns the bot's application, or is on the team that does
+pub async fn is_owner(ctx: &Context, user_id: UserId) -> bool {
+    let info = match with_retry(&ctx.http, || ctx.http.get_current_application_info()).await {
+        Ok(info) => info,
+        Err(why) => {
+            println!("Could not fetch application info: {:?}", why);
+            return false;
+        }
+    };
+    match info.team {
+        Some(team) => team.members.iter().any(|member| member.user.id == user_id),
+        None => info.owner.id == user_id,
+    }
+}
+
+/// What the bot needs in a channel to post haikus there
+pub fn posting_permissions() -> Permissions {
+    Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS
+}
+
+/// Which of the needed permissions the bot doesn't have in a channel. Nothing counts as missing
+/// if the channel isn't cached, since Discord will say so when the bot tries to post anyway.
+pub async fn missing_channel_permissions(
+    ctx: &Context,
+    channel_id: ChannelId,
+    needed: Permissions,
+) -> Permissions {
+    let channel = match ctx.cache.guild_channel(channel_id).await {
+        Some(channel) => channel,
+        None => return Permissions::empty(),
+    };
+    let bot_id = ctx.cache.current_user_id().await;
+    match channel.permissions_for_user(&ctx.cache, bot_id).await {
+        Ok(granted) => needed & !granted,
+        Err(why) => {
+            println!("Could not check permissions in {}: {:?}", channel_id, why);
+            Permissions::empty()
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::MessageComponentInteractionHandlers;
+use dashmap::DashMap;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{channel::Message, interactions::message_component::MessageComponentInteraction},
+    prelude::TypeMapKey,
+};
+use std::sync::Arc;
+
+const PREFIX_SEPARATOR: char = ':';
+
+/// Build a custom_id that will be routed to the handler registered for `prefix`
+pub fn custom_id(prefix: &str, value: &str) -> String {
+    format!("{}{}{}", prefix, PREFIX_SEPARATOR, value)
+}
+
+/// Split a custom_id into its routing prefix and the rest of the id
+pub fn parse_custom_id(custom_id: &str) -> (&str, &str) {
+    match custom_id.split_once(PREFIX_SEPARATOR) {
+        Some((prefix, value)) => (prefix, value),
+        None => (custom_id, ""),
+    }
+}
+
+/// Handles every component whose custom_id starts with the prefix it's routed to. Unlike a
+/// [slash_helper::MessageComponentInteractionHandler] it only borrows itself, so one handler can
+/// serve any number of interactions at once.
+#[async_trait]
+pub trait ComponentHandler {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        original_message: &mut Message,
+    );
+}
+
+/// Routes component interactions to handlers based on their custom_id prefix.
+///
+/// Unlike the per-interaction handlers in [MessageComponentInteractionHandlers],
+/// prefix handlers aren't tied to the command that created the component,
+/// so they keep working on old messages and across restarts.
+#[derive(Default)]
+pub struct ComponentRouter {
+    routes: DashMap<String, Arc<dyn ComponentHandler + Send + Sync>>,
+}
+
+impl TypeMapKey for ComponentRouter {
+    type Value = Arc<ComponentRouter>;
+}
+
+impl ComponentRouter {
+    pub fn route(&self, prefix: &str, handler: Arc<dyn ComponentHandler + Send + Sync>) {
+        self.routes.insert(prefix.to_owned(), handler);
+    }
+
+    pub async fn dispatch(ctx: &Context, interaction: &MessageComponentInteraction) {
+        let (prefix, _) = parse_custom_id(&interaction.data.custom_id);
+        let router = {
+            let data = ctx.data.read().await;
+            data.get::<ComponentRouter>()
+                .expect("Expected ComponentRouter in TypeMap")
+                .clone()
+        };
+        // Neither the route's entry nor the TypeMap stays locked while the handler runs
+        let handler = router
+            .routes
+            .get(prefix)
+            .map(|handler| Arc::clone(handler.value()));
+        if let Some(handler) = handler {
+            handler
+                .invoke(ctx, interaction, &mut interaction.message.clone())
+                .await;
+            return;
+        }
+
+        // Fall back to a handler registered by the command that sent the message
+        let handler = {
+            let data = ctx.data.read().await;
+            let handlers = data
+                .get::<MessageComponentInteractionHandlers>()
+                .expect("Expected Handlers in TypeMap");
+            interaction
+                .message
+                .interaction
+                .as_ref()
+                .and_then(|original_interaction| handlers.get(&original_interaction.id))
+                .map(|handler| Arc::clone(handler.value()))
+        };
+        match handler {
+            Some(handler) => {
+                handler
+                    .lock()
+                    .await
+                    .invoke(ctx, interaction, &mut interaction.message.clone())
+                    .await
+            }
+            None => println!(
+                "No handler found for component {}",
+                interaction.data.custom_id
+            ),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Extensions to the slash-helper command framework that the bot's commands build on
+
+pub mod checks;
+pub mod components;
+pub mod options;
+pub mod pagination;
+pub mod progress;
+pub mod response;
+pub mod spec;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,115 @@
+use serde_json::Value;
+use serenity::model::{
+    channel::PartialChannel,
+    guild::Role,
+    interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+        ApplicationCommandInteractionDataOptionValue,
+    },
+    user::User,
+};
+
+/// A type that can be read out of a command option.
+///
+/// slash-helper only parses String and Integer fields, so options of any other type are
+/// registered through a [super::spec::OptionSpec] and read in the command with [get_option].
+pub trait FromOption: Sized {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self>;
+}
+
+impl FromOption for String {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromOption for i64 {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromOption for bool {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromOption for f64 {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::Number(value) => Some(*value),
+            // Discord sends whole numbers for Number options without a decimal point
+            ApplicationCommandInteractionDataOptionValue::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromOption for User {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::User(user, _) => Some(user.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromOption for PartialChannel {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::Channel(channel) => Some(channel.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromOption for Role {
+    fn from_option(value: &ApplicationCommandInteractionDataOptionValue) -> Option<Self> {
+        match value {
+            ApplicationCommandInteractionDataOptionValue::Role(role) => Some(role.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Read an option from the command, or None if it wasn't given or has the wrong type
+pub fn get_option<T: FromOption>(command: &ApplicationCommandInteraction, name: &str) -> Option<T> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .map(|option| option.resolved.as_ref())
+        .flatten()
+        .map(T::from_option)
+        .flatten()
+}
+
+/// Fill in the resolved value of an option built from a plain value, as Discord would have
+pub fn resolve(option: &mut ApplicationCommandInteractionDataOption) {
+    option.resolved = match &option.value {
+        Some(Value::String(value)) => Some(ApplicationCommandInteractionDataOptionValue::String(
+            value.clone(),
+        )),
+        Some(Value::Bool(value)) => Some(ApplicationCommandInteractionDataOptionValue::Boolean(
+            *value,
+        )),
+        Some(Value::Number(value)) => match value.as_i64() {
+            Some(value) => Some(ApplicationCommandInteractionDataOptionValue::Integer(value)),
+            None => value
+                .as_f64()
+                .map(ApplicationCommandInteractionDataOptionValue::Number),
+        },
+        _ => None,
+    };
+}
@@ -0,0 +1,203 @@
+//! Messages that page through results with First, Previous, Next and Last buttons
+
+use crate::{
+    framework::response::respond, i18n::Locale, retry::with_retry,
+    MessageComponentInteractionHandlers,
+};
+use serenity::{
+    async_trait,
+    builder::{CreateComponents, CreateEmbed},
+    client::Context,
+    model::{
+        channel::Message,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionResponseType,
+        },
+    },
+    prelude::Mutex,
+};
+use slash_helper::MessageComponentInteractionHandler;
+use std::sync::Arc;
+
+/// The page buttons' custom_ids and labels, in the order they're shown
+const PAGE_BUTTONS: &[(&str, &str)] = &[
+    ("first", "pagination.first"),
+    ("previous", "pagination.previous"),
+    ("next", "pagination.next"),
+    ("last", "pagination.last"),
+];
+
+/// What's shown for one page
+#[derive(Default)]
+pub struct Page {
+    pub content: Option<String>,
+    /// Left out for pages shown as plain text
+    pub embed: Option<CreateEmbed>,
+    /// Rows shown under the page buttons, e.g. buttons for the haiku on the page
+    pub components: CreateComponents,
+}
+
+/// Something that can be shown a page at a time
+#[async_trait]
+pub trait Pages: Send + Sync {
+    /// How many pages there are, as of the last page shown
+    fn page_count(&self) -> usize;
+
+    /// Show a page, which may be loaded afresh each time
+    async fn show(&mut self, ctx: &Context, page: usize) -> Page;
+}
+
+/// The page a button leads to from the current one, if it leads anywhere
+pub fn target_page(custom_id: &str, current: usize, page_count: usize) -> Option<usize> {
+    let last = page_count.checked_sub(1)?;
+    let target = match custom_id {
+        "first" => 0,
+        "previous" => current.checked_sub(1)?,
+        "next" => current + 1,
+        "last" => last,
+        _ => return None,
+    };
+    Some(target).filter(|target| *target <= last && *target != current)
+}
+
+/// Sends the first page in reply to a command, then turns the pages as the buttons are pressed
+pub struct Paginator<P: Pages> {
+    pages: P,
+    page: usize,
+    locale: Locale,
+}
+
+impl<P: Pages + 'static> Paginator<P> {
+    pub fn new(pages: P, locale: Locale) -> Self {
+        Paginator {
+            pages,
+            page: 0,
+            locale,
+        }
+    }
+
+    /// Reply to the command with the first page. The buttons are only added, and only handled, if
+    /// there's more than one page.
+    pub async fn send(mut self, ctx: &Context, command: &ApplicationCommandInteraction) {
+        let page = self.pages.show(ctx, self.page).await;
+        let paged = self.pages.page_count() > 1;
+        let result = respond(ctx, command, |message| {
+            if let Some(content) = &page.content {
+                message
+                    .content(content)
+                    .allowed_mentions(|mentions| mentions.empty_parse());
+            }
+            if let Some(embed) = &page.embed {
+                message.add_embed(embed.clone());
+            }
+            message.components(|components| {
+                if paged {
+                    self.buttons(components);
+                }
+                components.0.extend(page.components.0.clone());
+                components
+            })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to send page: {:?}", why);
+        }
+        if paged {
+            let data = ctx.data.read().await;
+            let handlers = data
+                .get::<MessageComponentInteractionHandlers>()
+                .expect("Expected Handlers in TypeMap");
+            handlers.insert(command.id, Arc::new(Mutex::new(Box::new(self))));
+        }
+    }
+
+    fn buttons<'a>(&self, components: &'a mut CreateComponents) -> &'a mut CreateComponents {
+        let locale = self.locale;
+        let page_count = self.pages.page_count();
+        components.create_action_row(|row| {
+            for (custom_id, label) in PAGE_BUTTONS {
+                row.create_button(|button| {
+                    button
+                        .custom_id(custom_id)
+                        .label(t!(locale, label))
+                        .style(ButtonStyle::Primary)
+                        .disabled(target_page(custom_id, self.page, page_count).is_none())
+                });
+            }
+            row
+        })
+    }
+}
+
+#[async_trait]
+impl<P: Pages + 'static> MessageComponentInteractionHandler for Paginator<P> {
+    async fn invoke(
+        &mut self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        original_message: &mut Message,
+    ) {
+        let target = target_page(
+            &interaction.data.custom_id,
+            self.page,
+            self.pages.page_count(),
+        );
+        let target = match target {
+            Some(target) => target,
+            None => return,
+        };
+        let page = self.pages.show(ctx, target).await;
+        self.page = target;
+        let result = with_retry(&ctx.http, || {
+            interaction
+                .channel_id
+                .edit_message(&ctx.http, original_message.id, |message| {
+                    if let Some(content) = &page.content {
+                        message.content(content);
+                    }
+                    message
+                        .set_embeds(page.embed.iter().cloned().collect())
+                        .components(|components| {
+                            self.buttons(components);
+                            components.0.extend(page.components.0.clone());
+                            components
+                        })
+                })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to send page: {:?}", why);
+        }
+        let result = with_retry(&ctx.http, || {
+            interaction.create_interaction_response(&ctx.http, |response| {
+                response.kind(InteractionResponseType::UpdateMessage)
+            })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to respond to component interaction: {:?}", why);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::target_page;
+
+    #[test]
+    fn test_target_page() {
+        assert_eq!(target_page("next", 0, 3), Some(1));
+        assert_eq!(target_page("next", 2, 3), None);
+        assert_eq!(target_page("previous", 2, 3), Some(1));
+        assert_eq!(target_page("previous", 0, 3), None);
+        assert_eq!(target_page("first", 2, 3), Some(0));
+        assert_eq!(target_page("last", 0, 3), Some(2));
+        // Already there
+        assert_eq!(target_page("first", 0, 3), None);
+        assert_eq!(target_page("last", 2, 3), None);
+        assert_eq!(target_page("next", 0, 0), None);
+        assert_eq!(target_page("haiku:vote:5", 0, 3), None);
+    }
+}
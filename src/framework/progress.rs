@@ -0,0 +1,361 @@
+//! Reporting how far a long-running command has got, by editing its response now and then with
+//! how much is done and roughly how long is left, along with a button to cancel it
+
+use crate::{
+    formatting::format_duration,
+    framework::{
+        components::{custom_id, parse_custom_id, ComponentHandler},
+        response::{respond, TOKEN_LIFETIME},
+    },
+    i18n::{guild_locale, Locale},
+    repository::repository,
+    retry::with_retry,
+};
+use dashmap::DashMap;
+use serenity::{
+    async_trait,
+    builder::CreateComponents,
+    client::Context,
+    model::{
+        channel::Message,
+        id::{InteractionId, MessageId},
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+    prelude::{Mutex, TypeMapKey},
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+pub const PROGRESS_COMPONENT_PREFIX: &str = "progress";
+/// How often the response is edited, which keeps well clear of Discord's rate limits
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The operations that are still running, by the command that started them
+pub struct RunningOperations;
+impl TypeMapKey for RunningOperations {
+    type Value = Arc<DashMap<InteractionId, Arc<Progress>>>;
+}
+
+/// How much of an operation is done out of its total, which can be in any unit, e.g. haikus
+/// checked or seconds of history scanned
+pub struct Progress {
+    command: ApplicationCommandInteraction,
+    message_id: MessageId,
+    /// Whether only whoever ran the command can see the report, which can't be edited once the
+    /// interaction expires
+    private: bool,
+    locale: Locale,
+    started: Instant,
+    /// Where the operation started from, if it's carrying on from an earlier run
+    resumed_from: u64,
+    done: AtomicU64,
+    total: u64,
+    detail: std::sync::Mutex<String>,
+    cancelled: AtomicBool,
+    /// Held while the response is edited, so the final edit can't be overwritten by a late update
+    finished: Mutex<bool>,
+}
+
+/// The percentage done, and how long the rest should take going by the rate so far
+pub fn estimate(
+    resumed_from: u64,
+    done: u64,
+    total: u64,
+    elapsed: Duration,
+) -> (u64, Option<Duration>) {
+    if total == 0 {
+        return (100, None);
+    }
+    let done = done.min(total);
+    let percent = done * 100 / total;
+    let progressed = done.saturating_sub(resumed_from);
+    if progressed == 0 {
+        return (percent, None);
+    }
+    let remaining = elapsed.as_secs_f64() * (total - done) as f64 / progressed as f64;
+    (percent, Some(Duration::from_secs_f64(remaining)))
+}
+
+fn cancel_button(
+    components: &mut CreateComponents,
+    command_id: InteractionId,
+    locale: Locale,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id(custom_id(
+                    PROGRESS_COMPONENT_PREFIX,
+                    &command_id.to_string(),
+                ))
+                .label(t!(locale, "progress.cancel"))
+                .style(ButtonStyle::Danger)
+        })
+    })
+}
+
+impl Progress {
+    /// Respond to a command with the first progress report, then keep it up to date until the
+    /// operation is finished. Returns `None` if the response couldn't be sent.
+    pub async fn start(
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        private: bool,
+        locale: Locale,
+        detail: String,
+        done: u64,
+        total: u64,
+    ) -> Option<Arc<Progress>> {
+        let content = format!(
+            "{}\n{}",
+            detail,
+            status(locale, 0, done, total, Duration::ZERO)
+        );
+        let result = respond(ctx, command, |message| {
+            if private {
+                message.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL);
+            }
+            message
+                .content(content)
+                .components(|components| cancel_button(components, command.id, locale))
+        })
+        .await;
+        let message = match result {
+            Ok(()) => with_retry(&ctx.http, || command.get_interaction_response(&ctx.http)).await,
+            Err(why) => Err(why),
+        };
+        let message = match message {
+            Ok(message) => message,
+            Err(why) => {
+                println!("Could not send progress report: {:?}", why);
+                return None;
+            }
+        };
+        let progress = Arc::new(Progress {
+            command: command.clone(),
+            message_id: message.id,
+            private,
+            locale,
+            started: Instant::now(),
+            resumed_from: done,
+            done: AtomicU64::new(done),
+            total,
+            detail: std::sync::Mutex::new(detail),
+            cancelled: AtomicBool::new(false),
+            finished: Mutex::new(false),
+        });
+        {
+            let data = ctx.data.read().await;
+            data.get::<RunningOperations>()
+                .expect("Expected RunningOperations in TypeMap")
+                .insert(command.id, progress.clone());
+        }
+        tokio::spawn(keep_updated(ctx.clone(), progress.clone()));
+        Some(progress)
+    }
+
+    /// Record how much is done and what to say about it. Doesn't wait, so it can be called from
+    /// blocking code.
+    pub fn update(&self, done: u64, detail: String) {
+        self.done.store(done, Ordering::SeqCst);
+        *self.detail.lock().expect("Progress detail lock poisoned") = detail;
+    }
+
+    /// Whether someone's asked for the operation to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Replace the progress report with what the operation ended up doing, and stop updating it
+    pub async fn finish(&self, ctx: &Context, content: String) {
+        let mut finished = self.finished.lock().await;
+        *finished = true;
+        if let Err(why) = self.edit(ctx, &content, false).await {
+            println!("Could not edit final progress report: {:?}", why);
+            // Most likely the interaction expired and the report was private, so it's sent again
+            let result = respond(ctx, &self.command, |message| message.content(&content)).await;
+            if let Err(why) = result {
+                println!("Could not send final progress report: {:?}", why);
+            }
+        }
+        drop(finished);
+        let data = ctx.data.read().await;
+        data.get::<RunningOperations>()
+            .expect("Expected RunningOperations in TypeMap")
+            .remove(&self.command.id);
+    }
+
+    fn report(&self) -> String {
+        let detail = self
+            .detail
+            .lock()
+            .expect("Progress detail lock poisoned")
+            .clone();
+        let status = status(
+            self.locale,
+            self.resumed_from,
+            self.done.load(Ordering::SeqCst),
+            self.total,
+            self.started.elapsed(),
+        );
+        format!("{}\n{}", detail, status)
+    }
+
+    /// Edit the response through the interaction while it's valid, which works even if only
+    /// whoever ran the command can see it, then through the channel
+    async fn edit(&self, ctx: &Context, content: &str, cancellable: bool) -> serenity::Result<()> {
+        let command_id = self.command.id;
+        let locale = self.locale;
+        if self.started.elapsed() < TOKEN_LIFETIME {
+            with_retry(&ctx.http, || {
+                self.command
+                    .edit_original_interaction_response(&ctx.http, |response| {
+                        response.content(content).components(|components| {
+                            if cancellable {
+                                cancel_button(components, command_id, locale);
+                            }
+                            components
+                        })
+                    })
+            })
+            .await
+            .map(|_| ())
+        } else {
+            with_retry(&ctx.http, || {
+                self.command
+                    .channel_id
+                    .edit_message(&ctx.http, self.message_id, |message| {
+                        message.content(content).components(|components| {
+                            if cancellable {
+                                cancel_button(components, command_id, locale);
+                            }
+                            components
+                        })
+                    })
+            })
+            .await
+            .map(|_| ())
+        }
+    }
+}
+
+fn status(locale: Locale, resumed_from: u64, done: u64, total: u64, elapsed: Duration) -> String {
+    match estimate(resumed_from, done, total, elapsed) {
+        (percent, Some(remaining)) => t!(
+            locale,
+            "progress.status",
+            percent = percent,
+            remaining = format_duration(
+                chrono::Duration::from_std(remaining)
+                    .unwrap_or_else(|_| chrono::Duration::max_value()),
+                locale
+            )
+        ),
+        (percent, None) => t!(locale, "progress.status_no_estimate", percent = percent),
+    }
+}
+
+/// Edit the progress report every so often until the operation's finished
+async fn keep_updated(ctx: Context, progress: Arc<Progress>) {
+    loop {
+        tokio::time::sleep(UPDATE_INTERVAL).await;
+        let finished = progress.finished.lock().await;
+        if *finished {
+            break;
+        }
+        if progress.private && progress.started.elapsed() >= TOKEN_LIFETIME {
+            continue;
+        }
+        if let Err(why) = progress.edit(&ctx, &progress.report(), true).await {
+            println!("Could not update progress report: {:?}", why);
+        }
+    }
+}
+
+/// Cancels an operation when whoever started it presses its Cancel button
+pub struct CancelHandler;
+
+#[async_trait]
+impl ComponentHandler for CancelHandler {
+    async fn invoke(
+        &self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        _original_message: &mut Message,
+    ) {
+        let (_, command_id) = parse_custom_id(&interaction.data.custom_id);
+        let progress = {
+            let data = ctx.data.read().await;
+            command_id.parse::<u64>().ok().and_then(|command_id| {
+                data.get::<RunningOperations>()
+                    .expect("Expected RunningOperations in TypeMap")
+                    .get(&InteractionId(command_id))
+                    .map(|progress| progress.clone())
+            })
+        };
+        let refusal = match progress {
+            Some(progress) if interaction.user.id == progress.command.user.id => {
+                // The operation stops at the next point it checks, then says so
+                progress.cancelled.store(true, Ordering::SeqCst);
+                None
+            }
+            Some(progress) => Some(t!(progress.locale, "progress.not_yours")),
+            None => {
+                let repository = repository(ctx).await;
+                let locale = guild_locale(&*repository, interaction.guild_id);
+                Some(t!(locale, "progress.not_running"))
+            }
+        };
+        let result = with_retry(&ctx.http, || {
+            interaction.create_interaction_response(&ctx.http, |response| match &refusal {
+                Some(refusal) => response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(refusal)
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    }),
+                None => response.kind(InteractionResponseType::DeferredUpdateMessage),
+            })
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Failed to respond to cancel button: {:?}", why);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::estimate;
+    use std::time::Duration;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(estimate(0, 0, 100, Duration::from_secs(10)), (0, None));
+        assert_eq!(
+            estimate(0, 25, 100, Duration::from_secs(10)),
+            (25, Some(Duration::from_secs(30)))
+        );
+        // Only the progress made since resuming counts towards the rate
+        assert_eq!(
+            estimate(50, 75, 100, Duration::from_secs(10)),
+            (75, Some(Duration::from_secs(10)))
+        );
+        assert_eq!(estimate(50, 50, 100, Duration::from_secs(10)), (50, None));
+        assert_eq!(
+            estimate(0, 120, 100, Duration::from_secs(10)),
+            (100, Some(Duration::ZERO))
+        );
+        assert_eq!(estimate(0, 0, 0, Duration::from_secs(10)), (100, None));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::retry::with_retry;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serenity::{
+    builder::CreateInteractionResponseData,
+    client::Context,
+    model::{
+        id::InteractionId,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+    prelude::{Mutex, TypeMapKey},
+};
+use std::{sync::Arc, time::Duration};
+
+/// Discord requires an initial response within 3 seconds, so defer a little before then
+const DEFER_AFTER: Duration = Duration::from_millis(2500);
+/// After this long the interaction token expires and only plain channel messages work
+pub const TOKEN_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResponseStage {
+    Pending,
+    Deferred,
+    Responded,
+}
+
+#[derive(Debug)]
+pub struct ResponseState {
+    stage: ResponseStage,
+}
+
+impl ResponseState {
+    fn new() -> Self {
+        ResponseState {
+            stage: ResponseStage::Pending,
+        }
+    }
+}
+
+pub struct InteractionResponses;
+impl TypeMapKey for InteractionResponses {
+    type Value = Arc<DashMap<InteractionId, Arc<Mutex<ResponseState>>>>;
+}
+
+/// Start watching an incoming command so that it's deferred if it hasn't responded
+/// by the time Discord's initial response deadline is about to pass. Deferring can't know whether
+/// the response will be ephemeral, so [respond] deals with that once it does.
+pub async fn guard(ctx: &Context, command: &ApplicationCommandInteraction) {
+    let responses = {
+        let data = ctx.data.read().await;
+        data.get::<InteractionResponses>()
+            .expect("Expected InteractionResponses in TypeMap")
+            .clone()
+    };
+    let state = Arc::new(Mutex::new(ResponseState::new()));
+    responses.insert(command.id, state.clone());
+
+    let ctx = ctx.clone();
+    let command = command.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(DEFER_AFTER).await;
+        {
+            let mut state = state.lock().await;
+            if state.stage == ResponseStage::Pending {
+                let result = with_retry(&ctx.http, || {
+                    command.create_interaction_response(&ctx.http, |response| {
+                        response.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    })
+                })
+                .await;
+                match result {
+                    Ok(_) => state.stage = ResponseStage::Deferred,
+                    Err(why) => println!("Could not defer slow command: {:?}", why),
+                }
+            }
+        }
+        tokio::time::sleep(TOKEN_LIFETIME - DEFER_AFTER).await;
+        responses.remove(&command.id);
+    });
+}
+
+/// Whether a response is only shown to whoever ran the command
+fn is_ephemeral(data: &CreateInteractionResponseData) -> bool {
+    let ephemeral = InteractionApplicationCommandCallbackDataFlags::EPHEMERAL.bits();
+    data.0
+        .get("flags")
+        .and_then(|flags| flags.as_u64())
+        .map_or(false, |flags| flags & ephemeral != 0)
+}
+
+/// Whether the token of an interaction created at the given time has expired by now
+fn token_expired(created_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    let lifetime = chrono::Duration::from_std(TOKEN_LIFETIME).expect("Token lifetime out of range");
+    now - created_at >= lifetime
+}
+
+/// Respond to a command, using whichever kind of response is still valid:
+/// an initial response, an edit of the deferred response, a follow-up message,
+/// or once the interaction has expired, a plain message in the channel.
+/// Ephemeral responses to expired interactions are sent by DM so they stay private.
+pub async fn respond<F>(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    f: F,
+) -> serenity::Result<()>
+where
+    F: FnOnce(&mut CreateInteractionResponseData) -> &mut CreateInteractionResponseData,
+{
+    let mut data = CreateInteractionResponseData::default();
+    f(&mut data);
+
+    let state = {
+        let data = ctx.data.read().await;
+        data.get::<InteractionResponses>()
+            .expect("Expected InteractionResponses in TypeMap")
+            .get(&command.id)
+            .map(|state| state.clone())
+    }
+    .unwrap_or(Arc::new(Mutex::new(ResponseState::new())));
+    let mut state = state.lock().await;
+
+    // Interaction ids are snowflakes, so they know when the command was run even once the
+    // guard has forgotten it
+    if token_expired(command.id.created_at(), Utc::now()) {
+        let mut message = data.0.clone();
+        if is_ephemeral(&data) {
+            // Only whoever ran the command can see a DM, so the flag isn't needed there
+            message.remove("flags");
+            with_retry(&ctx.http, || {
+                command.user.direct_message(&ctx.http, |msg| {
+                    msg.0 = message.clone();
+                    msg
+                })
+            })
+            .await?;
+        } else {
+            with_retry(&ctx.http, || {
+                command.channel_id.send_message(&ctx.http, |msg| {
+                    msg.0 = message.clone();
+                    msg
+                })
+            })
+            .await?;
+        }
+    } else {
+        match state.stage {
+            ResponseStage::Pending => {
+                with_retry(&ctx.http, || {
+                    command.create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::ChannelMessageWithSource)
+                            .interaction_response_data(|message| {
+                                *message = data.clone();
+                                message
+                            })
+                    })
+                })
+                .await?;
+            }
+            // The deferred response is visible to everyone, so an ephemeral response can't
+            // replace it. It's sent as a follow-up instead and the deferred one is deleted.
+            ResponseStage::Deferred if is_ephemeral(&data) => {
+                with_retry(&ctx.http, || {
+                    command.create_followup_message(&ctx.http, |followup| {
+                        followup.0 = data.0.clone();
+                        followup
+                    })
+                })
+                .await?;
+                with_retry(&ctx.http, || {
+                    command.delete_original_interaction_response(&ctx.http)
+                })
+                .await?;
+            }
+            ResponseStage::Deferred => {
+                with_retry(&ctx.http, || {
+                    command.edit_original_interaction_response(&ctx.http, |response| {
+                        response.0 = data.0.clone();
+                        response
+                    })
+                })
+                .await?;
+            }
+            ResponseStage::Responded => {
+                with_retry(&ctx.http, || {
+                    command.create_followup_message(&ctx.http, |followup| {
+                        followup.0 = data.0.clone();
+                        followup
+                    })
+                })
+                .await?;
+            }
+        }
+    }
+    state.stage = ResponseStage::Responded;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_ephemeral, token_expired};
+    use chrono::{Duration, TimeZone, Utc};
+    use serenity::{
+        builder::CreateInteractionResponseData,
+        model::interactions::InteractionApplicationCommandCallbackDataFlags,
+    };
+
+    #[test]
+    fn test_is_ephemeral() {
+        let mut data = CreateInteractionResponseData::default();
+        data.content("Hello");
+        assert!(!is_ephemeral(&data));
+        data.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL);
+        assert!(is_ephemeral(&data));
+    }
+
+    #[test]
+    fn test_token_expired() {
+        let created_at = Utc.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        assert!(!token_expired(created_at, created_at));
+        assert!(!token_expired(
+            created_at,
+            created_at + Duration::minutes(14)
+        ));
+        assert!(token_expired(
+            created_at,
+            created_at + Duration::minutes(15)
+        ));
+    }
+}
@@ -0,0 +1,582 @@
+use super::options::resolve;
+use crate::{config::CommandsConfig, i18n::localizations, retry::with_retry};
+use serde_json::{json, Value};
+use serenity::{
+    builder::CreateApplicationCommand,
+    client::Context,
+    model::{
+        id::GuildId,
+        interactions::application_command::{
+            ApplicationCommand, ApplicationCommandInteraction,
+            ApplicationCommandInteractionDataOption, ApplicationCommandOptionType,
+        },
+        Permissions,
+    },
+    prelude::TypeMapKey,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Extra metadata for an option that slash-helper derives from a command struct field,
+/// or an option of a type slash-helper can't derive at all.
+///
+/// slash-helper only knows the field name, its type and its doc comment,
+/// and treats every option as required.
+pub struct OptionSpec {
+    field: &'static str,
+    kind: Option<ApplicationCommandOptionType>,
+    name: Option<&'static str>,
+    description: Option<&'static str>,
+    default: Option<Value>,
+    choices: Vec<(&'static str, &'static str)>,
+}
+
+/// An option that can only take one of a fixed set of values, which Discord shows as a dropdown
+pub trait Choice: Sized {
+    /// The name shown to users and the value sent back for each choice
+    fn choices() -> Vec<(&'static str, &'static str)>;
+
+    fn from_value(value: &str) -> Option<Self>;
+}
+
+impl OptionSpec {
+    pub fn new(field: &'static str) -> Self {
+        OptionSpec {
+            field,
+            kind: None,
+            name: None,
+            description: None,
+            default: None,
+            choices: Vec::new(),
+        }
+    }
+
+    /// An optional option with no matching struct field, for types slash-helper can't parse
+    /// (e.g. users or channels) or for options that may be left out without a default.
+    /// Read it in the command with [super::options::get_option], which gives None if it's missing.
+    pub fn extra(
+        field: &'static str,
+        kind: ApplicationCommandOptionType,
+        description: &'static str,
+    ) -> Self {
+        let mut option = OptionSpec::new(field).description(description);
+        option.kind = Some(kind);
+        option
+    }
+
+    /// Show the option to users under a different name than the struct field
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Replace the description taken from the field's doc comment
+    pub fn description(mut self, description: &'static str) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Make the option optional, filling in this value when the user leaves it out
+    pub fn default(mut self, value: impl Into<Value>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Only allow the values of a [Choice], to be parsed from the field with [Choice::from_value]
+    pub fn choices<C: Choice>(mut self) -> Self {
+        self.choices = C::choices();
+        self
+    }
+
+    fn user_facing_name(&self) -> &'static str {
+        self.name.unwrap_or(self.field)
+    }
+}
+
+pub struct CommandSpec {
+    name: &'static str,
+    path: Vec<&'static str>,
+    options: Vec<OptionSpec>,
+    permissions: Permissions,
+    owner_only: bool,
+}
+
+impl CommandSpec {
+    pub fn new(name: &'static str) -> Self {
+        CommandSpec {
+            name,
+            path: vec![name],
+            options: Vec::new(),
+            permissions: Permissions::empty(),
+            owner_only: false,
+        }
+    }
+
+    /// Show the command to users as a subcommand, e.g. `&["config", "set"]` for `/config set`
+    /// or `&["word", "list", "add"]` for a subcommand inside a subcommand group.
+    ///
+    /// Each parent in the path needs a description registered with [CommandSpecs::group].
+    pub fn subcommand(mut self, path: &[&'static str]) -> Self {
+        assert!(
+            (2..=3).contains(&path.len()),
+            "Subcommands must be nested one or two levels deep"
+        );
+        self.path = path.to_vec();
+        self
+    }
+
+    pub fn option(mut self, option: OptionSpec) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    /// Only let members with these permissions use the command.
+    ///
+    /// Discord hides the command from everyone else, but server admins can override that,
+    /// so the permissions are checked again before the command runs.
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Only let the bot's owner use the command. The command is hidden from everyone
+    /// but server admins, since Discord has no way to show it to just the owner.
+    pub fn owner_only(mut self) -> Self {
+        self.owner_only = true;
+        self
+    }
+
+    fn apply(&self, options: &mut Vec<Value>) {
+        // A misspelt field would otherwise leave the option as slash-helper derived it
+        for spec in self.options.iter().filter(|spec| spec.kind.is_none()) {
+            assert!(
+                options.iter().any(|option| option["name"] == spec.field),
+                "The spec for /{} has an option {} that the command doesn't",
+                self.name,
+                spec.field
+            );
+        }
+        for spec in self.options.iter() {
+            if let Some(kind) = spec.kind {
+                options.push(json!({
+                    "type": kind,
+                    "name": spec.field,
+                    "description": spec.description,
+                    "required": false,
+                }));
+            }
+        }
+        for option in options.iter_mut() {
+            let field = option["name"].as_str().unwrap_or_default().to_owned();
+            if let Some(spec) = self.options.iter().find(|spec| spec.field == field) {
+                option["name"] = json!(spec.user_facing_name());
+                if let Some(description) = spec.description {
+                    option["description"] = json!(description);
+                }
+                if spec.default.is_some() {
+                    option["required"] = json!(false);
+                }
+                if !spec.choices.is_empty() {
+                    option["choices"] = spec
+                        .choices
+                        .iter()
+                        .map(|(name, value)| json!({ "name": name, "value": value }))
+                        .collect();
+                }
+            }
+        }
+        // Discord rejects commands that list an optional option before a required one
+        options.sort_by_key(|option| !option["required"].as_bool().unwrap_or(false));
+    }
+
+    fn prepare(&self, options: &mut Vec<ApplicationCommandInteractionDataOption>) {
+        for spec in self.options.iter() {
+            let provided = options
+                .iter_mut()
+                .find(|option| option.name == spec.user_facing_name());
+            match (provided, &spec.default) {
+                (Some(option), _) => option.name = spec.field.to_owned(),
+                (None, Some(default)) => {
+                    let mut option = serde_json::from_value(json!({
+                        "name": spec.field,
+                        "type": option_type(default),
+                        "value": default,
+                    }))
+                    .expect("Invalid default option value");
+                    resolve(&mut option);
+                    options.push(option);
+                }
+                (None, None) => (),
+            }
+        }
+    }
+}
+
+const SUB_COMMAND: u8 = 1;
+const SUB_COMMAND_GROUP: u8 = 2;
+
+/// Translations of a command's description, from the locale catalogs
+fn description_localizations(path: &[&str]) -> Value {
+    json!(localizations(&format!(
+        "command_descriptions.{}",
+        path.join(" ")
+    )))
+}
+
+/// Discord's option type for a default value
+pub(super) fn option_type(value: &Value) -> u8 {
+    match value {
+        Value::Bool(_) => 5,
+        Value::Number(number) if number.is_i64() => 4,
+        Value::Number(_) => 10,
+        _ => 3,
+    }
+}
+
+struct TopLevelCommand {
+    name: String,
+    description: String,
+    options: Vec<Value>,
+    permissions: Permissions,
+}
+
+/// The specs for every command, keyed by command name
+pub struct CommandSpecs {
+    commands: HashMap<&'static str, CommandSpec>,
+    group_descriptions: HashMap<String, &'static str>,
+    /// The names top level commands are registered under, where they differ from their own
+    registered_names: HashMap<&'static str, String>,
+}
+
+/// Whether Discord accepts a command name
+fn is_valid_command_name(name: &str) -> bool {
+    (1..=32).contains(&name.chars().count())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+impl TypeMapKey for CommandSpecs {
+    type Value = Arc<CommandSpecs>;
+}
+
+impl CommandSpecs {
+    pub fn new(specs: Vec<CommandSpec>) -> Self {
+        CommandSpecs {
+            commands: specs.into_iter().map(|spec| (spec.name, spec)).collect(),
+            group_descriptions: HashMap::new(),
+            registered_names: HashMap::new(),
+        }
+    }
+
+    /// Register top level commands under the names in the config, e.g. `/haiku-search` for
+    /// `/search`, so they don't clash with other bots' commands in the same server
+    pub fn named(mut self, config: &CommandsConfig) -> Self {
+        let mut registered_names = HashMap::new();
+        for name in self.top_level_names() {
+            let registered = config
+                .rename
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| format!("{}{}", config.prefix, name));
+            assert!(
+                is_valid_command_name(&registered),
+                "Invalid command name /{}, names can only have lowercase letters, numbers, - and _",
+                registered
+            );
+            if registered != name {
+                registered_names.insert(name, registered);
+            }
+        }
+        let mut names = self
+            .top_level_names()
+            .into_iter()
+            .map(|name| registered_names.get(name).map_or(name, String::as_str))
+            .collect::<Vec<&str>>();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(
+            names.len(),
+            self.top_level_names().len(),
+            "Two commands were given the same name"
+        );
+        self.registered_names = registered_names;
+        self
+    }
+
+    /// The name a top level command is registered under
+    pub fn registered_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.registered_names.get(name).map_or(name, String::as_str)
+    }
+
+    /// The top level command registered under a name
+    pub fn original_name<'a>(&'a self, registered: &'a str) -> &'a str {
+        self.registered_names
+            .iter()
+            .find(|(_, name)| *name == registered)
+            .map_or(registered, |(name, _)| *name)
+    }
+
+    /// Describe a command or subcommand group that only exists to hold subcommands
+    pub fn group(mut self, path: &[&'static str], description: &'static str) -> Self {
+        self.group_descriptions.insert(path.join(" "), description);
+        self
+    }
+
+    /// Move top level commands under a single command as its subcommands, e.g. `/gethaiku` to
+    /// `/haiku get`, for servers that would rather have fewer commands in the list
+    pub fn umbrella(
+        mut self,
+        group: &'static str,
+        description: &'static str,
+        commands: &[(&'static str, &'static str)],
+    ) -> Self {
+        for &(name, subcommand) in commands {
+            let spec = self
+                .commands
+                .entry(name)
+                .or_insert_with(|| CommandSpec::new(name));
+            assert_eq!(spec.path.len(), 1, "Only top level commands can be moved");
+            spec.path = vec![group, subcommand];
+        }
+        self.group(&[group], description)
+    }
+
+    fn group_description(&self, path: &[&'static str]) -> &'static str {
+        self.group_descriptions
+            .get(&path.join(" "))
+            .expect("Missing description for command group")
+    }
+
+    /// The names of the commands as Discord sees them, with subcommands counted under their group
+    pub fn top_level_names(&self) -> Vec<&'static str> {
+        let mut names = self
+            .commands
+            .values()
+            .map(|spec| spec.path[0])
+            .collect::<Vec<&'static str>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// How a flattened command is shown to users, e.g. "config language" for "config-language"
+    pub fn display_name(&self, name: &str) -> String {
+        match self.commands.get(name) {
+            Some(spec) => {
+                let mut path = spec.path.clone();
+                path[0] = self.registered_name(path[0]);
+                path.join(" ")
+            }
+            None => name.to_owned(),
+        }
+    }
+
+    /// Rebuild the commands slash-helper derived so they match their specs, nesting subcommands
+    /// under their groups. Panics if a spec is for a command or option that doesn't exist.
+    pub fn apply(&self, commands: Vec<CreateApplicationCommand>) -> Vec<CreateApplicationCommand> {
+        let attribute = |command: &CreateApplicationCommand, key: &str| {
+            command
+                .0
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned()
+        };
+        for name in self.commands.keys() {
+            assert!(
+                commands
+                    .iter()
+                    .any(|command| attribute(command, "name") == *name),
+                "There's a spec for /{}, which isn't a command",
+                name
+            );
+        }
+        // Top level commands in registration order
+        let mut top_level: Vec<TopLevelCommand> = Vec::new();
+        for command in commands.iter() {
+            let name = attribute(command, "name");
+            let description = attribute(command, "description");
+            let mut options = command
+                .0
+                .get("options")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            let spec = self.commands.get(name.as_str());
+            if let Some(spec) = spec {
+                spec.apply(&mut options);
+            }
+            let permissions = match spec {
+                Some(spec) if spec.owner_only => Permissions::ADMINISTRATOR,
+                Some(spec) => spec.permissions,
+                None => Permissions::empty(),
+            };
+            let path = match spec {
+                Some(spec) if spec.path.len() > 1 => &spec.path,
+                _ => {
+                    top_level.push(TopLevelCommand {
+                        name,
+                        description,
+                        options,
+                        permissions,
+                    });
+                    continue;
+                }
+            };
+
+            let subcommand = json!({
+                "type": SUB_COMMAND,
+                "name": path[path.len() - 1],
+                "description": description,
+                "description_localizations": description_localizations(path),
+                "options": options,
+            });
+            let parent = match top_level.iter().position(|parent| parent.name == path[0]) {
+                Some(index) => index,
+                None => {
+                    top_level.push(TopLevelCommand {
+                        name: path[0].to_owned(),
+                        description: self.group_description(&path[..1]).to_owned(),
+                        options: Vec::new(),
+                        permissions: Permissions::all(),
+                    });
+                    top_level.len() - 1
+                }
+            };
+            let parent = &mut top_level[parent];
+            // A group can only be hidden from members who can't use any of its subcommands
+            parent.permissions &= permissions;
+            let parent = &mut parent.options;
+            if path.len() == 2 {
+                parent.push(subcommand);
+                continue;
+            }
+            let group = match parent.iter().position(|option| option["name"] == path[1]) {
+                Some(index) => index,
+                None => {
+                    parent.push(json!({
+                        "type": SUB_COMMAND_GROUP,
+                        "name": path[1],
+                        "description": self.group_description(&path[..2]),
+                        "description_localizations": description_localizations(&path[..2]),
+                        "options": [],
+                    }));
+                    parent.len() - 1
+                }
+            };
+            parent[group]["options"]
+                .as_array_mut()
+                .expect("Invalid subcommand group")
+                .push(subcommand);
+        }
+
+        top_level
+            .into_iter()
+            .map(|command| {
+                let mut builder = CreateApplicationCommand::default();
+                builder.0.insert(
+                    "description_localizations",
+                    description_localizations(&[command.name.as_str()]),
+                );
+                builder
+                    .name(self.registered_name(&command.name))
+                    .description(command.description);
+                builder.0.insert("options", Value::Array(command.options));
+                if !command.permissions.is_empty() {
+                    builder.0.insert(
+                        "default_member_permissions",
+                        json!(command.permissions.bits().to_string()),
+                    );
+                }
+                builder
+            })
+            .collect()
+    }
+
+    /// Whether a prepared command may only be used by the bot's owner
+    pub fn is_owner_only(&self, interaction: &ApplicationCommandInteraction) -> bool {
+        self.commands
+            .get(interaction.data.name.as_str())
+            .map(|spec| spec.owner_only)
+            .unwrap_or(false)
+    }
+
+    /// The permissions a prepared command needs that the member who invoked it doesn't have
+    pub fn missing_permissions(&self, interaction: &ApplicationCommandInteraction) -> Permissions {
+        let required = self
+            .commands
+            .get(interaction.data.name.as_str())
+            .map(|spec| spec.permissions)
+            .unwrap_or(Permissions::empty());
+        let granted = interaction
+            .member
+            .as_ref()
+            .map(|member| member.permissions)
+            .flatten()
+            .unwrap_or(Permissions::empty());
+        if granted.contains(Permissions::ADMINISTRATOR) {
+            return Permissions::empty();
+        }
+        required & !granted
+    }
+
+    /// Rewrite an incoming command into the shape slash-helper's parser expects,
+    /// turning renamed commands back into their own names and subcommands back into the flat
+    /// commands they were registered as
+    pub fn prepare(
+        &self,
+        interaction: &ApplicationCommandInteraction,
+    ) -> ApplicationCommandInteraction {
+        let mut interaction = interaction.clone();
+        interaction.data.name = self.original_name(&interaction.data.name).to_owned();
+        let mut path = vec![interaction.data.name.clone()];
+        while let Some(option) = interaction.data.options.first() {
+            match option.kind {
+                ApplicationCommandOptionType::SubCommandGroup
+                | ApplicationCommandOptionType::SubCommand => {
+                    path.push(option.name.clone());
+                    interaction.data.options = option.options.clone();
+                }
+                _ => break,
+            }
+        }
+        let spec = self
+            .commands
+            .values()
+            .find(|spec| spec.path.iter().eq(path.iter()));
+        if let Some(spec) = spec {
+            interaction.data.name = spec.name.to_owned();
+            spec.prepare(&mut interaction.data.options);
+        }
+        interaction
+    }
+}
+
+/// Replace the registered commands, either for a single guild or globally
+pub async fn overwrite_commands(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    commands: Vec<CreateApplicationCommand>,
+) -> serenity::Result<Vec<ApplicationCommand>> {
+    with_retry(&ctx.http, || {
+        let commands = commands.clone();
+        async move {
+            match guild_id {
+                Some(guild_id) => {
+                    guild_id
+                        .set_application_commands(&ctx.http, |builder| {
+                            builder.set_application_commands(commands)
+                        })
+                        .await
+                }
+                None => {
+                    ApplicationCommand::set_global_application_commands(&ctx.http, |builder| {
+                        builder.set_application_commands(commands)
+                    })
+                    .await
+                }
+            }
+        }
+    })
+    .await
+}
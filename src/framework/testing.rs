@@ -0,0 +1,108 @@
+//! Build the interactions Discord would send, so commands can be tested without connecting to it
+
+use super::spec::option_type;
+use serde_json::{json, Value};
+use serenity::model::{
+    interactions::application_command::ApplicationCommandInteraction, Permissions,
+};
+
+/// An application command interaction, e.g. for `/config language language:de`:
+/// `MockInteraction::new(&["config", "language"]).option("language", "de").build()`
+pub struct MockInteraction {
+    path: Vec<&'static str>,
+    options: Vec<Value>,
+    guild_id: Option<u64>,
+    permissions: Permissions,
+}
+
+impl MockInteraction {
+    /// A command as the user typed it, with any subcommands after the command's name
+    pub fn new(path: &[&'static str]) -> Self {
+        MockInteraction {
+            path: path.to_vec(),
+            options: Vec::new(),
+            guild_id: Some(1),
+            permissions: Permissions::empty(),
+        }
+    }
+
+    pub fn option(mut self, name: &str, value: impl Into<Value>) -> Self {
+        let value = value.into();
+        self.options.push(json!({
+            "name": name,
+            "type": option_type(&value),
+            "value": value,
+        }));
+        self
+    }
+
+    /// Sent from a DM rather than a guild
+    pub fn direct_message(mut self) -> Self {
+        self.guild_id = None;
+        self
+    }
+
+    /// The permissions the member using the command has in the channel
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn build(self) -> ApplicationCommandInteraction {
+        let MockInteraction {
+            path,
+            options,
+            guild_id,
+            permissions,
+        } = self;
+        let user = json!({
+            "id": "100",
+            "username": "Basho",
+            "discriminator": "1644",
+            "avatar": null,
+        });
+        // Nest the options under each subcommand, innermost first
+        let options = path[1..]
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(options, |options, (depth, name)| {
+                let is_last = depth == path.len() - 2;
+                vec![json!({
+                    "name": name,
+                    "type": if is_last { 1 } else { 2 },
+                    "options": options,
+                })]
+            });
+        let mut interaction = json!({
+            "id": "10",
+            "application_id": "20",
+            "type": 2,
+            "data": {
+                "id": "30",
+                "name": path[0],
+                "type": 1,
+                "options": options,
+            },
+            "channel_id": "40",
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+        });
+        match guild_id {
+            Some(guild_id) => {
+                interaction["guild_id"] = json!(guild_id.to_string());
+                interaction["member"] = json!({
+                    "user": user,
+                    "roles": [],
+                    "joined_at": "2021-01-01T00:00:00+00:00",
+                    "deaf": false,
+                    "mute": false,
+                    "permissions": permissions.bits().to_string(),
+                });
+            }
+            None => interaction["user"] = user,
+        }
+        serde_json::from_value(interaction).expect("Invalid mock interaction")
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    commands::register_all,
+    config::Config,
+    errors::report_error,
+    i18n::guild_locale,
+    jobs::{record_run, wait_until_due},
+    laureate::laureate_change,
+    models::Notification,
+    notifications::notify,
+    repository::{repository, HaikuRepository},
+    retention::enforce_retention,
+    retry::with_retry,
+    schedule::{next_monthly_run, parse_timezone, Job},
+};
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use serenity::{
+    client::Context,
+    model::{
+        guild::{Guild, GuildUnavailable},
+        id::GuildId,
+        misc::Mentionable,
+    },
+};
+
+// How often to check for departed guilds whose data should be purged
+const PURGE_INTERVAL_SECS: i64 = 60 * 60;
+
+// How often to clear out haikus older than each server's retention window
+const RETENTION_INTERVAL_SECS: i64 = 24 * 60 * 60;
+
+// How often to re-check every server's laureate, besides when each server's month rolls over
+const LAUREATE_INTERVAL_SECS: i64 = 60 * 60;
+
+pub async fn on_guild_create(ctx: &Context, guild: &Guild, is_new: bool) {
+    repository(ctx).await.create_server_config(guild.id);
+    if is_new {
+        println!("Joined guild {} ({})", guild.name, guild.id);
+    }
+    let guild_scoped = {
+        let data = ctx.data.read().await;
+        data.get::<Config>()
+            .expect("Expected Config in TypeMap")
+            .commands
+            .guild_scoped
+    };
+    if guild_scoped {
+        register_all(ctx, Some(guild.id)).await;
+    }
+}
+
+pub async fn on_guild_delete(ctx: &Context, incomplete: &GuildUnavailable) {
+    // Guilds also get "deleted" during Discord outages, which we don't want to act on
+    if incomplete.unavailable {
+        return;
+    }
+    println!("Removed from guild {}", incomplete.id);
+    repository(ctx).await.mark_server_departed(incomplete.id);
+}
+
+/// Periodically delete the data of guilds the bot was removed from longer ago than the grace period
+pub async fn purge_departed_guilds(ctx: Context) {
+    loop {
+        let interval = Duration::seconds(PURGE_INTERVAL_SECS);
+        wait_until_due(&ctx, Job::PurgeDepartedGuilds, interval).await;
+        let purge_after_days = {
+            let data = ctx.data.read().await;
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .guilds
+                .purge_after_days
+        };
+        if let Some(purge_after_days) = purge_after_days {
+            let repository = repository(&ctx).await;
+            let cutoff = Utc::now() - Duration::days(purge_after_days);
+            for server_id in repository.get_servers_departed_before(cutoff) {
+                println!("Purging data for departed guild {}", server_id);
+                repository.purge_server_data(server_id);
+            }
+        }
+        record_run(&ctx, Job::PurgeDepartedGuilds).await;
+    }
+}
+
+/// Periodically archive or delete the haikus that have outlived their server's retention window
+pub async fn enforce_retention_policies(ctx: Context) {
+    loop {
+        let interval = Duration::seconds(RETENTION_INTERVAL_SECS);
+        wait_until_due(&ctx, Job::EnforceRetention, interval).await;
+        let repository = repository(&ctx).await;
+        for (server_id, action, count) in enforce_retention(&*repository, Utc::now()) {
+            println!(
+                "Retention policy removed {} haikus from guild {} ({:?})",
+                count, server_id, action
+            );
+        }
+        record_run(&ctx, Job::EnforceRetention).await;
+    }
+}
+
+/// Move a server's laureate role to whoever leads this month's leaderboard, if that's changed.
+/// Returns false if Discord wouldn't let the bot give out the role.
+pub async fn update_laureate(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+) -> bool {
+    let change = match laureate_change(repository, server_id, Utc::now()) {
+        Some(change) => change,
+        None => return true,
+    };
+    if let Some(previous) = change.previous {
+        // They may have left the server, in which case they don't have the role to remove anyway
+        let result = with_retry(&ctx.http, || {
+            ctx.http
+                .remove_member_role(server_id.0, previous.0, change.role.0)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not remove laureate role in {}: {:?}", server_id, why);
+        }
+    }
+    if let Some(next) = change.next {
+        let result = with_retry(&ctx.http, || {
+            ctx.http.add_member_role(server_id.0, next.0, change.role.0)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not give laureate role in {}: {:?}", server_id, why);
+            let locale = guild_locale(repository, Some(server_id));
+            let content = t!(
+                locale,
+                "error_channel.laureate_failed",
+                role = change.role.mention(),
+                user = next.mention()
+            );
+            report_error(ctx, repository, server_id, &content).await;
+            return false;
+        }
+        let locale = guild_locale(repository, Some(server_id));
+        let server_name = server_id
+            .name(&ctx.cache)
+            .await
+            .unwrap_or_else(|| server_id.to_string());
+        let content = t!(locale, "notifications.laureate", server = server_name);
+        notify(
+            ctx,
+            repository,
+            next,
+            Notification::ContestWin,
+            &content,
+            None,
+        )
+        .await;
+    }
+    repository.set_server_laureate(server_id, change.next);
+    true
+}
+
+/// Periodically re-check the laureate of every server that has one, and again as soon as each
+/// server's month rolls over in its own timezone
+pub async fn crown_laureates(ctx: Context) {
+    loop {
+        let repository = repository(&ctx).await;
+        let servers = repository.get_laureate_servers();
+        for config in servers.iter() {
+            update_laureate(&ctx, &*repository, config.server).await;
+        }
+        let now = Utc::now();
+        let timezones = servers
+            .iter()
+            .map(|config| parse_timezone(&config.timezone).unwrap_or(Tz::UTC));
+        let next_run = next_monthly_run(now, timezones, Duration::seconds(LAUREATE_INTERVAL_SECS));
+        tokio::time::sleep((next_run - now).to_std().unwrap_or_default()).await;
+    }
+}
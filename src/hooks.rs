@@ -0,0 +1,190 @@
+//! What the bot does with each haiku the detection pipeline saves
+
+use crate::{
+    archive::mirror_haiku,
+    commands::buttons::haiku_buttons,
+    detection::pipeline::{PostSaveHook, SavedHaiku},
+    errors::report_error,
+    formatting::{
+        format_haiku, format_milestone, render_template, server_wants_plain_text, to_embed_data,
+    },
+    framework::checks::{missing_channel_permissions, posting_permissions},
+    guilds,
+    i18n::guild_locale,
+    milestones::reached_milestone,
+    models::Notification,
+    notifications::notify_authors,
+    repository::HaikuRepository,
+    retry::with_retry,
+    runtime_stats::RuntimeStats,
+    welcome::first_haiku_authors,
+};
+use serenity::{async_trait, client::Context, model::misc::Mentionable};
+
+/// Count the haiku towards /uptime's stats
+pub struct RecordStats;
+
+#[async_trait]
+impl PostSaveHook for RecordStats {
+    async fn on_saved(
+        &self,
+        ctx: &Context,
+        _repository: &dyn HaikuRepository,
+        _saved: &SavedHaiku,
+    ) {
+        ctx.data
+            .read()
+            .await
+            .get::<RuntimeStats>()
+            .expect("Expected RuntimeStats in TypeMap")
+            .record_haiku();
+    }
+}
+
+/// Post the haiku in the channel it was written in, celebrating it if it's a milestone, with the
+/// server's announcement text if it has one
+pub struct Announce;
+
+#[async_trait]
+impl PostSaveHook for Announce {
+    async fn on_saved(&self, ctx: &Context, repository: &dyn HaikuRepository, saved: &SavedHaiku) {
+        let SavedHaiku { id, haiku, .. } = saved;
+        let locale = guild_locale(repository, Some(haiku.server));
+        // Permissions can change after detection was turned on, so check them before each post
+        let missing = missing_channel_permissions(ctx, haiku.channel, posting_permissions()).await;
+        if !missing.is_empty() {
+            println!("Missing {} to announce haiku in {}", missing, haiku.channel);
+            let content = t!(
+                locale,
+                "error_channel.announce_missing",
+                id = id,
+                channel = haiku.channel.mention(),
+                permissions = missing
+            );
+            report_error(ctx, repository, haiku.server, &content).await;
+            return;
+        }
+        let embed_data = to_embed_data(*id, haiku, locale, ctx).await;
+        let plain_text = server_wants_plain_text(repository, Some(haiku.server));
+        let formatted = match reached_milestone(repository, haiku.server) {
+            Some(milestone) => format_milestone(milestone, embed_data, plain_text),
+            None => format_haiku(embed_data, plain_text),
+        };
+        let announcement = repository
+            .get_server_config(haiku.server)
+            .and_then(|config| config.announcement_template)
+            .and_then(|template| render_template(&template, *id, haiku).ok());
+        let content = formatted.content(announcement.as_deref());
+        let embed = formatted.embed();
+        let result = with_retry(&ctx.http, || {
+            haiku.channel.send_message(&ctx.http, |msg| {
+                if let Some(content) = &content {
+                    msg.content(content)
+                        .allowed_mentions(|mentions| mentions.empty_parse());
+                }
+                if let Some(embed) = &embed {
+                    msg.set_embed(embed.clone());
+                }
+                // A haiku that's just been found hasn't been edited yet
+                msg.components(|components| haiku_buttons(components, *id, false, locale));
+                msg
+            })
+        })
+        .await;
+        let message = match result {
+            Ok(message) => message,
+            Err(why) => {
+                println!("Failed to send haiku msg: {:?}", why);
+                let content = t!(
+                    locale,
+                    "error_channel.announce_failed",
+                    id = id,
+                    channel = haiku.channel.mention()
+                );
+                report_error(ctx, repository, haiku.server, &content).await;
+                return;
+            }
+        };
+        repository.set_announcement_message(haiku.server, *id, Some(message.id));
+    }
+}
+
+/// Post the haiku in the server's archive channel too, if it has one
+pub struct MirrorToArchive;
+
+#[async_trait]
+impl PostSaveHook for MirrorToArchive {
+    async fn on_saved(&self, ctx: &Context, repository: &dyn HaikuRepository, saved: &SavedHaiku) {
+        mirror_haiku(ctx, repository, saved.id, &saved.haiku).await;
+    }
+}
+
+/// Welcome authors to the collection when it's their first haiku, if the server wants that
+pub struct Welcome;
+
+#[async_trait]
+impl PostSaveHook for Welcome {
+    async fn on_saved(&self, ctx: &Context, repository: &dyn HaikuRepository, saved: &SavedHaiku) {
+        let SavedHaiku { id, haiku, .. } = saved;
+        let welcome_enabled = repository
+            .get_server_config(haiku.server)
+            .map(|config| config.welcome_enabled)
+            .unwrap_or(false);
+        if !welcome_enabled {
+            return;
+        }
+        let new_authors = first_haiku_authors(repository, haiku);
+        if new_authors.is_empty() {
+            return;
+        }
+        let locale = guild_locale(repository, Some(haiku.server));
+        let mentions = new_authors
+            .iter()
+            .map(|author| author.mention().to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let content = t!(locale, "welcome.message", users = mentions, id = id);
+        let result = with_retry(&ctx.http, || haiku.channel.say(&ctx.http, &content)).await;
+        if let Err(why) = result {
+            println!("Failed to send welcome msg: {:?}", why);
+            let content = t!(
+                locale,
+                "error_channel.welcome_failed",
+                channel = haiku.channel.mention()
+            );
+            report_error(ctx, repository, haiku.server, &content).await;
+        }
+    }
+}
+
+/// Hand the laureate role over if the haiku put someone else on top of this month's leaderboard
+pub struct CrownLaureate;
+
+#[async_trait]
+impl PostSaveHook for CrownLaureate {
+    async fn on_saved(&self, ctx: &Context, repository: &dyn HaikuRepository, saved: &SavedHaiku) {
+        guilds::update_laureate(ctx, repository, saved.haiku.server).await;
+    }
+}
+
+/// DM the authors who asked to hear when their haikus are found
+pub struct NotifyAuthors;
+
+#[async_trait]
+impl PostSaveHook for NotifyAuthors {
+    async fn on_saved(&self, ctx: &Context, repository: &dyn HaikuRepository, saved: &SavedHaiku) {
+        let SavedHaiku { id, haiku, .. } = saved;
+        let locale = guild_locale(repository, Some(haiku.server));
+        let content = t!(locale, "notifications.detected", id = id);
+        notify_authors(
+            ctx,
+            repository,
+            *id,
+            haiku,
+            Notification::Detected,
+            None,
+            &content,
+        )
+        .await;
+    }
+}
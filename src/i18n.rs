@@ -0,0 +1,201 @@
+use crate::repository::HaikuRepository;
+use lazy_static::lazy_static;
+use serenity::model::id::GuildId;
+use std::collections::HashMap;
+use toml::Value;
+
+/// Translate a string into the given locale, filling in any `{placeholders}`:
+/// `t!(locale, "errors.haiku_not_found", id = 5)`
+#[macro_export]
+macro_rules! t {
+    ($locale:expr, $key:expr) => {
+        $crate::i18n::translate($locale, $key, &[])
+    };
+    ($locale:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($locale, $key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+
+/// A language the bot can speak
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    English,
+    German,
+}
+
+lazy_static! {
+    static ref ENGLISH: HashMap<String, String> = parse_catalog(include_str!("../locales/en.toml"));
+    static ref GERMAN: HashMap<String, String> = parse_catalog(include_str!("../locales/de.toml"));
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::English
+    }
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::English, Locale::German];
+
+    /// The code the locale is stored as, which is also Discord's locale code for it
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::German => "de",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Locale::ALL
+            .iter()
+            .find(|locale| locale.code() == code)
+            .copied()
+    }
+
+    fn catalog(&self) -> &'static HashMap<String, String> {
+        match self {
+            Locale::English => &ENGLISH,
+            Locale::German => &GERMAN,
+        }
+    }
+}
+
+/// How numbers and dates are written, which depends on where someone is rather than on the
+/// language the bot speaks in their server, e.g. 1,234 in the US but 1.234 in Germany
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Between each group of three digits
+    pub thousands_separator: &'static str,
+    /// A `strftime` format for dates
+    pub date_format: &'static str,
+    pub ordinals: Ordinals,
+}
+
+/// How "first", "second" and so on are written as numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ordinals {
+    /// 1st, 2nd, 3rd, 4th
+    English,
+    /// 1er, 2e, 3e
+    French,
+    /// 1., 2., 3.
+    Period,
+    /// Just the number
+    Plain,
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Region::from_discord("en-US")
+    }
+}
+
+impl Region {
+    /// The region for one of Discord's locale codes, which it sends with every interaction.
+    /// Unknown codes are treated as US English.
+    pub fn from_discord(code: &str) -> Self {
+        let (thousands_separator, date_format, ordinals) = match code {
+            "en-GB" => (",", "%d/%m/%Y", Ordinals::English),
+            "de" | "da" | "no" | "fi" | "tr" | "hr" | "ro" | "cs" | "pl" | "ru" | "uk" | "bg" => {
+                (".", "%d.%m.%Y", Ordinals::Period)
+            }
+            "nl" => (".", "%d-%m-%Y", Ordinals::Period),
+            "es-ES" | "it" | "pt-BR" | "el" | "vi" => (".", "%d/%m/%Y", Ordinals::Period),
+            "fr" => ("\u{202f}", "%d/%m/%Y", Ordinals::French),
+            "sv-SE" | "lt" => ("\u{a0}", "%Y-%m-%d", Ordinals::Period),
+            "hu" => ("\u{a0}", "%Y. %m. %d.", Ordinals::Period),
+            "ja" | "zh-CN" | "zh-TW" | "ko" => (",", "%Y/%m/%d", Ordinals::Plain),
+            "hi" | "th" => (",", "%d/%m/%Y", Ordinals::Plain),
+            _ => (",", "%m/%d/%Y", Ordinals::English),
+        };
+        Region {
+            thousands_separator,
+            date_format,
+            ordinals,
+        }
+    }
+
+    /// The region to use for messages nobody asked for, like announcements, going by the
+    /// server's language
+    pub fn for_locale(locale: Locale) -> Self {
+        match locale {
+            Locale::English => Region::from_discord("en-US"),
+            Locale::German => Region::from_discord("de"),
+        }
+    }
+}
+
+/// Look up a string, falling back to English and then the key itself
+pub fn translate(locale: Locale, key: &str, args: &[(&str, String)]) -> String {
+    let template = locale
+        .catalog()
+        .get(key)
+        .or_else(|| ENGLISH.get(key))
+        .map(|template| template.as_str())
+        .unwrap_or(key);
+    args.iter()
+        .fold(template.to_owned(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// The locale's own translation of a key, without falling back to English
+pub fn lookup(locale: Locale, key: &str) -> Option<String> {
+    locale.catalog().get(key).cloned()
+}
+
+/// Every translation of a key other than English, keyed by Discord locale code
+pub fn localizations(key: &str) -> HashMap<&'static str, String> {
+    Locale::ALL
+        .iter()
+        .filter(|locale| **locale != Locale::English)
+        .filter_map(|locale| {
+            locale
+                .catalog()
+                .get(key)
+                .map(|text| (locale.code(), text.clone()))
+        })
+        .collect()
+}
+
+/// The locale a guild has chosen, or English outside of guilds
+pub fn guild_locale(repository: &dyn HaikuRepository, guild_id: Option<GuildId>) -> Locale {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return Locale::default(),
+    };
+    repository
+        .get_server_config(guild_id)
+        .map(|config| Locale::from_code(&config.locale))
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Flatten a catalog's tables into dotted keys, e.g. `[help] title` becomes `help.title`
+fn parse_catalog(source: &str) -> HashMap<String, String> {
+    fn flatten(prefix: &str, value: Value, strings: &mut HashMap<String, String>) {
+        match value {
+            Value::Table(table) => {
+                for (key, value) in table {
+                    let key = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    flatten(&key, value, strings);
+                }
+            }
+            Value::String(text) => {
+                strings.insert(prefix.to_owned(), text);
+            }
+            _ => panic!("Invalid string {} in locale catalog", prefix),
+        }
+    }
+    let mut strings = HashMap::new();
+    flatten(
+        "",
+        source.parse().expect("Invalid locale catalog"),
+        &mut strings,
+    );
+    strings
+}
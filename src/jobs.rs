@@ -0,0 +1,27 @@
+//! Keeping the bot's periodic work on schedule across restarts
+
+use crate::{
+    repository::repository,
+    schedule::{next_run, Job},
+};
+use chrono::{Duration, Utc};
+use serenity::client::Context;
+
+/// Sleep until a job is next due, going by when it last ran, even if that was before a restart
+pub async fn wait_until_due(ctx: &Context, job: Job, interval: Duration) {
+    let last_run = repository(ctx).await.get_job_last_run(job);
+    let now = Utc::now();
+    let due = next_run(last_run, interval, now);
+    if last_run.is_some() && due == now {
+        println!(
+            "Catching up on {} job missed while the bot was down",
+            job.name()
+        );
+    }
+    tokio::time::sleep((due - now).to_std().unwrap_or_default()).await;
+}
+
+/// Save that a job has just finished
+pub async fn record_run(ctx: &Context, job: Job) {
+    repository(ctx).await.record_job_run(job, Utc::now());
+}
@@ -0,0 +1,132 @@
+//! Crowning whoever has written the most haikus in a server this month with a role
+
+use crate::{
+    repository::HaikuRepository,
+    schedule::{month_start, server_timezone},
+};
+use chrono::{DateTime, Utc};
+use serenity::model::id::{GuildId, RoleId, UserId};
+
+/// Who should be laureate given this month's haiku counts (most first). The current laureate
+/// keeps the role until someone overtakes them, including while nobody has written anything yet.
+pub fn choose_laureate(counts: &[(UserId, i64)], current: Option<UserId>) -> Option<UserId> {
+    let (leader, most) = match counts.first() {
+        Some(first) => *first,
+        None => return current,
+    };
+    match current {
+        Some(current) if counts.contains(&(current, most)) => Some(current),
+        _ => Some(leader),
+    }
+}
+
+/// The role needs to move from one person to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaureateChange {
+    pub role: RoleId,
+    pub previous: Option<UserId>,
+    pub next: Option<UserId>,
+}
+
+/// Check whether a server's laureate role should change hands, without recording anything
+pub fn laureate_change(
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    now: DateTime<Utc>,
+) -> Option<LaureateChange> {
+    let config = repository.get_server_config(server_id)?;
+    let role = config.laureate_role?;
+    let since = month_start(now, server_timezone(repository, server_id));
+    let counts = repository.count_haikus_by_author_since(server_id, since);
+    let next = choose_laureate(&counts, config.laureate);
+    if next == config.laureate {
+        None
+    } else {
+        Some(LaureateChange {
+            role,
+            previous: config.laureate,
+            next,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{choose_laureate, laureate_change, LaureateChange};
+    use crate::{
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use chrono::{DateTime, TimeZone, Utc};
+    use serenity::model::id::{GuildId, RoleId, UserId};
+
+    fn save_haiku(repository: &MemoryRepository, author: u64, timestamp: DateTime<Utc>) {
+        repository.save_haiku(&HaikuBuilder::new().author(author).at(timestamp).build());
+    }
+
+    #[test]
+    fn test_choose_laureate() {
+        let counts = [(UserId(200), 3), (UserId(100), 3), (UserId(300), 1)];
+        assert_eq!(choose_laureate(&counts, None), Some(UserId(200)));
+        // Ties go to whoever already has the role
+        assert_eq!(
+            choose_laureate(&counts, Some(UserId(100))),
+            Some(UserId(100))
+        );
+        assert_eq!(
+            choose_laureate(&counts, Some(UserId(300))),
+            Some(UserId(200))
+        );
+        assert_eq!(choose_laureate(&[], Some(UserId(300))), Some(UserId(300)));
+        assert_eq!(choose_laureate(&[], None), None);
+    }
+
+    #[test]
+    fn test_laureate_change() {
+        let repository = MemoryRepository::new();
+        let now = Utc.ymd(2021, 3, 17).and_hms(12, 0, 0);
+        repository.create_server_config(GuildId(1));
+        save_haiku(&repository, 100, now);
+        // Not enabled
+        assert_eq!(laureate_change(&repository, GuildId(1), now), None);
+
+        repository.set_server_laureate_role(GuildId(1), Some(RoleId(5)));
+        assert_eq!(
+            laureate_change(&repository, GuildId(1), now),
+            Some(LaureateChange {
+                role: RoleId(5),
+                previous: None,
+                next: Some(UserId(100)),
+            })
+        );
+        repository.set_server_laureate(GuildId(1), Some(UserId(100)));
+        assert_eq!(laureate_change(&repository, GuildId(1), now), None);
+
+        // Last month's haikus don't count
+        for _ in 0..3 {
+            save_haiku(&repository, 200, Utc.ymd(2021, 2, 28).and_hms(12, 0, 0));
+        }
+        assert_eq!(laureate_change(&repository, GuildId(1), now), None);
+        // Unless it was already this month in the server's timezone
+        repository.set_server_timezone(GuildId(1), "Pacific/Kiritimati");
+        assert_eq!(
+            laureate_change(&repository, GuildId(1), now),
+            Some(LaureateChange {
+                role: RoleId(5),
+                previous: Some(UserId(100)),
+                next: Some(UserId(200)),
+            })
+        );
+        repository.set_server_timezone(GuildId(1), "UTC");
+        save_haiku(&repository, 200, now);
+        save_haiku(&repository, 200, now);
+        assert_eq!(
+            laureate_change(&repository, GuildId(1), now),
+            Some(LaureateChange {
+                role: RoleId(5),
+                previous: Some(UserId(100)),
+                next: Some(UserId(200)),
+            })
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Ranking a server's poets by how many haikus they've written a line of
+
+use crate::schedule::{month_start, week_start, year_start};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+/// The stretch of time a leaderboard counts haikus from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardPeriod {
+    /// Since Monday
+    Week,
+    Month,
+    Year,
+    AllTime,
+}
+
+impl LeaderboardPeriod {
+    pub fn code(self) -> &'static str {
+        match self {
+            LeaderboardPeriod::Week => "week",
+            LeaderboardPeriod::Month => "month",
+            LeaderboardPeriod::Year => "year",
+            LeaderboardPeriod::AllTime => "alltime",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "week" => Some(LeaderboardPeriod::Week),
+            "month" => Some(LeaderboardPeriod::Month),
+            "year" => Some(LeaderboardPeriod::Year),
+            "alltime" => Some(LeaderboardPeriod::AllTime),
+            _ => None,
+        }
+    }
+
+    /// When the period containing `now` started in the server's timezone, or None for all time
+    pub fn start(self, now: DateTime<Utc>, timezone: Tz) -> Option<DateTime<Utc>> {
+        match self {
+            LeaderboardPeriod::Week => Some(week_start(now, timezone)),
+            LeaderboardPeriod::Month => Some(month_start(now, timezone)),
+            LeaderboardPeriod::Year => Some(year_start(now, timezone)),
+            LeaderboardPeriod::AllTime => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LeaderboardPeriod;
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    #[test]
+    fn test_period_start() {
+        // A Wednesday
+        let now = Utc.ymd(2021, 3, 17).and_hms(12, 30, 0);
+        assert_eq!(
+            LeaderboardPeriod::Week.start(now, Tz::UTC),
+            Some(Utc.ymd(2021, 3, 15).and_hms(0, 0, 0))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Month.start(now, Tz::UTC),
+            Some(Utc.ymd(2021, 3, 1).and_hms(0, 0, 0))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Year.start(now, Tz::UTC),
+            Some(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0))
+        );
+        assert_eq!(LeaderboardPeriod::AllTime.start(now, Tz::UTC), None);
+        // Weeks can start in the previous month
+        assert_eq!(
+            LeaderboardPeriod::Week.start(Utc.ymd(2021, 4, 1).and_hms(0, 0, 0), Tz::UTC),
+            Some(Utc.ymd(2021, 3, 29).and_hms(0, 0, 0))
+        );
+    }
+}
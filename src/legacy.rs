@@ -0,0 +1,180 @@
+//! Importing haikus saved by the bot before the rewrite, when each haiku was a single row with its
+//! authors and lines in columns and ids were shared by every server.
+//!
+//! The old database is read from a dump with one JSON object per row, as written by e.g.
+//! `psql -At -c "SELECT row_to_json(haikus) FROM haikus" > haikus.jsonl`.
+
+use crate::{
+    models::{Haiku, HaikuLine},
+    repository::HaikuRepository,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use std::convert::TryFrom;
+
+#[derive(Deserialize)]
+struct LegacyHaikuRow {
+    id: i64,
+    channel: i64,
+    server: i64,
+    timestamp: String,
+    author_0: i64,
+    author_1: i64,
+    author_2: i64,
+    message_0: String,
+    message_1: String,
+    message_2: String,
+}
+
+/// A haiku from the old database, with the id it had there
+#[derive(Debug, Clone)]
+pub struct LegacyHaiku {
+    pub id: i64,
+    pub haiku: Haiku,
+}
+
+/// Where an imported haiku ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportedHaiku {
+    pub server: GuildId,
+    pub old_id: i64,
+    pub new_id: i64,
+}
+
+fn snowflake(id: i64) -> Option<u64> {
+    u64::try_from(id).ok()
+}
+
+impl LegacyHaikuRow {
+    fn into_haiku(self) -> Option<LegacyHaiku> {
+        let timestamp =
+            NaiveDateTime::parse_from_str(&self.timestamp, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        let line = |author: i64, content: String| {
+            Some(HaikuLine {
+                author: UserId(snowflake(author)?),
+                author_name: None,
+                content,
+            })
+        };
+        Some(LegacyHaiku {
+            id: self.id,
+            haiku: Haiku {
+                lines: [
+                    line(self.author_0, self.message_0)?,
+                    line(self.author_1, self.message_1)?,
+                    line(self.author_2, self.message_2)?,
+                ],
+                timestamp: DateTime::from_utc(timestamp, Utc),
+                channel: ChannelId(snowflake(self.channel)?),
+                server: GuildId(snowflake(self.server)?),
+                source: None,
+            },
+        })
+    }
+}
+
+/// Read a dump of the old database, oldest haiku first. Errors give the line that couldn't be
+/// read, counting from 1.
+pub fn parse_legacy_dump(dump: &str) -> Result<Vec<LegacyHaiku>, usize> {
+    let mut haikus = dump
+        .lines()
+        .enumerate()
+        .filter(|(_, row)| !row.trim().is_empty())
+        .map(|(index, row)| {
+            serde_json::from_str::<LegacyHaikuRow>(row)
+                .ok()
+                .and_then(LegacyHaikuRow::into_haiku)
+                .ok_or(index + 1)
+        })
+        .collect::<Result<Vec<LegacyHaiku>, usize>>()?;
+    haikus.sort_by_key(|legacy| (legacy.haiku.timestamp, legacy.id));
+    Ok(haikus)
+}
+
+/// Save the old haikus, oldest first so each server's are numbered in the order they were written.
+/// They're saved all at once: the old haikus have no message ids to tell if one is already saved,
+/// so an import that stopped part way couldn't safely be run again.
+pub fn import_legacy_haikus(
+    repository: &dyn HaikuRepository,
+    haikus: &[LegacyHaiku],
+) -> Vec<ImportedHaiku> {
+    let to_save = haikus
+        .iter()
+        .map(|legacy| legacy.haiku.clone())
+        .collect::<Vec<Haiku>>();
+    haikus
+        .iter()
+        .zip(repository.save_haikus(&to_save))
+        .filter_map(|(legacy, new_id)| {
+            new_id.map(|new_id| ImportedHaiku {
+                server: legacy.haiku.server,
+                old_id: legacy.id,
+                new_id,
+            })
+        })
+        .collect()
+}
+
+/// Which number each old haiku has now, for people looking for a haiku by its old id
+pub fn id_map_csv(imported: &[ImportedHaiku]) -> String {
+    let mut csv = String::from("server,old_id,new_id\n");
+    for haiku in imported {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            haiku.server, haiku.old_id, haiku.new_id
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod test {
+    use super::{import_legacy_haikus, parse_legacy_dump, ImportedHaiku};
+    use crate::repository::{HaikuRepository, MemoryRepository};
+    use serenity::model::id::{GuildId, UserId};
+
+    const DUMP: &str = r#"
+{"id":7,"channel":10,"server":2,"timestamp":"2021-03-01T12:00:00","author_0":100,"author_1":101,"author_2":100,"message_0":"An old silent pond","message_1":"A frog jumps into the pond","message_2":"Splash! Silence again"}
+{"id":3,"channel":10,"server":1,"timestamp":"2021-02-01T12:00:00.123456","author_0":100,"author_1":100,"author_2":100,"message_0":"The last winter leaves","message_1":"Clinging to the black branches","message_2":"Explode into birds"}
+{"id":9,"channel":11,"server":2,"timestamp":"2021-01-01T12:00:00","author_0":101,"author_1":101,"author_2":101,"message_0":"Over the wintry","message_1":"Forest, winds howl in rage with","message_2":"No leaves to blow"}
+"#;
+
+    #[test]
+    fn test_import_legacy_haikus() {
+        let haikus = parse_legacy_dump(DUMP).expect("Invalid dump");
+        assert_eq!(
+            haikus.iter().map(|legacy| legacy.id).collect::<Vec<i64>>(),
+            vec![9, 3, 7]
+        );
+        assert_eq!(haikus[2].haiku.lines[1].author, UserId(101));
+
+        let repository = MemoryRepository::new();
+        let imported = import_legacy_haikus(&repository, &haikus);
+        assert_eq!(
+            imported,
+            vec![
+                ImportedHaiku {
+                    server: GuildId(2),
+                    old_id: 9,
+                    new_id: 1
+                },
+                ImportedHaiku {
+                    server: GuildId(1),
+                    old_id: 3,
+                    new_id: 1
+                },
+                ImportedHaiku {
+                    server: GuildId(2),
+                    old_id: 7,
+                    new_id: 2
+                },
+            ]
+        );
+        let (_, haiku) = repository.get_haiku(GuildId(2), 2).unwrap();
+        assert_eq!(haiku.lines[0].content, "An old silent pond");
+
+        assert_eq!(parse_legacy_dump("{\"id\":1}").err(), Some(1));
+        assert_eq!(parse_legacy_dump("\nnot json").err(), Some(2));
+    }
+}
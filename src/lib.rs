@@ -0,0 +1,39 @@
+//! The core of haikubot: syllable counting, haiku detection, formatting and storage.
+//!
+//! The Discord bot itself lives in the binary, this is everything that can be reused without it.
+
+#[macro_use]
+extern crate diesel;
+
+// Declared first so the t! macro is available to every other module
+#[macro_use]
+pub mod i18n;
+
+pub mod api_tokens;
+pub mod build_info;
+pub mod chain;
+pub mod completion;
+pub mod counting;
+pub mod database;
+pub mod detection;
+pub mod export;
+pub mod formatting;
+pub mod laureate;
+pub mod leaderboard;
+pub mod legacy;
+pub mod milestones;
+pub mod models;
+pub mod repository;
+pub mod retention;
+pub mod revalidation;
+pub mod save_queue;
+pub mod schedule;
+pub mod schema;
+pub mod shuffle;
+pub mod translation;
+pub mod welcome;
+
+#[cfg(test)]
+mod test_support;
+
+pub use models::{Haiku, HaikuLine};
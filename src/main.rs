@@ -1,38 +1,95 @@
 #[macro_use]
-extern crate diesel;
+extern crate haikubot;
 
+mod archive;
+mod backfill;
+mod bulk;
 mod commands;
-mod counting;
-mod database;
-mod formatting;
-pub mod models;
-pub mod schema;
+mod config;
+#[cfg(feature = "critique")]
+mod critique;
+mod doctor;
+mod errors;
+mod framework;
+mod guilds;
+mod hooks;
+mod jobs;
+mod maintenance;
+mod notifications;
+mod outage;
+mod posts;
+mod presence;
+mod reactions;
+mod recent_lines;
+mod reload;
+mod retry;
+mod runtime_stats;
+#[cfg(test)]
+mod test_support;
+mod web;
 
+// Imported here so the bot's modules can reach the library's through `crate::`
+use haikubot::{
+    api_tokens, build_info, chain, completion, counting, database, detection, export, formatting,
+    i18n, laureate, leaderboard, legacy, milestones, models, repository, retention, revalidation,
+    schedule, shuffle, translation, welcome,
+};
+
+use bulk::{BulkExecutor, BulkRequests};
+use chain::ChainGames;
 use chrono::{DateTime, Utc};
 use commands::{
-    count::CountCommand, gethaiku::GetHaikuCommand, random::RandomHaikuCommand,
-    search::SearchCommand, uptime::UptimeCommand, Commands,
+    chain::ChainTracker, finishthis::CompletionTracker, random::RandomHaikuHistory, register_all,
+    scanhistory::HistoryScans, test_guild_id, Commands,
+};
+use completion::CompletionGames;
+use config::Config;
+use dashmap::{DashMap, DashSet};
+use detection::pipeline::{
+    AllowedSenders, BridgeNicks, DetectionPipeline, IncomingMessage, StripMarkdown,
+    StripQuotesAndCode,
 };
-use counting::{is_haiku, is_haiku_single};
-use dashmap::DashMap;
-use formatting::{format_haiku_embed, to_embed_data};
-use models::{Haiku, HaikuLine};
+use framework::{
+    checks::is_owner,
+    components::ComponentRouter,
+    progress::RunningOperations,
+    response::{respond, InteractionResponses},
+    spec::CommandSpecs,
+};
+use i18n::{guild_locale, Locale};
+use outage::{DatabaseOutage, Outage};
+use repository::{repository, PostgresRepository, Repository};
+use runtime_stats::RuntimeStats;
 use serenity::{
     async_trait,
-    client::{bridge::gateway::GatewayIntents, Context, EventHandler},
-    model::interactions::Interaction,
+    client::{bridge::gateway::ShardManager, Context, EventHandler},
+    model::interactions::{
+        application_command::ApplicationCommand, Interaction,
+        InteractionApplicationCommandCallbackDataFlags,
+    },
     model::prelude::*,
-    prelude::RwLock,
+    prelude::Mutex,
     prelude::TypeMapKey,
     Client,
 };
-use slash_helper::{register_commands, MessageComponentInteractionHandler};
-use std::env::{self, VarError};
-use std::{collections::HashMap, sync::Arc};
+use shuffle::ShuffleHistory;
+use slash_helper::MessageComponentInteractionHandler;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Instant;
+use web::PendingApiWrites;
+
+// Ready fires again on reconnect, so make sure background tasks only start once
+static BACKGROUND_TASKS_STARTED: AtomicBool = AtomicBool::new(false);
 
 struct HaikuTracker;
 impl TypeMapKey for HaikuTracker {
-    type Value = Arc<RwLock<HashMap<ChannelId, [Option<HaikuLine>; 3]>>>;
+    type Value = Arc<DetectionPipeline>;
 }
 
 struct UptimeStart;
@@ -40,81 +97,124 @@ impl TypeMapKey for UptimeStart {
     type Value = DateTime<Utc>;
 }
 
+struct ShardManagerContainer;
+impl TypeMapKey for ShardManagerContainer {
+    type Value = Arc<Mutex<ShardManager>>;
+}
+
+struct RegisteredCommands;
+impl TypeMapKey for RegisteredCommands {
+    type Value = Vec<ApplicationCommand>;
+}
+
 struct MessageComponentInteractionHandlers;
 impl TypeMapKey for MessageComponentInteractionHandlers {
-    type Value = DashMap<InteractionId, Box<dyn MessageComponentInteractionHandler + Send + Sync>>;
+    /// Each handler is locked while it runs rather than the whole map, as it may be awaited for a
+    /// while
+    type Value = DashMap<
+        InteractionId,
+        Arc<Mutex<Box<dyn MessageComponentInteractionHandler + Send + Sync>>>,
+    >;
 }
 
-async fn on_haiku_line(ctx: &Context, channel: ChannelId, line: HaikuLine) {
-    let data_read = ctx.data.read().await;
-    let tracker_lock = data_read
-        .get::<HaikuTracker>()
-        .expect("Expected HaikuTracker in TypeMap")
-        .clone();
-    let mut tracker = tracker_lock.write().await;
-    let channel_messages = tracker.entry(channel).or_insert([None, None, None]);
-    channel_messages[0] = channel_messages[1].clone();
-    channel_messages[1] = channel_messages[2].clone();
-    channel_messages[2] = Some(line.clone());
-    let haiku = if let Ok(Some(lines)) = is_haiku_single(&line.content) {
-        let guild_id = ctx.cache.guild_channel(channel).await.unwrap().guild_id;
-        let author = line.author;
-        let lines = [
-            HaikuLine {
-                author,
-                content: lines[0].clone(),
-            },
-            HaikuLine {
-                author,
-                content: lines[1].clone(),
-            },
-            HaikuLine {
-                author,
-                content: lines[2].clone(),
-            },
-        ];
-        Some(Haiku {
-            lines,
-            timestamp: Utc::now(),
-            channel: channel,
-            server: guild_id,
-        })
-    } else {
-        match channel_messages {
-            [Some(line_1), Some(line_2), Some(line_3)] => {
-                let lines = [line_1.clone(), line_2.clone(), line_3.clone()];
-                let line_contents = [
-                    line_1.content.clone(),
-                    line_2.content.clone(),
-                    line_3.content.clone(),
-                ];
-                if is_haiku(&line_contents) {
-                    let guild_id = ctx.cache.guild_channel(channel).await.unwrap().guild_id;
-                    Some(Haiku {
-                        lines,
-                        timestamp: Utc::now(),
-                        channel: channel,
-                        server: guild_id,
-                    })
-                } else {
-                    None
-                }
-            }
-            _ => None,
+async fn on_message(ctx: &Context, msg: &Message) {
+    // The bot's own announcements could otherwise be found as haikus all over again
+    if msg.author.id == ctx.cache.current_user_id().await {
+        return;
+    }
+    let message = match IncomingMessage::from_message(msg) {
+        Some(message) => message,
+        // Haikus are only kept for servers
+        None => return,
+    };
+    let (repository, pipeline, chains, completions) = {
+        let data_read = ctx.data.read().await;
+        if !data_read
+            .get::<Config>()
+            .expect("Expected Config in TypeMap")
+            .intents
+            .message_content
+        {
+            // Message content is always empty without the intent, so there's nothing to check
+            return;
         }
+        // Already holding the data lock, so don't take it again through repository()
+        (
+            data_read
+                .get::<Repository>()
+                .expect("Expected Repository in TypeMap")
+                .clone(),
+            data_read
+                .get::<HaikuTracker>()
+                .expect("Expected HaikuTracker in TypeMap")
+                .clone(),
+            data_read
+                .get::<ChainTracker>()
+                .expect("Expected ChainTracker in TypeMap")
+                .clone(),
+            data_read
+                .get::<CompletionTracker>()
+                .expect("Expected CompletionTracker in TypeMap")
+                .clone(),
+        )
     };
-    if let Some(haiku) = haiku {
-        let db_connection = database::establish_connection();
-        let id = database::save_haiku(&haiku, &db_connection);
-        let embed_data = to_embed_data(id, &haiku, ctx).await;
-        channel
-            .send_message(&ctx.http, |msg| {
-                msg.embed(|embed| format_haiku_embed(embed_data, embed));
-                msg
-            })
-            .await
-            .expect("Failed to send haiku msg");
+    let completion = completions.lock().await.submit(
+        message.channel,
+        message.author,
+        &message.content,
+        message.sent_at,
+    );
+    if completion.is_some() {
+        // Completions are saved when the game ends, alongside the haiku they finish
+        if let Err(why) = msg.react(&ctx.http, '✍').await {
+            println!("Failed to react to completion: {:?}", why);
+        }
+        return;
+    }
+    let chain_entry = chains.lock().await.submit(
+        message.channel,
+        message.author,
+        &message.content,
+        message.sent_at,
+    );
+    if chain_entry.is_some() {
+        // Entries are posted together when the chain ends, rather than as haikus of their own
+        if let Err(why) = msg.react(&ctx.http, '🔗').await {
+            println!("Failed to react to chain entry: {:?}", why);
+        }
+        return;
     }
+    // Checked once the database is back
+    if outage::buffer(ctx, message.clone()).await {
+        return;
+    }
+    // Haikus can still be saved on request in these channels, see reactions::on_reaction_add
+    if !repository.is_detection_enabled(message.server, message.channel) {
+        return;
+    }
+    pipeline.run(ctx, &*repository, message).await;
+}
+
+/// Pre-filters tidy or skip messages before they're checked, and hooks run for each saved haiku
+fn detection_pipeline(config: &Config) -> DetectionPipeline {
+    DetectionPipeline::new()
+        .max_gap(config.detection.max_gap())
+        .min_confidence(
+            config.detection.min_confidence,
+            config.detection.low_confidence,
+        )
+        .filter(AllowedSenders)
+        .filter(BridgeNicks)
+        .filter(StripQuotesAndCode)
+        .filter(config.detection.urls)
+        .filter(StripMarkdown)
+        .filter(config.detection.noise_rules())
+        .hook(hooks::RecordStats)
+        .hook(hooks::Announce)
+        .hook(hooks::MirrorToArchive)
+        .hook(hooks::Welcome)
+        .hook(hooks::CrownLaureate)
+        .hook(hooks::NotifyAuthors)
 }
 
 struct Handler;
@@ -124,109 +224,351 @@ impl EventHandler for Handler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         match interaction {
             Interaction::ApplicationCommand(command_interaction) => {
-                Commands::parse(&ctx, &command_interaction)
-                    .expect("Failed to parse command")
-                    .invoke(&ctx, &command_interaction)
-                    .await
-                    .expect("Failed to invoke command");
-            }
-            Interaction::MessageComponent(component_interaction) => {
-                if let Some(ref original_interaction) = component_interaction.message.interaction {
+                framework::response::guard(&ctx, &command_interaction).await;
+                // The server's locale is kept in the database too
+                if outage::is_down(&ctx).await {
+                    outage::respond_unavailable(&ctx, &command_interaction, Locale::default())
+                        .await;
+                    return;
+                }
+                let (command_interaction, missing_permissions, owner_only) = {
                     let data = ctx.data.read().await;
-                    let handlers = data
-                        .get::<MessageComponentInteractionHandlers>()
-                        .expect("Expected Handlers in TypeMap");
-                    let mut handler = handlers
-                        .get_mut(&original_interaction.id)
-                        .expect("No handler found for interaction");
-                    handler
-                        .invoke(
-                            &ctx,
-                            &component_interaction,
-                            &mut component_interaction.message.clone(),
-                        )
+                    let specs = data
+                        .get::<CommandSpecs>()
+                        .expect("Expected CommandSpecs in TypeMap");
+                    let command_interaction = specs.prepare(&command_interaction);
+                    let missing_permissions = specs.missing_permissions(&command_interaction);
+                    let owner_only = specs.is_owner_only(&command_interaction);
+                    (command_interaction, missing_permissions, owner_only)
+                };
+                let locale = guild_locale(&*repository(&ctx).await, command_interaction.guild_id);
+                if owner_only && !is_owner(&ctx, command_interaction.user.id).await {
+                    let result = respond(&ctx, &command_interaction, |message| {
+                        message
+                            .content(t!(locale, "errors.owner_only"))
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+                    .await;
+                    if let Err(why) = result {
+                        println!("Failed to send permission denied message: {:?}", why);
+                    }
+                    return;
+                }
+                if !missing_permissions.is_empty() {
+                    let result = respond(&ctx, &command_interaction, |message| {
+                        message
+                            .content(t!(
+                                locale,
+                                "errors.missing_permissions",
+                                permissions = missing_permissions
+                            ))
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+                    .await;
+                    if let Err(why) = result {
+                        println!("Failed to send permission denied message: {:?}", why);
+                    }
+                    return;
+                }
+                match Commands::parse(&ctx, &command_interaction) {
+                    Ok(command) => {
+                        let started = Instant::now();
+                        // Run apart so that a command failing because the database is down can
+                        // still be answered
+                        let invoked = tokio::spawn({
+                            let ctx = ctx.clone();
+                            let command_interaction = command_interaction.clone();
+                            async move { command.invoke(&ctx, &command_interaction).await }
+                        })
                         .await;
+                        match invoked {
+                            Ok(result) => {
+                                if let Err(why) = result {
+                                    println!("Failed to invoke command: {:?}", why);
+                                }
+                                commands::usage::record_usage(
+                                    &ctx,
+                                    &command_interaction,
+                                    started.elapsed(),
+                                )
+                                .await;
+                            }
+                            Err(why) => {
+                                println!("Command panicked: {:?}", why);
+                                if outage::check_database(&ctx).await {
+                                    outage::respond_unavailable(&ctx, &command_interaction, locale)
+                                        .await;
+                                }
+                            }
+                        }
+                    }
+                    Err(why) => {
+                        println!("Failed to parse command: {:?}", why);
+                        let result = respond(&ctx, &command_interaction, |message| {
+                            message
+                                .content(t!(locale, "errors.parse"))
+                                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                        })
+                        .await;
+                        if let Err(why) = result {
+                            println!("Failed to send parse error message: {:?}", why);
+                        }
+                    }
                 }
             }
+            Interaction::MessageComponent(component_interaction) => {
+                ComponentRouter::dispatch(&ctx, &component_interaction).await;
+            }
             _ => (),
         }
     }
 
     async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
-        let guild_id = env::var("TEST_GUILD_ID").map(|id| {
-            id.parse()
-                .map(|id| GuildId(id))
-                .expect("Invalid test guild id id")
-        });
-        let guild_id = match guild_id {
-            Ok(id) => Some(id),
-            Err(VarError::NotPresent) => None,
-            _ => panic!("Invalid guild id provided at $TEST_GUILD_ID"),
+        // Every time, since messages could have been missed while disconnected too
+        tokio::spawn(backfill::backfill_missed_messages(ctx.clone()));
+        if !BACKGROUND_TASKS_STARTED.swap(true, Ordering::SeqCst) {
+            tokio::spawn(presence::rotate_presence(ctx.clone()));
+            tokio::spawn(guilds::purge_departed_guilds(ctx.clone()));
+            tokio::spawn(guilds::crown_laureates(ctx.clone()));
+            tokio::spawn(guilds::enforce_retention_policies(ctx.clone()));
+            tokio::spawn(maintenance::run_periodic_maintenance(ctx.clone()));
+            tokio::spawn(web::finish_api_writes(ctx.clone()));
+            tokio::spawn(errors::report_migration_problems(
+                ctx.clone(),
+                ready.guilds.iter().map(|guild| guild.id()).collect(),
+            ));
+            #[cfg(unix)]
+            tokio::spawn(reload::reload_on_hangup(ctx.clone()));
+        }
+        let guild_id = test_guild_id();
+        let guild_scoped = {
+            let data = ctx.data.read().await;
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .commands
+                .guild_scoped
         };
-        let commands = register_commands!(
-            &ctx,
-            guild_id,
-            [
-                UptimeCommand,
-                CountCommand,
-                GetHaikuCommand,
-                RandomHaikuCommand,
-                SearchCommand
-            ]
-        )
-        .expect("Unable to register commands");
+        if guild_id.is_none() && guild_scoped {
+            // Commands get registered per guild as each guild is loaded
+            return;
+        }
+        register_all(&ctx, guild_id).await;
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: Guild, is_new: bool) {
+        guilds::on_guild_create(&ctx, &guild, is_new).await;
+    }
+
+    async fn guild_delete(&self, ctx: Context, incomplete: GuildUnavailable, _full: Option<Guild>) {
+        guilds::on_guild_delete(&ctx, &incomplete).await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        reactions::on_reaction_add(&ctx, &reaction).await;
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        {
+            let data = ctx.data.read().await;
+            data.get::<RuntimeStats>()
+                .expect("Expected RuntimeStats in TypeMap")
+                .record_message();
+        }
+        let (server_id, channel_id) = (msg.guild_id, msg.channel_id);
+        let incoming = IncomingMessage::from_message(&msg);
+        // Run apart so that if saving fails, the server's admins can be told about it
+        let handled = tokio::spawn({
+            let ctx = ctx.clone();
+            async move { on_message(&ctx, &msg).await }
+        })
+        .await;
+        if let (Err(why), Some(server_id)) = (handled, server_id) {
+            println!("Failed to handle message in {}: {:?}", server_id, why);
+            if outage::check_database(&ctx).await {
+                if let Some(incoming) = incoming {
+                    outage::buffer(&ctx, incoming).await;
+                }
+                return;
+            }
+            let repository = repository(&ctx).await;
+            let locale = guild_locale(&*repository, Some(server_id));
+            let content = t!(
+                locale,
+                "error_channel.message_failed",
+                channel = channel_id.mention()
+            );
+            errors::report_error(&ctx, &*repository, server_id, &content).await;
+        }
+    }
+}
+
+/// Import the haikus in a dump of the pre-rewrite database, see [legacy]
+fn migrate_from(path: Option<String>) {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            println!("Usage: haikubot migrate-from <dump of the old haikus table>");
+            std::process::exit(1);
+        }
+    };
+    // Written once the import's done, and checked first so the same dump isn't imported twice
+    let id_map_path = format!("{}.ids.csv", path);
+    if Path::new(&id_map_path).exists() {
         println!(
-            "Registered {} commands {}",
-            commands.len(),
-            match guild_id {
-                Some(id) => format!("for guild_id: {}", id),
-                None => "globally".to_owned(),
-            },
+            "{} has already been imported, delete {} to import it again",
+            path, id_map_path
         );
+        std::process::exit(1);
     }
+    let dump = fs::read_to_string(&path).expect("Unable to read legacy dump");
+    let haikus = match legacy::parse_legacy_dump(&dump) {
+        Ok(haikus) => haikus,
+        Err(line) => {
+            println!(
+                "Line {} of {} isn't a haiku from the old database",
+                line, path
+            );
+            std::process::exit(1);
+        }
+    };
+    let imported = legacy::import_legacy_haikus(&PostgresRepository::new(), &haikus);
+    fs::write(&id_map_path, legacy::id_map_csv(&imported)).expect("Unable to write id map");
+    println!(
+        "Imported {} of {} haikus, their new numbers are in {}",
+        imported.len(),
+        haikus.len(),
+        id_map_path
+    );
+}
 
-    async fn message(&self, ctx: Context, msg: Message) {
-        let channel = msg.channel_id;
-        let lines = msg.content.lines().map(|content| HaikuLine {
-            author: msg.author.id,
-            content: content.to_owned(),
-        });
-        for line in lines {
-            on_haiku_line(&ctx, channel, line).await;
+/// Wait for Ctrl-C, or SIGTERM (e.g. from `docker stop` or systemd)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Unable to listen for SIGTERM");
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.expect("Unable to listen for Ctrl-C"),
+            _ = terminate.recv() => {}
         }
     }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Unable to listen for Ctrl-C");
+}
+
+/// Disconnect every shard once the process is asked to stop, so `client.start()` returns and
+/// whatever's still in memory can be saved
+async fn shut_down_on_signal(shard_manager: Arc<Mutex<ShardManager>>) {
+    shutdown_signal().await;
+    println!("Shutting down");
+    shard_manager.lock().await.shutdown_all().await;
 }
 
 #[tokio::main]
 async fn main() {
+    if env::args().nth(1).as_deref() == Some("revalidate") {
+        let report = revalidation::revalidate_haikus(
+            &PostgresRepository::new(),
+            env::args().any(|arg| arg == "--flag"),
+            |_, _| true,
+        );
+        println!("{}", report.summary());
+        for (server_id, id) in report.invalid {
+            println!("Haiku #{} in server {}", id, server_id);
+        }
+        return;
+    }
+    if env::args().nth(1).as_deref() == Some("migrate-from") {
+        migrate_from(env::args().nth(2));
+        return;
+    }
+    if env::args().nth(1).as_deref() == Some("doctor") {
+        let healthy = doctor::report(&doctor::run_checks(true).await);
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
+
+    if !doctor::report(&doctor::run_checks(false).await) {
+        println!("Not starting until the errors above are fixed");
+        std::process::exit(1);
+    }
+
+    let config = Config::load().expect("Unable to load config");
+    if !config.intents.message_content {
+        println!("The message content intent is turned off, so haiku detection and chains are too");
+    }
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
     let application_id = env::var("DISCORD_USER_ID")
         .expect("Expected a user id in the environment")
         .parse::<u64>()
         .expect("Invalid user id");
+    let postgres = Arc::new(if config.save_queue.enabled {
+        PostgresRepository::with_save_queue(config.save_queue.capacity, config.save_queue.max_batch)
+    } else {
+        PostgresRepository::new()
+    });
+    let pipeline = Arc::new(detection_pipeline(&config));
+    recent_lines::restore(&pipeline, &config.detection.checkpoint_file);
+    let checkpoint_file = config.detection.checkpoint_file.clone();
     let mut client = Client::builder(&token)
         // .framework(framework)
         .event_handler(Handler)
         .application_id(application_id)
-        .intents(
-            GatewayIntents::GUILDS
-                | GatewayIntents::GUILD_MEMBERS
-                | GatewayIntents::GUILD_PRESENCES
-                | GatewayIntents::GUILD_MESSAGES
-                | GatewayIntents::GUILD_MESSAGE_REACTIONS,
-        )
+        .intents(config.intents.gateway_intents())
         .await
         .expect("Err creating client");
 
+    let runtime_stats = Arc::new(RuntimeStats::default());
+    // Haikus saved or deleted through the API are handed to the bot to announce or clean up
+    let (api_writes, pending_api_writes) = tokio::sync::mpsc::unbounded_channel();
+    if let Some(address) = config.web.address {
+        tokio::spawn(web::serve(
+            address,
+            client.shard_manager.clone(),
+            runtime_stats.clone(),
+            postgres.clone(),
+            client.cache_and_http.clone(),
+            pipeline.clone(),
+            api_writes,
+        ));
+    }
+
     {
         let mut data = client.data.write().await;
-        data.insert::<HaikuTracker>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<HaikuTracker>(pipeline.clone());
+        data.insert::<PendingApiWrites>(Some(pending_api_writes));
+        data.insert::<RandomHaikuHistory>(Arc::new(Mutex::new(ShuffleHistory::new())));
+        data.insert::<ChainTracker>(Arc::new(Mutex::new(ChainGames::new())));
+        data.insert::<CompletionTracker>(Arc::new(Mutex::new(CompletionGames::new())));
+        data.insert::<HistoryScans>(Arc::new(DashSet::new()));
+        data.insert::<Repository>(postgres.clone());
+        data.insert::<DatabaseOutage>(Arc::new(Outage::default()));
+        data.insert::<BulkRequests>(Arc::new(BulkExecutor::default()));
+        data.insert::<CommandSpecs>(Arc::new(commands::configured_command_specs(
+            &config.commands,
+        )));
+        data.insert::<Config>(Arc::new(config));
         data.insert::<UptimeStart>(Utc::now());
+        data.insert::<RuntimeStats>(runtime_stats);
+        data.insert::<ShardManagerContainer>(client.shard_manager.clone());
+        data.insert::<RegisteredCommands>(Vec::new());
+        data.insert::<InteractionResponses>(Arc::new(DashMap::new()));
+        data.insert::<RunningOperations>(Arc::new(DashMap::new()));
         data.insert::<MessageComponentInteractionHandlers>(DashMap::new());
+        data.insert::<ComponentRouter>(Arc::new(commands::component_router()));
     }
 
+    tokio::spawn(recent_lines::checkpoint_recent_lines(client.data.clone()));
+    tokio::spawn(shut_down_on_signal(client.shard_manager.clone()));
     if let Err(why) = client.start().await {
         println!("Client error: {:?}", why);
     }
+    // Don't lose votes that were still waiting to be written, or lines that could still finish a
+    // haiku once the bot's back
+    postgres.flush();
+    recent_lines::save(&pipeline, &checkpoint_file);
+    println!("Saved queued writes and recent lines, exiting");
 }
@@ -0,0 +1,87 @@
+use crate::{
+    config::Config,
+    database,
+    formatting::format_bytes,
+    jobs::{record_run, wait_until_due},
+    schedule::Job,
+    MessageComponentInteractionHandlers,
+};
+use chrono::{Duration, Utc};
+use serenity::client::Context;
+
+/// What a maintenance run did
+pub struct MaintenanceReport {
+    pub size_before: i64,
+    pub size_after: i64,
+    /// Pagination handlers for old search and stats messages that were forgotten
+    pub handlers_pruned: usize,
+}
+
+impl MaintenanceReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "Database maintenance finished. Size: {} -> {}. Pruned {} stale component handlers",
+            format_bytes(self.size_before as u64),
+            format_bytes(self.size_after as u64),
+            self.handlers_pruned
+        )
+    }
+}
+
+/// Forget the per-message component handlers (e.g. search pagination) of commands run longer
+/// ago than the configured lifetime, returning how many were forgotten
+async fn prune_component_handlers(ctx: &Context) -> usize {
+    let data = ctx.data.read().await;
+    let lifetime_hours = data
+        .get::<Config>()
+        .expect("Expected Config in TypeMap")
+        .maintenance
+        .component_ttl_hours;
+    let cutoff = Utc::now() - Duration::hours(lifetime_hours as i64);
+    let handlers = data
+        .get::<MessageComponentInteractionHandlers>()
+        .expect("Expected Handlers in TypeMap");
+    let before = handlers.len();
+    // Interaction ids are snowflakes, so they know when the command was run
+    handlers.retain(|id, _| id.created_at() >= cutoff);
+    before - handlers.len()
+}
+
+/// Vacuum and analyze the database and prune stale in-memory state
+pub async fn run_maintenance(ctx: &Context) -> MaintenanceReport {
+    let handlers_pruned = prune_component_handlers(ctx).await;
+    // VACUUM can take a while on a big database, so keep it off the async workers
+    let (size_before, size_after) = tokio::task::spawn_blocking(|| {
+        let db_connection = database::establish_connection();
+        let before = database::get_database_size(&db_connection);
+        database::run_maintenance(&db_connection);
+        (before, database::get_database_size(&db_connection))
+    })
+    .await
+    .expect("Database maintenance panicked");
+    MaintenanceReport {
+        size_before,
+        size_after,
+        handlers_pruned,
+    }
+}
+
+/// Run maintenance every so often, as configured
+pub async fn run_periodic_maintenance(ctx: Context) {
+    loop {
+        let config = {
+            let data = ctx.data.read().await;
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .maintenance
+                .clone()
+        };
+        if !config.enabled {
+            return;
+        }
+        let interval = Duration::hours(config.interval_hours.max(1) as i64);
+        wait_until_due(&ctx, Job::Maintenance, interval).await;
+        println!("{}", run_maintenance(&ctx).await.summary());
+        record_run(&ctx, Job::Maintenance).await;
+    }
+}
@@ -0,0 +1,64 @@
+//! Celebrating servers reaching round numbers of haikus
+
+use crate::repository::HaikuRepository;
+use serenity::model::id::GuildId;
+
+/// Whether a server's haiku count is worth celebrating: the 100th, the 500th, then every 1000th
+pub fn is_milestone(count: i64) -> bool {
+    count == 100 || count == 500 || (count > 0 && count % 1000 == 0)
+}
+
+/// Check a server's haiku count after saving a haiku, returning the milestone if it has just been
+/// reached. Each milestone is only announced once, even if haikus are deleted and it's reached again.
+pub fn reached_milestone(repository: &dyn HaikuRepository, server_id: GuildId) -> Option<i64> {
+    let count = repository.count_haikus(server_id);
+    if is_milestone(count) && repository.record_milestone(server_id, count) {
+        Some(count)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_milestone, reached_milestone};
+    use crate::{
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use serenity::model::id::GuildId;
+
+    fn save_haikus(repository: &MemoryRepository, count: usize) -> Vec<i64> {
+        (0..count)
+            .map(|_| repository.save_haiku(&HaikuBuilder::new().build()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_is_milestone() {
+        assert!(!is_milestone(0));
+        assert!(!is_milestone(99));
+        assert!(is_milestone(100));
+        assert!(!is_milestone(200));
+        assert!(is_milestone(500));
+        assert!(is_milestone(1000));
+        assert!(!is_milestone(1500));
+        assert!(is_milestone(3000));
+    }
+
+    #[test]
+    fn test_milestones_are_announced_once() {
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(1));
+        save_haikus(&repository, 99);
+        assert_eq!(reached_milestone(&repository, GuildId(1)), None);
+        let ids = save_haikus(&repository, 1);
+        assert_eq!(reached_milestone(&repository, GuildId(1)), Some(100));
+
+        // Dropping below and reaching it again doesn't repeat the announcement
+        repository.delete_haiku(GuildId(1), ids[0]);
+        save_haikus(&repository, 1);
+        assert_eq!(reached_milestone(&repository, GuildId(1)), None);
+        assert_eq!(reached_milestone(&repository, GuildId(2)), None);
+    }
+}
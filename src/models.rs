@@ -1,6 +1,10 @@
-use super::schema::haikus;
+use super::schema::{
+    api_tokens, command_usage, haiku_edits, haiku_lines, haikus, history_scans, quiz_scores,
+    server_configs,
+};
+use crate::counting::{count_line, HAIKU_SYLLABLES};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId, WebhookId};
 use std::convert::TryFrom;
 
 #[derive(Debug, Clone)]
@@ -9,16 +13,193 @@ pub struct Haiku {
     pub timestamp: DateTime<Utc>,
     pub channel: ChannelId,
     pub server: GuildId,
+    /// Unknown for haikus saved before message ids were kept
+    pub source: Option<HaikuSource>,
 }
 
 #[derive(Debug, Clone)]
 pub struct HaikuLine {
     pub author: UserId,
+    /// The name to show for an author who isn't a member of the server, e.g. the name a webhook
+    /// relaying someone's messages posted under
+    pub author_name: Option<String>,
     pub content: String,
 }
 
+/// One line of a stored haiku
+#[derive(Debug, Clone)]
+pub struct StoredLine {
+    pub haiku_id: i64,
+    /// Which line of the haiku it is, counting from 0
+    pub position: usize,
+    pub line: HaikuLine,
+}
+
+/// The messages a haiku was found in, which stop the same haiku being saved twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HaikuSource {
+    /// The message each line came from
+    pub messages: [MessageId; 3],
+    /// Which line of the last message completed the haiku, counting from 0
+    pub line: usize,
+}
+
 #[derive(Debug, Queryable)]
 pub struct HaikuDTO {
+    pub id: i64,
+    pub channel: i64,
+    pub server: i64,
+    pub timestamp: NaiveDateTime,
+    pub flagged: bool,
+    pub last_shown_at: Option<NaiveDateTime>,
+    pub votes: i64,
+    pub source_message: Option<i64>,
+    pub source_line: Option<i64>,
+    pub sensitive: bool,
+    pub mirror_message: Option<i64>,
+    pub announcement_message: Option<i64>,
+    /// What the haiku is called in its server, counting up from 1
+    pub number: i64,
+}
+
+impl HaikuDTO {
+    /// The haiku with its lines, which are stored separately. None if it doesn't have all three.
+    pub fn with_lines(self, mut lines: Vec<HaikuLineDTO>) -> Option<(i64, Haiku)> {
+        lines.sort_by_key(|line| line.position);
+        let [line_0, line_1, line_2] = match lines.as_slice() {
+            [line_0, line_1, line_2] => [line_0.clone(), line_1.clone(), line_2.clone()],
+            _ => return None,
+        };
+        let source = match (
+            line_0.message_id,
+            line_1.message_id,
+            line_2.message_id,
+            self.source_line,
+        ) {
+            (Some(message_0), Some(message_1), Some(message_2), Some(line)) => Some(HaikuSource {
+                messages: [
+                    MessageId::from(u64::try_from(message_0).unwrap()),
+                    MessageId::from(u64::try_from(message_1).unwrap()),
+                    MessageId::from(u64::try_from(message_2).unwrap()),
+                ],
+                line: usize::try_from(line).unwrap(),
+            }),
+            _ => None,
+        };
+        Some((
+            self.number,
+            Haiku {
+                lines: [line_0.into(), line_1.into(), line_2.into()],
+                timestamp: DateTime::from_utc(self.timestamp, Utc),
+                channel: ChannelId::from(u64::try_from(self.channel).unwrap()),
+                server: GuildId::from(u64::try_from(self.server).unwrap()),
+                source,
+            },
+        ))
+    }
+}
+
+/// One line of a saved haiku, with who wrote it and when
+#[derive(Debug, Clone, Queryable)]
+pub struct HaikuLineDTO {
+    pub server: i64,
+    pub haiku_id: i64,
+    /// Which line of the haiku it is, counting from 0
+    pub position: i64,
+    pub author: i64,
+    pub content: String,
+    /// Unknown for haikus saved before message ids were kept
+    pub message_id: Option<i64>,
+    pub sent_at: NaiveDateTime,
+    pub syllables: i64,
+    pub author_name: Option<String>,
+}
+
+impl Into<HaikuLine> for HaikuLineDTO {
+    fn into(self) -> HaikuLine {
+        HaikuLine {
+            content: self.content,
+            author: UserId::from(u64::try_from(self.author).unwrap()),
+            author_name: self.author_name,
+        }
+    }
+}
+
+impl Into<StoredLine> for HaikuLineDTO {
+    fn into(self) -> StoredLine {
+        StoredLine {
+            haiku_id: self.haiku_id,
+            position: usize::try_from(self.position).unwrap(),
+            line: self.into(),
+        }
+    }
+}
+
+/// A change made to one line of a haiku with /edithaiku
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HaikuEdit {
+    /// Which line was changed, counting from 0
+    pub position: usize,
+    /// What the line said before
+    pub previous: String,
+    pub content: String,
+    pub editor: UserId,
+    pub edited_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct HaikuEditDTO {
+    pub id: i64,
+    pub server: i64,
+    pub haiku_id: i64,
+    pub position: i64,
+    pub previous: String,
+    pub content: String,
+    pub editor: i64,
+    pub edited_at: NaiveDateTime,
+}
+
+impl Into<HaikuEdit> for HaikuEditDTO {
+    fn into(self) -> HaikuEdit {
+        HaikuEdit {
+            position: usize::try_from(self.position).unwrap(),
+            previous: self.previous,
+            content: self.content,
+            editor: UserId::from(u64::try_from(self.editor).unwrap()),
+            edited_at: DateTime::from_utc(self.edited_at, Utc),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "haiku_edits"]
+pub struct NewHaikuEditDTO {
+    pub server: i64,
+    pub haiku_id: i64,
+    pub position: i64,
+    pub previous: String,
+    pub content: String,
+    pub editor: i64,
+    pub edited_at: NaiveDateTime,
+}
+
+impl NewHaikuEditDTO {
+    pub fn new(server_id: GuildId, haiku_id: i64, edit: &HaikuEdit) -> Self {
+        NewHaikuEditDTO {
+            server: i64::try_from(*server_id.as_u64()).unwrap(),
+            haiku_id,
+            position: i64::try_from(edit.position).unwrap(),
+            previous: edit.previous.clone(),
+            content: edit.content.clone(),
+            editor: i64::try_from(*edit.editor.as_u64()).unwrap(),
+            edited_at: edit.edited_at.naive_utc(),
+        }
+    }
+}
+
+/// A haiku moved out of the haikus table by a server's retention policy
+#[derive(Debug, Queryable)]
+pub struct ArchivedHaikuDTO {
     pub id: i64,
     pub channel: i64,
     pub server: i64,
@@ -29,30 +210,28 @@ pub struct HaikuDTO {
     pub message_0: String,
     pub message_1: String,
     pub message_2: String,
+    pub archived_at: NaiveDateTime,
 }
 
-impl Into<(i64, Haiku)> for HaikuDTO {
+impl Into<(i64, Haiku)> for ArchivedHaikuDTO {
     fn into(self) -> (i64, Haiku) {
+        let line = |author: i64, content: String| HaikuLine {
+            content,
+            author: UserId::from(u64::try_from(author).unwrap()),
+            author_name: None,
+        };
         (
             self.id,
             Haiku {
                 lines: [
-                    HaikuLine {
-                        content: self.message_0,
-                        author: UserId::from(u64::try_from(self.author_0).unwrap()),
-                    },
-                    HaikuLine {
-                        content: self.message_1,
-                        author: UserId::from(u64::try_from(self.author_1).unwrap()),
-                    },
-                    HaikuLine {
-                        content: self.message_2,
-                        author: UserId::from(u64::try_from(self.author_2).unwrap()),
-                    },
+                    line(self.author_0, self.message_0),
+                    line(self.author_1, self.message_1),
+                    line(self.author_2, self.message_2),
                 ],
                 timestamp: DateTime::from_utc(self.timestamp, Utc),
                 channel: ChannelId::from(u64::try_from(self.channel).unwrap()),
                 server: GuildId::from(u64::try_from(self.server).unwrap()),
+                source: None,
             },
         )
     }
@@ -61,29 +240,543 @@ impl Into<(i64, Haiku)> for HaikuDTO {
 #[derive(Insertable)]
 #[table_name = "haikus"]
 pub struct NewHaikuDTO {
+    pub number: i64,
     pub channel: i64,
     pub server: i64,
     pub timestamp: NaiveDateTime,
-    pub author_0: i64,
-    pub author_1: i64,
-    pub author_2: i64,
-    pub message_0: String,
-    pub message_1: String,
-    pub message_2: String,
+    pub source_message: Option<i64>,
+    pub source_line: Option<i64>,
 }
 
-impl From<&Haiku> for NewHaikuDTO {
-    fn from(haiku: &Haiku) -> Self {
+impl NewHaikuDTO {
+    /// A haiku about to be saved with the next number in its server
+    pub fn from_haiku(number: i64, haiku: &Haiku) -> Self {
         NewHaikuDTO {
+            number,
             channel: i64::try_from(*haiku.channel.as_u64()).unwrap(),
             server: i64::try_from(*haiku.server.as_u64()).unwrap(),
             timestamp: haiku.timestamp.naive_utc(),
-            author_0: i64::try_from(*haiku.lines[0].author.as_u64()).unwrap(),
-            author_1: i64::try_from(*haiku.lines[1].author.as_u64()).unwrap(),
-            author_2: i64::try_from(*haiku.lines[2].author.as_u64()).unwrap(),
-            message_0: haiku.lines[0].content.clone(),
-            message_1: haiku.lines[1].content.clone(),
-            message_2: haiku.lines[2].content.clone(),
+            source_message: haiku
+                .source
+                .map(|source| i64::try_from(*source.messages[2].as_u64()).unwrap()),
+            source_line: haiku
+                .source
+                .map(|source| i64::try_from(source.line).unwrap()),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "haiku_lines"]
+pub struct NewHaikuLineDTO {
+    pub server: i64,
+    pub haiku_id: i64,
+    pub position: i64,
+    pub author: i64,
+    pub content: String,
+    pub message_id: Option<i64>,
+    pub sent_at: NaiveDateTime,
+    pub syllables: i64,
+    pub author_name: Option<String>,
+}
+
+impl NewHaikuLineDTO {
+    /// The lines of a haiku that's just been saved with the given number. Each line was sent when its
+    /// message was, if that's known.
+    pub fn from_haiku(haiku_id: i64, haiku: &Haiku) -> Vec<Self> {
+        haiku
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(position, line)| {
+                let message = haiku.source.map(|source| source.messages[position]);
+                NewHaikuLineDTO {
+                    server: i64::try_from(*haiku.server.as_u64()).unwrap(),
+                    haiku_id,
+                    position: i64::try_from(position).unwrap(),
+                    author: i64::try_from(*line.author.as_u64()).unwrap(),
+                    content: line.content.clone(),
+                    message_id: message.map(|message| i64::try_from(*message.as_u64()).unwrap()),
+                    sent_at: message
+                        .map(|message| message.created_at())
+                        .unwrap_or(haiku.timestamp)
+                        .naive_utc(),
+                    syllables: count_line(&line.content)
+                        .map(|count| count.syllables())
+                        .unwrap_or_else(|_| HAIKU_SYLLABLES[position])
+                        as i64,
+                    author_name: line.author_name.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub server: GuildId,
+    pub joined_at: DateTime<Utc>,
+    pub left_at: Option<DateTime<Utc>>,
+    pub locale: String,
+    /// The highest haiku count milestone that has been announced
+    pub last_milestone: i64,
+    /// Whether to explain the bot to people when their first haiku is found
+    pub welcome_enabled: bool,
+    /// How /randomhaiku favours some haikus over others, see [crate::database::RandomWeighting]
+    pub random_weighting: String,
+    /// The role given to whoever has written the most haikus this month, if enabled
+    pub laureate_role: Option<RoleId>,
+    /// Who currently holds the laureate role
+    pub laureate: Option<UserId>,
+    /// How many years haikus are kept for before the retention action applies, forever if None
+    pub retention_years: Option<i64>,
+    /// What happens to haikus older than the retention window, see [crate::database::RetentionAction]
+    pub retention_action: String,
+    /// Whether the server's haiku counts are included in /globalstats
+    pub global_stats_enabled: bool,
+    /// Text posted with each announced haiku, see [crate::formatting::render_template]
+    pub announcement_template: Option<String>,
+    /// The reaction that saves a haiku in channels where they aren't detected automatically
+    pub manual_save_emoji: Option<String>,
+    /// Who may save haikus with [ServerConfig::manual_save_emoji]
+    pub manual_save_role: Option<RoleId>,
+    /// Whether messages sent by bots can be haikus
+    pub detect_bot_messages: bool,
+    /// Whether messages sent by webhooks, e.g. bridges from IRC or Matrix, can be haikus
+    pub detect_webhook_messages: bool,
+    /// Picks the real author out of messages relayed by a bridge bot, see
+    /// [crate::detection::bridge]
+    pub bridge_pattern: Option<String>,
+    /// Whether haikus are only saved when one person wrote all three lines
+    pub same_author_only: bool,
+    /// Where the bot tells admins when something goes wrong
+    pub error_channel: Option<ChannelId>,
+    /// The IANA timezone periods like "this month" are counted in, see [crate::schedule]
+    pub timezone: String,
+    /// Whether haikus are shown as plain text to anyone who hasn't chosen for themselves
+    pub plain_text: bool,
+    /// Where every saved haiku is mirrored, if anywhere
+    pub archive: Option<ArchiveWebhook>,
+    /// Whether members may make the haikus they wrote on their own public with /feed
+    pub public_feeds: bool,
+}
+
+/// The webhook the bot posts each of a server's haikus to in its archive channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveWebhook {
+    pub channel: ChannelId,
+    pub webhook: WebhookId,
+    pub token: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct ServerConfigDTO {
+    pub server: i64,
+    pub joined_at: NaiveDateTime,
+    pub left_at: Option<NaiveDateTime>,
+    pub locale: String,
+    pub last_milestone: i64,
+    pub welcome_enabled: bool,
+    pub random_weighting: String,
+    pub laureate_role: Option<i64>,
+    pub laureate: Option<i64>,
+    pub retention_years: Option<i64>,
+    pub retention_action: String,
+    pub global_stats_enabled: bool,
+    pub announcement_template: Option<String>,
+    pub manual_save_emoji: Option<String>,
+    pub manual_save_role: Option<i64>,
+    pub detect_bot_messages: bool,
+    pub detect_webhook_messages: bool,
+    pub bridge_pattern: Option<String>,
+    pub same_author_only: bool,
+    pub error_channel: Option<i64>,
+    pub timezone: String,
+    pub plain_text: bool,
+    pub archive_channel: Option<i64>,
+    pub archive_webhook: Option<i64>,
+    pub archive_webhook_token: Option<String>,
+    pub public_feeds: bool,
+}
+
+impl Into<ServerConfig> for ServerConfigDTO {
+    fn into(self) -> ServerConfig {
+        ServerConfig {
+            server: GuildId::from(u64::try_from(self.server).unwrap()),
+            joined_at: DateTime::from_utc(self.joined_at, Utc),
+            left_at: self.left_at.map(|left_at| DateTime::from_utc(left_at, Utc)),
+            locale: self.locale,
+            last_milestone: self.last_milestone,
+            welcome_enabled: self.welcome_enabled,
+            random_weighting: self.random_weighting,
+            laureate_role: self
+                .laureate_role
+                .map(|role| RoleId::from(u64::try_from(role).unwrap())),
+            laureate: self
+                .laureate
+                .map(|user| UserId::from(u64::try_from(user).unwrap())),
+            retention_years: self.retention_years,
+            retention_action: self.retention_action,
+            global_stats_enabled: self.global_stats_enabled,
+            announcement_template: self.announcement_template,
+            manual_save_emoji: self.manual_save_emoji,
+            manual_save_role: self
+                .manual_save_role
+                .map(|role| RoleId::from(u64::try_from(role).unwrap())),
+            detect_bot_messages: self.detect_bot_messages,
+            detect_webhook_messages: self.detect_webhook_messages,
+            bridge_pattern: self.bridge_pattern,
+            same_author_only: self.same_author_only,
+            error_channel: self
+                .error_channel
+                .map(|channel| ChannelId::from(u64::try_from(channel).unwrap())),
+            timezone: self.timezone,
+            plain_text: self.plain_text,
+            archive: match (
+                self.archive_channel,
+                self.archive_webhook,
+                self.archive_webhook_token,
+            ) {
+                (Some(channel), Some(webhook), Some(token)) => Some(ArchiveWebhook {
+                    channel: ChannelId::from(u64::try_from(channel).unwrap()),
+                    webhook: WebhookId::from(u64::try_from(webhook).unwrap()),
+                    token,
+                }),
+                _ => None,
+            },
+            public_feeds: self.public_feeds,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "server_configs"]
+pub struct NewServerConfigDTO {
+    pub server: i64,
+    pub joined_at: NaiveDateTime,
+}
+
+/// Something the bot can DM people about, if they've asked it to with /notifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notification {
+    /// A haiku they wrote a line of was found
+    Detected,
+    /// Someone else bookmarked a haiku they wrote a line of
+    Bookmarked,
+    /// They won a contest, e.g. became their server's laureate
+    ContestWin,
+    /// A haiku they wrote a line of reached a number of votes
+    VoteThreshold,
+}
+
+impl Notification {
+    pub fn code(self) -> &'static str {
+        match self {
+            Notification::Detected => "detected",
+            Notification::Bookmarked => "bookmarked",
+            Notification::ContestWin => "contest_win",
+            Notification::VoteThreshold => "vote_threshold",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "detected" => Some(Notification::Detected),
+            "bookmarked" => Some(Notification::Bookmarked),
+            "contest_win" => Some(Notification::ContestWin),
+            "vote_threshold" => Some(Notification::VoteThreshold),
+            _ => None,
+        }
+    }
+
+    pub fn all() -> [Notification; 4] {
+        [
+            Notification::Detected,
+            Notification::Bookmarked,
+            Notification::ContestWin,
+            Notification::VoteThreshold,
+        ]
+    }
+}
+
+/// Which notifications someone wants. Nobody gets any until they turn them on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserPreferences {
+    pub user: UserId,
+    pub detected: bool,
+    pub bookmarked: bool,
+    pub contest_win: bool,
+    pub vote_threshold: bool,
+    /// Whether haikus are shown to them as plain text instead of embeds, or None to go by each
+    /// server's default
+    pub plain_text: Option<bool>,
+}
+
+impl UserPreferences {
+    pub fn new(user: UserId) -> Self {
+        UserPreferences {
+            user,
+            detected: false,
+            bookmarked: false,
+            contest_win: false,
+            vote_threshold: false,
+            plain_text: None,
+        }
+    }
+
+    pub fn wants(&self, notification: Notification) -> bool {
+        match notification {
+            Notification::Detected => self.detected,
+            Notification::Bookmarked => self.bookmarked,
+            Notification::ContestWin => self.contest_win,
+            Notification::VoteThreshold => self.vote_threshold,
+        }
+    }
+
+    pub fn set(&mut self, notification: Notification, enabled: bool) {
+        match notification {
+            Notification::Detected => self.detected = enabled,
+            Notification::Bookmarked => self.bookmarked = enabled,
+            Notification::ContestWin => self.contest_win = enabled,
+            Notification::VoteThreshold => self.vote_threshold = enabled,
+        }
+    }
+}
+
+#[derive(Debug, Queryable)]
+pub struct UserPreferencesDTO {
+    pub user_id: i64,
+    pub notify_detected: bool,
+    pub notify_bookmarked: bool,
+    pub notify_contest_win: bool,
+    pub notify_vote_threshold: bool,
+    pub plain_text: Option<bool>,
+}
+
+impl Into<UserPreferences> for UserPreferencesDTO {
+    fn into(self) -> UserPreferences {
+        UserPreferences {
+            user: UserId::from(u64::try_from(self.user_id).unwrap()),
+            detected: self.notify_detected,
+            bookmarked: self.notify_bookmarked,
+            contest_win: self.notify_contest_win,
+            vote_threshold: self.notify_vote_threshold,
+            plain_text: self.plain_text,
+        }
+    }
+}
+
+/// How someone has done at guessing who wrote haikus with /quiz
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuizScore {
+    pub user: UserId,
+    pub correct: i64,
+    pub answered: i64,
+}
+
+#[derive(Debug, Queryable)]
+pub struct QuizScoreDTO {
+    pub server: i64,
+    pub user_id: i64,
+    pub correct: i64,
+    pub answered: i64,
+}
+
+impl Into<QuizScore> for QuizScoreDTO {
+    fn into(self) -> QuizScore {
+        QuizScore {
+            user: UserId::from(u64::try_from(self.user_id).unwrap()),
+            correct: self.correct,
+            answered: self.answered,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "quiz_scores"]
+pub struct NewQuizScoreDTO {
+    pub server: i64,
+    pub user_id: i64,
+    pub correct: i64,
+    pub answered: i64,
+}
+
+/// How far /scanhistory has got through a channel, working back from the newest message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryScan {
+    pub server: GuildId,
+    pub channel: ChannelId,
+    /// The oldest message checked so far, the scan carrying on from just before it
+    pub before: Option<MessageId>,
+    /// Messages sent before this are left alone
+    pub until: DateTime<Utc>,
+    pub messages: i64,
+    pub haikus: i64,
+}
+
+#[derive(Debug, Queryable)]
+pub struct HistoryScanDTO {
+    pub channel: i64,
+    pub server: i64,
+    pub before_message: Option<i64>,
+    pub until_time: NaiveDateTime,
+    pub messages: i64,
+    pub haikus: i64,
+}
+
+impl Into<HistoryScan> for HistoryScanDTO {
+    fn into(self) -> HistoryScan {
+        HistoryScan {
+            server: GuildId::from(u64::try_from(self.server).unwrap()),
+            channel: ChannelId::from(u64::try_from(self.channel).unwrap()),
+            before: self
+                .before_message
+                .map(|id| MessageId::from(u64::try_from(id).unwrap())),
+            until: DateTime::from_utc(self.until_time, Utc),
+            messages: self.messages,
+            haikus: self.haikus,
+        }
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "history_scans"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewHistoryScanDTO {
+    pub channel: i64,
+    pub server: i64,
+    pub before_message: Option<i64>,
+    pub until_time: NaiveDateTime,
+    pub messages: i64,
+    pub haikus: i64,
+}
+
+impl From<&HistoryScan> for NewHistoryScanDTO {
+    fn from(scan: &HistoryScan) -> Self {
+        NewHistoryScanDTO {
+            channel: i64::try_from(*scan.channel.as_u64()).unwrap(),
+            server: i64::try_from(*scan.server.as_u64()).unwrap(),
+            before_message: scan.before.map(|id| i64::try_from(*id.as_u64()).unwrap()),
+            until_time: scan.until.naive_utc(),
+            messages: scan.messages,
+            haikus: scan.haikus,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "command_usage"]
+pub struct NewCommandUsageDTO {
+    pub server: i64,
+    pub command: String,
+    pub used_at: NaiveDateTime,
+    pub latency_ms: i64,
+}
+
+/// What an API token lets an outside tool do to a server's haikus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiScope {
+    /// Add haikus written somewhere else, e.g. in a form on the server's website
+    Submit,
+    Delete,
+}
+
+impl ApiScope {
+    pub fn code(self) -> &'static str {
+        match self {
+            ApiScope::Submit => "submit",
+            ApiScope::Delete => "delete",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "submit" => Some(ApiScope::Submit),
+            "delete" => Some(ApiScope::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// A token made with /apitoken for using the HTTP API's write endpoints in one server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiToken {
+    pub server: GuildId,
+    /// What the token is for, so it can be told apart from the server's other tokens
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    /// When the token stops working, or None if it never does
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Debug, Queryable)]
+pub struct ApiTokenDTO {
+    pub id: i64,
+    pub server: i64,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created_by: i64,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl Into<(i64, ApiToken)> for ApiTokenDTO {
+    fn into(self) -> (i64, ApiToken) {
+        let token = ApiToken {
+            server: GuildId::from(u64::try_from(self.server).unwrap()),
+            name: self.name,
+            // Scopes this version doesn't know about are left out rather than granted
+            scopes: self
+                .scopes
+                .iter()
+                .filter_map(|scope| ApiScope::from_code(scope))
+                .collect(),
+            created_by: UserId::from(u64::try_from(self.created_by).unwrap()),
+            created_at: DateTime::from_utc(self.created_at, Utc),
+            expires_at: self
+                .expires_at
+                .map(|expires_at| DateTime::from_utc(expires_at, Utc)),
+        };
+        (self.id, token)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "api_tokens"]
+pub struct NewApiTokenDTO {
+    pub server: i64,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created_by: i64,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl NewApiTokenDTO {
+    pub fn new(token: &ApiToken, token_hash: &str) -> Self {
+        NewApiTokenDTO {
+            server: i64::try_from(*token.server.as_u64()).unwrap(),
+            name: token.name.clone(),
+            token_hash: token_hash.to_owned(),
+            scopes: token
+                .scopes
+                .iter()
+                .map(|scope| scope.code().to_owned())
+                .collect(),
+            created_by: i64::try_from(*token.created_by.as_u64()).unwrap(),
+            created_at: token.created_at.naive_utc(),
+            expires_at: token.expires_at.map(|expires_at| expires_at.naive_utc()),
         }
     }
 }
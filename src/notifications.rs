@@ -0,0 +1,125 @@
+//! DMs about people's haikus, sent only to those who have turned them on with /notifications
+
+use crate::{
+    bulk::{bulk, Bucket},
+    formatting::{format_haiku, to_embed_data, wants_plain_text, FormattedHaiku},
+    i18n::guild_locale,
+    models::{Haiku, Notification},
+    repository::HaikuRepository,
+};
+use serenity::{client::Context, model::id::UserId};
+
+/// Vote counts at which the authors of a haiku are told how popular it's become
+const VOTE_THRESHOLDS: &[i64] = &[5, 10, 25, 50, 100, 250, 500, 1000];
+
+/// The vote threshold a haiku passed going from `before` votes to `after`, if any
+pub fn crossed_vote_threshold(before: i64, after: i64) -> Option<i64> {
+    VOTE_THRESHOLDS
+        .iter()
+        .rev()
+        .find(|threshold| before < **threshold && after >= **threshold)
+        .copied()
+}
+
+/// DM someone, if they want this kind of notification
+pub async fn notify(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    user: UserId,
+    notification: Notification,
+    content: &str,
+    haiku: Option<&FormattedHaiku>,
+) {
+    if !repository.get_user_preferences(user).wants(notification) {
+        return;
+    }
+    // Sent through the bulk executor since one vote or save can notify several people at once
+    let result = bulk(ctx)
+        .await
+        .run(&ctx.http, Bucket::DirectMessages, || {
+            user.direct_message(&ctx.http, |message| {
+                match haiku {
+                    Some(haiku) => {
+                        if let Some(content) = haiku.content(Some(content)) {
+                            message.content(content);
+                        }
+                        if let Some(embed) = haiku.embed() {
+                            message.set_embed(embed);
+                        }
+                    }
+                    None => {
+                        message.content(content);
+                    }
+                }
+                message
+            })
+        })
+        .await;
+    if let Err(why) = result {
+        println!(
+            "Could not send {} notification to {}: {:?}",
+            notification.code(),
+            user,
+            why
+        );
+    }
+}
+
+/// DM each author of a haiku who wants this kind of notification, other than `except`
+/// (e.g. someone bookmarking their own haiku)
+pub async fn notify_authors(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    id: i64,
+    haiku: &Haiku,
+    notification: Notification,
+    except: Option<UserId>,
+    content: &str,
+) {
+    let mut authors = haiku
+        .lines
+        .iter()
+        .map(|line| line.author)
+        .filter(|author| Some(*author) != except)
+        .collect::<Vec<UserId>>();
+    authors.sort_unstable();
+    authors.dedup();
+    authors.retain(|author| repository.get_user_preferences(*author).wants(notification));
+    if authors.is_empty() {
+        return;
+    }
+    let locale = guild_locale(repository, Some(haiku.server));
+    let embed_data = to_embed_data(id, haiku, locale, ctx).await;
+    let embed = format_haiku(embed_data.clone(), false);
+    let text = format_haiku(embed_data, true);
+    for author in authors {
+        let formatted = if wants_plain_text(repository, author, Some(haiku.server)) {
+            &text
+        } else {
+            &embed
+        };
+        notify(
+            ctx,
+            repository,
+            author,
+            notification,
+            content,
+            Some(formatted),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::crossed_vote_threshold;
+
+    #[test]
+    fn test_crossed_vote_threshold() {
+        assert_eq!(crossed_vote_threshold(0, 1), None);
+        assert_eq!(crossed_vote_threshold(4, 5), Some(5));
+        assert_eq!(crossed_vote_threshold(5, 6), None);
+        // Several votes counted at once only announce the highest threshold passed
+        assert_eq!(crossed_vote_threshold(8, 26), Some(25));
+    }
+}
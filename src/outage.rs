@@ -0,0 +1,144 @@
+//! Carrying on while the database can't be reached: commands say the haiku archive is
+//! unavailable, messages that might be part of a haiku are kept in memory, and the bot keeps
+//! trying to reconnect so it can check them for haikus once the database is back
+
+use crate::{
+    detection::pipeline::IncomingMessage,
+    framework::response::respond,
+    i18n::Locale,
+    repository::{repository, HaikuRepository},
+    HaikuTracker,
+};
+use serenity::{
+    client::Context,
+    model::interactions::{
+        application_command::ApplicationCommandInteraction,
+        InteractionApplicationCommandCallbackDataFlags,
+    },
+    prelude::{Mutex, TypeMapKey},
+};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// The most messages kept while the database is down, after which the oldest are dropped
+const MAX_BUFFERED: usize = 1000;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Default)]
+pub struct Outage {
+    down: AtomicBool,
+    buffered: Mutex<VecDeque<IncomingMessage>>,
+}
+
+pub struct DatabaseOutage;
+impl TypeMapKey for DatabaseOutage {
+    type Value = Arc<Outage>;
+}
+
+async fn outage(ctx: &Context) -> Arc<Outage> {
+    ctx.data
+        .read()
+        .await
+        .get::<DatabaseOutage>()
+        .expect("Expected DatabaseOutage in TypeMap")
+        .clone()
+}
+
+async fn is_available(repository: Arc<dyn HaikuRepository>) -> bool {
+    tokio::task::spawn_blocking(move || repository.is_available())
+        .await
+        .unwrap_or(false)
+}
+
+/// Whether the bot is running without its database
+pub async fn is_down(ctx: &Context) -> bool {
+    outage(ctx).await.down.load(Ordering::SeqCst)
+}
+
+/// After something has failed, check whether the database is why, and if so start running
+/// without it until it's back. Returns whether it's down.
+pub async fn check_database(ctx: &Context) -> bool {
+    if is_available(repository(ctx).await).await {
+        return false;
+    }
+    if !outage(ctx).await.down.swap(true, Ordering::SeqCst) {
+        println!("Lost the database connection, running without it until it's back");
+        tokio::spawn(reconnect(ctx.clone()));
+    }
+    true
+}
+
+/// Keep a message to check for haikus once the database is back, if it's down. Returns whether
+/// the message was kept.
+pub async fn buffer(ctx: &Context, message: IncomingMessage) -> bool {
+    let outage = outage(ctx).await;
+    let mut buffered = outage.buffered.lock().await;
+    // Checked with the lock held, so a message can't be kept just after the buffer's emptied
+    if !outage.down.load(Ordering::SeqCst) {
+        return false;
+    }
+    if buffered.len() >= MAX_BUFFERED {
+        buffered.pop_front();
+    }
+    buffered.push_back(message);
+    true
+}
+
+/// Tell whoever ran a command that it can't be run until the database is back
+pub async fn respond_unavailable(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    locale: Locale,
+) {
+    let result = respond(ctx, command, |message| {
+        message
+            .content(t!(locale, "outage.unavailable"))
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Failed to send database unavailable message: {:?}", why);
+    }
+}
+
+/// Try connecting again, backing off each time it fails, then check the kept messages
+async fn reconnect(ctx: Context) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        tokio::time::sleep(backoff).await;
+        if is_available(repository(&ctx).await).await {
+            break;
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        println!("Database still unavailable, retrying in {:?}", backoff);
+    }
+    let outage = outage(&ctx).await;
+    let buffered = {
+        let mut buffered = outage.buffered.lock().await;
+        outage.down.store(false, Ordering::SeqCst);
+        std::mem::take(&mut *buffered)
+    };
+    println!(
+        "Database connection is back, checking {} messages for haikus",
+        buffered.len()
+    );
+    let repository = repository(&ctx).await;
+    let pipeline = {
+        let data = ctx.data.read().await;
+        data.get::<HaikuTracker>()
+            .expect("Expected HaikuTracker in TypeMap")
+            .clone()
+    };
+    for message in buffered {
+        if repository.is_detection_enabled(message.server, message.channel) {
+            pipeline.run(&ctx, &*repository, message).await;
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Keeping the messages the bot posted for a haiku, its announcement and its mirror in the archive
+//! channel, in step with the haiku when it changes or is deleted
+
+use crate::{
+    archive::{delete_mirror, update_mirror},
+    formatting::{format_haiku, haiku_framing, to_embed_data},
+    i18n::guild_locale,
+    models::Haiku,
+    repository::HaikuRepository,
+    retry::with_retry,
+};
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId, MessageId},
+};
+
+/// The messages posted for a haiku, which have to be looked up before it's deleted
+pub struct HaikuPosts {
+    channel: ChannelId,
+    announcement: Option<MessageId>,
+    mirror: Option<MessageId>,
+}
+
+/// Where a haiku was posted, or None if there's no such haiku
+pub fn find_posts(
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    id: i64,
+) -> Option<HaikuPosts> {
+    let (_, haiku) = repository.get_haiku(server_id, id)?;
+    Some(HaikuPosts {
+        channel: haiku.channel,
+        announcement: repository.get_announcement_message(server_id, id),
+        mirror: repository.get_mirror_message(server_id, id),
+    })
+}
+
+/// Show a haiku's announcement as the haiku is now, keeping its title and any text before it
+async fn update_announcement(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    id: i64,
+    haiku: &Haiku,
+) {
+    let message_id = match repository.get_announcement_message(haiku.server, id) {
+        Some(message_id) => message_id,
+        None => return,
+    };
+    let message = match with_retry(&ctx.http, || haiku.channel.message(&ctx.http, message_id)).await
+    {
+        Ok(message) => message,
+        Err(why) => {
+            println!("Could not fetch announcement of haiku {}: {:?}", id, why);
+            return;
+        }
+    };
+    let plain_text = message.embeds.is_empty();
+    let framing = haiku_framing(
+        &message.content,
+        plain_text,
+        message
+            .embeds
+            .first()
+            .and_then(|embed| embed.title.as_deref()),
+    );
+    let locale = guild_locale(repository, Some(haiku.server));
+    let mut formatted = format_haiku(to_embed_data(id, haiku, locale, ctx).await, plain_text);
+    if let Some(title) = framing.title {
+        formatted = formatted.titled(title);
+    }
+    let content = formatted.content(framing.before.as_deref());
+    let embed = formatted.embed();
+    let result = with_retry(&ctx.http, || {
+        haiku
+            .channel
+            .edit_message(&ctx.http, message_id, |message| {
+                message
+                    .content(content.as_deref().unwrap_or_default())
+                    .set_embeds(embed.iter().cloned().collect())
+            })
+    })
+    .await;
+    if let Err(why) = result {
+        println!("Could not update announcement of haiku {}: {:?}", id, why);
+    }
+}
+
+/// Show the haiku as it is now everywhere it was posted, e.g. after a content warning is added
+pub async fn refresh_posts(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    id: i64,
+) {
+    let haiku = match repository.get_haiku(server_id, id) {
+        Some((_, haiku)) => haiku,
+        None => return,
+    };
+    update_announcement(ctx, repository, id, &haiku).await;
+    update_mirror(ctx, repository, server_id, id).await;
+}
+
+/// Delete everything posted for a haiku that has been deleted
+pub async fn delete_posts(
+    ctx: &Context,
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    posts: HaikuPosts,
+) {
+    if let Some(announcement) = posts.announcement {
+        let result = with_retry(&ctx.http, || {
+            posts.channel.delete_message(&ctx.http, announcement)
+        })
+        .await;
+        if let Err(why) = result {
+            println!("Could not delete announcement {}: {:?}", announcement, why);
+        }
+    }
+    if let Some(mirror) = posts.mirror {
+        delete_mirror(ctx, repository, server_id, mirror).await;
+    }
+}
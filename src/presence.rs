@@ -0,0 +1,58 @@
+use crate::{
+    config::{ActivityConfig, ActivityKind, Config},
+    repository::{repository, HaikuRepository},
+};
+use serenity::{client::Context, model::gateway::Activity};
+use std::time::Duration;
+
+// Discord truncates activity names beyond this length
+const MAX_ACTIVITY_LENGTH: usize = 128;
+
+/// Cycle through the configured activities forever
+pub async fn rotate_presence(ctx: Context) {
+    let mut index = 0;
+    loop {
+        let config = {
+            let data = ctx.data.read().await;
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .presence
+                .clone()
+        };
+        if !config.enabled || config.activities.is_empty() {
+            return;
+        }
+        index %= config.activities.len();
+        let repository = repository(&ctx).await;
+        if let Some(activity) = render_activity(&*repository, &config.activities[index]) {
+            ctx.set_activity(activity).await;
+        }
+        index += 1;
+        tokio::time::sleep(Duration::from_secs(config.interval_secs.max(15))).await;
+    }
+}
+
+fn render_activity(
+    repository: &dyn HaikuRepository,
+    activity: &ActivityConfig,
+) -> Option<Activity> {
+    let mut text = activity.text.clone();
+    if text.contains("{haiku_count}") || text.contains("{recent_line}") {
+        if text.contains("{haiku_count}") {
+            let haiku_count = repository.count_all_haikus();
+            text = text.replace("{haiku_count}", &haiku_count.to_string());
+        }
+        if text.contains("{recent_line}") {
+            // Skip this activity entirely until there's a haiku to quote
+            let (_, haiku) = repository.get_latest_haiku()?;
+            text = text.replace("{recent_line}", &haiku.lines[0].content);
+        }
+    }
+    let text = text.chars().take(MAX_ACTIVITY_LENGTH).collect::<String>();
+    Some(match activity.kind {
+        ActivityKind::Playing => Activity::playing(&text),
+        ActivityKind::Listening => Activity::listening(&text),
+        ActivityKind::Watching => Activity::watching(&text),
+        ActivityKind::Competing => Activity::competing(&text),
+    })
+}
@@ -0,0 +1,62 @@
+use crate::{
+    config::Config,
+    detection::pipeline::{fetch_earlier, IncomingMessage},
+    repository::repository,
+    HaikuTracker,
+};
+use serenity::{client::Context, model::channel::Reaction};
+
+/// Save the haiku ending in a message when someone with the server's manual save role reacts to
+/// it with the manual save emoji, in channels where haikus aren't detected automatically
+pub async fn on_reaction_add(ctx: &Context, reaction: &Reaction) {
+    let (server_id, user_id) = match (reaction.guild_id, reaction.user_id) {
+        (Some(server_id), Some(user_id)) => (server_id, user_id),
+        _ => return,
+    };
+    let repository = repository(ctx).await;
+    let (emoji, role) = match repository.get_server_config(server_id) {
+        Some(config) => match (config.manual_save_emoji, config.manual_save_role) {
+            (Some(emoji), Some(role)) => (emoji, role),
+            _ => return,
+        },
+        None => return,
+    };
+    if reaction.emoji.to_string() != emoji
+        || repository.is_detection_enabled(server_id, reaction.channel_id)
+    {
+        return;
+    }
+    let (can_read_messages, pipeline) = {
+        let data = ctx.data.read().await;
+        (
+            data.get::<Config>()
+                .expect("Expected Config in TypeMap")
+                .intents
+                .message_content,
+            data.get::<HaikuTracker>()
+                .expect("Expected HaikuTracker in TypeMap")
+                .clone(),
+        )
+    };
+    if !can_read_messages {
+        return;
+    }
+    match server_id.member(ctx, user_id).await {
+        Ok(member) if member.roles.contains(&role) => (),
+        _ => return,
+    }
+
+    let message = match reaction.message(&ctx.http).await {
+        Ok(message) => message,
+        Err(why) => {
+            println!("Failed to fetch message to save: {:?}", why);
+            return;
+        }
+    };
+    let incoming = IncomingMessage::in_server(&message, server_id);
+    let earlier = fetch_earlier(ctx, server_id, reaction.channel_id, message.id).await;
+    // Saved haikus are announced by the pipeline's hooks, like detected ones
+    pipeline
+        .run_requested(ctx, &*repository, earlier, incoming)
+        .await;
+}
@@ -0,0 +1,58 @@
+//! Saving the lines each channel could still finish a haiku with to a file, and reading them back
+//! on startup, see [crate::detection::checkpoint]
+
+use crate::{config::Config, detection::pipeline::DetectionPipeline, HaikuTracker};
+use serenity::prelude::{RwLock, TypeMap};
+use std::{fs, sync::Arc, time::Duration};
+
+/// Read back the recent lines saved before the bot last stopped, if there are any
+pub fn restore(pipeline: &DetectionPipeline, path: &str) {
+    if path.is_empty() {
+        return;
+    }
+    let checkpoint = match fs::read_to_string(path) {
+        Ok(checkpoint) => checkpoint,
+        // Nothing's been saved yet
+        Err(_) => return,
+    };
+    match pipeline.restore(&checkpoint) {
+        Ok(()) => println!("Restored recent lines from {}", path),
+        Err(why) => println!("Could not restore recent lines from {}: {:?}", path, why),
+    }
+}
+
+/// Save the recent lines, writing them elsewhere first so a crash partway through can't leave
+/// the file half written
+pub fn save(pipeline: &DetectionPipeline, path: &str) {
+    if path.is_empty() {
+        return;
+    }
+    let partial = format!("{}.partial", path);
+    let result =
+        fs::write(&partial, pipeline.checkpoint()).and_then(|_| fs::rename(&partial, path));
+    if let Err(why) = result {
+        println!("Could not save recent lines to {}: {:?}", path, why);
+    }
+}
+
+/// Save the recent lines every so often, as often as the config says at the time. Started with
+/// the client rather than once it's connected, so lines are still saved while it reconnects.
+pub async fn checkpoint_recent_lines(data: Arc<RwLock<TypeMap>>) {
+    loop {
+        let (pipeline, path, interval) = {
+            let data = data.read().await;
+            let config = data.get::<Config>().expect("Expected Config in TypeMap");
+            (
+                data.get::<HaikuTracker>()
+                    .expect("Expected HaikuTracker in TypeMap")
+                    .clone(),
+                config.detection.checkpoint_file.clone(),
+                config.detection.checkpoint_secs.max(1),
+            )
+        };
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        tokio::task::spawn_blocking(move || save(&pipeline, &path))
+            .await
+            .expect("Saving recent lines panicked");
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{config::Config, counting::reload_dictionary};
+use serenity::client::Context;
+use std::sync::Arc;
+
+/// Load the config file and the syllable dictionary again, describing what happened to each
+pub async fn reload(ctx: &Context) -> String {
+    let config = match Config::load() {
+        Ok(config) => {
+            let mut data = ctx.data.write().await;
+            data.insert::<Config>(Arc::new(config));
+            "Reloaded config. The web server address only changes after a restart".to_owned()
+        }
+        Err(why) => format!("Could not reload config: {:?}", why),
+    };
+    let dictionary = match reload_dictionary() {
+        Ok(entries) => format!("Reloaded dictionary with {} words", entries),
+        Err(why) => format!("Could not reload dictionary: {:?}", why),
+    };
+    format!("{}\n{}", config, dictionary)
+}
+
+/// Reload whenever the process is sent SIGHUP, so the bot can pick up changes without dropping
+/// its gateway session
+#[cfg(unix)]
+pub async fn reload_on_hangup(ctx: Context) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(why) => {
+            println!("Could not listen for SIGHUP: {:?}", why);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        println!("Got SIGHUP\n{}", reload(&ctx).await);
+    }
+}
@@ -0,0 +1,2135 @@
+use super::HaikuRepository;
+use crate::{
+    counting::WordOverrides,
+    database::{
+        CommandUsage, GlobalStats, HaikuFilter, KeywordMatch, RandomWeighting, RetentionAction,
+        SearchCounts, SearchOrder, SearchTerms,
+    },
+    models::{
+        ApiToken, ArchiveWebhook, Haiku, HaikuEdit, HistoryScan, Notification, QuizScore,
+        ServerConfig, StoredLine, UserPreferences,
+    },
+    schedule::Job,
+};
+use chrono::{DateTime, Timelike, Utc};
+use rand::seq::SliceRandom;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Mutex,
+};
+
+struct StoredHaiku {
+    id: i64,
+    haiku: Haiku,
+    flagged: bool,
+    sensitive: bool,
+    mirror_message: Option<MessageId>,
+    announcement_message: Option<MessageId>,
+    last_shown_at: Option<DateTime<Utc>>,
+    voters: HashSet<UserId>,
+    edits: Vec<HaikuEdit>,
+}
+
+#[derive(Default)]
+struct Store {
+    /// The last number given to a haiku in each server
+    haiku_numbers: HashMap<GuildId, i64>,
+    haikus: Vec<StoredHaiku>,
+    archived_haikus: Vec<StoredHaiku>,
+    server_configs: HashMap<GuildId, ServerConfig>,
+    /// (server, command, used at, latency in ms)
+    command_usage: Vec<(GuildId, String, DateTime<Utc>, i64)>,
+    user_preferences: HashMap<UserId, UserPreferences>,
+    quiz_scores: HashMap<(GuildId, UserId), QuizScore>,
+    detection_disabled_channels: HashSet<(GuildId, ChannelId)>,
+    public_feeds: HashSet<(GuildId, UserId)>,
+    word_overrides: HashMap<GuildId, WordOverrides>,
+    job_runs: HashMap<Job, DateTime<Utc>>,
+    history_scans: HashMap<ChannelId, HistoryScan>,
+    /// The last id given to an API token
+    last_api_token_id: i64,
+    /// (id, hash of the secret, token)
+    api_tokens: Vec<(i64, String, ApiToken)>,
+}
+
+/// Keeps everything in memory, for tests and trying things out without a database
+#[derive(Default)]
+pub struct MemoryRepository {
+    store: Mutex<Store>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        MemoryRepository::default()
+    }
+
+    /// Every author with a haiku since a point in time (or ever), most haikus first
+    fn leaderboard(&self, server_id: GuildId, since: Option<DateTime<Utc>>) -> Vec<(UserId, i64)> {
+        let store = self.store.lock().unwrap();
+        SearchCounts::from_authors(
+            store
+                .haikus
+                .iter()
+                .filter(|stored| {
+                    stored.haiku.server == server_id
+                        && since.map_or(true, |since| stored.haiku.timestamp >= since)
+                })
+                .map(|stored| {
+                    [
+                        stored.haiku.lines[0].author,
+                        stored.haiku.lines[1].author,
+                        stored.haiku.lines[2].author,
+                    ]
+                }),
+        )
+        .by_author
+    }
+
+    /// Whether a haiku has been flagged for review
+    pub fn is_flagged(&self, server_id: GuildId, haiku_id: i64) -> bool {
+        self.store.lock().unwrap().haikus.iter().any(|stored| {
+            stored.haiku.server == server_id && stored.id == haiku_id && stored.flagged
+        })
+    }
+}
+
+/// How many of the keywords appear in the haiku, ignoring case and punctuation
+fn matching_keywords(haiku: &Haiku, keywords: &[String]) -> usize {
+    let words = haiku
+        .lines
+        .iter()
+        .flat_map(|line| line.content.split_whitespace())
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .collect::<Vec<String>>();
+    keywords
+        .iter()
+        .filter(|keyword| words.contains(&keyword.to_lowercase()))
+        .count()
+}
+
+/// The haikus in a server with at least one line written by the author, oldest first
+fn haikus_by_author(
+    haikus: &[StoredHaiku],
+    server_id: GuildId,
+    author_id: UserId,
+) -> Vec<(i64, Haiku)> {
+    let mut haikus = haikus
+        .iter()
+        .filter(|stored| stored.haiku.server == server_id)
+        .filter(|stored| {
+            stored
+                .haiku
+                .lines
+                .iter()
+                .any(|line| line.author == author_id)
+        })
+        .collect::<Vec<&StoredHaiku>>();
+    haikus.sort_by_key(|stored| stored.haiku.timestamp);
+    haikus
+        .into_iter()
+        .map(|stored| (stored.id, stored.haiku.clone()))
+        .collect()
+}
+
+/// The haikus matching a search, with how many of the keywords each one matched
+fn search_matches<'a>(
+    store: &'a Store,
+    server_id: GuildId,
+    terms: &SearchTerms,
+    author_id: Option<UserId>,
+    channel_id: Option<ChannelId>,
+) -> Vec<(usize, &'a StoredHaiku)> {
+    store
+        .haikus
+        .iter()
+        .filter(|stored| stored.haiku.server == server_id)
+        .filter(|stored| match author_id {
+            Some(author_id) => stored
+                .haiku
+                .lines
+                .iter()
+                .any(|line| line.author == author_id),
+            None => true,
+        })
+        .filter(|stored| match channel_id {
+            Some(channel_id) => stored.haiku.channel == channel_id,
+            None => true,
+        })
+        .filter(|stored| matching_keywords(&stored.haiku, &terms.excluded) == 0)
+        .map(|stored| (matching_keywords(&stored.haiku, &terms.keywords), stored))
+        .filter(|(matches, _)| match terms.mode {
+            KeywordMatch::Any => *matches > 0,
+            KeywordMatch::All => *matches > 0 && *matches == terms.keywords.len(),
+        })
+        .collect()
+}
+
+impl HaikuRepository for MemoryRepository {
+    fn save_haiku(&self, haiku: &Haiku) -> Option<i64> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(source) = haiku.source {
+            let already_saved = store.haikus.iter().any(|stored| {
+                stored.haiku.server == haiku.server
+                    && stored
+                        .haiku
+                        .source
+                        .map(|saved| (saved.messages[2], saved.line))
+                        == Some((source.messages[2], source.line))
+            });
+            if already_saved {
+                return None;
+            }
+        }
+        let id = store.haiku_numbers.entry(haiku.server).or_insert(0);
+        *id += 1;
+        let id = *id;
+        store.haikus.push(StoredHaiku {
+            id,
+            haiku: haiku.clone(),
+            flagged: false,
+            sensitive: false,
+            mirror_message: None,
+            announcement_message: None,
+            last_shown_at: None,
+            voters: HashSet::new(),
+            edits: Vec::new(),
+        });
+        Some(id)
+    }
+
+    fn save_haikus(&self, haikus: &[Haiku]) -> Vec<Option<i64>> {
+        haikus.iter().map(|haiku| self.save_haiku(haiku)).collect()
+    }
+
+    fn get_haiku(&self, server_id: GuildId, haiku_id: i64) -> Option<(i64, Haiku)> {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id)
+            .map(|stored| (stored.id, stored.haiku.clone()))
+    }
+
+    fn get_all_haikus(&self) -> Vec<(i64, Haiku)> {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .map(|stored| (stored.id, stored.haiku.clone()))
+            .collect()
+    }
+
+    fn get_random_haiku(
+        &self,
+        server_id: GuildId,
+        exclude: &[i64],
+        weighting: RandomWeighting,
+        written: Option<&Range<DateTime<Utc>>>,
+    ) -> Option<(i64, Haiku)> {
+        let store = self.store.lock().unwrap();
+        let haikus = store
+            .haikus
+            .iter()
+            .filter(|stored| stored.haiku.server == server_id && !exclude.contains(&stored.id))
+            .filter(|stored| !stored.sensitive)
+            .filter(|stored| {
+                written.map_or(true, |written| written.contains(&stored.haiku.timestamp))
+            })
+            .collect::<Vec<&StoredHaiku>>();
+        let now = Utc::now();
+        haikus
+            .choose_weighted(&mut rand::thread_rng(), |stored| {
+                let shown_at = stored.last_shown_at.unwrap_or(stored.haiku.timestamp);
+                let hours_since_shown = (now - shown_at).num_seconds() as f64 / 3600.0;
+                weighting.weight(hours_since_shown, stored.voters.len() as i64)
+            })
+            .ok()
+            .map(|stored| (stored.id, stored.haiku.clone()))
+    }
+
+    fn get_random_line(&self, server_id: GuildId, positions: &[usize]) -> Option<StoredLine> {
+        let mut rng = rand::thread_rng();
+        let position = *positions.choose(&mut rng)?;
+        let store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter()
+            .filter(|stored| stored.haiku.server == server_id && !stored.sensitive)
+            .collect::<Vec<&StoredHaiku>>();
+        let stored = stored.choose(&mut rng)?;
+        Some(StoredLine {
+            haiku_id: stored.id,
+            position,
+            line: stored.haiku.lines[position].clone(),
+        })
+    }
+
+    fn mark_haiku_shown(&self, server_id: GuildId, haiku_id: i64) {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id);
+        if let Some(stored) = stored {
+            stored.last_shown_at = Some(Utc::now());
+        }
+    }
+
+    fn vote_for_haiku(&self, server_id: GuildId, haiku_id: i64, user_id: UserId) -> Option<i64> {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id)?;
+        if !stored.voters.insert(user_id) {
+            return None;
+        }
+        Some(stored.voters.len() as i64)
+    }
+
+    fn count_votes(&self, server_id: GuildId, haiku_id: i64) -> i64 {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id)
+            .map(|stored| stored.voters.len() as i64)
+            .unwrap_or(0)
+    }
+
+    fn get_latest_haiku(&self) -> Option<(i64, Haiku)> {
+        let store = self.store.lock().unwrap();
+        store
+            .haikus
+            .iter()
+            .filter(|stored| {
+                store.server_configs.values().any(|config| {
+                    config.server == stored.haiku.server
+                        && config.global_stats_enabled
+                        && config.left_at.is_none()
+                })
+            })
+            .max_by_key(|stored| stored.haiku.timestamp)
+            .map(|stored| (stored.id, stored.haiku.clone()))
+    }
+
+    fn count_all_haikus(&self) -> i64 {
+        self.store.lock().unwrap().haikus.len() as i64
+    }
+
+    fn count_haikus(&self, server_id: GuildId) -> i64 {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .filter(|stored| stored.haiku.server == server_id)
+            .count() as i64
+    }
+
+    fn count_haikus_by_author(&self, server_id: GuildId, author_id: UserId) -> i64 {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .filter(|stored| stored.haiku.server == server_id)
+            .filter(|stored| {
+                stored
+                    .haiku
+                    .lines
+                    .iter()
+                    .any(|line| line.author == author_id)
+            })
+            .count() as i64
+    }
+
+    fn count_haikus_by_author_since(
+        &self,
+        server_id: GuildId,
+        since: DateTime<Utc>,
+    ) -> Vec<(UserId, i64)> {
+        let store = self.store.lock().unwrap();
+        SearchCounts::from_authors(
+            store
+                .haikus
+                .iter()
+                .filter(|stored| {
+                    stored.haiku.server == server_id && stored.haiku.timestamp >= since
+                })
+                .map(|stored| {
+                    [
+                        stored.haiku.lines[0].author,
+                        stored.haiku.lines[1].author,
+                        stored.haiku.lines[2].author,
+                    ]
+                }),
+        )
+        .by_author
+    }
+
+    fn get_leaderboard(
+        &self,
+        server_id: GuildId,
+        since: Option<DateTime<Utc>>,
+        offset: i64,
+        limit: i64,
+    ) -> Vec<(UserId, i64)> {
+        self.leaderboard(server_id, since)
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect()
+    }
+
+    fn count_leaderboard_authors(&self, server_id: GuildId, since: Option<DateTime<Utc>>) -> i64 {
+        self.leaderboard(server_id, since).len() as i64
+    }
+
+    fn count_haikus_by_channel(&self, server_id: GuildId) -> Vec<(ChannelId, i64)> {
+        let store = self.store.lock().unwrap();
+        let mut counts = HashMap::new();
+        for stored in store
+            .haikus
+            .iter()
+            .filter(|stored| stored.haiku.server == server_id)
+        {
+            *counts.entry(stored.haiku.channel).or_insert(0) += 1;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<(ChannelId, i64)>>();
+        counts.sort_by_key(|(channel_id, count)| (std::cmp::Reverse(*count), *channel_id));
+        counts
+    }
+
+    fn search_haikus(
+        &self,
+        server_id: GuildId,
+        terms: &SearchTerms,
+        order: SearchOrder,
+        author_id: Option<UserId>,
+        channel_id: Option<ChannelId>,
+    ) -> Vec<(i64, Haiku)> {
+        let store = self.store.lock().unwrap();
+        let mut results = search_matches(&store, server_id, terms, author_id, channel_id);
+        match order {
+            SearchOrder::Relevance => {
+                results.sort_by_key(|(matches, _)| std::cmp::Reverse(*matches))
+            }
+            SearchOrder::Newest => {
+                results.sort_by_key(|(_, stored)| std::cmp::Reverse(stored.haiku.timestamp))
+            }
+            SearchOrder::Oldest => results.sort_by_key(|(_, stored)| stored.haiku.timestamp),
+        }
+        results
+            .into_iter()
+            .take(5)
+            .map(|(_, stored)| (stored.id, stored.haiku.clone()))
+            .collect()
+    }
+
+    fn count_search_results(
+        &self,
+        server_id: GuildId,
+        terms: &SearchTerms,
+        author_id: Option<UserId>,
+        channel_id: Option<ChannelId>,
+    ) -> SearchCounts {
+        let store = self.store.lock().unwrap();
+        SearchCounts::from_authors(
+            search_matches(&store, server_id, terms, author_id, channel_id)
+                .into_iter()
+                .map(|(_, stored)| {
+                    let lines = &stored.haiku.lines;
+                    [lines[0].author, lines[1].author, lines[2].author]
+                }),
+        )
+    }
+
+    fn get_haikus_by_author(&self, server_id: GuildId, author_id: UserId) -> Vec<(i64, Haiku)> {
+        haikus_by_author(&self.store.lock().unwrap().haikus, server_id, author_id)
+    }
+
+    fn get_public_haikus_by_author(
+        &self,
+        server_id: GuildId,
+        author_id: UserId,
+        filter: &HaikuFilter,
+        limit: i64,
+    ) -> Vec<(i64, Haiku)> {
+        let store = self.store.lock().unwrap();
+        let mut haikus = store
+            .haikus
+            .iter()
+            .filter(|stored| stored.haiku.server == server_id && !stored.sensitive)
+            .filter(|stored| {
+                stored
+                    .haiku
+                    .lines
+                    .iter()
+                    .all(|line| line.author == author_id)
+            })
+            .filter(|stored| match &filter.terms {
+                Some(terms) => {
+                    let matches = matching_keywords(&stored.haiku, &terms.keywords);
+                    matching_keywords(&stored.haiku, &terms.excluded) == 0
+                        && (terms.keywords.is_empty()
+                            || match terms.mode {
+                                KeywordMatch::Any => matches > 0,
+                                KeywordMatch::All => matches == terms.keywords.len(),
+                            })
+                }
+                None => true,
+            })
+            .filter(|stored| {
+                filter
+                    .since
+                    .map_or(true, |since| stored.haiku.timestamp >= since)
+            })
+            .filter(|stored| {
+                filter
+                    .until
+                    .map_or(true, |until| stored.haiku.timestamp < until)
+            })
+            .filter(|stored| {
+                filter.after.map_or(true, |after| {
+                    (stored.haiku.timestamp, stored.id) < (after.timestamp, after.id)
+                })
+            })
+            .map(|stored| (stored.id, stored.haiku.clone()))
+            .collect::<Vec<(i64, Haiku)>>();
+        haikus.sort_by_key(|(id, haiku)| std::cmp::Reverse((haiku.timestamp, *id)));
+        haikus.truncate(limit as usize);
+        haikus
+    }
+
+    fn get_archived_haikus_by_author(
+        &self,
+        server_id: GuildId,
+        author_id: UserId,
+    ) -> Vec<(i64, Haiku)> {
+        haikus_by_author(
+            &self.store.lock().unwrap().archived_haikus,
+            server_id,
+            author_id,
+        )
+    }
+
+    fn archive_haikus_before(&self, server_id: GuildId, before: DateTime<Utc>) -> usize {
+        let mut store = self.store.lock().unwrap();
+        let (old, kept) = store
+            .haikus
+            .drain(..)
+            .partition::<Vec<StoredHaiku>, _>(|stored| {
+                stored.haiku.server == server_id && stored.haiku.timestamp < before
+            });
+        store.haikus = kept;
+        let archived = old.len();
+        store.archived_haikus.extend(old);
+        archived
+    }
+
+    fn delete_haikus_before(&self, server_id: GuildId, before: DateTime<Utc>) -> usize {
+        let mut store = self.store.lock().unwrap();
+        let count = store.haikus.len();
+        store
+            .haikus
+            .retain(|stored| stored.haiku.server != server_id || stored.haiku.timestamp >= before);
+        count - store.haikus.len()
+    }
+
+    fn flag_haiku(&self, server_id: GuildId, haiku_id: i64) {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id);
+        if let Some(stored) = stored {
+            stored.flagged = true;
+        }
+    }
+
+    fn set_haiku_sensitive(&self, server_id: GuildId, haiku_id: i64, sensitive: bool) -> bool {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id);
+        match stored {
+            Some(stored) => {
+                stored.sensitive = sensitive;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_haiku_sensitive(&self, server_id: GuildId, haiku_id: i64) -> bool {
+        self.store.lock().unwrap().haikus.iter().any(|stored| {
+            stored.haiku.server == server_id && stored.id == haiku_id && stored.sensitive
+        })
+    }
+
+    fn edit_haiku_line(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        edit: &HaikuEdit,
+        _syllables: usize,
+    ) -> bool {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id);
+        match stored {
+            Some(stored) => {
+                stored.haiku.lines[edit.position].content = edit.content.clone();
+                stored.edits.push(edit.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_haiku_edits(&self, server_id: GuildId, haiku_id: i64) -> Vec<HaikuEdit> {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id)
+            .map(|stored| stored.edits.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_announcement_message(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        message: Option<MessageId>,
+    ) {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id);
+        if let Some(stored) = stored {
+            stored.announcement_message = message;
+        }
+    }
+
+    fn get_announcement_message(&self, server_id: GuildId, haiku_id: i64) -> Option<MessageId> {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id)
+            .and_then(|stored| stored.announcement_message)
+    }
+
+    fn set_mirror_message(&self, server_id: GuildId, haiku_id: i64, message: Option<MessageId>) {
+        let mut store = self.store.lock().unwrap();
+        let stored = store
+            .haikus
+            .iter_mut()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id);
+        if let Some(stored) = stored {
+            stored.mirror_message = message;
+        }
+    }
+
+    fn get_mirror_message(&self, server_id: GuildId, haiku_id: i64) -> Option<MessageId> {
+        self.store
+            .lock()
+            .unwrap()
+            .haikus
+            .iter()
+            .find(|stored| stored.haiku.server == server_id && stored.id == haiku_id)
+            .and_then(|stored| stored.mirror_message)
+    }
+
+    fn delete_haiku(&self, server_id: GuildId, haiku_id: i64) -> bool {
+        let mut store = self.store.lock().unwrap();
+        let before = store.haikus.len();
+        store
+            .haikus
+            .retain(|stored| !(stored.haiku.server == server_id && stored.id == haiku_id));
+        store.haikus.len() < before
+    }
+
+    fn create_server_config(&self, server_id: GuildId) {
+        let mut store = self.store.lock().unwrap();
+        store
+            .server_configs
+            .entry(server_id)
+            .or_insert_with(|| ServerConfig {
+                server: server_id,
+                joined_at: Utc::now(),
+                left_at: None,
+                locale: "en".to_owned(),
+                last_milestone: 0,
+                welcome_enabled: false,
+                random_weighting: "uniform".to_owned(),
+                laureate_role: None,
+                laureate: None,
+                retention_years: None,
+                retention_action: "archive".to_owned(),
+                global_stats_enabled: false,
+                announcement_template: None,
+                manual_save_emoji: None,
+                manual_save_role: None,
+                detect_bot_messages: false,
+                detect_webhook_messages: false,
+                bridge_pattern: None,
+                same_author_only: false,
+                error_channel: None,
+                timezone: "UTC".to_owned(),
+                plain_text: false,
+                archive: None,
+                public_feeds: false,
+            })
+            .left_at = None;
+    }
+
+    fn get_server_config(&self, server_id: GuildId) -> Option<ServerConfig> {
+        self.store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get(&server_id)
+            .cloned()
+    }
+
+    fn set_server_locale(&self, server_id: GuildId, locale: &str) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.locale = locale.to_owned();
+        }
+    }
+
+    fn set_server_welcome(&self, server_id: GuildId, enabled: bool) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.welcome_enabled = enabled;
+        }
+    }
+
+    fn set_server_random_weighting(&self, server_id: GuildId, weighting: RandomWeighting) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.random_weighting = weighting.code().to_owned();
+        }
+    }
+
+    fn set_server_laureate_role(&self, server_id: GuildId, role: Option<RoleId>) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.laureate_role = role;
+            config.laureate = None;
+        }
+    }
+
+    fn set_server_laureate(&self, server_id: GuildId, user: Option<UserId>) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.laureate = user;
+        }
+    }
+
+    fn set_server_retention(
+        &self,
+        server_id: GuildId,
+        years: Option<i64>,
+        action: RetentionAction,
+    ) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.retention_years = years;
+            config.retention_action = action.code().to_owned();
+        }
+    }
+
+    fn get_retention_servers(&self) -> Vec<ServerConfig> {
+        self.store
+            .lock()
+            .unwrap()
+            .server_configs
+            .values()
+            .filter(|config| config.retention_years.is_some() && config.left_at.is_none())
+            .cloned()
+            .collect()
+    }
+
+    fn set_server_global_stats(&self, server_id: GuildId, enabled: bool) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.global_stats_enabled = enabled;
+        }
+    }
+
+    fn set_server_announcement_template(&self, server_id: GuildId, template: Option<&str>) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.announcement_template = template.map(str::to_owned);
+        }
+    }
+
+    fn set_server_manual_save(
+        &self,
+        server_id: GuildId,
+        emoji: Option<&str>,
+        role: Option<RoleId>,
+    ) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.manual_save_emoji = emoji.map(str::to_owned);
+            config.manual_save_role = role;
+        }
+    }
+
+    fn set_server_senders(&self, server_id: GuildId, bots: bool, webhooks: bool) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.detect_bot_messages = bots;
+            config.detect_webhook_messages = webhooks;
+        }
+    }
+
+    fn set_server_bridge_pattern(&self, server_id: GuildId, pattern: Option<&str>) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.bridge_pattern = pattern.map(str::to_owned);
+        }
+    }
+
+    fn set_server_same_author(&self, server_id: GuildId, enabled: bool) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.same_author_only = enabled;
+        }
+    }
+
+    fn set_server_error_channel(&self, server_id: GuildId, channel: Option<ChannelId>) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.error_channel = channel;
+        }
+    }
+
+    fn set_server_archive(&self, server_id: GuildId, archive: Option<&ArchiveWebhook>) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.archive = archive.cloned();
+        }
+    }
+
+    fn set_server_timezone(&self, server_id: GuildId, timezone: &str) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.timezone = timezone.to_owned();
+        }
+    }
+
+    fn set_server_plain_text(&self, server_id: GuildId, enabled: bool) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.plain_text = enabled;
+        }
+    }
+
+    fn set_server_public_feeds(&self, server_id: GuildId, enabled: bool) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.public_feeds = enabled;
+        }
+    }
+
+    fn set_channel_detection(&self, server_id: GuildId, channel_id: ChannelId, enabled: bool) {
+        let mut store = self.store.lock().unwrap();
+        if enabled {
+            store
+                .detection_disabled_channels
+                .remove(&(server_id, channel_id));
+        } else {
+            store
+                .detection_disabled_channels
+                .insert((server_id, channel_id));
+        }
+    }
+
+    fn is_detection_enabled(&self, server_id: GuildId, channel_id: ChannelId) -> bool {
+        !self
+            .store
+            .lock()
+            .unwrap()
+            .detection_disabled_channels
+            .contains(&(server_id, channel_id))
+    }
+
+    fn get_global_stats(&self) -> GlobalStats {
+        let store = self.store.lock().unwrap();
+        let servers = store
+            .server_configs
+            .values()
+            .filter(|config| config.global_stats_enabled && config.left_at.is_none())
+            .map(|config| config.server)
+            .collect::<Vec<GuildId>>();
+        let mut days = HashMap::new();
+        for stored in store
+            .haikus
+            .iter()
+            .filter(|stored| servers.contains(&stored.haiku.server))
+        {
+            *days
+                .entry(stored.haiku.timestamp.date().naive_utc())
+                .or_insert(0) += 1;
+        }
+        GlobalStats {
+            servers: servers.len() as i64,
+            haikus: days.values().sum(),
+            biggest_day: days
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0))),
+        }
+    }
+
+    fn get_laureate_servers(&self) -> Vec<ServerConfig> {
+        self.store
+            .lock()
+            .unwrap()
+            .server_configs
+            .values()
+            .filter(|config| config.laureate_role.is_some() && config.left_at.is_none())
+            .cloned()
+            .collect()
+    }
+
+    fn record_milestone(&self, server_id: GuildId, milestone: i64) -> bool {
+        match self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            Some(config) if config.last_milestone < milestone => {
+                config.last_milestone = milestone;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn mark_server_departed(&self, server_id: GuildId) {
+        if let Some(config) = self
+            .store
+            .lock()
+            .unwrap()
+            .server_configs
+            .get_mut(&server_id)
+        {
+            config.left_at = Some(Utc::now());
+        }
+    }
+
+    fn get_servers_departed_before(&self, before: DateTime<Utc>) -> Vec<GuildId> {
+        self.store
+            .lock()
+            .unwrap()
+            .server_configs
+            .values()
+            .filter(|config| matches!(config.left_at, Some(left_at) if left_at < before))
+            .map(|config| config.server)
+            .collect()
+    }
+
+    fn purge_server_data(&self, server_id: GuildId) {
+        let mut store = self.store.lock().unwrap();
+        store
+            .haikus
+            .retain(|stored| stored.haiku.server != server_id);
+        store
+            .archived_haikus
+            .retain(|stored| stored.haiku.server != server_id);
+        store.server_configs.remove(&server_id);
+        store
+            .command_usage
+            .retain(|(server, _, _, _)| *server != server_id);
+        store
+            .quiz_scores
+            .retain(|(server, _), _| *server != server_id);
+        store
+            .detection_disabled_channels
+            .retain(|(server, _)| *server != server_id);
+        store.word_overrides.remove(&server_id);
+        store
+            .history_scans
+            .retain(|_, scan| scan.server != server_id);
+        store.haiku_numbers.remove(&server_id);
+        store
+            .api_tokens
+            .retain(|(_, _, token)| token.server != server_id);
+        store
+            .public_feeds
+            .retain(|(server, _)| *server != server_id);
+    }
+
+    fn record_command_usage(&self, server_id: GuildId, command: &str, latency_ms: i64) {
+        self.store.lock().unwrap().command_usage.push((
+            server_id,
+            command.to_owned(),
+            Utc::now(),
+            latency_ms,
+        ));
+    }
+
+    fn get_command_usage(&self, server_id: GuildId, since: DateTime<Utc>) -> Vec<CommandUsage> {
+        let store = self.store.lock().unwrap();
+        // Command name -> (uses, total latency)
+        let mut totals: HashMap<&str, (i64, i64)> = HashMap::new();
+        for (_, command, _, latency_ms) in store
+            .command_usage
+            .iter()
+            .filter(|(server, _, used_at, _)| *server == server_id && *used_at >= since)
+        {
+            let total = totals.entry(command.as_str()).or_insert((0, 0));
+            total.0 += 1;
+            total.1 += latency_ms;
+        }
+        let mut usage = totals
+            .into_iter()
+            .map(|(command, (uses, total_ms))| CommandUsage {
+                command: command.to_owned(),
+                uses,
+                average_latency_ms: total_ms / uses,
+            })
+            .collect::<Vec<CommandUsage>>();
+        usage.sort_by(|a, b| b.uses.cmp(&a.uses).then_with(|| a.command.cmp(&b.command)));
+        usage
+    }
+
+    fn count_command_usage_by_hour(
+        &self,
+        server_id: GuildId,
+        since: DateTime<Utc>,
+    ) -> Vec<(u32, i64)> {
+        let store = self.store.lock().unwrap();
+        let mut counts = HashMap::new();
+        for (_, _, used_at, _) in store
+            .command_usage
+            .iter()
+            .filter(|(server, _, used_at, _)| *server == server_id && *used_at >= since)
+        {
+            *counts.entry(used_at.hour()).or_insert(0) += 1;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<(u32, i64)>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    fn get_user_preferences(&self, user_id: UserId) -> UserPreferences {
+        self.store
+            .lock()
+            .unwrap()
+            .user_preferences
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_else(|| UserPreferences::new(user_id))
+    }
+
+    fn set_user_notification(&self, user_id: UserId, notification: Notification, enabled: bool) {
+        self.store
+            .lock()
+            .unwrap()
+            .user_preferences
+            .entry(user_id)
+            .or_insert_with(|| UserPreferences::new(user_id))
+            .set(notification, enabled);
+    }
+
+    fn set_user_plain_text(&self, user_id: UserId, enabled: Option<bool>) {
+        self.store
+            .lock()
+            .unwrap()
+            .user_preferences
+            .entry(user_id)
+            .or_insert_with(|| UserPreferences::new(user_id))
+            .plain_text = enabled;
+    }
+
+    fn set_user_public_feed(&self, server_id: GuildId, user_id: UserId, enabled: bool) {
+        let mut store = self.store.lock().unwrap();
+        if enabled {
+            store.public_feeds.insert((server_id, user_id));
+        } else {
+            store.public_feeds.remove(&(server_id, user_id));
+        }
+    }
+
+    fn has_public_feed(&self, server_id: GuildId, user_id: UserId) -> bool {
+        self.store
+            .lock()
+            .unwrap()
+            .public_feeds
+            .contains(&(server_id, user_id))
+    }
+
+    fn record_quiz_answer(&self, server_id: GuildId, user_id: UserId, was_correct: bool) {
+        let mut store = self.store.lock().unwrap();
+        let score = store
+            .quiz_scores
+            .entry((server_id, user_id))
+            .or_insert_with(|| QuizScore {
+                user: user_id,
+                correct: 0,
+                answered: 0,
+            });
+        score.correct += was_correct as i64;
+        score.answered += 1;
+    }
+
+    fn get_quiz_leaderboard(&self, server_id: GuildId, limit: i64) -> Vec<QuizScore> {
+        let store = self.store.lock().unwrap();
+        let mut scores = store
+            .quiz_scores
+            .iter()
+            .filter(|((server, _), _)| *server == server_id)
+            .map(|(_, score)| score.clone())
+            .collect::<Vec<QuizScore>>();
+        scores.sort_by(|a, b| {
+            b.correct
+                .cmp(&a.correct)
+                .then(a.answered.cmp(&b.answered))
+                .then(a.user.cmp(&b.user))
+        });
+        scores.truncate(limit as usize);
+        scores
+    }
+
+    fn set_word_override(&self, server_id: GuildId, word: &str, syllables: Option<usize>) {
+        let mut store = self.store.lock().unwrap();
+        let overrides = store.word_overrides.entry(server_id).or_default();
+        match syllables {
+            Some(syllables) => overrides.insert(word.to_lowercase(), syllables),
+            None => overrides.remove(&word.to_lowercase()),
+        };
+    }
+
+    fn get_word_overrides(&self, server_id: GuildId) -> WordOverrides {
+        self.store
+            .lock()
+            .unwrap()
+            .word_overrides
+            .get(&server_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn get_job_last_run(&self, job: Job) -> Option<DateTime<Utc>> {
+        self.store.lock().unwrap().job_runs.get(&job).copied()
+    }
+
+    fn record_job_run(&self, job: Job, finished_at: DateTime<Utc>) {
+        self.store.lock().unwrap().job_runs.insert(job, finished_at);
+    }
+
+    fn get_history_scan(&self, channel_id: ChannelId) -> Option<HistoryScan> {
+        self.store
+            .lock()
+            .unwrap()
+            .history_scans
+            .get(&channel_id)
+            .cloned()
+    }
+
+    fn save_history_scan(&self, scan: &HistoryScan) {
+        self.store
+            .lock()
+            .unwrap()
+            .history_scans
+            .insert(scan.channel, scan.clone());
+    }
+
+    fn delete_history_scan(&self, channel_id: ChannelId) {
+        self.store.lock().unwrap().history_scans.remove(&channel_id);
+    }
+
+    fn create_api_token(&self, token: &ApiToken, token_hash: &str) -> i64 {
+        let mut store = self.store.lock().unwrap();
+        store.last_api_token_id += 1;
+        let id = store.last_api_token_id;
+        store
+            .api_tokens
+            .push((id, token_hash.to_owned(), token.clone()));
+        id
+    }
+
+    fn get_api_token(&self, token_hash: &str) -> Option<(i64, ApiToken)> {
+        self.store
+            .lock()
+            .unwrap()
+            .api_tokens
+            .iter()
+            .find(|(_, hash, _)| hash == token_hash)
+            .map(|(id, _, token)| (*id, token.clone()))
+    }
+
+    fn get_api_tokens(&self, server_id: GuildId) -> Vec<(i64, ApiToken)> {
+        self.store
+            .lock()
+            .unwrap()
+            .api_tokens
+            .iter()
+            .filter(|(_, _, token)| token.server == server_id)
+            .map(|(id, _, token)| (*id, token.clone()))
+            .collect()
+    }
+
+    fn delete_api_token(&self, server_id: GuildId, token_id: i64) -> bool {
+        let mut store = self.store.lock().unwrap();
+        let before = store.api_tokens.len();
+        store
+            .api_tokens
+            .retain(|(id, _, token)| !(*id == token_id && token.server == server_id));
+        store.api_tokens.len() < before
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryRepository;
+    use crate::{
+        database::{
+            HaikuCursor, HaikuFilter, KeywordMatch, RandomWeighting, SearchCounts, SearchOrder,
+            SearchTerms,
+        },
+        models::{
+            ApiScope, ApiToken, ArchiveWebhook, Haiku, HaikuEdit, HaikuSource, HistoryScan,
+            Notification,
+        },
+        repository::HaikuRepository,
+        schedule::Job,
+        test_support::HaikuBuilder,
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use serenity::model::id::{ChannelId, GuildId, MessageId, UserId, WebhookId};
+
+    /// Found `minute` minutes into 1 January 2021
+    fn haiku(server: u64, channel: u64, author: u64, minute: u32, lines: [&str; 3]) -> Haiku {
+        HaikuBuilder::new()
+            .server(server)
+            .channel(channel)
+            .author(author)
+            .lines(lines)
+            .at(Utc.ymd(2021, 1, 1).and_hms(0, minute, 0))
+            .build()
+    }
+
+    fn ids(results: Vec<(i64, Haiku)>) -> Vec<i64> {
+        results.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn test_saves_are_idempotent() {
+        let repository = MemoryRepository::new();
+        let from = |server, line| Haiku {
+            source: Some(HaikuSource {
+                messages: [MessageId(1), MessageId(2), MessageId(3)],
+                line,
+            }),
+            ..haiku(server, 10, 100, 0, ["a", "b", "c"])
+        };
+        assert!(repository.save_haiku(&from(1, 0)).is_some());
+        assert_eq!(repository.save_haiku(&from(1, 0)), None);
+        // Another haiku completed later in the same message
+        assert!(repository.save_haiku(&from(1, 1)).is_some());
+        assert!(repository.save_haiku(&from(2, 0)).is_some());
+        // Haikus from before message ids were kept never clash
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]));
+        assert!(repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .is_some());
+        assert_eq!(repository.count_haikus(GuildId(1)), 4);
+    }
+
+    #[test]
+    fn test_haikus_are_numbered_by_server() {
+        let repository = MemoryRepository::new();
+        let from = |server, message| Haiku {
+            source: Some(HaikuSource {
+                messages: [MessageId(1), MessageId(2), MessageId(message)],
+                line: 0,
+            }),
+            ..haiku(server, 10, 100, 0, ["a", "b", "c"])
+        };
+        assert_eq!(repository.save_haiku(&from(1, 3)), Some(1));
+        assert_eq!(repository.save_haiku(&from(1, 4)), Some(2));
+        assert_eq!(repository.save_haiku(&from(2, 3)), Some(1));
+        // Haikus that were already saved don't use up a number
+        assert_eq!(repository.save_haiku(&from(1, 4)), None);
+        assert_eq!(repository.save_haiku(&from(1, 5)), Some(3));
+        // Numbers aren't given out again after a haiku's deleted
+        assert!(repository.delete_haiku(GuildId(1), 3));
+        assert_eq!(repository.save_haiku(&from(1, 6)), Some(4));
+    }
+
+    #[test]
+    fn test_haikus_are_scoped_to_servers() {
+        let repository = MemoryRepository::new();
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        assert!(repository.get_haiku(GuildId(1), id).is_some());
+        assert!(repository.get_haiku(GuildId(2), id).is_none());
+        assert!(repository
+            .get_random_haiku(GuildId(2), &[], RandomWeighting::Uniform, None)
+            .is_none());
+        assert!(repository
+            .get_random_haiku(GuildId(1), &[id], RandomWeighting::Uniform, None)
+            .is_none());
+        assert!(!repository.delete_haiku(GuildId(2), id));
+        assert!(repository.delete_haiku(GuildId(1), id));
+        assert!(repository.get_haiku(GuildId(1), id).is_none());
+    }
+
+    #[test]
+    fn test_random_haiku_written_between() {
+        let repository = MemoryRepository::new();
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]));
+        let later = repository
+            .save_haiku(&haiku(1, 10, 100, 30, ["d", "e", "f"]))
+            .unwrap();
+        let written = Utc.ymd(2021, 1, 1).and_hms(0, 10, 0)..Utc.ymd(2021, 1, 1).and_hms(0, 30, 0);
+        assert!(repository
+            .get_random_haiku(GuildId(1), &[], RandomWeighting::Uniform, Some(&written))
+            .is_none());
+        let written = written.start..Utc.ymd(2021, 1, 1).and_hms(1, 0, 0);
+        for _ in 0..20 {
+            let (id, _) = repository
+                .get_random_haiku(GuildId(1), &[], RandomWeighting::Uniform, Some(&written))
+                .unwrap();
+            assert_eq!(id, later);
+        }
+        assert_eq!(repository.count_all_haikus(), 0);
+    }
+
+    #[test]
+    fn test_search_haikus() {
+        let repository = MemoryRepository::new();
+        let old = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["Frog", "pond", "splash"]))
+            .unwrap();
+        let new = repository
+            .save_haiku(&haiku(1, 11, 200, 5, ["frog", "pond!", "leaves"]))
+            .unwrap();
+        repository.save_haiku(&haiku(2, 12, 100, 9, ["frog", "pond", "leaves"]));
+        let search = |terms: &str, order, author_id, channel_id| {
+            ids(repository.search_haikus(
+                GuildId(1),
+                &SearchTerms::parse(terms, KeywordMatch::Any),
+                order,
+                author_id,
+                channel_id,
+            ))
+        };
+        assert_eq!(
+            search("frog", SearchOrder::Newest, None, None),
+            vec![new, old]
+        );
+        assert_eq!(
+            search("frog", SearchOrder::Oldest, None, None),
+            vec![old, new]
+        );
+        assert_eq!(
+            search("splash pond", SearchOrder::Relevance, None, None),
+            vec![old, new]
+        );
+        assert_eq!(
+            search("frog", SearchOrder::Relevance, Some(UserId(200)), None),
+            vec![new]
+        );
+        assert_eq!(
+            search("frog", SearchOrder::Relevance, None, Some(ChannelId(10))),
+            vec![old]
+        );
+        assert!(search("toad", SearchOrder::Relevance, None, None).is_empty());
+    }
+
+    #[test]
+    fn test_search_terms() {
+        let repository = MemoryRepository::new();
+        let splash = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["frog", "pond", "splash"]))
+            .unwrap();
+        let leaves = repository
+            .save_haiku(&haiku(1, 10, 100, 1, ["frog", "lake", "leaves"]))
+            .unwrap();
+        let search = |terms: &str, mode| {
+            ids(repository.search_haikus(
+                GuildId(1),
+                &SearchTerms::parse(terms, mode),
+                SearchOrder::Oldest,
+                None,
+                None,
+            ))
+        };
+        assert_eq!(search("frog pond", KeywordMatch::Any), vec![splash, leaves]);
+        assert_eq!(search("frog pond", KeywordMatch::All), vec![splash]);
+        assert_eq!(search("frog -splash", KeywordMatch::Any), vec![leaves]);
+        assert_eq!(
+            search("frog -splash -lake", KeywordMatch::Any),
+            Vec::<i64>::new()
+        );
+        // Only excluding words isn't a search
+        assert!(search("-splash", KeywordMatch::Any).is_empty());
+    }
+
+    #[test]
+    fn test_parse_search_terms() {
+        let terms = SearchTerms::parse("frog  -splash - pond", KeywordMatch::All);
+        assert_eq!(terms.keywords, vec!["frog", "pond"]);
+        assert_eq!(terms.excluded, vec!["splash"]);
+    }
+
+    #[test]
+    fn test_count_search_results() {
+        let repository = MemoryRepository::new();
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["frog", "pond", "splash"]));
+        repository.save_haiku(&haiku(1, 10, 200, 1, ["frog", "pond", "leaves"]));
+        repository.save_haiku(&haiku(1, 10, 200, 2, ["frog", "lake", "leaves"]));
+        repository.save_haiku(&haiku(1, 10, 300, 3, ["toad", "lake", "leaves"]));
+        let any = |terms: &str| SearchTerms::parse(terms, KeywordMatch::Any);
+        let counts = repository.count_search_results(GuildId(1), &any("frog"), None, None);
+        assert_eq!(counts.total, 3);
+        assert_eq!(counts.by_author, vec![(UserId(200), 2), (UserId(100), 1)]);
+        let counts =
+            repository.count_search_results(GuildId(1), &any("pond"), Some(UserId(100)), None);
+        assert_eq!(counts.total, 1);
+        assert_eq!(
+            repository.count_search_results(GuildId(1), &any(""), None, None),
+            SearchCounts::default()
+        );
+    }
+
+    #[test]
+    fn test_count_haikus_by_channel() {
+        let repository = MemoryRepository::new();
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(1, 11, 100, 1, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(1, 11, 100, 2, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(1, 12, 100, 3, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(2, 10, 100, 4, ["a", "b", "c"]));
+        assert_eq!(
+            repository.count_haikus_by_channel(GuildId(1)),
+            vec![(ChannelId(11), 2), (ChannelId(10), 1), (ChannelId(12), 1)]
+        );
+        assert!(repository.count_haikus_by_channel(GuildId(3)).is_empty());
+    }
+
+    #[test]
+    fn test_get_haikus_by_author() {
+        let repository = MemoryRepository::new();
+        let newer = repository
+            .save_haiku(&haiku(1, 10, 100, 5, ["a", "b", "c"]))
+            .unwrap();
+        let older = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        repository.save_haiku(&haiku(1, 10, 200, 2, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(2, 10, 100, 3, ["a", "b", "c"]));
+        assert_eq!(
+            ids(repository.get_haikus_by_author(GuildId(1), UserId(100))),
+            vec![older, newer]
+        );
+        assert!(repository
+            .get_haikus_by_author(GuildId(1), UserId(300))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_public_haikus_by_author() {
+        let repository = MemoryRepository::new();
+        let older = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        let newer = repository
+            .save_haiku(&haiku(1, 10, 100, 5, ["a", "b", "c"]))
+            .unwrap();
+        let newest = repository
+            .save_haiku(&haiku(1, 10, 100, 9, ["frog", "b", "c"]))
+            .unwrap();
+        let mut shared = haiku(1, 10, 100, 7, ["a", "b", "c"]);
+        shared.lines[1].author = UserId(200);
+        repository.save_haiku(&shared);
+        let sensitive = repository
+            .save_haiku(&haiku(1, 10, 100, 8, ["a", "b", "c"]))
+            .unwrap();
+        repository.set_haiku_sensitive(GuildId(1), sensitive, true);
+        repository.save_haiku(&haiku(2, 10, 100, 3, ["a", "b", "c"]));
+
+        // Co-written and content warned haikus aren't theirs alone to publish
+        assert_eq!(
+            ids(repository.get_public_haikus_by_author(
+                GuildId(1),
+                UserId(100),
+                &HaikuFilter::default(),
+                10
+            )),
+            vec![newest, newer, older]
+        );
+        assert!(repository
+            .get_public_haikus_by_author(GuildId(1), UserId(200), &HaikuFilter::default(), 10)
+            .is_empty());
+
+        let list = |filter: HaikuFilter| {
+            ids(repository.get_public_haikus_by_author(GuildId(1), UserId(100), &filter, 10))
+        };
+        assert_eq!(
+            list(HaikuFilter {
+                terms: Some(SearchTerms::parse("frog", KeywordMatch::Any)),
+                ..HaikuFilter::default()
+            }),
+            vec![newest]
+        );
+        assert_eq!(
+            list(HaikuFilter {
+                terms: Some(SearchTerms::parse("-frog", KeywordMatch::Any)),
+                ..HaikuFilter::default()
+            }),
+            vec![newer, older]
+        );
+        assert_eq!(
+            list(HaikuFilter {
+                since: Some(Utc.ymd(2021, 1, 1).and_hms(0, 5, 0)),
+                until: Some(Utc.ymd(2021, 1, 1).and_hms(0, 9, 0)),
+                ..HaikuFilter::default()
+            }),
+            vec![newer]
+        );
+        // The next page starts after the last haiku of the one before
+        assert_eq!(
+            list(HaikuFilter {
+                after: Some(HaikuCursor {
+                    timestamp: Utc.ymd(2021, 1, 1).and_hms(0, 5, 0),
+                    id: newer,
+                }),
+                ..HaikuFilter::default()
+            }),
+            vec![older]
+        );
+    }
+
+    #[test]
+    fn test_flag_haiku() {
+        let repository = MemoryRepository::new();
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        repository.flag_haiku(GuildId(2), id);
+        assert!(!repository.is_flagged(GuildId(1), id));
+        repository.flag_haiku(GuildId(1), id);
+        assert!(repository.is_flagged(GuildId(1), id));
+    }
+
+    #[test]
+    fn test_content_warnings() {
+        let repository = MemoryRepository::new();
+        let sensitive = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        let other = repository
+            .save_haiku(&haiku(1, 10, 100, 1, ["d", "e", "f"]))
+            .unwrap();
+        assert!(!repository.set_haiku_sensitive(GuildId(2), sensitive, true));
+        assert!(!repository.is_haiku_sensitive(GuildId(1), sensitive));
+        assert!(repository.set_haiku_sensitive(GuildId(1), sensitive, true));
+        assert!(repository.is_haiku_sensitive(GuildId(1), sensitive));
+        assert!(!repository.is_haiku_sensitive(GuildId(1), other));
+
+        // Never picked at random, but still there when asked for
+        for _ in 0..20 {
+            let (id, _) = repository
+                .get_random_haiku(GuildId(1), &[], RandomWeighting::Uniform, None)
+                .unwrap();
+            assert_eq!(id, other);
+            let line = repository.get_random_line(GuildId(1), &[0, 1, 2]).unwrap();
+            assert_eq!(line.haiku_id, other);
+        }
+        assert!(repository
+            .get_random_haiku(GuildId(1), &[other], RandomWeighting::Uniform, None)
+            .is_none());
+        assert!(repository.get_haiku(GuildId(1), sensitive).is_some());
+
+        repository.set_haiku_sensitive(GuildId(1), sensitive, false);
+        assert!(repository
+            .get_random_haiku(GuildId(1), &[other], RandomWeighting::Uniform, None)
+            .is_some());
+    }
+
+    #[test]
+    fn test_votes() {
+        let repository = MemoryRepository::new();
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        assert_eq!(
+            repository.vote_for_haiku(GuildId(1), id, UserId(200)),
+            Some(1)
+        );
+        // Bookmarking the same haiku again doesn't count twice
+        assert_eq!(repository.vote_for_haiku(GuildId(1), id, UserId(200)), None);
+        assert_eq!(
+            repository.vote_for_haiku(GuildId(1), id, UserId(201)),
+            Some(2)
+        );
+        assert_eq!(repository.count_votes(GuildId(1), id), 2);
+        assert_eq!(repository.vote_for_haiku(GuildId(2), id, UserId(200)), None);
+        assert_eq!(repository.count_votes(GuildId(2), id), 0);
+    }
+
+    #[test]
+    fn test_haiku_edits() {
+        let repository = MemoryRepository::new();
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        let edit = HaikuEdit {
+            position: 1,
+            previous: "b".to_owned(),
+            content: "e".to_owned(),
+            editor: UserId(100),
+            edited_at: Utc.ymd(2021, 1, 2).and_hms(0, 0, 0),
+        };
+        assert!(!repository.edit_haiku_line(GuildId(2), id, &edit, 7));
+        assert_eq!(repository.get_haiku_edits(GuildId(2), id), Vec::new());
+        assert_eq!(repository.get_haiku_edits(GuildId(1), id), Vec::new());
+
+        assert!(repository.edit_haiku_line(GuildId(1), id, &edit, 7));
+        let (_, edited) = repository.get_haiku(GuildId(1), id).unwrap();
+        assert_eq!(edited.lines[1].content, "e");
+        assert_eq!(edited.lines[0].content, "a");
+        assert_eq!(repository.get_haiku_edits(GuildId(1), id), vec![edit]);
+
+        repository.delete_haiku(GuildId(1), id);
+        assert_eq!(repository.get_haiku_edits(GuildId(1), id), Vec::new());
+    }
+
+    #[test]
+    fn test_archive() {
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(1));
+        let archive = ArchiveWebhook {
+            channel: ChannelId(20),
+            webhook: WebhookId(30),
+            token: "token".to_owned(),
+        };
+        repository.set_server_archive(GuildId(1), Some(&archive));
+        assert_eq!(
+            repository.get_server_config(GuildId(1)).unwrap().archive,
+            Some(archive)
+        );
+        repository.set_server_archive(GuildId(1), None);
+        assert_eq!(
+            repository.get_server_config(GuildId(1)).unwrap().archive,
+            None
+        );
+
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        assert_eq!(repository.get_mirror_message(GuildId(1), id), None);
+        repository.set_mirror_message(GuildId(1), id, Some(MessageId(40)));
+        assert_eq!(
+            repository.get_mirror_message(GuildId(1), id),
+            Some(MessageId(40))
+        );
+        assert_eq!(repository.get_mirror_message(GuildId(2), id), None);
+
+        repository.set_announcement_message(GuildId(1), id, Some(MessageId(41)));
+        assert_eq!(
+            repository.get_announcement_message(GuildId(1), id),
+            Some(MessageId(41))
+        );
+        assert_eq!(
+            repository.get_mirror_message(GuildId(1), id),
+            Some(MessageId(40))
+        );
+        repository.set_announcement_message(GuildId(1), id, None);
+        assert_eq!(repository.get_announcement_message(GuildId(1), id), None);
+    }
+
+    #[test]
+    fn test_server_lifecycle() {
+        let repository = MemoryRepository::new();
+        repository.create_server_config(GuildId(1));
+        repository.set_server_locale(GuildId(1), "de");
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(2, 10, 100, 0, ["a", "b", "c"]));
+        assert_eq!(
+            repository.get_server_config(GuildId(1)).unwrap().locale,
+            "de"
+        );
+
+        repository.mark_server_departed(GuildId(1));
+        let later = Utc::now() + Duration::minutes(1);
+        assert_eq!(
+            repository.get_servers_departed_before(later),
+            vec![GuildId(1)]
+        );
+        // Rejoining keeps the config
+        repository.create_server_config(GuildId(1));
+        assert!(repository.get_servers_departed_before(later).is_empty());
+        assert_eq!(
+            repository.get_server_config(GuildId(1)).unwrap().locale,
+            "de"
+        );
+
+        repository.purge_server_data(GuildId(1));
+        assert!(repository.get_server_config(GuildId(1)).is_none());
+        assert_eq!(repository.count_all_haikus(), 1);
+    }
+
+    #[test]
+    fn test_channel_detection() {
+        let repository = MemoryRepository::new();
+        assert!(repository.is_detection_enabled(GuildId(1), ChannelId(10)));
+        repository.set_channel_detection(GuildId(1), ChannelId(10), false);
+        assert!(!repository.is_detection_enabled(GuildId(1), ChannelId(10)));
+        assert!(repository.is_detection_enabled(GuildId(1), ChannelId(11)));
+        assert!(repository.is_detection_enabled(GuildId(2), ChannelId(10)));
+        repository.set_channel_detection(GuildId(1), ChannelId(10), true);
+        assert!(repository.is_detection_enabled(GuildId(1), ChannelId(10)));
+
+        repository.set_channel_detection(GuildId(1), ChannelId(10), false);
+        repository.purge_server_data(GuildId(1));
+        assert!(repository.is_detection_enabled(GuildId(1), ChannelId(10)));
+    }
+
+    #[test]
+    fn test_random_weighting() {
+        assert_eq!(RandomWeighting::Uniform.weight(1000.0, 50), 1.0);
+        assert_eq!(RandomWeighting::LeastRecentlyShown.weight(23.0, 50), 24.0);
+        // Clock drift can't make a haiku less likely than one that was just shown
+        assert_eq!(RandomWeighting::LeastRecentlyShown.weight(-5.0, 0), 1.0);
+        assert_eq!(RandomWeighting::MostVoted.weight(1000.0, 3), 4.0);
+        assert_eq!(RandomWeighting::MostVoted.weight(1000.0, 0), 1.0);
+
+        // Weighting only changes the odds, every haiku can still be picked
+        let repository = MemoryRepository::new();
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]))
+            .unwrap();
+        repository.mark_haiku_shown(GuildId(1), id);
+        for weighting in [
+            RandomWeighting::Uniform,
+            RandomWeighting::LeastRecentlyShown,
+            RandomWeighting::MostVoted,
+        ]
+        .iter()
+        {
+            assert_eq!(
+                ids(repository
+                    .get_random_haiku(GuildId(1), &[], *weighting, None)
+                    .into_iter()
+                    .collect()),
+                vec![id]
+            );
+            assert_eq!(
+                RandomWeighting::from_code(weighting.code()),
+                Some(*weighting)
+            );
+        }
+    }
+
+    #[test]
+    fn test_command_usage() {
+        let repository = MemoryRepository::new();
+        let since = Utc::now() - Duration::days(1);
+        repository.record_command_usage(GuildId(1), "randomhaiku", 100);
+        repository.record_command_usage(GuildId(1), "randomhaiku", 300);
+        repository.record_command_usage(GuildId(1), "config locale", 50);
+        repository.record_command_usage(GuildId(2), "config locale", 50);
+
+        let usage = repository.get_command_usage(GuildId(1), since);
+        assert_eq!(
+            usage
+                .iter()
+                .map(|usage| (usage.command.as_str(), usage.uses, usage.average_latency_ms))
+                .collect::<Vec<_>>(),
+            vec![("randomhaiku", 2, 200), ("config locale", 1, 50)]
+        );
+        let hours = repository.count_command_usage_by_hour(GuildId(1), since);
+        assert_eq!(hours.iter().map(|(_, uses)| uses).sum::<i64>(), 3);
+        assert!(repository
+            .get_command_usage(GuildId(1), Utc::now() + Duration::minutes(1))
+            .is_empty());
+
+        repository.purge_server_data(GuildId(1));
+        assert!(repository.get_command_usage(GuildId(1), since).is_empty());
+        assert_eq!(repository.get_command_usage(GuildId(2), since).len(), 1);
+    }
+
+    #[test]
+    fn test_user_preferences() {
+        let repository = MemoryRepository::new();
+        let preferences = repository.get_user_preferences(UserId(100));
+        assert!(Notification::all()
+            .iter()
+            .all(|notification| !preferences.wants(*notification)));
+
+        repository.set_user_notification(UserId(100), Notification::Bookmarked, true);
+        repository.set_user_notification(UserId(100), Notification::VoteThreshold, true);
+        repository.set_user_notification(UserId(100), Notification::VoteThreshold, false);
+        let preferences = repository.get_user_preferences(UserId(100));
+        assert!(preferences.wants(Notification::Bookmarked));
+        assert!(!preferences.wants(Notification::VoteThreshold));
+        assert!(!preferences.wants(Notification::Detected));
+        assert!(!repository
+            .get_user_preferences(UserId(101))
+            .wants(Notification::Bookmarked));
+    }
+
+    #[test]
+    fn test_public_feeds() {
+        let repository = MemoryRepository::new();
+        assert!(!repository.has_public_feed(GuildId(1), UserId(100)));
+        repository.set_user_public_feed(GuildId(1), UserId(100), true);
+        assert!(repository.has_public_feed(GuildId(1), UserId(100)));
+        assert!(!repository.has_public_feed(GuildId(2), UserId(100)));
+        assert!(!repository.has_public_feed(GuildId(1), UserId(101)));
+        repository.set_user_public_feed(GuildId(1), UserId(100), false);
+        assert!(!repository.has_public_feed(GuildId(1), UserId(100)));
+
+        repository.set_user_public_feed(GuildId(1), UserId(100), true);
+        repository.purge_server_data(GuildId(1));
+        assert!(!repository.has_public_feed(GuildId(1), UserId(100)));
+    }
+
+    #[test]
+    fn test_quiz_scores() {
+        let repository = MemoryRepository::new();
+        repository.record_quiz_answer(GuildId(1), UserId(100), true);
+        repository.record_quiz_answer(GuildId(1), UserId(100), false);
+        repository.record_quiz_answer(GuildId(1), UserId(200), true);
+        repository.record_quiz_answer(GuildId(1), UserId(300), false);
+        repository.record_quiz_answer(GuildId(2), UserId(300), true);
+
+        let scores = repository
+            .get_quiz_leaderboard(GuildId(1), 10)
+            .iter()
+            .map(|score| (score.user, score.correct, score.answered))
+            .collect::<Vec<_>>();
+        // Fewer answers breaks the tie
+        assert_eq!(
+            scores,
+            vec![
+                (UserId(200), 1, 1),
+                (UserId(100), 1, 2),
+                (UserId(300), 0, 1)
+            ]
+        );
+        assert_eq!(repository.get_quiz_leaderboard(GuildId(1), 1).len(), 1);
+
+        repository.purge_server_data(GuildId(1));
+        assert!(repository.get_quiz_leaderboard(GuildId(1), 10).is_empty());
+        assert_eq!(repository.get_quiz_leaderboard(GuildId(2), 10).len(), 1);
+    }
+
+    #[test]
+    fn test_word_overrides() {
+        let repository = MemoryRepository::new();
+        repository.set_word_override(GuildId(1), "Ystharion", Some(3));
+        repository.set_word_override(GuildId(1), "skyrim", Some(2));
+        repository.set_word_override(GuildId(2), "skyrim", Some(3));
+        repository.set_word_override(GuildId(1), "Skyrim", None);
+        let overrides = repository.get_word_overrides(GuildId(1));
+        assert_eq!(overrides.get("ystharion"), Some(&3));
+        assert_eq!(overrides.get("skyrim"), None);
+        assert_eq!(repository.get_word_overrides(GuildId(2)).len(), 1);
+
+        repository.purge_server_data(GuildId(1));
+        assert!(repository.get_word_overrides(GuildId(1)).is_empty());
+    }
+
+    #[test]
+    fn test_job_runs() {
+        let repository = MemoryRepository::new();
+        assert_eq!(repository.get_job_last_run(Job::Maintenance), None);
+        let earlier = Utc.ymd(2021, 3, 17).and_hms(12, 0, 0);
+        let later = Utc.ymd(2021, 3, 18).and_hms(12, 0, 0);
+        repository.record_job_run(Job::Maintenance, earlier);
+        repository.record_job_run(Job::Maintenance, later);
+        repository.record_job_run(Job::EnforceRetention, earlier);
+        assert_eq!(repository.get_job_last_run(Job::Maintenance), Some(later));
+        assert_eq!(
+            repository.get_job_last_run(Job::EnforceRetention),
+            Some(earlier)
+        );
+        assert_eq!(repository.get_job_last_run(Job::PurgeDepartedGuilds), None);
+    }
+
+    #[test]
+    fn test_history_scans() {
+        let repository = MemoryRepository::new();
+        let scan = HistoryScan {
+            server: GuildId(1),
+            channel: ChannelId(10),
+            before: None,
+            until: Utc.ymd(2021, 3, 17).and_hms(12, 0, 0),
+            messages: 0,
+            haikus: 0,
+        };
+        repository.save_history_scan(&scan);
+        let progress = HistoryScan {
+            before: Some(MessageId(500)),
+            messages: 100,
+            haikus: 2,
+            ..scan.clone()
+        };
+        repository.save_history_scan(&progress);
+        assert_eq!(repository.get_history_scan(ChannelId(10)), Some(progress));
+        assert_eq!(repository.get_history_scan(ChannelId(11)), None);
+
+        repository.purge_server_data(GuildId(1));
+        assert_eq!(repository.get_history_scan(ChannelId(10)), None);
+        repository.save_history_scan(&scan);
+        repository.delete_history_scan(ChannelId(10));
+        assert_eq!(repository.get_history_scan(ChannelId(10)), None);
+    }
+
+    #[test]
+    fn test_api_tokens() {
+        let repository = MemoryRepository::new();
+        let token = ApiToken {
+            server: GuildId(1),
+            name: "Website form".to_owned(),
+            scopes: vec![ApiScope::Submit],
+            created_by: UserId(100),
+            created_at: Utc.ymd(2021, 3, 1).and_hms(12, 0, 0),
+            expires_at: Some(Utc.ymd(2021, 4, 1).and_hms(12, 0, 0)),
+        };
+        let first = repository.create_api_token(&token, "hash 1");
+        let other_server = ApiToken {
+            server: GuildId(2),
+            ..token.clone()
+        };
+        let second = repository.create_api_token(&other_server, "hash 2");
+        assert_ne!(first, second);
+        assert_eq!(
+            repository.get_api_token("hash 1"),
+            Some((first, token.clone()))
+        );
+        assert_eq!(
+            repository.get_api_token("hash 2"),
+            Some((second, other_server))
+        );
+        assert_eq!(repository.get_api_token("hash 3"), None);
+        assert_eq!(repository.get_api_tokens(GuildId(1)), vec![(first, token)]);
+
+        // Tokens can only be revoked from the server they were made in
+        assert!(!repository.delete_api_token(GuildId(1), second));
+        assert!(repository.delete_api_token(GuildId(2), second));
+        assert!(!repository.delete_api_token(GuildId(2), second));
+        assert_eq!(repository.get_api_token("hash 2"), None);
+
+        repository.purge_server_data(GuildId(1));
+        assert_eq!(repository.get_api_tokens(GuildId(1)), Vec::new());
+        assert_eq!(repository.get_api_token("hash 1"), None);
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let repository = MemoryRepository::new();
+        for author in [100, 100, 100, 200, 200, 300].iter() {
+            repository.save_haiku(&haiku(1, 10, *author, 0, ["a", "b", "c"]));
+        }
+        let mut old = haiku(1, 10, 400, 0, ["d", "e", "f"]);
+        old.timestamp = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        repository.save_haiku(&old);
+
+        assert_eq!(repository.count_leaderboard_authors(GuildId(1), None), 4);
+        assert_eq!(
+            repository.get_leaderboard(GuildId(1), None, 0, 2),
+            vec![(UserId(100), 3), (UserId(200), 2)]
+        );
+        assert_eq!(
+            repository.get_leaderboard(GuildId(1), None, 2, 2),
+            vec![(UserId(300), 1), (UserId(400), 1)]
+        );
+        let since = Some(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0));
+        assert_eq!(repository.count_leaderboard_authors(GuildId(1), since), 3);
+        assert!(repository
+            .get_leaderboard(GuildId(1), since, 3, 2)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_global_stats() {
+        let repository = MemoryRepository::new();
+        for server in 1..=3 {
+            repository.create_server_config(GuildId(server));
+        }
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["a", "b", "c"]));
+        repository.save_haiku(&haiku(1, 10, 100, 1, ["a", "b", "c"]));
+        let mut later = haiku(2, 20, 200, 0, ["a", "b", "c"]);
+        later.timestamp = Utc.ymd(2021, 1, 2).and_hms(0, 0, 0);
+        repository.save_haiku(&later);
+        repository.save_haiku(&haiku(3, 30, 300, 0, ["a", "b", "c"]));
+        // Nothing is shared until servers opt in
+        assert_eq!(repository.get_global_stats().servers, 0);
+        assert_eq!(repository.get_global_stats().biggest_day, None);
+
+        repository.set_server_global_stats(GuildId(1), true);
+        repository.set_server_global_stats(GuildId(2), true);
+        let stats = repository.get_global_stats();
+        assert_eq!(stats.servers, 2);
+        assert_eq!(stats.haikus, 3);
+        assert_eq!(
+            stats.biggest_day,
+            Some((Utc.ymd(2021, 1, 1).naive_utc(), 2))
+        );
+
+        repository.mark_server_departed(GuildId(1));
+        assert_eq!(repository.get_global_stats().haikus, 1);
+    }
+
+    #[test]
+    fn test_latest_haiku_only_from_shared_servers() {
+        let repository = MemoryRepository::new();
+        for server in 1..=2 {
+            repository.create_server_config(GuildId(server));
+        }
+        repository.save_haiku(&haiku(1, 10, 100, 0, ["shared", "b", "c"]));
+        let mut later = haiku(2, 20, 200, 0, ["private", "b", "c"]);
+        later.timestamp = Utc.ymd(2021, 1, 2).and_hms(0, 0, 0);
+        repository.save_haiku(&later);
+        assert!(repository.get_latest_haiku().is_none());
+
+        repository.set_server_global_stats(GuildId(1), true);
+        let (_, latest) = repository.get_latest_haiku().unwrap();
+        assert_eq!(latest.lines[0].content, "shared");
+
+        repository.mark_server_departed(GuildId(1));
+        assert!(repository.get_latest_haiku().is_none());
+    }
+
+    #[test]
+    fn test_random_line() {
+        let repository = MemoryRepository::new();
+        assert!(repository.get_random_line(GuildId(1), &[0, 1, 2]).is_none());
+        let id = repository
+            .save_haiku(&haiku(1, 10, 100, 0, ["first", "second", "third"]))
+            .unwrap();
+        repository.save_haiku(&haiku(2, 20, 200, 0, ["other", "server", "here"]));
+
+        let line = repository.get_random_line(GuildId(1), &[1]).unwrap();
+        assert_eq!(
+            (line.haiku_id, line.position, line.line.content.as_str()),
+            (id, 1, "second")
+        );
+        assert_eq!(line.line.author, UserId(100));
+        for _ in 0..10 {
+            let line = repository.get_random_line(GuildId(1), &[0, 2]).unwrap();
+            assert!(["first", "third"].contains(&line.line.content.as_str()));
+        }
+        assert!(repository.get_random_line(GuildId(1), &[]).is_none());
+    }
+}
@@ -0,0 +1,376 @@
+mod memory;
+mod postgres;
+
+pub use memory::MemoryRepository;
+pub use postgres::PostgresRepository;
+
+use crate::{
+    counting::WordOverrides,
+    database::{
+        CommandUsage, GlobalStats, HaikuFilter, RandomWeighting, RetentionAction, SearchCounts,
+        SearchOrder, SearchTerms,
+    },
+    models::{
+        ApiToken, ArchiveWebhook, Haiku, HaikuEdit, HistoryScan, Notification, QuizScore,
+        ServerConfig, StoredLine, UserPreferences,
+    },
+    schedule::Job,
+};
+use chrono::{DateTime, Utc};
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+    prelude::TypeMapKey,
+};
+use std::{ops::Range, sync::Arc};
+
+/// Where haikus and server configs are stored.
+///
+/// The bot uses [PostgresRepository], tests can use [MemoryRepository] instead.
+/// Anything that only makes sense for Postgres (e.g. the database size) stays in [crate::database].
+pub trait HaikuRepository: Send + Sync {
+    /// Store a new haiku, returning its number, or None if a haiku completed by the same line of
+    /// the same message has already been saved (e.g. because a gateway event was replayed).
+    /// Numbers count up from 1 in each server, without skipping any, and are what haikus are
+    /// looked up by everywhere else.
+    fn save_haiku(&self, haiku: &Haiku) -> Option<i64>;
+
+    /// Store several haikus at once, returning what [HaikuRepository::save_haiku] would for each
+    /// in the same order. If any of them can't be saved, none of them are.
+    fn save_haikus(&self, haikus: &[Haiku]) -> Vec<Option<i64>>;
+
+    fn get_haiku(&self, server_id: GuildId, haiku_id: i64) -> Option<(i64, Haiku)>;
+
+    /// Every haiku from every server, oldest first
+    fn get_all_haikus(&self) -> Vec<(i64, Haiku)>;
+
+    /// A random haiku from a server, other than those with the excluded ids or a content warning.
+    /// Only haikus written in the given stretch of time are picked, if there is one.
+    fn get_random_haiku(
+        &self,
+        server_id: GuildId,
+        exclude: &[i64],
+        weighting: RandomWeighting,
+        written: Option<&Range<DateTime<Utc>>>,
+    ) -> Option<(i64, Haiku)>;
+
+    /// A random line from a server's haikus without a content warning, from one of the given
+    /// positions (0 to 2)
+    fn get_random_line(&self, server_id: GuildId, positions: &[usize]) -> Option<StoredLine>;
+
+    /// Record that a haiku has just been shown by /randomhaiku
+    fn mark_haiku_shown(&self, server_id: GuildId, haiku_id: i64);
+
+    /// Count someone's vote for a haiku, making it more likely to be picked with
+    /// [RandomWeighting::MostVoted]. Each person's vote counts once, so this returns how many votes
+    /// the haiku now has, or None if they'd already voted for it or there's no such haiku.
+    fn vote_for_haiku(&self, server_id: GuildId, haiku_id: i64, user_id: UserId) -> Option<i64>;
+
+    /// How many people have voted for a haiku, or 0 if it doesn't exist
+    fn count_votes(&self, server_id: GuildId, haiku_id: i64) -> i64;
+
+    /// The most recently saved haiku from any server still using the bot that has opted in to the
+    /// global stats, as other servers' haikus shouldn't be shown outside them
+    fn get_latest_haiku(&self) -> Option<(i64, Haiku)>;
+
+    fn count_all_haikus(&self) -> i64;
+
+    fn count_haikus(&self, server_id: GuildId) -> i64;
+
+    /// How many haikus in a server have at least one line written by the author
+    fn count_haikus_by_author(&self, server_id: GuildId, author_id: UserId) -> i64;
+
+    /// How many haikus each author has a line in since a point in time, most first
+    fn count_haikus_by_author_since(
+        &self,
+        server_id: GuildId,
+        since: DateTime<Utc>,
+    ) -> Vec<(UserId, i64)>;
+
+    /// One page of the authors with the most haikus since a point in time (or ever), most first
+    fn get_leaderboard(
+        &self,
+        server_id: GuildId,
+        since: Option<DateTime<Utc>>,
+        offset: i64,
+        limit: i64,
+    ) -> Vec<(UserId, i64)>;
+
+    /// How many authors have a haiku since a point in time (or ever)
+    fn count_leaderboard_authors(&self, server_id: GuildId, since: Option<DateTime<Utc>>) -> i64;
+
+    /// How many haikus each channel in a server has produced, most first
+    fn count_haikus_by_channel(&self, server_id: GuildId) -> Vec<(ChannelId, i64)>;
+
+    /// Up to 5 haikus matching the search terms, optionally only from one author or channel
+    fn search_haikus(
+        &self,
+        server_id: GuildId,
+        terms: &SearchTerms,
+        order: SearchOrder,
+        author_id: Option<UserId>,
+        channel_id: Option<ChannelId>,
+    ) -> Vec<(i64, Haiku)>;
+
+    /// Every haiku in a server with at least one line written by the author, oldest first
+    fn get_haikus_by_author(&self, server_id: GuildId, author_id: UserId) -> Vec<(i64, Haiku)>;
+
+    /// The most recent haikus in a server that the author wrote every line of and that match the
+    /// filter, leaving out those with a content warning, newest first
+    fn get_public_haikus_by_author(
+        &self,
+        server_id: GuildId,
+        author_id: UserId,
+        filter: &HaikuFilter,
+        limit: i64,
+    ) -> Vec<(i64, Haiku)>;
+
+    /// Every archived haiku in a server with at least one line written by the author, oldest first
+    fn get_archived_haikus_by_author(
+        &self,
+        server_id: GuildId,
+        author_id: UserId,
+    ) -> Vec<(i64, Haiku)>;
+
+    /// Move a server's haikus saved before a point in time to the archive, returning how many moved
+    fn archive_haikus_before(&self, server_id: GuildId, before: DateTime<Utc>) -> usize;
+
+    /// Delete a server's haikus saved before a point in time, returning how many were deleted
+    fn delete_haikus_before(&self, server_id: GuildId, before: DateTime<Utc>) -> usize;
+
+    /// How many haikus match a search, with the same filters as [HaikuRepository::search_haikus]
+    fn count_search_results(
+        &self,
+        server_id: GuildId,
+        terms: &SearchTerms,
+        author_id: Option<UserId>,
+        channel_id: Option<ChannelId>,
+    ) -> SearchCounts;
+
+    /// Mark a haiku as needing review, e.g. because it no longer counts as a haiku
+    fn flag_haiku(&self, server_id: GuildId, haiku_id: i64);
+
+    /// Give a haiku a content warning or take it away, returning whether the haiku exists
+    fn set_haiku_sensitive(&self, server_id: GuildId, haiku_id: i64, sensitive: bool) -> bool;
+
+    /// Whether a haiku has a content warning
+    fn is_haiku_sensitive(&self, server_id: GuildId, haiku_id: i64) -> bool;
+
+    /// Change one line of a haiku, given how many syllables it now has, and keep the edit in its
+    /// history. Returns whether the haiku exists.
+    fn edit_haiku_line(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        edit: &HaikuEdit,
+        syllables: usize,
+    ) -> bool;
+
+    /// Every edit made to a haiku, oldest first
+    fn get_haiku_edits(&self, server_id: GuildId, haiku_id: i64) -> Vec<HaikuEdit>;
+
+    /// Remember which message announced a haiku in the channel it was written in
+    fn set_announcement_message(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        message: Option<MessageId>,
+    );
+
+    fn get_announcement_message(&self, server_id: GuildId, haiku_id: i64) -> Option<MessageId>;
+
+    /// Remember which message in the server's archive channel is a haiku's mirror
+    fn set_mirror_message(&self, server_id: GuildId, haiku_id: i64, message: Option<MessageId>);
+
+    fn get_mirror_message(&self, server_id: GuildId, haiku_id: i64) -> Option<MessageId>;
+
+    /// Delete a haiku, returning whether it existed
+    fn delete_haiku(&self, server_id: GuildId, haiku_id: i64) -> bool;
+
+    /// Create the default config for a server, or mark it as active again if it has rejoined
+    fn create_server_config(&self, server_id: GuildId);
+
+    fn get_server_config(&self, server_id: GuildId) -> Option<ServerConfig>;
+
+    fn set_server_locale(&self, server_id: GuildId, locale: &str);
+
+    fn set_server_welcome(&self, server_id: GuildId, enabled: bool);
+
+    fn set_server_random_weighting(&self, server_id: GuildId, weighting: RandomWeighting);
+
+    /// Set or clear the laureate role, forgetting who held the previous one
+    fn set_server_laureate_role(&self, server_id: GuildId, role: Option<RoleId>);
+
+    /// Record who currently holds the laureate role
+    fn set_server_laureate(&self, server_id: GuildId, user: Option<UserId>);
+
+    /// Set how many years a server's haikus are kept for (forever if None) and what happens after
+    fn set_server_retention(&self, server_id: GuildId, years: Option<i64>, action: RetentionAction);
+
+    /// The configs of the servers the bot is still in that have a retention window
+    fn get_retention_servers(&self) -> Vec<ServerConfig>;
+
+    /// Choose whether a server's haiku counts are included in the global stats
+    fn set_server_global_stats(&self, server_id: GuildId, enabled: bool);
+
+    /// Set or clear the text posted with each announced haiku
+    fn set_server_announcement_template(&self, server_id: GuildId, template: Option<&str>);
+
+    /// Set or clear the reaction that saves a haiku in channels without automatic detection, and
+    /// the role needed to use it
+    fn set_server_manual_save(&self, server_id: GuildId, emoji: Option<&str>, role: Option<RoleId>);
+
+    /// Choose whether messages from bots and from webhooks can be haikus
+    fn set_server_senders(&self, server_id: GuildId, bots: bool, webhooks: bool);
+
+    /// Set or clear the pattern that picks the real author out of bridged messages
+    fn set_server_bridge_pattern(&self, server_id: GuildId, pattern: Option<&str>);
+
+    /// Choose whether haikus are only saved when all three lines have the same author
+    fn set_server_same_author(&self, server_id: GuildId, enabled: bool);
+
+    /// Set or clear the channel the bot posts in when something goes wrong
+    fn set_server_error_channel(&self, server_id: GuildId, channel: Option<ChannelId>);
+
+    /// Start mirroring the server's haikus to an archive channel, or stop if None
+    fn set_server_archive(&self, server_id: GuildId, archive: Option<&ArchiveWebhook>);
+
+    /// Set the timezone the server's periods and monthly roles roll over in, by its IANA name
+    fn set_server_timezone(&self, server_id: GuildId, timezone: &str);
+
+    /// Choose whether haikus are shown as plain text to members who haven't chosen for themselves
+    fn set_server_plain_text(&self, server_id: GuildId, enabled: bool);
+
+    /// Choose whether members may make the haikus they wrote on their own public with /feed
+    fn set_server_public_feeds(&self, server_id: GuildId, enabled: bool);
+
+    /// Turn automatic haiku detection on or off in a channel. It's on everywhere to begin with.
+    fn set_channel_detection(&self, server_id: GuildId, channel_id: ChannelId, enabled: bool);
+
+    fn is_detection_enabled(&self, server_id: GuildId, channel_id: ChannelId) -> bool;
+
+    /// Counts across every server still using the bot that has opted in to the global stats
+    fn get_global_stats(&self) -> GlobalStats;
+
+    /// The configs of the servers the bot is still in that have a laureate role
+    fn get_laureate_servers(&self) -> Vec<ServerConfig>;
+
+    /// Record that a server has reached a milestone, returning false if it already had
+    fn record_milestone(&self, server_id: GuildId, milestone: i64) -> bool;
+
+    fn mark_server_departed(&self, server_id: GuildId);
+
+    fn get_servers_departed_before(&self, before: DateTime<Utc>) -> Vec<GuildId>;
+
+    /// Delete everything stored for a server, including archived haikus and command usage
+    fn purge_server_data(&self, server_id: GuildId);
+
+    /// Record that a command was used in a server, and how long it took to answer
+    fn record_command_usage(&self, server_id: GuildId, command: &str, latency_ms: i64);
+
+    /// Each command used in a server since a point in time, most used first
+    fn get_command_usage(&self, server_id: GuildId, since: DateTime<Utc>) -> Vec<CommandUsage>;
+
+    /// How many commands were used in each hour of the day (UTC) since a point in time, busiest first
+    fn count_command_usage_by_hour(
+        &self,
+        server_id: GuildId,
+        since: DateTime<Utc>,
+    ) -> Vec<(u32, i64)>;
+
+    /// Which notifications someone wants, with everything off if they've never chosen
+    fn get_user_preferences(&self, user_id: UserId) -> UserPreferences;
+
+    /// Turn one of someone's notifications on or off
+    fn set_user_notification(&self, user_id: UserId, notification: Notification, enabled: bool);
+
+    /// Choose whether someone sees haikus as plain text, or None to go by each server's default
+    fn set_user_plain_text(&self, user_id: UserId, enabled: Option<bool>);
+
+    /// Choose whether the haikus someone wrote on their own in a server are listed on the HTTP
+    /// API and in their feed
+    fn set_user_public_feed(&self, server_id: GuildId, user_id: UserId, enabled: bool);
+
+    /// Whether someone has made their haikus in a server public. They're only served if the
+    /// server allows it too, see [ServerConfig::public_feeds].
+    fn has_public_feed(&self, server_id: GuildId, user_id: UserId) -> bool;
+
+    /// Count someone's answer to a /quiz question
+    fn record_quiz_answer(&self, server_id: GuildId, user_id: UserId, was_correct: bool);
+
+    /// The people with the most correct /quiz answers in a server, best first, with fewer
+    /// answers breaking ties
+    fn get_quiz_leaderboard(&self, server_id: GuildId, limit: i64) -> Vec<QuizScore>;
+
+    /// Set how many syllables a word (in any case) has in a server, or with None, go back to
+    /// counting it normally
+    fn set_word_override(&self, server_id: GuildId, word: &str, syllables: Option<usize>);
+
+    /// The syllable counts a server has set for words, keyed by the word in lowercase
+    fn get_word_overrides(&self, server_id: GuildId) -> WordOverrides;
+
+    /// When a periodic job last finished, if it ever has
+    fn get_job_last_run(&self, job: Job) -> Option<DateTime<Utc>>;
+
+    /// Save when a periodic job finished, so it isn't run again too soon after a restart
+    fn record_job_run(&self, job: Job, finished_at: DateTime<Utc>);
+
+    /// How far /scanhistory has got through a channel, if a scan of it hasn't finished
+    fn get_history_scan(&self, channel_id: ChannelId) -> Option<HistoryScan>;
+
+    /// Save a scan's progress, replacing what was saved for its channel before
+    fn save_history_scan(&self, scan: &HistoryScan);
+
+    /// Forget a channel's scan, once it's finished
+    fn delete_history_scan(&self, channel_id: ChannelId);
+
+    /// Store a new API token by the hash of its secret, returning the token's id. The secret
+    /// itself is never stored.
+    fn create_api_token(&self, token: &ApiToken, token_hash: &str) -> i64;
+
+    /// The token whose secret has a hash, from whichever server it was made in, expired or not
+    fn get_api_token(&self, token_hash: &str) -> Option<(i64, ApiToken)>;
+
+    /// Every API token made in a server, oldest first
+    fn get_api_tokens(&self, server_id: GuildId) -> Vec<(i64, ApiToken)>;
+
+    /// Revoke an API token, returning whether the server had one with that id
+    fn delete_api_token(&self, server_id: GuildId, token_id: i64) -> bool;
+
+    /// Whether the storage can be reached right now. Everything else panics when it can't.
+    fn is_available(&self) -> bool;
+}
+
+/// Whether a haiku found by a query for a server is from that server. One that isn't means the
+/// query is missing its server filter, so it fails debug builds, and in release builds it's
+/// dropped rather than shown in the wrong server.
+fn is_in_server(server_id: GuildId, (id, haiku): &(i64, Haiku)) -> bool {
+    debug_assert_eq!(
+        haiku.server, server_id,
+        "Haiku #{} leaked across servers",
+        id
+    );
+    if haiku.server != server_id {
+        println!(
+            "Dropped haiku #{} from server {} found when asking for server {}",
+            id, haiku.server, server_id
+        );
+        return false;
+    }
+    true
+}
+
+pub struct Repository;
+impl TypeMapKey for Repository {
+    type Value = Arc<dyn HaikuRepository>;
+}
+
+/// The repository the bot was started with
+pub async fn repository(ctx: &Context) -> Arc<dyn HaikuRepository> {
+    ctx.data
+        .read()
+        .await
+        .get::<Repository>()
+        .expect("Expected Repository in TypeMap")
+        .clone()
+}
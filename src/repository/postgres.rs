@@ -0,0 +1,516 @@
+use super::{is_in_server, HaikuRepository};
+use crate::{
+    counting::WordOverrides,
+    database::{
+        self, establish_connection, CommandUsage, GlobalStats, HaikuFilter, RandomWeighting,
+        RetentionAction, SearchCounts, SearchOrder, SearchTerms,
+    },
+    models::{
+        ApiToken, ArchiveWebhook, Haiku, HaikuEdit, HistoryScan, Notification, QuizScore,
+        ServerConfig, StoredLine, UserPreferences,
+    },
+    save_queue::{BatchWriter, SaveQueue},
+    schedule::Job,
+};
+use chrono::{DateTime, Utc};
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use std::ops::Range;
+
+/// Stores everything in the database at `DATABASE_URL`, connecting afresh for each call.
+/// Haiku saves and votes can go through a [SaveQueue] instead, to batch them under load.
+pub struct PostgresRepository {
+    queue: Option<SaveQueue>,
+}
+
+impl PostgresRepository {
+    pub fn new() -> Self {
+        PostgresRepository { queue: None }
+    }
+
+    /// Batch haiku saves and votes through a background writer, see [SaveQueue::start]
+    pub fn with_save_queue(capacity: usize, max_batch: usize) -> Self {
+        PostgresRepository {
+            queue: Some(SaveQueue::start(PostgresWriter, capacity, max_batch)),
+        }
+    }
+
+    /// Write everything still queued, after which saves and votes fail
+    pub fn flush(&self) {
+        if let Some(queue) = &self.queue {
+            queue.shutdown();
+        }
+    }
+}
+
+impl Default for PostgresRepository {
+    fn default() -> Self {
+        PostgresRepository::new()
+    }
+}
+
+/// Writes each batch with a single connection
+struct PostgresWriter;
+
+impl BatchWriter for PostgresWriter {
+    fn save_haikus(&mut self, haikus: &[Haiku]) -> Vec<Option<i64>> {
+        database::save_haikus(haikus, &establish_connection())
+    }
+
+    fn add_votes(&mut self, votes: &[(GuildId, i64, UserId)]) -> Vec<Option<i64>> {
+        database::add_votes(votes, &establish_connection())
+    }
+}
+
+impl HaikuRepository for PostgresRepository {
+    fn save_haiku(&self, haiku: &Haiku) -> Option<i64> {
+        match &self.queue {
+            Some(queue) => queue.save_haiku(haiku),
+            None => database::save_haiku(haiku, &establish_connection()),
+        }
+    }
+
+    fn save_haikus(&self, haikus: &[Haiku]) -> Vec<Option<i64>> {
+        database::save_haikus(haikus, &establish_connection())
+    }
+
+    fn get_haiku(&self, server_id: GuildId, haiku_id: i64) -> Option<(i64, Haiku)> {
+        database::get_haiku(server_id, haiku_id, &establish_connection())
+            .filter(|found| is_in_server(server_id, found))
+    }
+
+    fn get_all_haikus(&self) -> Vec<(i64, Haiku)> {
+        database::get_all_haikus(&establish_connection())
+    }
+
+    fn get_random_haiku(
+        &self,
+        server_id: GuildId,
+        exclude: &[i64],
+        weighting: RandomWeighting,
+        written: Option<&Range<DateTime<Utc>>>,
+    ) -> Option<(i64, Haiku)> {
+        database::get_random_haiku(
+            server_id,
+            exclude,
+            weighting,
+            written,
+            &establish_connection(),
+        )
+        .filter(|found| is_in_server(server_id, found))
+    }
+
+    fn get_random_line(&self, server_id: GuildId, positions: &[usize]) -> Option<StoredLine> {
+        database::get_random_line(server_id, positions, &establish_connection())
+    }
+
+    fn mark_haiku_shown(&self, server_id: GuildId, haiku_id: i64) {
+        database::mark_haiku_shown(server_id, haiku_id, &establish_connection())
+    }
+
+    fn vote_for_haiku(&self, server_id: GuildId, haiku_id: i64, user_id: UserId) -> Option<i64> {
+        match &self.queue {
+            Some(queue) => queue.vote_for_haiku(server_id, haiku_id, user_id),
+            None => database::vote_for_haiku(server_id, haiku_id, user_id, &establish_connection()),
+        }
+    }
+
+    fn count_votes(&self, server_id: GuildId, haiku_id: i64) -> i64 {
+        database::count_votes(server_id, haiku_id, &establish_connection())
+    }
+
+    fn get_latest_haiku(&self) -> Option<(i64, Haiku)> {
+        database::get_latest_haiku(&establish_connection())
+    }
+
+    fn count_all_haikus(&self) -> i64 {
+        database::count_all_haikus(&establish_connection())
+    }
+
+    fn count_haikus(&self, server_id: GuildId) -> i64 {
+        database::count_haikus(server_id, &establish_connection())
+    }
+
+    fn count_haikus_by_author(&self, server_id: GuildId, author_id: UserId) -> i64 {
+        database::count_haikus_by_author(server_id, author_id, &establish_connection())
+    }
+
+    fn count_haikus_by_author_since(
+        &self,
+        server_id: GuildId,
+        since: DateTime<Utc>,
+    ) -> Vec<(UserId, i64)> {
+        database::count_haikus_by_author_since(server_id, since, &establish_connection())
+    }
+
+    fn get_leaderboard(
+        &self,
+        server_id: GuildId,
+        since: Option<DateTime<Utc>>,
+        offset: i64,
+        limit: i64,
+    ) -> Vec<(UserId, i64)> {
+        database::get_leaderboard(server_id, since, offset, limit, &establish_connection())
+    }
+
+    fn count_leaderboard_authors(&self, server_id: GuildId, since: Option<DateTime<Utc>>) -> i64 {
+        database::count_leaderboard_authors(server_id, since, &establish_connection())
+    }
+
+    fn count_haikus_by_channel(&self, server_id: GuildId) -> Vec<(ChannelId, i64)> {
+        database::count_haikus_by_channel(server_id, &establish_connection())
+    }
+
+    fn search_haikus(
+        &self,
+        server_id: GuildId,
+        terms: &SearchTerms,
+        order: SearchOrder,
+        author_id: Option<UserId>,
+        channel_id: Option<ChannelId>,
+    ) -> Vec<(i64, Haiku)> {
+        database::search_haikus(
+            server_id,
+            terms,
+            order,
+            author_id,
+            channel_id,
+            &establish_connection(),
+        )
+        .into_iter()
+        .filter(|found| is_in_server(server_id, found))
+        .collect()
+    }
+
+    fn count_search_results(
+        &self,
+        server_id: GuildId,
+        terms: &SearchTerms,
+        author_id: Option<UserId>,
+        channel_id: Option<ChannelId>,
+    ) -> SearchCounts {
+        database::count_search_results(
+            server_id,
+            terms,
+            author_id,
+            channel_id,
+            &establish_connection(),
+        )
+    }
+
+    fn get_haikus_by_author(&self, server_id: GuildId, author_id: UserId) -> Vec<(i64, Haiku)> {
+        database::get_haikus_by_author(server_id, author_id, &establish_connection())
+            .into_iter()
+            .filter(|found| is_in_server(server_id, found))
+            .collect()
+    }
+
+    fn get_public_haikus_by_author(
+        &self,
+        server_id: GuildId,
+        author_id: UserId,
+        filter: &HaikuFilter,
+        limit: i64,
+    ) -> Vec<(i64, Haiku)> {
+        database::get_public_haikus_by_author(
+            server_id,
+            author_id,
+            filter,
+            limit,
+            &establish_connection(),
+        )
+        .into_iter()
+        .filter(|found| is_in_server(server_id, found))
+        .collect()
+    }
+
+    fn get_archived_haikus_by_author(
+        &self,
+        server_id: GuildId,
+        author_id: UserId,
+    ) -> Vec<(i64, Haiku)> {
+        database::get_archived_haikus_by_author(server_id, author_id, &establish_connection())
+            .into_iter()
+            .filter(|found| is_in_server(server_id, found))
+            .collect()
+    }
+
+    fn archive_haikus_before(&self, server_id: GuildId, before: DateTime<Utc>) -> usize {
+        database::archive_haikus_before(server_id, before, &establish_connection())
+    }
+
+    fn delete_haikus_before(&self, server_id: GuildId, before: DateTime<Utc>) -> usize {
+        database::delete_haikus_before(server_id, before, &establish_connection())
+    }
+
+    fn flag_haiku(&self, server_id: GuildId, haiku_id: i64) {
+        database::flag_haiku(server_id, haiku_id, &establish_connection())
+    }
+
+    fn set_haiku_sensitive(&self, server_id: GuildId, haiku_id: i64, sensitive: bool) -> bool {
+        database::set_haiku_sensitive(server_id, haiku_id, sensitive, &establish_connection())
+    }
+
+    fn is_haiku_sensitive(&self, server_id: GuildId, haiku_id: i64) -> bool {
+        database::is_haiku_sensitive(server_id, haiku_id, &establish_connection())
+    }
+
+    fn edit_haiku_line(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        edit: &HaikuEdit,
+        syllables: usize,
+    ) -> bool {
+        database::edit_haiku_line(
+            server_id,
+            haiku_id,
+            edit,
+            syllables,
+            &establish_connection(),
+        )
+    }
+
+    fn get_haiku_edits(&self, server_id: GuildId, haiku_id: i64) -> Vec<HaikuEdit> {
+        database::get_haiku_edits(server_id, haiku_id, &establish_connection())
+    }
+
+    fn set_announcement_message(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        message: Option<MessageId>,
+    ) {
+        database::set_announcement_message(server_id, haiku_id, message, &establish_connection())
+    }
+
+    fn get_announcement_message(&self, server_id: GuildId, haiku_id: i64) -> Option<MessageId> {
+        database::get_announcement_message(server_id, haiku_id, &establish_connection())
+    }
+
+    fn set_mirror_message(&self, server_id: GuildId, haiku_id: i64, message: Option<MessageId>) {
+        database::set_mirror_message(server_id, haiku_id, message, &establish_connection())
+    }
+
+    fn get_mirror_message(&self, server_id: GuildId, haiku_id: i64) -> Option<MessageId> {
+        database::get_mirror_message(server_id, haiku_id, &establish_connection())
+    }
+
+    fn delete_haiku(&self, server_id: GuildId, haiku_id: i64) -> bool {
+        database::delete_haiku(server_id, haiku_id, &establish_connection())
+    }
+
+    fn create_server_config(&self, server_id: GuildId) {
+        database::create_server_config(server_id, &establish_connection())
+    }
+
+    fn get_server_config(&self, server_id: GuildId) -> Option<ServerConfig> {
+        database::get_server_config(server_id, &establish_connection())
+    }
+
+    fn set_server_locale(&self, server_id: GuildId, locale: &str) {
+        database::set_server_locale(server_id, locale, &establish_connection())
+    }
+
+    fn set_server_welcome(&self, server_id: GuildId, enabled: bool) {
+        database::set_server_welcome(server_id, enabled, &establish_connection())
+    }
+
+    fn set_server_random_weighting(&self, server_id: GuildId, weighting: RandomWeighting) {
+        database::set_server_random_weighting(server_id, weighting, &establish_connection())
+    }
+
+    fn set_server_laureate_role(&self, server_id: GuildId, role: Option<RoleId>) {
+        database::set_server_laureate_role(server_id, role, &establish_connection())
+    }
+
+    fn set_server_laureate(&self, server_id: GuildId, user: Option<UserId>) {
+        database::set_server_laureate(server_id, user, &establish_connection())
+    }
+
+    fn set_server_retention(
+        &self,
+        server_id: GuildId,
+        years: Option<i64>,
+        action: RetentionAction,
+    ) {
+        database::set_server_retention(server_id, years, action, &establish_connection())
+    }
+
+    fn get_retention_servers(&self) -> Vec<ServerConfig> {
+        database::get_retention_servers(&establish_connection())
+    }
+
+    fn set_server_global_stats(&self, server_id: GuildId, enabled: bool) {
+        database::set_server_global_stats(server_id, enabled, &establish_connection())
+    }
+
+    fn set_server_announcement_template(&self, server_id: GuildId, template: Option<&str>) {
+        database::set_server_announcement_template(server_id, template, &establish_connection())
+    }
+
+    fn set_server_manual_save(
+        &self,
+        server_id: GuildId,
+        emoji: Option<&str>,
+        role: Option<RoleId>,
+    ) {
+        database::set_server_manual_save(server_id, emoji, role, &establish_connection())
+    }
+
+    fn set_server_senders(&self, server_id: GuildId, bots: bool, webhooks: bool) {
+        database::set_server_senders(server_id, bots, webhooks, &establish_connection())
+    }
+
+    fn set_server_bridge_pattern(&self, server_id: GuildId, pattern: Option<&str>) {
+        database::set_server_bridge_pattern(server_id, pattern, &establish_connection())
+    }
+
+    fn set_server_same_author(&self, server_id: GuildId, enabled: bool) {
+        database::set_server_same_author(server_id, enabled, &establish_connection())
+    }
+
+    fn set_server_error_channel(&self, server_id: GuildId, channel: Option<ChannelId>) {
+        database::set_server_error_channel(server_id, channel, &establish_connection())
+    }
+
+    fn set_server_archive(&self, server_id: GuildId, archive: Option<&ArchiveWebhook>) {
+        database::set_server_archive(server_id, archive, &establish_connection())
+    }
+
+    fn set_server_timezone(&self, server_id: GuildId, timezone: &str) {
+        database::set_server_timezone(server_id, timezone, &establish_connection())
+    }
+
+    fn set_server_plain_text(&self, server_id: GuildId, enabled: bool) {
+        database::set_server_plain_text(server_id, enabled, &establish_connection())
+    }
+
+    fn set_server_public_feeds(&self, server_id: GuildId, enabled: bool) {
+        database::set_server_public_feeds(server_id, enabled, &establish_connection())
+    }
+
+    fn set_channel_detection(&self, server_id: GuildId, channel_id: ChannelId, enabled: bool) {
+        database::set_channel_detection(server_id, channel_id, enabled, &establish_connection())
+    }
+
+    fn is_detection_enabled(&self, server_id: GuildId, channel_id: ChannelId) -> bool {
+        database::is_detection_enabled(server_id, channel_id, &establish_connection())
+    }
+
+    fn get_global_stats(&self) -> GlobalStats {
+        database::get_global_stats(&establish_connection())
+    }
+
+    fn get_laureate_servers(&self) -> Vec<ServerConfig> {
+        database::get_laureate_servers(&establish_connection())
+    }
+
+    fn record_milestone(&self, server_id: GuildId, milestone: i64) -> bool {
+        database::record_milestone(server_id, milestone, &establish_connection())
+    }
+
+    fn mark_server_departed(&self, server_id: GuildId) {
+        database::mark_server_departed(server_id, &establish_connection())
+    }
+
+    fn get_servers_departed_before(&self, before: DateTime<Utc>) -> Vec<GuildId> {
+        database::get_servers_departed_before(before, &establish_connection())
+    }
+
+    fn purge_server_data(&self, server_id: GuildId) {
+        database::purge_server_data(server_id, &establish_connection())
+    }
+
+    fn record_command_usage(&self, server_id: GuildId, command: &str, latency_ms: i64) {
+        database::record_command_usage(server_id, command, latency_ms, &establish_connection())
+    }
+
+    fn get_command_usage(&self, server_id: GuildId, since: DateTime<Utc>) -> Vec<CommandUsage> {
+        database::get_command_usage(server_id, since, &establish_connection())
+    }
+
+    fn count_command_usage_by_hour(
+        &self,
+        server_id: GuildId,
+        since: DateTime<Utc>,
+    ) -> Vec<(u32, i64)> {
+        database::count_command_usage_by_hour(server_id, since, &establish_connection())
+    }
+
+    fn get_user_preferences(&self, user_id: UserId) -> UserPreferences {
+        database::get_user_preferences(user_id, &establish_connection())
+            .unwrap_or_else(|| UserPreferences::new(user_id))
+    }
+
+    fn set_user_notification(&self, user_id: UserId, notification: Notification, enabled: bool) {
+        database::set_user_notification(user_id, notification, enabled, &establish_connection())
+    }
+
+    fn set_user_plain_text(&self, user_id: UserId, enabled: Option<bool>) {
+        database::set_user_plain_text(user_id, enabled, &establish_connection())
+    }
+
+    fn set_user_public_feed(&self, server_id: GuildId, user_id: UserId, enabled: bool) {
+        database::set_user_public_feed(server_id, user_id, enabled, &establish_connection())
+    }
+
+    fn has_public_feed(&self, server_id: GuildId, user_id: UserId) -> bool {
+        database::has_public_feed(server_id, user_id, &establish_connection())
+    }
+
+    fn record_quiz_answer(&self, server_id: GuildId, user_id: UserId, was_correct: bool) {
+        database::record_quiz_answer(server_id, user_id, was_correct, &establish_connection())
+    }
+
+    fn get_quiz_leaderboard(&self, server_id: GuildId, limit: i64) -> Vec<QuizScore> {
+        database::get_quiz_leaderboard(server_id, limit, &establish_connection())
+    }
+
+    fn set_word_override(&self, server_id: GuildId, word: &str, syllables: Option<usize>) {
+        database::set_word_override(server_id, word, syllables, &establish_connection())
+    }
+
+    fn get_word_overrides(&self, server_id: GuildId) -> WordOverrides {
+        database::get_word_overrides(server_id, &establish_connection())
+    }
+
+    fn get_job_last_run(&self, job: Job) -> Option<DateTime<Utc>> {
+        database::get_job_last_run(job.name(), &establish_connection())
+    }
+
+    fn record_job_run(&self, job: Job, finished_at: DateTime<Utc>) {
+        database::record_job_run(job.name(), finished_at, &establish_connection())
+    }
+
+    fn get_history_scan(&self, channel_id: ChannelId) -> Option<HistoryScan> {
+        database::get_history_scan(channel_id, &establish_connection())
+    }
+
+    fn save_history_scan(&self, scan: &HistoryScan) {
+        database::save_history_scan(scan, &establish_connection())
+    }
+
+    fn delete_history_scan(&self, channel_id: ChannelId) {
+        database::delete_history_scan(channel_id, &establish_connection())
+    }
+
+    fn create_api_token(&self, token: &ApiToken, token_hash: &str) -> i64 {
+        database::create_api_token(token, token_hash, &establish_connection())
+    }
+
+    fn get_api_token(&self, token_hash: &str) -> Option<(i64, ApiToken)> {
+        database::get_api_token(token_hash, &establish_connection())
+    }
+
+    fn get_api_tokens(&self, server_id: GuildId) -> Vec<(i64, ApiToken)> {
+        database::get_api_tokens(server_id, &establish_connection())
+    }
+
+    fn delete_api_token(&self, server_id: GuildId, token_id: i64) -> bool {
+        database::delete_api_token(server_id, token_id, &establish_connection())
+    }
+
+    fn is_available(&self) -> bool {
+        database::try_establish_connection().is_ok()
+    }
+}
@@ -0,0 +1,110 @@
+//! Clearing old haikus out of servers that only want to keep them for a while
+
+use crate::{database::RetentionAction, repository::HaikuRepository};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serenity::model::id::GuildId;
+use std::convert::TryFrom;
+
+/// The time haikus must have been saved after to be kept, `years` before now
+pub fn retention_cutoff(now: DateTime<Utc>, years: i64) -> DateTime<Utc> {
+    i32::try_from(years)
+        .ok()
+        .and_then(|years| now.with_year(now.year() - years))
+        // 29th February, or a window longer than chrono can count back
+        .unwrap_or_else(|| now - Duration::days(365 * years.min(10_000)))
+}
+
+/// Apply every server's retention policy, returning how many haikus were archived or deleted in
+/// each server that had any old enough
+pub fn enforce_retention(
+    repository: &dyn HaikuRepository,
+    now: DateTime<Utc>,
+) -> Vec<(GuildId, RetentionAction, usize)> {
+    let mut removed = Vec::new();
+    for config in repository.get_retention_servers() {
+        let years = match config.retention_years {
+            Some(years) => years,
+            None => continue,
+        };
+        let cutoff = retention_cutoff(now, years);
+        let action = RetentionAction::from_code(&config.retention_action)
+            .unwrap_or(RetentionAction::Archive);
+        let count = match action {
+            RetentionAction::Archive => repository.archive_haikus_before(config.server, cutoff),
+            RetentionAction::Delete => repository.delete_haikus_before(config.server, cutoff),
+        };
+        if count > 0 {
+            removed.push((config.server, action, count));
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use super::{enforce_retention, retention_cutoff};
+    use crate::{
+        database::RetentionAction,
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use chrono::{DateTime, TimeZone, Utc};
+    use serenity::model::id::{GuildId, UserId};
+
+    fn save_haiku(repository: &MemoryRepository, server: u64, timestamp: DateTime<Utc>) {
+        repository.save_haiku(&HaikuBuilder::new().server(server).at(timestamp).build());
+    }
+
+    #[test]
+    fn test_retention_cutoff() {
+        let now = Utc.ymd(2024, 2, 29).and_hms(12, 0, 0);
+        assert_eq!(
+            retention_cutoff(now, 4),
+            Utc.ymd(2020, 2, 29).and_hms(12, 0, 0)
+        );
+        assert_eq!(
+            retention_cutoff(now, 1),
+            Utc.ymd(2023, 3, 1).and_hms(12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_enforce_retention() {
+        let repository = MemoryRepository::new();
+        let now = Utc.ymd(2024, 6, 1).and_hms(0, 0, 0);
+        let old = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let recent = Utc.ymd(2024, 1, 1).and_hms(0, 0, 0);
+        for server in 1..=3 {
+            repository.create_server_config(GuildId(server));
+            save_haiku(&repository, server, old);
+            save_haiku(&repository, server, recent);
+        }
+        repository.set_server_retention(GuildId(1), Some(2), RetentionAction::Archive);
+        repository.set_server_retention(GuildId(2), Some(2), RetentionAction::Delete);
+
+        let mut removed = enforce_retention(&repository, now);
+        removed.sort_by_key(|(server, _, _)| *server);
+        assert_eq!(
+            removed,
+            vec![
+                (GuildId(1), RetentionAction::Archive, 1),
+                (GuildId(2), RetentionAction::Delete, 1),
+            ]
+        );
+        assert_eq!(repository.count_haikus(GuildId(1)), 1);
+        assert_eq!(repository.count_haikus(GuildId(2)), 1);
+        assert_eq!(repository.count_haikus(GuildId(3)), 2);
+        // Archived haikus can still be exported, deleted ones are gone
+        assert_eq!(
+            repository
+                .get_archived_haikus_by_author(GuildId(1), UserId(100))
+                .len(),
+            1
+        );
+        assert!(repository
+            .get_archived_haikus_by_author(GuildId(2), UserId(100))
+            .is_empty());
+        // Nothing left to remove the next time round
+        assert!(enforce_retention(&repository, now).is_empty());
+    }
+}
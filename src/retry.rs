@@ -0,0 +1,93 @@
+use serenity::{
+    http::{Http, HttpError},
+    Error,
+};
+use std::{
+    future::Future,
+    time::{Duration, SystemTime},
+};
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Rate limits longer than this fail the request rather than holding up whoever's waiting on it
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Run a Discord API request, retrying transient failures with exponential backoff. Rate limited
+/// requests are retried once the rate limit Discord gave has reset instead.
+pub async fn with_retry<T, F, Fut>(http: &Http, mut request: F) -> serenity::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = serenity::Result<T>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(err) if attempt < MAX_ATTEMPTS && is_transient(&err) => {
+                let wait = if is_rate_limited(&err) {
+                    match rate_limit_reset(http).await {
+                        Some(wait) if wait > MAX_RATE_LIMIT_WAIT => return Err(err),
+                        Some(wait) => wait,
+                        None => backoff,
+                    }
+                } else {
+                    backoff
+                };
+                println!(
+                    "Discord request failed (attempt {}/{}), retrying in {:?}: {:?}",
+                    attempt, MAX_ATTEMPTS, wait, err
+                );
+                tokio::time::sleep(wait).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// How long until the rate limit a request ran into resets, from the rate limit headers serenity
+/// keeps for each bucket. Serenity waits out any 429 with a Retry-After itself, so one that
+/// reaches us came without it. Which bucket it was isn't known, so this is the longest wait of
+/// those that have run out.
+async fn rate_limit_reset(http: &Http) -> Option<Duration> {
+    let routes = http.ratelimiter.routes();
+    let routes = routes.read().await;
+    let mut wait = None;
+    for ratelimit in routes.values() {
+        let ratelimit = ratelimit.lock().await;
+        if ratelimit.remaining() > 0 {
+            continue;
+        }
+        let until_reset = ratelimit
+            .reset()
+            .and_then(|reset| reset.duration_since(SystemTime::now()).ok());
+        wait = wait.max(until_reset);
+    }
+    wait
+}
+
+fn is_rate_limited(err: &Error) -> bool {
+    match err {
+        Error::Http(http_error) => matches!(
+            http_error.as_ref(),
+            HttpError::UnsuccessfulRequest(response) if response.status_code.as_u16() == 429
+        ),
+        _ => false,
+    }
+}
+
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Http(http_error) => match http_error.as_ref() {
+            HttpError::UnsuccessfulRequest(response) => {
+                response.status_code.is_server_error() || response.status_code.as_u16() == 429
+            }
+            HttpError::Request(request_error) => {
+                request_error.is_timeout() || request_error.is_connect()
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
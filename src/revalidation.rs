@@ -0,0 +1,131 @@
+use crate::{
+    counting::{is_haiku_with, WordOverrides},
+    repository::HaikuRepository,
+};
+use serenity::model::id::GuildId;
+use std::collections::HashMap;
+
+/// The haikus that no longer pass the syllable counter
+pub struct RevalidationReport {
+    pub checked: usize,
+    pub total: usize,
+    pub invalid: Vec<(GuildId, i64)>,
+}
+
+impl RevalidationReport {
+    pub fn summary(&self) -> String {
+        if self.checked < self.total {
+            format!(
+                "Stopped after checking {} of {} haikus, {} no longer validate",
+                self.checked,
+                self.total,
+                self.invalid.len()
+            )
+        } else {
+            format!(
+                "Checked {} haikus, {} no longer validate",
+                self.checked,
+                self.invalid.len()
+            )
+        }
+    }
+}
+
+/// Re-run the syllable counter over every stored haiku, e.g. after changing the dictionary.
+/// Words are counted with each server's overrides. Haikus that fail are only flagged if asked, and
+/// never deleted.
+///
+/// `on_progress` is told how many haikus have been checked out of how many after each one, and
+/// stops the check early by returning false.
+pub fn revalidate_haikus<F>(
+    repository: &dyn HaikuRepository,
+    flag: bool,
+    mut on_progress: F,
+) -> RevalidationReport
+where
+    F: FnMut(usize, usize) -> bool,
+{
+    let haikus = repository.get_all_haikus();
+    let total = haikus.len();
+    let mut checked = 0;
+    let mut overrides = HashMap::<GuildId, WordOverrides>::new();
+    let mut invalid = Vec::new();
+    for (id, haiku) in haikus {
+        let lines = haiku
+            .lines
+            .iter()
+            .map(|line| line.content.clone())
+            .collect::<Vec<String>>();
+        let overrides = overrides
+            .entry(haiku.server)
+            .or_insert_with(|| repository.get_word_overrides(haiku.server));
+        if is_haiku_with(&lines, overrides).is_err() {
+            invalid.push((haiku.server, id));
+        }
+        checked += 1;
+        if !on_progress(checked, total) {
+            break;
+        }
+    }
+    if flag {
+        for (server_id, id) in invalid.iter() {
+            repository.flag_haiku(*server_id, *id);
+        }
+    }
+    RevalidationReport {
+        checked,
+        total,
+        invalid,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::revalidate_haikus;
+    use crate::{
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use serenity::model::id::GuildId;
+
+    #[test]
+    fn test_revalidate_haikus() {
+        let repository = MemoryRepository::new();
+        let valid = repository
+            .save_haiku(
+                &HaikuBuilder::new()
+                    .lines([
+                        "The last winter leaves",
+                        "Clinging to the black branches",
+                        "Explode into birds",
+                    ])
+                    .build(),
+            )
+            .unwrap();
+        let invalid = repository
+            .save_haiku(
+                &HaikuBuilder::new()
+                    .lines([
+                        "The last spring leaves",
+                        "Clinging to the black branches",
+                        "Explode into birds",
+                    ])
+                    .build(),
+            )
+            .unwrap();
+
+        let report = revalidate_haikus(&repository, false, |_, _| true);
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.invalid, vec![(GuildId(1), invalid)]);
+        assert!(!repository.is_flagged(GuildId(1), invalid));
+
+        revalidate_haikus(&repository, true, |_, _| true);
+        assert!(repository.is_flagged(GuildId(1), invalid));
+        assert!(!repository.is_flagged(GuildId(1), valid));
+        // Flagged haikus are kept
+        assert_eq!(repository.count_all_haikus(), 2);
+
+        let report = revalidate_haikus(&repository, false, |checked, _| checked < 1);
+        assert_eq!((report.checked, report.total), (1, 2));
+    }
+}
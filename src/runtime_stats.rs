@@ -0,0 +1,80 @@
+use serenity::prelude::TypeMapKey;
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Counters updated by the event handler while the bot is running
+#[derive(Debug, Default)]
+pub struct RuntimeStats {
+    messages_processed: AtomicU64,
+    haikus_detected: AtomicU64,
+    commands: Mutex<HashMap<String, CommandStats>>,
+}
+
+/// How often a command has been used since the bot started, and how long it took in total
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CommandStats {
+    pub uses: u64,
+    pub total_latency_ms: u64,
+}
+
+impl TypeMapKey for RuntimeStats {
+    type Value = Arc<RuntimeStats>;
+}
+
+impl RuntimeStats {
+    pub fn record_message(&self) {
+        self.messages_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_haiku(&self) {
+        self.haikus_detected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn messages_processed(&self) -> u64 {
+        self.messages_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn haikus_detected(&self) -> u64 {
+        self.haikus_detected.load(Ordering::Relaxed)
+    }
+
+    pub fn record_command(&self, command: &str, latency: Duration) {
+        let mut commands = self.commands.lock().expect("Command stats lock poisoned");
+        let stats = commands.entry(command.to_owned()).or_default();
+        stats.uses += 1;
+        stats.total_latency_ms += latency.as_millis() as u64;
+    }
+
+    /// Every command used since the bot started, sorted by name
+    pub fn command_stats(&self) -> Vec<(String, CommandStats)> {
+        let mut stats = self
+            .commands
+            .lock()
+            .expect("Command stats lock poisoned")
+            .iter()
+            .map(|(command, stats)| (command.clone(), *stats))
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+}
+
+/// Resident memory of the bot process in bytes, where the platform exposes it
+pub fn memory_usage() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let resident_kb = status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse::<u64>()
+        .ok()?;
+    Some(resident_kb * 1024)
+}
@@ -0,0 +1,268 @@
+//! Funnelling haiku saves and votes through one background writer, so bursts of activity become a
+//! few batched writes instead of a connection and insert each
+
+use crate::models::Haiku;
+use serenity::model::id::{GuildId, UserId};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+use tokio::{sync::oneshot, task};
+
+/// Somewhere batches of writes can be made
+pub trait BatchWriter: Send + 'static {
+    /// Store the haikus, returning their ids in the same order (None for any already saved)
+    fn save_haikus(&mut self, haikus: &[Haiku]) -> Vec<Option<i64>>;
+
+    /// Count votes, given as (server, haiku id, voter), returning each haiku's votes after each
+    /// vote in the same order (None for any from someone who'd already voted for the haiku)
+    fn add_votes(&mut self, votes: &[(GuildId, i64, UserId)]) -> Vec<Option<i64>>;
+}
+
+enum Write {
+    /// A haiku to save, and where to send its id once it has been
+    Haiku(Box<Haiku>, oneshot::Sender<Option<i64>>),
+    /// A vote to count, and where to send the haiku's votes once it has been
+    Vote(GuildId, i64, UserId, oneshot::Sender<Option<i64>>),
+}
+
+pub struct SaveQueue {
+    sender: Mutex<Option<SyncSender<Write>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SaveQueue {
+    /// Start the writer thread. Once `capacity` writes are waiting, anything queueing another
+    /// waits for room, and each batch holds at most `max_batch` writes.
+    pub fn start(writer: impl BatchWriter, capacity: usize, max_batch: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let max_batch = max_batch.max(1);
+        let writer = thread::Builder::new()
+            .name("save-queue".to_owned())
+            .spawn(move || write_batches(receiver, writer, max_batch))
+            .expect("Failed to start save queue writer");
+        SaveQueue {
+            sender: Mutex::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+        }
+    }
+
+    /// Queue a write, returning whether it was queued. Writes aren't taken once the queue has
+    /// been shut down or its writer has stopped.
+    fn send(&self, write: Write) -> bool {
+        // Cloned so the lock isn't held while waiting for room in the queue
+        let sender = self
+            .sender
+            .lock()
+            .expect("Save queue lock poisoned")
+            .clone();
+        match sender {
+            Some(sender) => sender.send(write).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Queue a haiku to be saved, returning where its id is sent once the batch it's in has been
+    /// written, or None if the queue isn't taking writes
+    pub fn queue_haiku(&self, haiku: &Haiku) -> Option<oneshot::Receiver<Option<i64>>> {
+        let (reply, id) = oneshot::channel();
+        if !self.send(Write::Haiku(Box::new(haiku.clone()), reply)) {
+            println!("Save queue has stopped, so a haiku could not be saved");
+            return None;
+        }
+        Some(id)
+    }
+
+    /// Save a haiku, waiting until the batch it's in has been written. None if it was already
+    /// saved, or the queue isn't taking writes.
+    pub fn save_haiku(&self, haiku: &Haiku) -> Option<i64> {
+        // Saves come from async tasks through the synchronous repository, so the runtime moves
+        // this thread's other tasks elsewhere while it waits for room in the queue and the batch
+        task::block_in_place(|| {
+            let id = self.queue_haiku(haiku)?;
+            // The writer drops the reply if the batch failed
+            id.blocking_recv().ok().flatten()
+        })
+    }
+
+    /// Count a vote, waiting until the batch it's in has been written. Returns the haiku's votes,
+    /// or None if the voter had already voted for it or the queue isn't taking writes.
+    pub fn vote_for_haiku(
+        &self,
+        server_id: GuildId,
+        haiku_id: i64,
+        user_id: UserId,
+    ) -> Option<i64> {
+        task::block_in_place(|| {
+            let (reply, votes) = oneshot::channel();
+            if !self.send(Write::Vote(server_id, haiku_id, user_id, reply)) {
+                println!(
+                    "Save queue has stopped, so a vote for haiku {} in server {} was lost",
+                    haiku_id, server_id
+                );
+                return None;
+            }
+            votes.blocking_recv().ok().flatten()
+        })
+    }
+
+    /// Stop taking writes and wait for everything already queued to be written
+    pub fn shutdown(&self) {
+        self.sender.lock().expect("Save queue lock poisoned").take();
+        let writer = self.writer.lock().expect("Save queue lock poisoned").take();
+        if let Some(writer) = writer {
+            if writer.join().is_err() {
+                println!("Save queue writer panicked while shutting down");
+            }
+        }
+    }
+}
+
+impl Drop for SaveQueue {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Write whatever has queued up while the previous batch was being written, until every sender
+/// has gone and the queue is empty
+fn write_batches(receiver: Receiver<Write>, mut writer: impl BatchWriter, max_batch: usize) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+        while batch.len() < max_batch {
+            match receiver.try_recv() {
+                Ok(write) => batch.push(write),
+                Err(_) => break,
+            }
+        }
+        // A failed batch only fails the saves in it, rather than stopping the writer for good
+        let result = panic::catch_unwind(AssertUnwindSafe(|| write_batch(&mut writer, batch)));
+        if result.is_err() {
+            println!("Failed to write a batch of haikus and votes");
+        }
+    }
+}
+
+fn write_batch(writer: &mut impl BatchWriter, batch: Vec<Write>) {
+    let mut haikus = Vec::new();
+    let mut haiku_replies = Vec::new();
+    let mut votes = Vec::new();
+    let mut vote_replies = Vec::new();
+    for write in batch {
+        match write {
+            Write::Haiku(haiku, reply) => {
+                haikus.push(*haiku);
+                haiku_replies.push(reply);
+            }
+            Write::Vote(server_id, haiku_id, user_id, reply) => {
+                votes.push((server_id, haiku_id, user_id));
+                vote_replies.push(reply);
+            }
+        }
+    }
+    // Whoever's waiting only goes away if it panicked, in which case nobody needs the reply
+    if !haikus.is_empty() {
+        let ids = writer.save_haikus(&haikus);
+        for (reply, id) in haiku_replies.into_iter().zip(ids) {
+            let _ = reply.send(id);
+        }
+    }
+    if !votes.is_empty() {
+        let counts = writer.add_votes(&votes);
+        for (reply, count) in vote_replies.into_iter().zip(counts) {
+            let _ = reply.send(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BatchWriter, SaveQueue};
+    use crate::{models::Haiku, test_support::HaikuBuilder};
+    use serenity::model::id::{GuildId, UserId};
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    #[derive(Default)]
+    struct Written {
+        haikus: i64,
+        votes: Vec<(GuildId, i64, UserId)>,
+    }
+
+    struct TestWriter(Arc<Mutex<Written>>);
+
+    impl BatchWriter for TestWriter {
+        fn save_haikus(&mut self, haikus: &[Haiku]) -> Vec<Option<i64>> {
+            let mut written = self.0.lock().unwrap();
+            let first = written.haikus + 1;
+            written.haikus += haikus.len() as i64;
+            (first..=written.haikus).map(Some).collect()
+        }
+
+        fn add_votes(&mut self, votes: &[(GuildId, i64, UserId)]) -> Vec<Option<i64>> {
+            let mut written = self.0.lock().unwrap();
+            votes
+                .iter()
+                .map(|vote| {
+                    if written.votes.contains(vote) {
+                        return None;
+                    }
+                    written.votes.push(*vote);
+                    Some(
+                        written
+                            .votes
+                            .iter()
+                            .filter(|(_, id, _)| *id == vote.1)
+                            .count() as i64,
+                    )
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_save_queue() {
+        let written = Arc::new(Mutex::new(Written::default()));
+        let queue = Arc::new(SaveQueue::start(TestWriter(written.clone()), 2, 4));
+
+        let savers = (0..8)
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.save_haiku(&HaikuBuilder::new().build()))
+            })
+            .collect::<Vec<_>>();
+        let mut ids = savers
+            .into_iter()
+            .map(|saver| saver.join().unwrap().unwrap())
+            .collect::<Vec<i64>>();
+        ids.sort_unstable();
+        assert_eq!(ids, (1..=8).collect::<Vec<i64>>());
+
+        let voters = (1..=3)
+            .map(|user| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.vote_for_haiku(GuildId(1), 5, UserId(user)))
+            })
+            .collect::<Vec<_>>();
+        let mut counts = voters
+            .into_iter()
+            .map(|voter| voter.join().unwrap().unwrap())
+            .collect::<Vec<i64>>();
+        counts.sort_unstable();
+        assert_eq!(counts, vec![1, 2, 3]);
+        assert_eq!(queue.vote_for_haiku(GuildId(1), 5, UserId(1)), None);
+        assert_eq!(queue.vote_for_haiku(GuildId(1), 6, UserId(1)), Some(1));
+
+        queue.shutdown();
+        // Once it's shut down, writes fail rather than panicking
+        assert_eq!(queue.save_haiku(&HaikuBuilder::new().build()), None);
+        assert_eq!(queue.vote_for_haiku(GuildId(1), 6, UserId(2)), None);
+        assert_eq!(written.lock().unwrap().votes.len(), 4);
+    }
+}
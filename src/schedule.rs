@@ -0,0 +1,246 @@
+//! Working out when periods start and scheduled work is due in each server's own timezone
+
+use crate::repository::HaikuRepository;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serenity::model::id::GuildId;
+use std::ops::Range;
+
+/// Work the bot does every so often, whose last run is saved so that restarting doesn't put it off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Job {
+    PurgeDepartedGuilds,
+    EnforceRetention,
+    Maintenance,
+}
+
+impl Job {
+    pub fn name(self) -> &'static str {
+        match self {
+            Job::PurgeDepartedGuilds => "purge_departed_guilds",
+            Job::EnforceRetention => "enforce_retention",
+            Job::Maintenance => "maintenance",
+        }
+    }
+}
+
+/// When a job is next due: an interval after it last ran, or straight away if it's never run or
+/// that time has passed, e.g. while the bot was down. Several missed runs are caught up with once.
+pub fn next_run(
+    last_run: Option<DateTime<Utc>>,
+    interval: Duration,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    match last_run {
+        Some(last_run) => (last_run + interval).max(now),
+        None => now,
+    }
+}
+
+/// Timezones are named like the IANA database does, e.g. "Europe/Berlin" or "UTC"
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// The server's timezone, UTC unless it's been set with /config timezone
+pub fn server_timezone(repository: &dyn HaikuRepository, server_id: GuildId) -> Tz {
+    repository
+        .get_server_config(server_id)
+        .and_then(|config| parse_timezone(&config.timezone))
+        .unwrap_or(Tz::UTC)
+}
+
+/// When a day starts in a timezone. Where daylight saving skips midnight the day starts at the
+/// first time that exists, and where midnight happens twice it starts at the first one.
+pub fn local_midnight(timezone: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let mut time = date.and_hms(0, 0, 0);
+    loop {
+        if let Some(start) = timezone.from_local_datetime(&time).earliest() {
+            return start.with_timezone(&Utc);
+        }
+        time += Duration::minutes(15);
+    }
+}
+
+fn local_date(now: DateTime<Utc>, timezone: Tz) -> NaiveDate {
+    now.with_timezone(&timezone).naive_local().date()
+}
+
+/// The start of the week (Monday) a time falls in
+pub fn week_start(now: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+    let today = local_date(now, timezone);
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    local_midnight(timezone, monday)
+}
+
+/// The start of the month a time falls in, which the monthly leaderboard counts from
+pub fn month_start(now: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+    let today = local_date(now, timezone);
+    local_midnight(
+        timezone,
+        NaiveDate::from_ymd(today.year(), today.month(), 1),
+    )
+}
+
+/// The start of the month after the one a time falls in
+pub fn next_month_start(now: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+    let today = local_date(now, timezone);
+    let (year, month) = match today.month() {
+        12 => (today.year() + 1, 1),
+        month => (today.year(), month + 1),
+    };
+    local_midnight(timezone, NaiveDate::from_ymd(year, month, 1))
+}
+
+/// The start of the year a time falls in
+pub fn year_start(now: DateTime<Utc>, timezone: Tz) -> DateTime<Utc> {
+    let today = local_date(now, timezone);
+    local_midnight(timezone, NaiveDate::from_ymd(today.year(), 1, 1))
+}
+
+/// A whole year, or one of its months (1 to 12), from its first midnight up to the next period's.
+/// None if there's no such month.
+pub fn calendar_period(
+    year: i32,
+    month: Option<u32>,
+    timezone: Tz,
+) -> Option<Range<DateTime<Utc>>> {
+    let start = NaiveDate::from_ymd_opt(year, month.unwrap_or(1), 1)?;
+    let end = match month {
+        Some(12) | None => NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+        Some(month) => NaiveDate::from_ymd_opt(year, month + 1, 1)?,
+    };
+    Some(local_midnight(timezone, start)..local_midnight(timezone, end))
+}
+
+/// When monthly work should next run: at the soonest month start in any of the timezones, or
+/// after the interval if that comes first
+pub fn next_monthly_run(
+    now: DateTime<Utc>,
+    timezones: impl IntoIterator<Item = Tz>,
+    interval: Duration,
+) -> DateTime<Utc> {
+    timezones
+        .into_iter()
+        .map(|timezone| next_month_start(now, timezone))
+        .fold(now + interval, DateTime::min)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        calendar_period, local_midnight, month_start, next_month_start, next_monthly_run, next_run,
+        parse_timezone, week_start, year_start,
+    };
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    #[test]
+    fn test_next_run() {
+        let now = Utc.ymd(2021, 3, 17).and_hms(12, 0, 0);
+        let day = Duration::days(1);
+        assert_eq!(next_run(None, day, now), now);
+        assert_eq!(
+            next_run(Some(now - Duration::hours(6)), day, now),
+            now + Duration::hours(18)
+        );
+        // Missed while the bot was down
+        assert_eq!(next_run(Some(now - Duration::days(3)), day, now), now);
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("Europe/Berlin"), Some(Tz::Europe__Berlin));
+        assert_eq!(parse_timezone(" UTC "), Some(Tz::UTC));
+        assert_eq!(parse_timezone("Mars/Olympus_Mons"), None);
+        assert_eq!(parse_timezone(""), None);
+    }
+
+    #[test]
+    fn test_month_start() {
+        assert_eq!(
+            month_start(Utc.ymd(2021, 3, 17).and_hms(12, 30, 0), Tz::UTC),
+            Utc.ymd(2021, 3, 1).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_period_starts() {
+        // A Wednesday evening in New York, but already Thursday the 1st in UTC
+        let now = Utc.ymd(2021, 4, 1).and_hms(2, 0, 0);
+        let new_york = Tz::America__New_York;
+        assert_eq!(
+            month_start(now, Tz::UTC),
+            Utc.ymd(2021, 4, 1).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            month_start(now, new_york),
+            Utc.ymd(2021, 3, 1).and_hms(5, 0, 0)
+        );
+        // Daylight saving started in between
+        assert_eq!(
+            next_month_start(now, new_york),
+            Utc.ymd(2021, 4, 1).and_hms(4, 0, 0)
+        );
+        assert_eq!(
+            next_month_start(Utc.ymd(2021, 12, 15).and_hms(0, 0, 0), Tz::UTC),
+            Utc.ymd(2022, 1, 1).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            week_start(now, Tz::UTC),
+            Utc.ymd(2021, 3, 29).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            week_start(now, new_york),
+            Utc.ymd(2021, 3, 29).and_hms(4, 0, 0)
+        );
+        assert_eq!(
+            year_start(now, new_york),
+            Utc.ymd(2021, 1, 1).and_hms(5, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_calendar_period() {
+        assert_eq!(
+            calendar_period(2021, Some(3), Tz::UTC),
+            Some(Utc.ymd(2021, 3, 1).and_hms(0, 0, 0)..Utc.ymd(2021, 4, 1).and_hms(0, 0, 0))
+        );
+        assert_eq!(
+            calendar_period(2021, Some(12), Tz::Europe__Berlin),
+            Some(Utc.ymd(2021, 11, 30).and_hms(23, 0, 0)..Utc.ymd(2021, 12, 31).and_hms(23, 0, 0))
+        );
+        assert_eq!(
+            calendar_period(2021, None, Tz::UTC),
+            Some(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0)..Utc.ymd(2022, 1, 1).and_hms(0, 0, 0))
+        );
+        assert_eq!(calendar_period(2021, Some(13), Tz::UTC), None);
+        assert_eq!(calendar_period(2021, Some(0), Tz::UTC), None);
+    }
+
+    #[test]
+    fn test_local_midnight() {
+        // Clocks went from midnight straight to 1am in São Paulo
+        assert_eq!(
+            local_midnight(Tz::America__Sao_Paulo, NaiveDate::from_ymd(2018, 11, 4)),
+            Utc.ymd(2018, 11, 4).and_hms(3, 0, 0)
+        );
+        assert_eq!(
+            local_midnight(Tz::America__Sao_Paulo, NaiveDate::from_ymd(2018, 11, 5)),
+            Utc.ymd(2018, 11, 5).and_hms(2, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_next_monthly_run() {
+        let now = Utc.ymd(2021, 3, 31).and_hms(21, 30, 0);
+        let hour = Duration::hours(1);
+        assert_eq!(next_monthly_run(now, vec![], hour), now + hour);
+        assert_eq!(next_monthly_run(now, vec![Tz::UTC], hour), now + hour);
+        // It's nearly midnight in Berlin
+        assert_eq!(
+            next_monthly_run(now, vec![Tz::UTC, Tz::Europe__Berlin], hour),
+            Utc.ymd(2021, 3, 31).and_hms(22, 0, 0)
+        );
+    }
+}
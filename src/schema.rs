@@ -1,5 +1,68 @@
 table! {
     haikus (id, server) {
+        id -> Int8,
+        channel -> Int8,
+        server -> Int8,
+        timestamp -> Timestamp,
+        flagged -> Bool,
+        last_shown_at -> Nullable<Timestamp>,
+        votes -> Int8,
+        source_message -> Nullable<Int8>,
+        source_line -> Nullable<Int8>,
+        sensitive -> Bool,
+        mirror_message -> Nullable<Int8>,
+        announcement_message -> Nullable<Int8>,
+        number -> Int8,
+    }
+}
+
+table! {
+    haiku_lines (server, haiku_id, position) {
+        server -> Int8,
+        haiku_id -> Int8,
+        position -> Int8,
+        author -> Int8,
+        content -> Text,
+        message_id -> Nullable<Int8>,
+        sent_at -> Timestamp,
+        syllables -> Int8,
+        author_name -> Nullable<Text>,
+    }
+}
+
+table! {
+    server_configs (server) {
+        server -> Int8,
+        joined_at -> Timestamp,
+        left_at -> Nullable<Timestamp>,
+        locale -> Text,
+        last_milestone -> Int8,
+        welcome_enabled -> Bool,
+        random_weighting -> Text,
+        laureate_role -> Nullable<Int8>,
+        laureate -> Nullable<Int8>,
+        retention_years -> Nullable<Int8>,
+        retention_action -> Text,
+        global_stats_enabled -> Bool,
+        announcement_template -> Nullable<Text>,
+        manual_save_emoji -> Nullable<Text>,
+        manual_save_role -> Nullable<Int8>,
+        detect_bot_messages -> Bool,
+        detect_webhook_messages -> Bool,
+        bridge_pattern -> Nullable<Text>,
+        same_author_only -> Bool,
+        error_channel -> Nullable<Int8>,
+        timezone -> Text,
+        plain_text -> Bool,
+        archive_channel -> Nullable<Int8>,
+        archive_webhook -> Nullable<Int8>,
+        archive_webhook_token -> Nullable<Text>,
+        public_feeds -> Bool,
+    }
+}
+
+table! {
+    archived_haikus (id, server) {
         id -> Int8,
         channel -> Int8,
         server -> Int8,
@@ -10,5 +73,137 @@ table! {
         message_0 -> Text,
         message_1 -> Text,
         message_2 -> Text,
+        archived_at -> Timestamp,
+    }
+}
+
+table! {
+    command_usage (id) {
+        id -> Int8,
+        server -> Int8,
+        command -> Text,
+        used_at -> Timestamp,
+        latency_ms -> Int8,
+    }
+}
+
+table! {
+    user_preferences (user_id) {
+        user_id -> Int8,
+        notify_detected -> Bool,
+        notify_bookmarked -> Bool,
+        notify_contest_win -> Bool,
+        notify_vote_threshold -> Bool,
+        plain_text -> Nullable<Bool>,
+    }
+}
+
+table! {
+    detection_disabled_channels (server, channel) {
+        server -> Int8,
+        channel -> Int8,
+    }
+}
+
+table! {
+    haiku_edits (id) {
+        id -> Int8,
+        server -> Int8,
+        haiku_id -> Int8,
+        position -> Int8,
+        previous -> Text,
+        content -> Text,
+        editor -> Int8,
+        edited_at -> Timestamp,
+    }
+}
+
+table! {
+    haiku_votes (server, haiku_id, user_id) {
+        server -> Int8,
+        haiku_id -> Int8,
+        user_id -> Int8,
+        voted_at -> Timestamp,
+    }
+}
+
+table! {
+    haiku_numbers (server) {
+        server -> Int8,
+        last_number -> Int8,
+    }
+}
+
+table! {
+    history_scans (channel) {
+        channel -> Int8,
+        server -> Int8,
+        before_message -> Nullable<Int8>,
+        until_time -> Timestamp,
+        messages -> Int8,
+        haikus -> Int8,
+    }
+}
+
+table! {
+    public_feeds (server, user_id) {
+        server -> Int8,
+        user_id -> Int8,
+    }
+}
+
+table! {
+    quiz_scores (server, user_id) {
+        server -> Int8,
+        user_id -> Int8,
+        correct -> Int8,
+        answered -> Int8,
+    }
+}
+
+table! {
+    scheduled_jobs (name) {
+        name -> Text,
+        last_run_at -> Timestamp,
+    }
+}
+
+table! {
+    word_overrides (server, word) {
+        server -> Int8,
+        word -> Text,
+        syllables -> Int8,
+    }
+}
+
+table! {
+    api_tokens (id) {
+        id -> Int8,
+        server -> Int8,
+        name -> Text,
+        token_hash -> Text,
+        scopes -> Array<Text>,
+        created_by -> Int8,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
     }
 }
+
+allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    archived_haikus,
+    command_usage,
+    detection_disabled_channels,
+    haiku_edits,
+    haiku_lines,
+    haiku_numbers,
+    haiku_votes,
+    haikus,
+    history_scans,
+    public_feeds,
+    quiz_scores,
+    scheduled_jobs,
+    server_configs,
+    user_preferences,
+    word_overrides,
+);
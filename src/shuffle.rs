@@ -0,0 +1,99 @@
+//! Picking random haikus without showing the same few over and over
+
+use crate::{database::RandomWeighting, models::Haiku, repository::HaikuRepository};
+use chrono::{DateTime, Utc};
+use serenity::model::id::GuildId;
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Range,
+};
+
+/// How many recently shown haikus are remembered for each server
+const HISTORY_SIZE: usize = 100;
+
+/// The haikus recently picked at random in each server, so they aren't picked again until the
+/// rest of the server's haikus have had a turn
+#[derive(Default)]
+pub struct ShuffleHistory {
+    served: HashMap<GuildId, VecDeque<i64>>,
+}
+
+impl ShuffleHistory {
+    pub fn new() -> Self {
+        ShuffleHistory::default()
+    }
+
+    /// A random haiku that hasn't been shown recently, weighted as configured for the server and
+    /// optionally only from those written in a stretch of time. Once every haiku has been shown,
+    /// the history starts over, avoiding only the haiku that was shown last.
+    pub fn next_haiku(
+        &mut self,
+        repository: &dyn HaikuRepository,
+        server_id: GuildId,
+        written: Option<&Range<DateTime<Utc>>>,
+    ) -> Option<(i64, Haiku)> {
+        let weighting = repository
+            .get_server_config(server_id)
+            .and_then(|config| RandomWeighting::from_code(&config.random_weighting))
+            .unwrap_or(RandomWeighting::Uniform);
+        let served = self.served.entry(server_id).or_default();
+        let exclude = served.iter().copied().collect::<Vec<i64>>();
+        let haiku = repository
+            .get_random_haiku(server_id, &exclude, weighting, written)
+            .or_else(|| {
+                let last = served.back().copied().into_iter().collect::<Vec<i64>>();
+                served.clear();
+                repository.get_random_haiku(server_id, &last, weighting, written)
+            })
+            // A server (or stretch of time) with a single haiku can only ever show that one
+            .or_else(|| repository.get_random_haiku(server_id, &[], weighting, written));
+        if let Some((id, _)) = &haiku {
+            repository.mark_haiku_shown(server_id, *id);
+            served.push_back(*id);
+            if served.len() > HISTORY_SIZE {
+                served.pop_front();
+            }
+        }
+        haiku
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ShuffleHistory;
+    use crate::{
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use serenity::model::id::GuildId;
+    use std::collections::HashSet;
+
+    fn save_haikus(repository: &MemoryRepository, server: u64, count: usize) {
+        for _ in 0..count {
+            repository.save_haiku(&HaikuBuilder::new().server(server).build());
+        }
+    }
+
+    #[test]
+    fn test_every_haiku_is_shown_before_repeating() {
+        let repository = MemoryRepository::new();
+        save_haikus(&repository, 1, 5);
+        let mut history = ShuffleHistory::new();
+        let mut next = || history.next_haiku(&repository, GuildId(1), None).unwrap().0;
+
+        let first_cycle = (0..5).map(|_| next()).collect::<Vec<i64>>();
+        assert_eq!(first_cycle.iter().collect::<HashSet<_>>().len(), 5);
+        // The next cycle doesn't start with the haiku that ended the last one
+        assert_ne!(next(), first_cycle[4]);
+    }
+
+    #[test]
+    fn test_small_servers() {
+        let repository = MemoryRepository::new();
+        save_haikus(&repository, 1, 1);
+        let mut history = ShuffleHistory::new();
+        assert!(history.next_haiku(&repository, GuildId(1), None).is_some());
+        assert!(history.next_haiku(&repository, GuildId(1), None).is_some());
+        assert!(history.next_haiku(&repository, GuildId(2), None).is_none());
+    }
+}
@@ -0,0 +1,72 @@
+//! Haikus for tests, so each test only spells out what it cares about.
+//!
+//! The library, the bot and the integration tests each build this file into their own tests, so
+//! it only reaches for `crate::models` and leaves some of the builder unused in each of them.
+#![allow(dead_code)]
+
+use crate::models::{Haiku, HaikuLine};
+use chrono::{DateTime, TimeZone, Utc};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+
+/// A haiku found in channel 10 of server 1, written by user 100 at midnight on 1 January 2021
+pub struct HaikuBuilder {
+    haiku: Haiku,
+}
+
+impl HaikuBuilder {
+    pub fn new() -> Self {
+        let line = |content: &str| HaikuLine {
+            author: UserId(100),
+            author_name: None,
+            content: content.to_owned(),
+        };
+        HaikuBuilder {
+            haiku: Haiku {
+                lines: [line("a"), line("b"), line("c")],
+                timestamp: Utc.ymd(2021, 1, 1).and_hms(0, 0, 0),
+                channel: ChannelId(10),
+                server: GuildId(1),
+                source: None,
+            },
+        }
+    }
+
+    pub fn server(mut self, server: u64) -> Self {
+        self.haiku.server = GuildId(server);
+        self
+    }
+
+    pub fn channel(mut self, channel: u64) -> Self {
+        self.haiku.channel = ChannelId(channel);
+        self
+    }
+
+    pub fn lines(mut self, lines: [&str; 3]) -> Self {
+        for (line, content) in self.haiku.lines.iter_mut().zip(lines.iter()) {
+            line.content = (*content).to_owned();
+        }
+        self
+    }
+
+    /// Who wrote every line
+    pub fn author(self, author: u64) -> Self {
+        self.authors([author; 3])
+    }
+
+    /// Who wrote each line
+    pub fn authors(mut self, authors: [u64; 3]) -> Self {
+        for (line, author) in self.haiku.lines.iter_mut().zip(authors.iter()) {
+            line.author = UserId(*author);
+        }
+        self
+    }
+
+    pub fn at(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.haiku.timestamp = timestamp;
+        self
+    }
+
+    pub fn build(self) -> Haiku {
+        self.haiku
+    }
+}
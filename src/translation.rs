@@ -0,0 +1,102 @@
+use serde_json::{json, Value};
+use serenity::async_trait;
+use std::env;
+
+#[derive(Debug)]
+pub enum TranslationError {
+    NotConfigured,
+    Request(reqwest::Error),
+    InvalidResponse,
+}
+
+impl From<reqwest::Error> for TranslationError {
+    fn from(err: reqwest::Error) -> Self {
+        TranslationError::Request(err)
+    }
+}
+
+#[async_trait]
+pub trait TranslationProvider {
+    /// Translate text into the language with the given code (e.g. "fr", "pt-BR"),
+    /// auto-detecting the source language
+    async fn translate(&self, text: &str, language: &str) -> Result<String, TranslationError>;
+}
+
+/// Select the translation provider named by $TRANSLATION_PROVIDER.
+/// Translations are disabled if it is not set.
+pub fn provider_from_env() -> Result<Box<dyn TranslationProvider + Send + Sync>, TranslationError> {
+    let api_url = env::var("TRANSLATION_API_URL").ok();
+    let api_key = env::var("TRANSLATION_API_KEY").ok();
+    match env::var("TRANSLATION_PROVIDER").as_deref() {
+        Ok("libretranslate") => Ok(Box::new(LibreTranslate {
+            api_url: api_url.unwrap_or("https://libretranslate.com".to_owned()),
+            api_key,
+        })),
+        Ok("deepl") => Ok(Box::new(DeepL {
+            api_url: api_url.unwrap_or("https://api-free.deepl.com".to_owned()),
+            api_key: api_key.ok_or(TranslationError::NotConfigured)?,
+        })),
+        _ => Err(TranslationError::NotConfigured),
+    }
+}
+
+/// https://libretranslate.com, or a self-hosted instance
+pub struct LibreTranslate {
+    api_url: String,
+    api_key: Option<String>,
+}
+
+#[async_trait]
+impl TranslationProvider for LibreTranslate {
+    async fn translate(&self, text: &str, language: &str) -> Result<String, TranslationError> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/translate", self.api_url.trim_end_matches('/')))
+            .json(&json!({
+                "q": text,
+                "source": "auto",
+                "target": language,
+                "format": "text",
+                "api_key": self.api_key,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+        response["translatedText"]
+            .as_str()
+            .map(|translation| translation.to_owned())
+            .ok_or(TranslationError::InvalidResponse)
+    }
+}
+
+/// https://www.deepl.com/docs-api
+pub struct DeepL {
+    api_url: String,
+    api_key: String,
+}
+
+#[async_trait]
+impl TranslationProvider for DeepL {
+    async fn translate(&self, text: &str, language: &str) -> Result<String, TranslationError> {
+        let response = reqwest::Client::new()
+            .post(format!(
+                "{}/v2/translate",
+                self.api_url.trim_end_matches('/')
+            ))
+            .header("Authorization", format!("DeepL-Auth-Key {}", self.api_key))
+            .json(&json!({
+                "text": [text],
+                "target_lang": language.to_uppercase(),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+        response["translations"][0]["text"]
+            .as_str()
+            .map(|translation| translation.to_owned())
+            .ok_or(TranslationError::InvalidResponse)
+    }
+}
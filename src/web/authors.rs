@@ -0,0 +1,524 @@
+use super::error;
+use crate::{
+    database::{HaikuCursor, HaikuFilter, KeywordMatch, SearchTerms},
+    framework::spec::Choice,
+    models::Haiku,
+    repository::HaikuRepository,
+};
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use serenity::{
+    cache::Cache,
+    model::id::{GuildId, UserId},
+};
+use sha2::{Digest, Sha256};
+use std::{convert::Infallible, sync::Arc};
+use warp::{http::StatusCode, reply::Response, Filter, Rejection, Reply};
+
+/// How many haikus are listed at once, newest first, unless a limit is asked for
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+
+/// GET /guilds/{id}/authors/{user}/haikus - an author's haikus as JSON
+/// GET /guilds/{id}/authors/{user}/feed.rss - the same haikus as an RSS feed
+///
+/// Only the haikus someone wrote on their own are listed, and only once the server has allowed
+/// feeds with /config feeds and they've opted in there with /feed. Everyone else gets a 404.
+/// Both take the filters in [ListQuery], and send an ETag so clients can ask with If-None-Match
+/// whether anything has changed.
+pub fn routes(
+    repository: Arc<dyn HaikuRepository>,
+    cache: Arc<Cache>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let haikus = {
+        let repository = repository.clone();
+        let cache = cache.clone();
+        warp::path!("guilds" / u64 / "authors" / u64 / "haikus")
+            .and(warp::get())
+            .and(warp::query::<ListQuery>())
+            .and(warp::header::optional::<String>("if-none-match"))
+            .and_then(move |server, author, query, if_none_match| {
+                list_haikus(
+                    repository.clone(),
+                    cache.clone(),
+                    List {
+                        server: GuildId(server),
+                        author: UserId(author),
+                        format: Format::Json,
+                        query,
+                        if_none_match,
+                    },
+                )
+            })
+    };
+    let feed = warp::path!("guilds" / u64 / "authors" / u64 / "feed.rss")
+        .and(warp::get())
+        .and(warp::query::<ListQuery>())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(move |server, author, query, if_none_match| {
+            list_haikus(
+                repository.clone(),
+                cache.clone(),
+                List {
+                    server: GuildId(server),
+                    author: UserId(author),
+                    format: Format::Rss,
+                    query,
+                    if_none_match,
+                },
+            )
+        });
+    haikus.or(feed)
+}
+
+enum Format {
+    Json,
+    Rss,
+}
+
+/// The query string, e.g. `?q=frog+-pond&since=2021-03-01&limit=10`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ListQuery {
+    /// Words to look for, as in /search. Haikus with a word starting with - are left out.
+    q: Option<String>,
+    /// "any" (the default) for haikus with any of the words, "all" for those with every one
+    #[serde(rename = "match")]
+    keyword_match: Option<String>,
+    /// Only haikus written on or after this date, or RFC 3339 time
+    since: Option<String>,
+    /// Only haikus written before this date, or RFC 3339 time
+    until: Option<String>,
+    /// Where the last page left off, from its next_cursor
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+/// A request for one of the lists
+struct List {
+    server: GuildId,
+    author: UserId,
+    format: Format,
+    query: ListQuery,
+    if_none_match: Option<String>,
+}
+
+/// Dates are midnight UTC
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+        })
+}
+
+/// Cursors are opaque to clients, but are the last haiku's time in microseconds and its id
+fn encode_cursor(cursor: HaikuCursor) -> String {
+    let micros = cursor.timestamp.timestamp() * 1_000_000
+        + i64::from(cursor.timestamp.timestamp_subsec_micros());
+    format!("{}_{}", micros, cursor.id)
+}
+
+fn decode_cursor(cursor: &str) -> Option<HaikuCursor> {
+    let (micros, id) = cursor.split_once('_')?;
+    let micros = micros.parse::<i64>().ok()?;
+    Some(HaikuCursor {
+        timestamp: Utc
+            .timestamp_opt(
+                micros.div_euclid(1_000_000),
+                (micros.rem_euclid(1_000_000) * 1000) as u32,
+            )
+            .single()?,
+        id: id.parse().ok()?,
+    })
+}
+
+/// The filter and page size asked for, or what's wrong with the query
+fn parse_query(query: &ListQuery) -> Result<(HaikuFilter, i64), &'static str> {
+    let mode = match &query.keyword_match {
+        Some(mode) => KeywordMatch::from_value(mode).ok_or("match must be \"any\" or \"all\"")?,
+        None => KeywordMatch::Any,
+    };
+    let time = |value: &Option<String>| match value {
+        Some(value) => parse_time(value).map(Some),
+        None => Some(None),
+    };
+    let filter = HaikuFilter {
+        terms: query.q.as_deref().map(|q| SearchTerms::parse(q, mode)),
+        since: time(&query.since).ok_or("since must be a date or RFC 3339 time")?,
+        until: time(&query.until).ok_or("until must be a date or RFC 3339 time")?,
+        after: match &query.cursor {
+            Some(cursor) => Some(decode_cursor(cursor).ok_or("invalid cursor")?),
+            None => None,
+        },
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err("limit must be from 1 to 100");
+    }
+    Ok((filter, limit))
+}
+
+/// An ETag for a page, from what was asked for and what was found rather than the rendered body,
+/// so it can be checked before rendering. It changes when a haiku on the page is added, deleted or
+/// edited, or the page after it starts somewhere else.
+fn etag(list: &List, haikus: &[(i64, Haiku)], next_cursor: Option<&str>) -> String {
+    let query = &list.query;
+    let mut hasher = Sha256::new();
+    let kind = match list.format {
+        Format::Json => "json",
+        Format::Rss => "rss",
+    };
+    for field in [
+        Some(kind),
+        query.q.as_deref(),
+        query.keyword_match.as_deref(),
+        query.since.as_deref(),
+        query.until.as_deref(),
+        query.cursor.as_deref(),
+        next_cursor,
+    ]
+    .iter()
+    {
+        // Length prefixed, so adjacent fields can't run into each other
+        let field = field.unwrap_or_default();
+        hasher.update(format!("{}:{}\n", field.len(), field));
+    }
+    hasher.update(format!("{:?}\n", query.limit));
+    let latest = haikus.iter().map(|(_, haiku)| haiku.timestamp).max();
+    hasher.update(format!("{:?}\n", latest.map(|time| time.to_rfc3339())));
+    for (id, haiku) in haikus {
+        hasher.update(format!("{}\n", id));
+        for line in haiku.lines.iter() {
+            hasher.update(format!("{}:{}\n", line.content.len(), line.content));
+        }
+    }
+    format!("\"{:x}\"", hasher.finalize())
+}
+
+/// Whether an If-None-Match header lists the ETag, so the client already has the response
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+async fn list_haikus(
+    repository: Arc<dyn HaikuRepository>,
+    cache: Arc<Cache>,
+    list: List,
+) -> Result<Response, Infallible> {
+    let (filter, limit) = match parse_query(&list.query) {
+        Ok(parsed) => parsed,
+        Err(message) => return Ok(error(StatusCode::BAD_REQUEST, message)),
+    };
+    let (server, author) = (list.server, list.author);
+    let haikus = tokio::task::spawn_blocking(move || {
+        let allowed = repository
+            .get_server_config(server)
+            .map_or(false, |config| config.public_feeds);
+        if allowed && repository.has_public_feed(server, author) {
+            // One more than the page, to tell whether there's another page after it
+            Some(repository.get_public_haikus_by_author(server, author, &filter, limit + 1))
+        } else {
+            None
+        }
+    })
+    .await
+    .unwrap_or(None);
+    let mut haikus = match haikus {
+        Some(haikus) => haikus,
+        None => return Ok(error(StatusCode::NOT_FOUND, "not found")),
+    };
+    let next_cursor = if haikus.len() as i64 > limit {
+        haikus.truncate(limit as usize);
+        haikus.last().map(|(id, haiku)| {
+            encode_cursor(HaikuCursor {
+                timestamp: haiku.timestamp,
+                id: *id,
+            })
+        })
+    } else {
+        None
+    };
+    let etag = etag(&list, &haikus, next_cursor.as_deref());
+    if let Some(if_none_match) = &list.if_none_match {
+        if etag_matches(if_none_match, &etag) {
+            return Ok(warp::reply::with_header(
+                warp::reply::with_status(warp::reply(), StatusCode::NOT_MODIFIED),
+                "ETag",
+                etag,
+            )
+            .into_response());
+        }
+    }
+    let name = cache
+        .user(author)
+        .await
+        .map(|user| user.name)
+        .unwrap_or_else(|| author.to_string());
+    let (body, content_type) = match list.format {
+        Format::Json => (
+            haikus_json(server, author, &name, &haikus, next_cursor.as_deref()).to_string(),
+            "application/json",
+        ),
+        Format::Rss => (
+            rss_feed(server, &name, &haikus),
+            "application/rss+xml; charset=utf-8",
+        ),
+    };
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(body, "Content-Type", content_type),
+        "ETag",
+        etag,
+    )
+    .into_response())
+}
+
+/// Where a haiku was posted in Discord, for people who can see the server
+fn haiku_link(server: GuildId, haiku: &Haiku) -> String {
+    match &haiku.source {
+        Some(source) => format!(
+            "https://discord.com/channels/{}/{}/{}",
+            server, haiku.channel, source.messages[2]
+        ),
+        None => format!("https://discord.com/channels/{}/{}", server, haiku.channel),
+    }
+}
+
+/// Ids are strings, as Discord gives them, since they don't fit in a JavaScript number. The next
+/// cursor is null on the last page.
+fn haikus_json(
+    server: GuildId,
+    author: UserId,
+    name: &str,
+    haikus: &[(i64, Haiku)],
+    next_cursor: Option<&str>,
+) -> Value {
+    json!({
+        "server": server.to_string(),
+        "author": {
+            "id": author.to_string(),
+            "name": name,
+        },
+        "haikus": haikus
+            .iter()
+            .map(|(id, haiku)| json!({
+                "id": id,
+                "timestamp": haiku.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "channel": haiku.channel.to_string(),
+                "link": haiku_link(server, haiku),
+                "lines": haiku
+                    .lines
+                    .iter()
+                    .map(|line| line.content.as_str())
+                    .collect::<Vec<&str>>(),
+            }))
+            .collect::<Vec<Value>>(),
+        "next_cursor": next_cursor,
+    })
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn rss_feed(server: GuildId, name: &str, haikus: &[(i64, Haiku)]) -> String {
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n\
+         <title>Haikus by {name}</title>\n\
+         <link>https://discord.com/channels/{server}</link>\n\
+         <description>Haikus written by {name}, as found by haikubot</description>\n",
+        name = escape_xml(name),
+        server = server,
+    );
+    for (id, haiku) in haikus {
+        // The description is HTML, so each line is escaped once for HTML and again for XML
+        let html = haiku
+            .lines
+            .iter()
+            .map(|line| escape_xml(&line.content))
+            .collect::<Vec<String>>()
+            .join("<br>");
+        feed.push_str(&format!(
+            "<item>\n\
+             <title>Haiku #{id}</title>\n\
+             <link>{link}</link>\n\
+             <description>{description}</description>\n\
+             <pubDate>{date}</pubDate>\n\
+             <guid isPermaLink=\"false\">{server}-{id}</guid>\n\
+             </item>\n",
+            id = id,
+            link = escape_xml(&haiku_link(server, haiku)),
+            description = escape_xml(&html),
+            date = haiku.timestamp.format("%a, %d %b %Y %H:%M:%S +0000"),
+            server = server,
+        ));
+    }
+    feed.push_str("</channel>\n</rss>\n");
+    feed
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_cursor, encode_cursor, etag, etag_matches, haikus_json, parse_query, rss_feed,
+        Format, List, ListQuery,
+    };
+    use crate::{
+        database::{HaikuCursor, KeywordMatch, SearchTerms},
+        models::Haiku,
+        test_support::HaikuBuilder,
+    };
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use serenity::model::id::{GuildId, UserId};
+
+    fn haiku() -> Haiku {
+        HaikuBuilder::new()
+            .lines([
+                "An old silent pond",
+                "A frog jumps into the pond",
+                "Splash! <Silence> & again",
+            ])
+            .at(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0))
+            .build()
+    }
+
+    #[test]
+    fn test_haikus_json() {
+        let value = haikus_json(GuildId(1), UserId(100), "Bashō", &[(7, haiku())], Some("c"));
+        assert_eq!(value["author"], json!({ "id": "100", "name": "Bashō" }));
+        assert_eq!(value["next_cursor"], json!("c"));
+        assert_eq!(
+            value["haikus"][0],
+            json!({
+                "id": 7,
+                "timestamp": "2021-03-01T12:00:00Z",
+                "channel": "10",
+                "link": "https://discord.com/channels/1/10",
+                "lines": [
+                    "An old silent pond",
+                    "A frog jumps into the pond",
+                    "Splash! <Silence> & again",
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_rss_feed() {
+        let feed = rss_feed(GuildId(1), "<Bashō>", &[(7, haiku())]);
+        assert!(feed.contains("<title>Haikus by &lt;Bashō&gt;</title>"));
+        assert!(feed.contains("<title>Haiku #7</title>"));
+        assert!(feed.contains(
+            "<description>An old silent pond&lt;br&gt;A frog jumps into the pond&lt;br&gt;\
+             Splash! &amp;lt;Silence&amp;gt; &amp;amp; again</description>"
+        ));
+        assert!(feed.contains("<pubDate>Mon, 01 Mar 2021 12:00:00 +0000</pubDate>"));
+        assert!(feed.contains("<guid isPermaLink=\"false\">1-7</guid>"));
+        assert!(feed.ends_with("</channel>\n</rss>\n"));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = ListQuery {
+            q: Some("frog -pond".to_owned()),
+            keyword_match: Some("all".to_owned()),
+            since: Some("2021-03-01".to_owned()),
+            until: Some("2021-04-01T12:00:00+02:00".to_owned()),
+            limit: Some(10),
+            ..ListQuery::default()
+        };
+        let (filter, limit) = parse_query(&query).unwrap();
+        assert_eq!(limit, 10);
+        assert_eq!(
+            filter.terms,
+            Some(SearchTerms::parse("frog -pond", KeywordMatch::All))
+        );
+        assert_eq!(filter.since, Some(Utc.ymd(2021, 3, 1).and_hms(0, 0, 0)));
+        assert_eq!(filter.until, Some(Utc.ymd(2021, 4, 1).and_hms(10, 0, 0)));
+        assert_eq!(filter.after, None);
+
+        assert_eq!(parse_query(&ListQuery::default()).unwrap().1, 50);
+        let invalid = |query: ListQuery| parse_query(&query).is_err();
+        assert!(invalid(ListQuery {
+            keyword_match: Some("some".to_owned()),
+            ..ListQuery::default()
+        }));
+        assert!(invalid(ListQuery {
+            since: Some("March".to_owned()),
+            ..ListQuery::default()
+        }));
+        assert!(invalid(ListQuery {
+            cursor: Some("abc".to_owned()),
+            ..ListQuery::default()
+        }));
+        assert!(invalid(ListQuery {
+            limit: Some(0),
+            ..ListQuery::default()
+        }));
+        assert!(invalid(ListQuery {
+            limit: Some(101),
+            ..ListQuery::default()
+        }));
+    }
+
+    #[test]
+    fn test_cursors() {
+        let cursor = HaikuCursor {
+            timestamp: Utc.ymd(2021, 3, 1).and_hms_micro(12, 0, 0, 123_456),
+            id: 7,
+        };
+        assert_eq!(decode_cursor(&encode_cursor(cursor)), Some(cursor));
+        let before_1970 = HaikuCursor {
+            timestamp: Utc.ymd(1969, 12, 31).and_hms_micro(23, 59, 59, 500_000),
+            id: 1,
+        };
+        assert_eq!(
+            decode_cursor(&encode_cursor(before_1970)),
+            Some(before_1970)
+        );
+        assert_eq!(decode_cursor("12_"), None);
+        assert_eq!(decode_cursor("12"), None);
+    }
+
+    #[test]
+    fn test_etags() {
+        let list = |format: Format, q: Option<&str>| List {
+            server: GuildId(1),
+            author: UserId(100),
+            format,
+            query: ListQuery {
+                q: q.map(str::to_owned),
+                ..ListQuery::default()
+            },
+            if_none_match: None,
+        };
+        let page = [(7, haiku())];
+        let tag = etag(&list(Format::Json, None), &page, None);
+        assert_eq!(tag, etag(&list(Format::Json, None), &page, None));
+        assert_ne!(tag, etag(&list(Format::Rss, None), &page, None));
+        assert_ne!(tag, etag(&list(Format::Json, Some("frog")), &page, None));
+        assert_ne!(tag, etag(&list(Format::Json, None), &page, Some("c")));
+        assert_ne!(tag, etag(&list(Format::Json, None), &[], None));
+        let mut edited = haiku();
+        edited.lines[2].content = "Splash!".to_owned();
+        assert_ne!(tag, etag(&list(Format::Json, None), &[(7, edited)], None));
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+        assert!(etag_matches(&tag, &tag));
+        assert!(etag_matches(&format!("\"old\", W/{}", tag), &tag));
+        assert!(etag_matches("*", &tag));
+        assert!(!etag_matches("\"old\"", &tag));
+    }
+}
@@ -0,0 +1,41 @@
+use crate::database;
+use serde_json::json;
+use serenity::{client::bridge::gateway::ShardManager, gateway::ConnectionStage, prelude::Mutex};
+use std::{convert::Infallible, sync::Arc};
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+/// GET /healthz - 200 when connected to both Discord and the database, 503 otherwise
+pub fn routes(
+    shard_manager: Arc<Mutex<ShardManager>>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path("healthz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(move || check_health(shard_manager.clone()))
+}
+
+async fn check_health(shard_manager: Arc<Mutex<ShardManager>>) -> Result<impl Reply, Infallible> {
+    let gateway_connected = {
+        let manager = shard_manager.lock().await;
+        let runners = manager.runners.lock().await;
+        !runners.is_empty()
+            && runners
+                .values()
+                .all(|runner| runner.stage == ConnectionStage::Connected)
+    };
+    let database_reachable = tokio::task::spawn_blocking(database::check_connection)
+        .await
+        .unwrap_or(false);
+    let status = if gateway_connected && database_reachable {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({
+            "gateway_connected": gateway_connected,
+            "database_reachable": database_reachable,
+        })),
+        status,
+    ))
+}
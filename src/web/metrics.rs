@@ -0,0 +1,83 @@
+use crate::runtime_stats::RuntimeStats;
+use haikubot::counting::line_cache_stats;
+use std::{convert::Infallible, sync::Arc};
+use warp::{Filter, Rejection, Reply};
+
+/// GET /metrics - the runtime stats and per-command usage in the Prometheus text format
+pub fn routes(
+    runtime_stats: Arc<RuntimeStats>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(move || render_metrics(runtime_stats.clone()))
+}
+
+async fn render_metrics(runtime_stats: Arc<RuntimeStats>) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::with_header(
+        format_metrics(&runtime_stats),
+        "Content-Type",
+        "text/plain; version=0.0.4",
+    ))
+}
+
+fn format_metrics(runtime_stats: &RuntimeStats) -> String {
+    let mut lines = vec![
+        "# TYPE haikubot_messages_processed_total counter".to_owned(),
+        format!(
+            "haikubot_messages_processed_total {}",
+            runtime_stats.messages_processed()
+        ),
+        "# TYPE haikubot_haikus_detected_total counter".to_owned(),
+        format!(
+            "haikubot_haikus_detected_total {}",
+            runtime_stats.haikus_detected()
+        ),
+    ];
+    let line_cache = line_cache_stats();
+    lines.extend(vec![
+        "# TYPE haikubot_line_cache_hits_total counter".to_owned(),
+        format!("haikubot_line_cache_hits_total {}", line_cache.hits),
+        "# TYPE haikubot_line_cache_misses_total counter".to_owned(),
+        format!("haikubot_line_cache_misses_total {}", line_cache.misses),
+    ]);
+    let commands = runtime_stats.command_stats();
+    lines.push("# TYPE haikubot_command_uses_total counter".to_owned());
+    for (command, stats) in commands.iter() {
+        lines.push(format!(
+            "haikubot_command_uses_total{{command=\"{}\"}} {}",
+            command, stats.uses
+        ));
+    }
+    lines.push("# TYPE haikubot_command_latency_milliseconds_sum counter".to_owned());
+    for (command, stats) in commands.iter() {
+        lines.push(format!(
+            "haikubot_command_latency_milliseconds_sum{{command=\"{}\"}} {}",
+            command, stats.total_latency_ms
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_metrics;
+    use crate::runtime_stats::RuntimeStats;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_metrics() {
+        let stats = RuntimeStats::default();
+        stats.record_message();
+        stats.record_command("config language", Duration::from_millis(120));
+        stats.record_command("config language", Duration::from_millis(80));
+        let metrics = format_metrics(&stats);
+        assert!(metrics.contains("haikubot_messages_processed_total 1\n"));
+        assert!(metrics.contains("haikubot_haikus_detected_total 0\n"));
+        assert!(metrics.contains("# TYPE haikubot_line_cache_hits_total counter\n"));
+        assert!(metrics.contains("haikubot_command_uses_total{command=\"config language\"} 2\n"));
+        assert!(metrics.contains(
+            "haikubot_command_latency_milliseconds_sum{command=\"config language\"} 200\n"
+        ));
+    }
+}
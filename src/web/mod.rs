@@ -0,0 +1,51 @@
+use crate::{
+    detection::pipeline::DetectionPipeline, repository::HaikuRepository,
+    runtime_stats::RuntimeStats,
+};
+use serde_json::json;
+use serenity::{client::bridge::gateway::ShardManager, prelude::Mutex, CacheAndHttp};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::mpsc::UnboundedSender;
+use warp::{http::StatusCode, reply::Response, Filter, Reply};
+
+mod authors;
+mod health;
+mod metrics;
+mod writes;
+
+pub use writes::{finish_api_writes, ApiWrite, PendingApiWrites};
+
+/// Serve the bot's HTTP endpoints until the process exits
+pub async fn serve(
+    address: SocketAddr,
+    shard_manager: Arc<Mutex<ShardManager>>,
+    runtime_stats: Arc<RuntimeStats>,
+    repository: Arc<dyn HaikuRepository>,
+    cache_and_http: Arc<CacheAndHttp>,
+    pipeline: Arc<DetectionPipeline>,
+    api_writes: UnboundedSender<ApiWrite>,
+) {
+    println!("Serving HTTP on {}", address);
+    warp::serve(
+        health::routes(shard_manager)
+            .or(metrics::routes(runtime_stats))
+            .or(authors::routes(
+                repository.clone(),
+                cache_and_http.cache.clone(),
+            ))
+            .or(writes::routes(
+                repository,
+                cache_and_http,
+                pipeline,
+                api_writes,
+            )),
+    )
+    .run(address)
+    .await;
+}
+
+/// A JSON error for the haiku endpoints
+fn error(status: StatusCode, message: &str) -> Response {
+    warp::reply::with_status(warp::reply::json(&json!({ "error": message })), status)
+        .into_response()
+}
@@ -0,0 +1,451 @@
+use super::error;
+use crate::{
+    api_tokens::hash_secret,
+    counting::{is_haiku_with, NotHaiku},
+    detection::pipeline::{DetectionPipeline, SavedHaiku},
+    models::{ApiScope, Haiku, HaikuLine},
+    posts::{delete_posts, find_posts, HaikuPosts},
+    repository::{repository, HaikuRepository},
+    HaikuTracker,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::TypeMapKey,
+    CacheAndHttp,
+};
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use warp::{http::StatusCode, reply::Response, Filter, Rejection, Reply};
+
+/// Submissions are three short lines, so anything much bigger isn't one
+const MAX_BODY_BYTES: u64 = 16 * 1024;
+const MAX_LINE_LENGTH: usize = 200;
+const MAX_AUTHOR_NAME_LENGTH: usize = 80;
+
+/// What's left to do in Discord after a write through the API, which waits for the bot to connect
+pub enum ApiWrite {
+    /// A haiku was saved, so it's announced and so on as if it had been found in the channel
+    Saved(SavedHaiku),
+    /// A haiku was deleted, so everything posted for it goes too
+    Deleted(GuildId, HaikuPosts),
+}
+
+/// Where the API's writes wait until [finish_api_writes] takes them
+pub struct PendingApiWrites;
+impl TypeMapKey for PendingApiWrites {
+    type Value = Option<UnboundedReceiver<ApiWrite>>;
+}
+
+/// Run the hooks for haikus saved through the API, and delete the posts of those deleted through
+/// it, for as long as the bot runs
+pub async fn finish_api_writes(ctx: Context) {
+    let (receiver, pipeline) = {
+        let mut data = ctx.data.write().await;
+        let receiver = data
+            .get_mut::<PendingApiWrites>()
+            .expect("Expected PendingApiWrites in TypeMap")
+            .take();
+        let pipeline = data
+            .get::<HaikuTracker>()
+            .expect("Expected HaikuTracker in TypeMap")
+            .clone();
+        (receiver, pipeline)
+    };
+    let mut receiver = match receiver {
+        Some(receiver) => receiver,
+        None => return,
+    };
+    while let Some(write) = receiver.recv().await {
+        let repository = repository(&ctx).await;
+        match write {
+            ApiWrite::Saved(saved) => pipeline.run_hooks(&ctx, &*repository, &[saved]).await,
+            ApiWrite::Deleted(server_id, posts) => {
+                delete_posts(&ctx, &*repository, server_id, posts).await
+            }
+        }
+    }
+}
+
+/// What the write endpoints need, cloned into each request
+#[derive(Clone)]
+struct Writes {
+    repository: Arc<dyn HaikuRepository>,
+    cache_and_http: Arc<CacheAndHttp>,
+    pipeline: Arc<DetectionPipeline>,
+    sender: UnboundedSender<ApiWrite>,
+}
+
+/// POST /guilds/{id}/haikus - save a haiku written outside Discord, e.g. in a form on the server's
+/// website, announcing it in the channel given
+/// DELETE /guilds/{id}/haikus/{haiku} - delete a haiku, along with its announcement
+///
+/// Both need a token made with /apitoken in the same server, with the scope for what they do, sent
+/// as `Authorization: Bearer <token>`.
+pub fn routes(
+    repository: Arc<dyn HaikuRepository>,
+    cache_and_http: Arc<CacheAndHttp>,
+    pipeline: Arc<DetectionPipeline>,
+    sender: UnboundedSender<ApiWrite>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let writes = Writes {
+        repository,
+        cache_and_http,
+        pipeline,
+        sender,
+    };
+    let submit = {
+        let writes = writes.clone();
+        warp::path!("guilds" / u64 / "haikus")
+            .and(warp::post())
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+            .and(warp::body::json::<Submission>())
+            .and_then(move |server, authorization, submission| {
+                submit_haiku(writes.clone(), GuildId(server), authorization, submission)
+            })
+    };
+    let delete = warp::path!("guilds" / u64 / "haikus" / i64)
+        .and(warp::delete())
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |server, id, authorization| {
+            delete_haiku(writes.clone(), GuildId(server), id, authorization)
+        });
+    submit.or(delete)
+}
+
+/// A haiku sent to be saved. Ids are strings, as in the rest of the API.
+#[derive(Debug, Deserialize)]
+struct Submission {
+    /// The channel to announce the haiku in, which it's counted as written in
+    channel: String,
+    lines: [String; 3],
+    /// The Discord user id of whoever wrote it, who must be a member of the server
+    author: String,
+    /// The name to show for the author if they leave the server
+    #[serde(default)]
+    author_name: Option<String>,
+}
+
+/// The token from an Authorization header
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+/// Check the request has a token that can do this in the server, or say why not
+fn authorize(
+    repository: &dyn HaikuRepository,
+    server_id: GuildId,
+    authorization: Option<&str>,
+    scope: ApiScope,
+) -> Result<i64, Response> {
+    let secret = authorization.and_then(bearer_token).ok_or_else(|| {
+        error(
+            StatusCode::UNAUTHORIZED,
+            "an Authorization: Bearer header with an API token is needed",
+        )
+    })?;
+    let (id, token) = repository
+        .get_api_token(&hash_secret(secret))
+        // A token from another server is as good as no token
+        .filter(|(_, token)| token.server == server_id)
+        .ok_or_else(|| error(StatusCode::UNAUTHORIZED, "unknown API token"))?;
+    if token.is_expired(Utc::now()) {
+        return Err(error(StatusCode::UNAUTHORIZED, "the API token has expired"));
+    }
+    if !token.scopes.contains(&scope) {
+        return Err(error(
+            StatusCode::FORBIDDEN,
+            &format!("the API token can't {} haikus", scope.code()),
+        ));
+    }
+    Ok(id)
+}
+
+/// The haiku a submission is for, or what's wrong with it, without checking it's a haiku
+fn parse_submission(server_id: GuildId, submission: Submission) -> Result<Haiku, &'static str> {
+    let channel = submission
+        .channel
+        .parse::<u64>()
+        .map(ChannelId)
+        .map_err(|_| "channel must be a channel id")?;
+    let author = submission
+        .author
+        .parse::<u64>()
+        .map(UserId)
+        .map_err(|_| "author must be a user id")?;
+    let author_name = match submission.author_name.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) if name.chars().count() > MAX_AUTHOR_NAME_LENGTH => {
+            return Err("author_name is too long")
+        }
+        Some(name) => Some(name.to_owned()),
+    };
+    let [line_0, line_1, line_2] = submission.lines;
+    let line = |content: String| {
+        let content = content.trim();
+        if content.is_empty() || content.contains('\n') {
+            Err("each line must be a single line of text")
+        } else if content.chars().count() > MAX_LINE_LENGTH {
+            Err("a line is too long")
+        } else {
+            Ok(HaikuLine {
+                author,
+                author_name: author_name.clone(),
+                content: content.to_owned(),
+            })
+        }
+    };
+    Ok(Haiku {
+        lines: [line(line_0)?, line(line_1)?, line(line_2)?],
+        timestamp: Utc::now(),
+        channel,
+        server: server_id,
+        source: None,
+    })
+}
+
+fn not_haiku_message(not_haiku: &NotHaiku) -> String {
+    match not_haiku {
+        NotHaiku::WrongSyllables {
+            line,
+            expected,
+            found,
+            ..
+        } => format!(
+            "line {} has {} syllables instead of {}",
+            line + 1,
+            found,
+            expected
+        ),
+        _ => "the lines aren't a haiku".to_owned(),
+    }
+}
+
+async fn submit_haiku(
+    writes: Writes,
+    server_id: GuildId,
+    authorization: Option<String>,
+    submission: Submission,
+) -> Result<Response, Infallible> {
+    let haiku = match parse_submission(server_id, submission) {
+        Ok(haiku) => haiku,
+        Err(message) => return Ok(error(StatusCode::BAD_REQUEST, message)),
+    };
+    let repository = writes.repository.clone();
+    let token = tokio::task::spawn_blocking(move || {
+        authorize(
+            &*repository,
+            server_id,
+            authorization.as_deref(),
+            ApiScope::Submit,
+        )
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "could not save haiku",
+        ))
+    });
+    let token = match token {
+        Ok(token) => token,
+        Err(response) => return Ok(response),
+    };
+    // Only announced in channels the bot can see in the server
+    let channel = writes
+        .cache_and_http
+        .cache
+        .guild_channel(haiku.channel)
+        .await;
+    if channel.map(|channel| channel.guild_id) != Some(server_id) {
+        return Ok(error(
+            StatusCode::BAD_REQUEST,
+            "channel isn't a channel in this server",
+        ));
+    }
+    // Checked after the token, so the API can't be used to find out who's in a server. Members
+    // that aren't cached are fetched from Discord.
+    let author = haiku.lines[0].author;
+    if server_id
+        .member(&writes.cache_and_http, author)
+        .await
+        .is_err()
+    {
+        return Ok(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "author isn't a member of this server",
+        ));
+    }
+    let repository = writes.repository.clone();
+    let pipeline = writes.pipeline.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let lines = haiku
+            .lines
+            .iter()
+            .map(|line| line.content.clone())
+            .collect::<Vec<String>>();
+        if let Err(not_haiku) = is_haiku_with(&lines, &repository.get_word_overrides(server_id)) {
+            return Err(error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                &not_haiku_message(&not_haiku),
+            ));
+        }
+        let saved = pipeline
+            .save_submitted(&*repository, haiku)
+            .ok_or_else(|| {
+                error(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "the server's settings leave this haiku out",
+                )
+            })?;
+        println!(
+            "Saved haiku #{} in server {} with API token #{}",
+            saved.id, server_id, token
+        );
+        Ok(saved)
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "could not save haiku",
+        ))
+    });
+    let saved = match result {
+        Ok(saved) => saved,
+        Err(response) => return Ok(response),
+    };
+    let body = json!({ "id": saved.id, "flagged": saved.flagged });
+    if writes.sender.send(ApiWrite::Saved(saved)).is_err() {
+        println!("Could not hand a haiku saved through the API to the bot");
+    }
+    Ok(warp::reply::with_status(warp::reply::json(&body), StatusCode::CREATED).into_response())
+}
+
+async fn delete_haiku(
+    writes: Writes,
+    server_id: GuildId,
+    id: i64,
+    authorization: Option<String>,
+) -> Result<Response, Infallible> {
+    let repository = writes.repository.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let token = authorize(
+            &*repository,
+            server_id,
+            authorization.as_deref(),
+            ApiScope::Delete,
+        )?;
+        let posts = find_posts(&*repository, server_id, id);
+        match posts {
+            Some(posts) if repository.delete_haiku(server_id, id) => {
+                println!(
+                    "Deleted haiku #{} in server {} with API token #{}",
+                    id, server_id, token
+                );
+                Ok(posts)
+            }
+            _ => Err(error(StatusCode::NOT_FOUND, "not found")),
+        }
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "could not delete haiku",
+        ))
+    });
+    let posts = match result {
+        Ok(posts) => posts,
+        Err(response) => return Ok(response),
+    };
+    if writes
+        .sender
+        .send(ApiWrite::Deleted(server_id, posts))
+        .is_err()
+    {
+        println!("Could not hand a haiku deleted through the API to the bot");
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bearer_token, parse_submission, Submission};
+    use serenity::model::id::{ChannelId, GuildId, UserId};
+
+    fn submission() -> Submission {
+        Submission {
+            channel: "10".to_owned(),
+            lines: [
+                "An old silent pond".to_owned(),
+                " A frog jumps into the pond ".to_owned(),
+                "Splash! Silence again".to_owned(),
+            ],
+            author: "100".to_owned(),
+            author_name: Some("Bashō".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer haikubot_abc"), Some("haikubot_abc"));
+        assert_eq!(bearer_token("bearer  haikubot_abc "), Some("haikubot_abc"));
+        assert_eq!(bearer_token("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("haikubot_abc"), None);
+    }
+
+    #[test]
+    fn test_parse_submission() {
+        let haiku = parse_submission(GuildId(1), submission()).unwrap();
+        assert_eq!(haiku.server, GuildId(1));
+        assert_eq!(haiku.channel, ChannelId(10));
+        assert_eq!(haiku.lines[1].content, "A frog jumps into the pond");
+        assert_eq!(haiku.lines[2].author, UserId(100));
+        assert_eq!(haiku.lines[2].author_name.as_deref(), Some("Bashō"));
+        assert!(haiku.source.is_none());
+
+        let blank_name = Submission {
+            author_name: Some(" ".to_owned()),
+            ..submission()
+        };
+        let haiku = parse_submission(GuildId(1), blank_name).unwrap();
+        assert_eq!(haiku.lines[0].author_name, None);
+
+        let invalid = |submission: Submission| parse_submission(GuildId(1), submission).is_err();
+        assert!(invalid(Submission {
+            channel: "general".to_owned(),
+            ..submission()
+        }));
+        assert!(invalid(Submission {
+            author: "-1".to_owned(),
+            ..submission()
+        }));
+        assert!(invalid(Submission {
+            lines: ["An old".to_owned(), "".to_owned(), "pond".to_owned()],
+            ..submission()
+        }));
+        assert!(invalid(Submission {
+            lines: [
+                "An old silent pond\nA frog".to_owned(),
+                "b".to_owned(),
+                "c".to_owned()
+            ],
+            ..submission()
+        }));
+        assert!(invalid(Submission {
+            author_name: Some("x".repeat(81)),
+            ..submission()
+        }));
+    }
+}
@@ -0,0 +1,50 @@
+//! Introducing the bot to people the first time it finds a haiku of theirs
+
+use crate::{models::Haiku, repository::HaikuRepository};
+use serenity::model::id::UserId;
+
+/// The authors of a saved haiku for whom it's their first haiku in its server
+pub fn first_haiku_authors(repository: &dyn HaikuRepository, haiku: &Haiku) -> Vec<UserId> {
+    let mut authors = Vec::new();
+    for line in haiku.lines.iter() {
+        if !authors.contains(&line.author)
+            && repository.count_haikus_by_author(haiku.server, line.author) == 1
+        {
+            authors.push(line.author);
+        }
+    }
+    authors
+}
+
+#[cfg(test)]
+mod test {
+    use super::first_haiku_authors;
+    use crate::{
+        repository::{HaikuRepository, MemoryRepository},
+        test_support::HaikuBuilder,
+    };
+    use serenity::model::id::UserId;
+
+    #[test]
+    fn test_first_haiku_authors() {
+        let repository = MemoryRepository::new();
+        let first = HaikuBuilder::new().author(100).build();
+        repository.save_haiku(&first);
+        assert_eq!(first_haiku_authors(&repository, &first), vec![UserId(100)]);
+
+        let second = HaikuBuilder::new().authors([100, 200, 300]).build();
+        repository.save_haiku(&second);
+        assert_eq!(
+            first_haiku_authors(&repository, &second),
+            vec![UserId(200), UserId(300)]
+        );
+
+        // Haikus in other servers don't count
+        let other_server = HaikuBuilder::new().server(2).author(100).build();
+        repository.save_haiku(&other_server);
+        assert_eq!(
+            first_haiku_authors(&repository, &other_server),
+            vec![UserId(100)]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Feed scripted conversations through haiku detection and check which haikus get saved
+
+use chrono::{DateTime, Duration, Utc};
+use haikubot::{
+    detection::{
+        find_haiku_for_message,
+        pipeline::{IncomingMessage, Sender},
+        Detector,
+    },
+    repository::{HaikuRepository, MemoryRepository},
+};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+const SERVER: GuildId = GuildId(1);
+const GENERAL: ChannelId = ChannelId(10);
+const RANDOM: ChannelId = ChannelId(11);
+const BASHO: UserId = UserId(100);
+const BUSON: UserId = UserId(101);
+
+/// A conversation in one server, recording every haiku that gets saved
+struct Conversation {
+    detector: Detector,
+    repository: MemoryRepository,
+    last_message: u64,
+    now: DateTime<Utc>,
+}
+
+impl Conversation {
+    fn new() -> Self {
+        Conversation {
+            detector: Detector::new().max_gap(Some(Duration::minutes(10))),
+            repository: MemoryRepository::new(),
+            last_message: 0,
+            now: Utc::now(),
+        }
+    }
+
+    /// Let time pass before the next message is sent
+    fn wait(&mut self, minutes: i64) {
+        self.now = self.now + Duration::minutes(minutes);
+    }
+
+    /// Send a message, returning the lines of any haikus it completed
+    fn send(&mut self, channel: ChannelId, author: UserId, content: &str) -> Vec<[String; 3]> {
+        self.last_message += 1;
+        let message = MessageId(self.last_message);
+        self.receive(channel, author, message, content)
+    }
+
+    /// Receive a message that may already have been received before
+    fn receive(
+        &mut self,
+        channel: ChannelId,
+        author: UserId,
+        message: MessageId,
+        content: &str,
+    ) -> Vec<[String; 3]> {
+        let message = IncomingMessage {
+            id: message,
+            server: SERVER,
+            channel,
+            author,
+            author_name: None,
+            sender: Sender::Person,
+            content: content.to_owned(),
+            sent_at: self.now,
+        };
+        self.detector
+            .on_message(&self.repository, &message)
+            .into_iter()
+            .map(|(_, haiku)| {
+                let [line_1, line_2, line_3] = haiku.lines;
+                [line_1.content, line_2.content, line_3.content]
+            })
+            .collect()
+    }
+
+    fn saved(&self) -> usize {
+        self.repository.count_all_haikus() as usize
+    }
+}
+
+fn lines(lines: [&str; 3]) -> [String; 3] {
+    [
+        lines[0].to_owned(),
+        lines[1].to_owned(),
+        lines[2].to_owned(),
+    ]
+}
+
+#[test]
+fn test_single_message_haiku() {
+    let mut conversation = Conversation::new();
+    let haikus = conversation.send(
+        GENERAL,
+        BASHO,
+        "The last winter leaves clinging to the black branches explode into birds",
+    );
+    assert_eq!(
+        haikus,
+        vec![lines([
+            "The last winter leaves",
+            "clinging to the black branches",
+            "explode into birds"
+        ])]
+    );
+    assert_eq!(conversation.saved(), 1);
+}
+
+#[test]
+fn test_haiku_over_three_messages() {
+    let mut conversation = Conversation::new();
+    assert!(conversation
+        .send(GENERAL, BASHO, "An old silent pond")
+        .is_empty());
+    assert!(conversation
+        .send(GENERAL, BUSON, "A frog jumps into the pond")
+        .is_empty());
+    let haikus = conversation.send(GENERAL, BASHO, "Splash! Silence again.");
+    assert_eq!(
+        haikus,
+        vec![lines([
+            "An old silent pond",
+            "A frog jumps into the pond",
+            "Splash! Silence again."
+        ])]
+    );
+
+    let (_, haiku) = conversation
+        .repository
+        .get_all_haikus()
+        .pop()
+        .expect("Haiku wasn't saved");
+    let authors = haiku
+        .lines
+        .iter()
+        .map(|line| line.author)
+        .collect::<Vec<UserId>>();
+    assert_eq!(authors, vec![BASHO, BUSON, BASHO]);
+    assert_eq!(haiku.channel, GENERAL);
+    assert_eq!(haiku.server, SERVER);
+}
+
+#[test]
+fn test_multi_line_message() {
+    let mut conversation = Conversation::new();
+    let haikus = conversation.send(
+        GENERAL,
+        BASHO,
+        "An old silent pond\nA frog jumps into the pond\nSplash! Silence again.",
+    );
+    assert_eq!(haikus.len(), 1);
+    assert_eq!(conversation.saved(), 1);
+}
+
+#[test]
+fn test_replayed_messages_are_saved_once() {
+    let mut conversation = Conversation::new();
+    let content = "An old silent pond\nA frog jumps into the pond\nSplash! Silence again.\n\
+        The last winter leaves clinging to the black branches explode into birds";
+    assert_eq!(
+        conversation
+            .receive(GENERAL, BASHO, MessageId(5), content)
+            .len(),
+        2
+    );
+    // The gateway sends the same message again after reconnecting
+    assert!(conversation
+        .receive(GENERAL, BASHO, MessageId(5), content)
+        .is_empty());
+    assert_eq!(conversation.saved(), 2);
+
+    let (_, haiku) = conversation.repository.get_all_haikus().remove(0);
+    let source = haiku.source.expect("Haiku saved without its source");
+    assert_eq!(source.messages, [MessageId(5); 3]);
+    assert_eq!(source.line, 2);
+}
+
+#[test]
+fn test_channels_are_tracked_separately() {
+    let mut conversation = Conversation::new();
+    conversation.send(GENERAL, BASHO, "An old silent pond");
+    conversation.send(RANDOM, BASHO, "A frog jumps into the pond");
+    conversation.send(GENERAL, BASHO, "A frog jumps into the pond");
+    assert!(conversation
+        .send(RANDOM, BASHO, "Splash! Silence again.")
+        .is_empty());
+    assert_eq!(
+        conversation.send(GENERAL, BASHO, "Splash! Silence again."),
+        vec![lines([
+            "An old silent pond",
+            "A frog jumps into the pond",
+            "Splash! Silence again."
+        ])]
+    );
+    assert_eq!(conversation.saved(), 1);
+}
+
+#[test]
+fn test_interrupted_haiku() {
+    let mut conversation = Conversation::new();
+    conversation.send(GENERAL, BASHO, "An old silent pond");
+    conversation.send(GENERAL, BASHO, "A frog jumps into the pond");
+    conversation.send(GENERAL, BUSON, "lol");
+    assert!(conversation
+        .send(GENERAL, BASHO, "Splash! Silence again.")
+        .is_empty());
+    assert_eq!(conversation.saved(), 0);
+}
+
+#[test]
+fn test_long_gaps_start_a_new_conversation() {
+    let mut conversation = Conversation::new();
+    conversation.send(GENERAL, BASHO, "An old silent pond");
+    conversation.send(GENERAL, BASHO, "A frog jumps into the pond");
+    conversation.wait(60);
+    assert!(conversation
+        .send(GENERAL, BASHO, "Splash! Silence again.")
+        .is_empty());
+
+    // Pauses shorter than the gap don't break a haiku up
+    conversation.send(GENERAL, BUSON, "An old silent pond");
+    conversation.wait(9);
+    conversation.send(GENERAL, BUSON, "A frog jumps into the pond");
+    conversation.wait(9);
+    assert_eq!(
+        conversation.send(GENERAL, BUSON, "Splash! Silence again."),
+        vec![lines([
+            "An old silent pond",
+            "A frog jumps into the pond",
+            "Splash! Silence again."
+        ])]
+    );
+}
+
+#[test]
+fn test_uncountable_messages() {
+    let mut conversation = Conversation::new();
+    for message in [
+        "",
+        "🐸🐸🐸",
+        "https://example.com",
+        "\u{202e}pond old silent an",
+    ]
+    .iter()
+    {
+        assert!(conversation.send(GENERAL, BASHO, message).is_empty());
+    }
+    assert_eq!(conversation.saved(), 0);
+}
+
+#[test]
+fn test_find_haiku_for_message() {
+    let mut conversation = Conversation::new();
+    let sent_at = Utc::now();
+    let single = "The last winter leaves clinging to the black branches explode into birds";
+    conversation.send(GENERAL, BASHO, single);
+    conversation.send(RANDOM, BASHO, "An old silent pond");
+    conversation.send(RANDOM, BUSON, "A frog jumps into the pond");
+    conversation.send(RANDOM, BASHO, "Splash! Silence again");
+    let haikus = conversation.repository.get_haikus_by_author(SERVER, BASHO);
+    let find = |channel, author, content| {
+        find_haiku_for_message(&haikus, channel, author, content, sent_at)
+    };
+    let ids = haikus.iter().map(|(id, _)| *id).collect::<Vec<i64>>();
+
+    assert_eq!(find(GENERAL, BASHO, single), Some(ids[0]));
+    assert_eq!(find(RANDOM, BASHO, "An old silent pond"), Some(ids[1]));
+    assert_eq!(
+        find(RANDOM, BUSON, "A frog jumps into the pond"),
+        Some(ids[1])
+    );
+    // Right words, wrong author or channel
+    assert_eq!(find(RANDOM, BUSON, "An old silent pond"), None);
+    assert_eq!(find(GENERAL, BASHO, "An old silent pond"), None);
+    assert_eq!(find(RANDOM, BASHO, "An old silent"), None);
+}
@@ -0,0 +1,207 @@
+//! Save haikus in one server, then check that nothing asked of another server, even with the same
+//! ids, authors and channels, ever finds or changes them
+
+use chrono::{Duration, Utc};
+// Imported here so the shared test support can reach the library's models through `crate::`
+use haikubot::{
+    database::{KeywordMatch, RandomWeighting, SearchOrder, SearchTerms},
+    models::{self, Haiku},
+    repository::{HaikuRepository, MemoryRepository},
+};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use test_support::HaikuBuilder;
+
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+const HOME: GuildId = GuildId(1);
+const AWAY: GuildId = GuildId(2);
+const GENERAL: ChannelId = ChannelId(10);
+const BASHO: UserId = UserId(100);
+
+const WEIGHTINGS: &[RandomWeighting] = &[
+    RandomWeighting::Uniform,
+    RandomWeighting::LeastRecentlyShown,
+    RandomWeighting::MostVoted,
+];
+
+/// Written by Bashō in the general channel yesterday
+fn haiku(server: GuildId, lines: [&str; 3]) -> Haiku {
+    HaikuBuilder::new()
+        .server(server.0)
+        .channel(GENERAL.0)
+        .author(BASHO.0)
+        .lines(lines)
+        .at(Utc::now() - Duration::days(1))
+        .build()
+}
+
+/// A repository where only the home server has haikus, returning their ids
+fn home_only() -> (MemoryRepository, Vec<i64>) {
+    let repository = MemoryRepository::new();
+    let ids = vec![
+        repository
+            .save_haiku(&haiku(
+                HOME,
+                [
+                    "An old silent pond",
+                    "A frog jumps into the pond",
+                    "Splash! Silence again",
+                ],
+            ))
+            .unwrap(),
+        repository
+            .save_haiku(&haiku(
+                HOME,
+                [
+                    "The last winter leaves",
+                    "Clinging to the black branches",
+                    "Explode into birds",
+                ],
+            ))
+            .unwrap(),
+    ];
+    (repository, ids)
+}
+
+fn search(keywords: &str) -> SearchTerms {
+    SearchTerms::parse(keywords, KeywordMatch::Any)
+}
+
+#[test]
+fn test_reads_never_cross_servers() {
+    let (repository, ids) = home_only();
+    let since = Utc::now() - Duration::days(7);
+
+    for id in &ids {
+        assert!(repository.get_haiku(HOME, *id).is_some());
+        assert!(repository.get_haiku(AWAY, *id).is_none());
+    }
+    for weighting in WEIGHTINGS {
+        assert!(repository
+            .get_random_haiku(AWAY, &[], *weighting, None)
+            .is_none());
+    }
+    assert!(repository.get_random_line(AWAY, &[0, 1, 2]).is_none());
+    assert_eq!(repository.count_haikus(AWAY), 0);
+    assert_eq!(repository.count_haikus_by_author(AWAY, BASHO), 0);
+    assert!(repository
+        .count_haikus_by_author_since(AWAY, since)
+        .is_empty());
+    assert!(repository.get_leaderboard(AWAY, None, 0, 10).is_empty());
+    assert_eq!(repository.count_leaderboard_authors(AWAY, None), 0);
+    assert!(repository.count_haikus_by_channel(AWAY).is_empty());
+    assert!(repository.get_haikus_by_author(AWAY, BASHO).is_empty());
+    for order in &[
+        SearchOrder::Relevance,
+        SearchOrder::Newest,
+        SearchOrder::Oldest,
+    ] {
+        assert!(repository
+            .search_haikus(AWAY, &search("pond birds"), *order, None, None)
+            .is_empty());
+        assert!(repository
+            .search_haikus(AWAY, &search("pond"), *order, Some(BASHO), Some(GENERAL))
+            .is_empty());
+    }
+    assert_eq!(
+        repository
+            .count_search_results(AWAY, &search("pond birds"), None, None)
+            .total,
+        0
+    );
+
+    // The home server still sees everything
+    assert_eq!(repository.count_haikus(HOME), 2);
+    assert_eq!(
+        repository
+            .count_search_results(HOME, &search("pond birds"), None, None)
+            .total,
+        2
+    );
+}
+
+#[test]
+fn test_writes_never_cross_servers() {
+    let (repository, ids) = home_only();
+
+    for id in &ids {
+        repository.vote_for_haiku(AWAY, *id, BASHO);
+        repository.flag_haiku(AWAY, *id);
+        repository.mark_haiku_shown(AWAY, *id);
+        assert!(!repository.delete_haiku(AWAY, *id));
+        assert_eq!(repository.count_votes(HOME, *id), 0);
+        assert!(!repository.is_flagged(HOME, *id));
+    }
+    assert_eq!(repository.archive_haikus_before(AWAY, Utc::now()), 0);
+    assert_eq!(repository.delete_haikus_before(AWAY, Utc::now()), 0);
+    repository.purge_server_data(AWAY);
+    assert_eq!(repository.count_haikus(HOME), 2);
+
+    // Archived haikus stay with their server too
+    assert_eq!(repository.archive_haikus_before(HOME, Utc::now()), 2);
+    assert!(repository
+        .get_archived_haikus_by_author(AWAY, BASHO)
+        .is_empty());
+    assert_eq!(
+        repository.get_archived_haikus_by_author(HOME, BASHO).len(),
+        2
+    );
+}
+
+#[test]
+fn test_settings_never_cross_servers() {
+    let (repository, _) = home_only();
+    repository.create_server_config(HOME);
+    repository.create_server_config(AWAY);
+
+    repository.set_word_override(HOME, "pond", Some(3));
+    assert!(repository.get_word_overrides(AWAY).is_empty());
+
+    repository.set_channel_detection(HOME, GENERAL, false);
+    assert!(repository.is_detection_enabled(AWAY, GENERAL));
+
+    repository.record_quiz_answer(HOME, BASHO, true);
+    assert!(repository.get_quiz_leaderboard(AWAY, 10).is_empty());
+
+    repository.record_command_usage(HOME, "randomhaiku", 50);
+    let since = Utc::now() - Duration::days(1);
+    assert!(repository.get_command_usage(AWAY, since).is_empty());
+    assert!(repository
+        .count_command_usage_by_hour(AWAY, since)
+        .is_empty());
+}
+
+#[test]
+fn test_servers_sharing_haiku_numbers() {
+    let repository = MemoryRepository::new();
+    let home = repository
+        .save_haiku(&haiku(
+            HOME,
+            [
+                "An old silent pond",
+                "A frog jumps into the pond",
+                "Splash! Silence again",
+            ],
+        ))
+        .unwrap();
+    let away = repository
+        .save_haiku(&haiku(
+            AWAY,
+            [
+                "The last winter leaves",
+                "Clinging to the black branches",
+                "Explode into birds",
+            ],
+        ))
+        .unwrap();
+    // Each server numbers its own haikus from 1
+    assert_eq!(home, away);
+
+    let (_, found) = repository.get_haiku(AWAY, away).unwrap();
+    assert_eq!(found.lines[0].content, "The last winter leaves");
+    repository.vote_for_haiku(AWAY, away, BASHO);
+    assert_eq!(repository.count_votes(HOME, home), 0);
+    assert!(repository.delete_haiku(AWAY, away));
+    assert!(repository.get_haiku(HOME, home).is_some());
+}